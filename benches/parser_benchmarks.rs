@@ -1,347 +1,75 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use html_css_parser::{HtmlParser, HtmlTokenizer, CssParser, CssTokenizer};
+use html_css_parser::{minify_css, minify_html, HtmlParser, HtmlTokenizer, CssParser, CssTokenizer, Node, Serializer, Text};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-const SMALL_HTML: &str = r#"
-<div class="container">
-    <h1>Hello World</h1>
-    <p>This is a test paragraph.</p>
-    <ul>
-        <li>Item 1</li>
-        <li>Item 2</li>
-        <li>Item 3</li>
-    </ul>
-</div>
-"#;
+#[path = "../tests/fixtures/mod.rs"]
+mod fixtures;
 
-const LARGE_HTML: &str = r#"
-<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Test Document</title>
-</head>
-<body>
-    <header class="main-header">
-        <nav class="navigation">
-            <ul class="nav-list">
-                <li><a href="#home">Home</a></li>
-                <li><a href="#about">About</a></li>
-                <li><a href="#services">Services</a></li>
-                <li><a href="#contact">Contact</a></li>
-            </ul>
-        </nav>
-    </header>
-    <main class="content">
-        <section class="hero">
-            <h1>Welcome to Our Website</h1>
-            <p>This is a comprehensive test document with various HTML elements.</p>
-            <button class="cta-button">Get Started</button>
-        </section>
-        <section class="features">
-            <div class="feature-grid">
-                <div class="feature-item">
-                    <h3>Feature 1</h3>
-                    <p>Description of feature 1 with some detailed text content.</p>
-                    <img src="feature1.jpg" alt="Feature 1 Image">
-                </div>
-                <div class="feature-item">
-                    <h3>Feature 2</h3>
-                    <p>Description of feature 2 with some detailed text content.</p>
-                    <img src="feature2.jpg" alt="Feature 2 Image">
-                </div>
-                <div class="feature-item">
-                    <h3>Feature 3</h3>
-                    <p>Description of feature 3 with some detailed text content.</p>
-                    <img src="feature3.jpg" alt="Feature 3 Image">
-                </div>
-            </div>
-        </section>
-        <section class="testimonials">
-            <h2>What Our Customers Say</h2>
-            <div class="testimonial-list">
-                <blockquote class="testimonial">
-                    <p>"This service is amazing! Highly recommended."</p>
-                    <cite>- John Doe</cite>
-                </blockquote>
-                <blockquote class="testimonial">
-                    <p>"Great experience, will use again."</p>
-                    <cite>- Jane Smith</cite>
-                </blockquote>
-            </div>
-        </section>
-    </main>
-    <footer class="main-footer">
-        <div class="footer-content">
-            <p>&copy; 2024 Test Company. All rights reserved.</p>
-            <div class="social-links">
-                <a href="#facebook">Facebook</a>
-                <a href="#twitter">Twitter</a>
-                <a href="#linkedin">LinkedIn</a>
-            </div>
-        </div>
-    </footer>
-</body>
-</html>
-"#;
+use fixtures::{LARGE_CSS, LARGE_HTML, SMALL_CSS, SMALL_HTML};
 
-const SMALL_CSS: &str = r#"
-.container {
-    max-width: 1200px;
-    margin: 0 auto;
-    padding: 20px;
+/// The system allocator, keeping count of the bytes in use and the most
+/// in use at once, for `html_large_text_memory`.
+struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
 }
 
-h1 {
-    color: #333;
-    font-size: 2rem;
-}
-
-p {
-    line-height: 1.6;
-    color: #666;
-}
-"#;
-
-const LARGE_CSS: &str = r#"
-/* Reset and base styles */
-* {
-    margin: 0;
-    padding: 0;
-    box-sizing: border-box;
-}
-
-body {
-    font-family: 'Arial', sans-serif;
-    line-height: 1.6;
-    color: #333;
-    background-color: #f4f4f4;
-}
-
-/* Header styles */
-.main-header {
-    background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
-    color: white;
-    padding: 1rem 0;
-    position: fixed;
-    top: 0;
-    width: 100%;
-    z-index: 1000;
-    box-shadow: 0 2px 10px rgba(0,0,0,0.1);
-}
-
-.navigation {
-    max-width: 1200px;
-    margin: 0 auto;
-    padding: 0 2rem;
-}
-
-.nav-list {
-    display: flex;
-    list-style: none;
-    justify-content: center;
-    gap: 2rem;
-}
-
-.nav-list li a {
-    color: white;
-    text-decoration: none;
-    font-weight: 500;
-    transition: color 0.3s ease;
-    padding: 0.5rem 1rem;
-    border-radius: 4px;
-}
-
-.nav-list li a:hover {
-    background-color: rgba(255,255,255,0.1);
-    color: #f0f0f0;
-}
-
-/* Main content */
-.content {
-    margin-top: 80px;
-    min-height: calc(100vh - 160px);
-}
-
-.hero {
-    background: linear-gradient(rgba(0,0,0,0.4), rgba(0,0,0,0.4)), url('hero-bg.jpg');
-    background-size: cover;
-    background-position: center;
-    color: white;
-    text-align: center;
-    padding: 8rem 2rem;
-}
-
-.hero h1 {
-    font-size: 3.5rem;
-    margin-bottom: 1rem;
-    text-shadow: 2px 2px 4px rgba(0,0,0,0.5);
-}
-
-.hero p {
-    font-size: 1.2rem;
-    margin-bottom: 2rem;
-    max-width: 600px;
-    margin-left: auto;
-    margin-right: auto;
-}
-
-.cta-button {
-    background: #ff6b6b;
-    color: white;
-    border: none;
-    padding: 1rem 2rem;
-    font-size: 1.1rem;
-    border-radius: 50px;
-    cursor: pointer;
-    transition: all 0.3s ease;
-    text-transform: uppercase;
-    font-weight: bold;
-    letter-spacing: 1px;
-}
-
-.cta-button:hover {
-    background: #ff5252;
-    transform: translateY(-2px);
-    box-shadow: 0 4px 15px rgba(255,107,107,0.4);
-}
-
-/* Features section */
-.features {
-    padding: 6rem 2rem;
-    background: white;
-}
-
-.feature-grid {
-    display: grid;
-    grid-template-columns: repeat(auto-fit, minmax(300px, 1fr));
-    gap: 3rem;
-    max-width: 1200px;
-    margin: 0 auto;
-}
-
-.feature-item {
-    text-align: center;
-    padding: 2rem;
-    border-radius: 10px;
-    box-shadow: 0 5px 15px rgba(0,0,0,0.1);
-    transition: transform 0.3s ease;
-}
-
-.feature-item:hover {
-    transform: translateY(-5px);
-}
-
-.feature-item h3 {
-    color: #667eea;
-    margin-bottom: 1rem;
-    font-size: 1.5rem;
-}
-
-.feature-item img {
-    width: 100%;
-    max-width: 200px;
-    height: auto;
-    border-radius: 8px;
-    margin-top: 1rem;
-}
-
-/* Testimonials */
-.testimonials {
-    background: #f8f9fa;
-    padding: 6rem 2rem;
-    text-align: center;
-}
-
-.testimonials h2 {
-    color: #333;
-    margin-bottom: 3rem;
-    font-size: 2.5rem;
-}
-
-.testimonial-list {
-    display: flex;
-    gap: 2rem;
-    max-width: 800px;
-    margin: 0 auto;
-    flex-wrap: wrap;
-}
-
-.testimonial {
-    flex: 1;
-    background: white;
-    padding: 2rem;
-    border-radius: 10px;
-    box-shadow: 0 3px 10px rgba(0,0,0,0.1);
-    border-left: 4px solid #667eea;
-    min-width: 300px;
-}
-
-.testimonial p {
-    font-style: italic;
-    margin-bottom: 1rem;
-    font-size: 1.1rem;
-}
+impl PeakAlloc {
+    fn grew(&self, by: usize) {
+        let now = self.current.fetch_add(by, Ordering::Relaxed) + by;
+        self.peak.fetch_max(now, Ordering::Relaxed);
+    }
 
-.testimonial cite {
-    color: #667eea;
-    font-weight: bold;
+    /// The most heap in use at once while `f` runs, beyond what was in use
+    /// when it started.
+    fn peak_during<T>(&self, f: impl FnOnce() -> T) -> (T, usize) {
+        let start = self.current.load(Ordering::Relaxed);
+        self.peak.store(start, Ordering::Relaxed);
+        let result = f();
+        (result, self.peak.load(Ordering::Relaxed) - start)
+    }
 }
 
-/* Footer */
-.main-footer {
-    background: #333;
-    color: white;
-    padding: 3rem 2rem 1rem;
-    text-align: center;
-}
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            self.grew(layout.size());
+        }
+        ptr
+    }
 
-.footer-content {
-    max-width: 1200px;
-    margin: 0 auto;
-}
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
 
-.social-links {
-    margin-top: 1rem;
-    display: flex;
-    justify-content: center;
-    gap: 1rem;
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                self.grew(new_size - layout.size());
+            } else {
+                self.current.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
 }
 
-.social-links a {
-    color: white;
-    text-decoration: none;
-    padding: 0.5rem 1rem;
-    border: 1px solid #555;
-    border-radius: 4px;
-    transition: all 0.3s ease;
-}
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc { current: AtomicUsize::new(0), peak: AtomicUsize::new(0) };
 
-.social-links a:hover {
-    background: #555;
-    border-color: #777;
-}
+/// A writer that keeps nothing, so only the writing side's memory counts.
+struct Discard;
 
-/* Responsive design */
-@media (max-width: 768px) {
-    .hero h1 {
-        font-size: 2.5rem;
-    }
-    
-    .nav-list {
-        flex-direction: column;
-        gap: 1rem;
-    }
-    
-    .testimonial-list {
-        flex-direction: column;
-    }
-    
-    .feature-grid {
-        grid-template-columns: 1fr;
+impl fmt::Write for Discard {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
     }
 }
-"#;
 
 fn html_tokenizer_small(c: &mut Criterion) {
     c.bench_function("html_tokenizer_small", |b| {
@@ -383,6 +111,68 @@ fn html_parser_large(c: &mut Criterion) {
     });
 }
 
+fn html_serialize_large_text(c: &mut Criterion) {
+    let html = format!("<article><p>{}</p></article>", "lorem ipsum dolor sit amet ".repeat(200_000));
+    let nodes = HtmlParser::new(&html).parse();
+    c.bench_function("html_serialize_large_text", |b| {
+        b.iter(|| {
+            let serialized = Serializer::default().serialize(black_box(&nodes));
+            black_box(serialized);
+        })
+    });
+}
+
+/// Peak memory for a book-length text node with entities in it: parsing,
+/// serializing and reading it back in chunks, and building it up a
+/// paragraph at a time, against the same text held as one `String`.
+fn html_large_text_memory(c: &mut Criterion) {
+    let html = format!("<article><p>{}</p></article>", "lorem &amp; ipsum &lt;dolor&gt; sit amet ".repeat(200_000));
+    let (nodes, parse_peak) = ALLOC.peak_during(|| HtmlParser::new(&html).parse());
+    let flat: Vec<Node> = nodes.iter().map(|node| {
+        let mut node = node.clone();
+        if let Node::Element(article) = &mut node
+            && let Node::Element(p) = &mut article.children[0]
+        {
+            p.children = vec![Node::Text(Text::from(p.text_content()))];
+        }
+        node
+    }).collect();
+    let serialize = |nodes: &[Node]| Serializer::default().write(nodes, &mut Discard, None).unwrap();
+    let (_, serialize_peak) = ALLOC.peak_during(|| serialize(&nodes));
+    let (_, flat_serialize_peak) = ALLOC.peak_during(|| serialize(&flat));
+    let (_, text_peak) = ALLOC.peak_during(|| nodes[0].write_text_content(&mut Discard, None).unwrap());
+    let paragraph = "lorem ipsum dolor sit amet ".repeat(150);
+    let (_, append_peak) = ALLOC.peak_during(|| {
+        let mut text = Text::new();
+        for _ in 0..2_000 {
+            text.push_str(&paragraph);
+        }
+        black_box(text)
+    });
+    let (_, flat_append_peak) = ALLOC.peak_during(|| {
+        let mut text = String::new();
+        for _ in 0..2_000 {
+            text.push_str(&paragraph);
+        }
+        black_box(text)
+    });
+    let kib = |bytes: usize| bytes / 1024;
+    println!(
+        "html_large_text_memory: {} KiB of HTML; peak KiB: parse {}, serialize {} (one String: {}), \
+         write_text_content {}, append {} (one String: {})",
+        kib(html.len()),
+        kib(parse_peak),
+        kib(serialize_peak),
+        kib(flat_serialize_peak),
+        kib(text_peak),
+        kib(append_peak),
+        kib(flat_append_peak)
+    );
+    c.bench_function("html_large_text_memory", |b| {
+        b.iter(|| serialize(black_box(&nodes)))
+    });
+}
+
 fn css_tokenizer_small(c: &mut Criterion) {
     c.bench_function("css_tokenizer_small", |b| {
         b.iter(|| {
@@ -423,15 +213,51 @@ fn css_parser_large(c: &mut Criterion) {
     });
 }
 
+fn css_minify_large(c: &mut Criterion) {
+    let minified = minify_css(LARGE_CSS);
+    println!(
+        "css_minify_large: {} -> {} bytes ({:.1}% smaller)",
+        LARGE_CSS.len(),
+        minified.len(),
+        100.0 * (1.0 - minified.len() as f64 / LARGE_CSS.len() as f64)
+    );
+    c.bench_function("css_minify_large", |b| {
+        b.iter(|| {
+            let minified = minify_css(black_box(LARGE_CSS));
+            black_box(minified);
+        })
+    });
+}
+
+fn html_minify_large(c: &mut Criterion) {
+    let minified = minify_html(LARGE_HTML);
+    println!(
+        "html_minify_large: {} -> {} bytes ({:.1}% smaller)",
+        LARGE_HTML.len(),
+        minified.len(),
+        100.0 * (1.0 - minified.len() as f64 / LARGE_HTML.len() as f64)
+    );
+    c.bench_function("html_minify_large", |b| {
+        b.iter(|| {
+            let minified = minify_html(black_box(LARGE_HTML));
+            black_box(minified);
+        })
+    });
+}
+
 criterion_group!(
     benches,
     html_tokenizer_small,
     html_tokenizer_large,
     html_parser_small,
     html_parser_large,
+    html_serialize_large_text,
+    html_large_text_memory,
+    html_minify_large,
     css_tokenizer_small,
     css_tokenizer_large,
     css_parser_small,
-    css_parser_large
+    css_parser_large,
+    css_minify_large
 );
 criterion_main!(benches);
\ No newline at end of file
@@ -79,8 +79,8 @@ fn main() {
         println!("\nRule {}:", i + 1);
         println!("  Selectors: {:?}", rule.selectors);
         println!("  Declarations:");
-        for (property, value) in &rule.declarations {
-            println!("    {}: {}", property, value);
+        for declaration in &rule.declarations {
+            println!("    {}: {}", declaration.property, declaration.value);
         }
     }
 }
@@ -107,5 +107,8 @@ fn print_node(node: &Node, indent: usize) {
         Node::Comment(comment) => {
             println!("{}Comment: {:?}", indent_str, comment);
         }
+        Node::Doctype(doctype) => {
+            println!("{}Doctype: {:?}", indent_str, doctype);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Builds and tests each feature on its own, plus the defaults, so a change
+# that only compiles with every feature enabled is caught.
+set -e
+
+for features in "" html-dom css-ast matching serialize extract locations serde "html-dom,css-ast"; do
+    echo "== features: ${features:-<none>}"
+    cargo clippy --no-default-features --features "$features" --all-targets -- -D warnings
+    cargo test --no-default-features --features "$features"
+done
+
+echo "== default features"
+cargo clippy --all-targets -- -D warnings
+cargo test
+echo "== all features"
+cargo clippy --all-features --all-targets -- -D warnings
+cargo test --all-features
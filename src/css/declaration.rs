@@ -0,0 +1,532 @@
+use crate::css::parser::Rule;
+use std::sync::OnceLock;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A single `property: value` pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Declaration {
+    pub property: String,
+    pub value: String,
+    pub important: bool,
+}
+
+impl Declaration {
+    pub fn new(property: impl Into<String>, value: impl Into<String>) -> Self {
+        Self { property: property.into(), value: value.into(), important: false }
+    }
+}
+
+/// Declarations in source order, duplicates included, with lookups by
+/// property that ignore ASCII case (`Color` finds `color`). Custom
+/// properties are case-sensitive, as in CSS: `--Accent` and `--accent` are
+/// different variables.
+///
+/// Iteration yields the declarations as written, original casing included.
+/// `get` goes through an index built on first use and dropped by every
+/// mutation.
+#[derive(Clone, Default)]
+pub struct DeclarationList {
+    declarations: Vec<Declaration>,
+    /// Lookup key of each property to the index of its last declaration.
+    index: OnceLock<HashMap<String, usize>>,
+}
+
+impl DeclarationList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.declarations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.declarations.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Declaration> {
+        self.declarations.iter()
+    }
+
+    /// Returns the last declaration of `property`, which is the one that
+    /// applies among equally important duplicates.
+    pub fn get(&self, property: &str) -> Option<&Declaration> {
+        let index = self.index.get_or_init(|| {
+            let mut index = HashMap::with_capacity(self.declarations.len());
+            for (position, declaration) in self.declarations.iter().enumerate() {
+                index.insert(lookup_key(&declaration.property).into_owned(), position);
+            }
+            index
+        });
+        index.get(lookup_key(property).as_ref()).map(|&position| &self.declarations[position])
+    }
+
+    /// Returns every declaration of `property` in source order, such as the
+    /// fallbacks in `display: flex; display: grid`.
+    pub fn get_all<'s>(&'s self, property: &str) -> impl Iterator<Item = &'s Declaration> + use<'s> {
+        let key = lookup_key(property).into_owned();
+        self.declarations.iter().filter(move |declaration| lookup_key(&declaration.property) == key)
+    }
+
+    pub fn push(&mut self, declaration: Declaration) {
+        self.index.take();
+        self.declarations.push(declaration);
+    }
+
+    /// Replaces every declaration of its property with `declaration`, at
+    /// the position of the first one, or appends it if there is none.
+    pub fn set(&mut self, declaration: Declaration) {
+        self.index.take();
+        let key = lookup_key(&declaration.property).into_owned();
+        let Some(first) = self.declarations.iter().position(|d| lookup_key(&d.property) == key) else {
+            self.declarations.push(declaration);
+            return;
+        };
+        self.declarations[first] = declaration;
+        let mut position = 0;
+        self.declarations.retain(|d| {
+            position += 1;
+            position <= first + 1 || lookup_key(&d.property) != key
+        });
+    }
+
+    /// Removes every declaration of `property` and returns how many there
+    /// were.
+    pub fn remove(&mut self, property: &str) -> usize {
+        self.index.take();
+        let key = lookup_key(property);
+        let before = self.declarations.len();
+        self.declarations.retain(|declaration| lookup_key(&declaration.property) != key);
+        before - self.declarations.len()
+    }
+
+    /// Gives mutable access to the declarations, in source order. The
+    /// index is rebuilt on the next lookup, so renaming a property is fine.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Declaration> {
+        self.index.take();
+        self.declarations.iter_mut()
+    }
+
+    pub fn into_vec(self) -> Vec<Declaration> {
+        self.declarations
+    }
+}
+
+/// Lower-cases `property` unless it is a custom property.
+pub(crate) fn lookup_key(property: &str) -> std::borrow::Cow<'_, str> {
+    if property.starts_with("--") || !property.bytes().any(|b| b.is_ascii_uppercase()) {
+        property.into()
+    } else {
+        property.to_ascii_lowercase().into()
+    }
+}
+
+impl fmt::Debug for DeclarationList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.declarations).finish()
+    }
+}
+
+impl PartialEq for DeclarationList {
+    fn eq(&self, other: &Self) -> bool {
+        self.declarations == other.declarations
+    }
+}
+
+impl Eq for DeclarationList {}
+
+impl PartialEq<Vec<Declaration>> for DeclarationList {
+    fn eq(&self, other: &Vec<Declaration>) -> bool {
+        &self.declarations == other
+    }
+}
+
+/// Serialized as a plain sequence of declarations.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeclarationList {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.declarations.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeclarationList {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Declaration>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl From<Vec<Declaration>> for DeclarationList {
+    fn from(declarations: Vec<Declaration>) -> Self {
+        Self { declarations, index: OnceLock::new() }
+    }
+}
+
+impl FromIterator<Declaration> for DeclarationList {
+    fn from_iter<I: IntoIterator<Item = Declaration>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+impl<'a> IntoIterator for &'a DeclarationList {
+    type Item = &'a Declaration;
+    type IntoIter = std::slice::Iter<'a, Declaration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for DeclarationList {
+    type Item = Declaration;
+    type IntoIter = std::vec::IntoIter<Declaration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.declarations.into_iter()
+    }
+}
+
+/// Shorthands expanding to top, right, bottom and left longhands from one
+/// to four values.
+const BOX_SHORTHANDS: &[(&str, [&str; 4])] = &[
+    ("margin", ["margin-top", "margin-right", "margin-bottom", "margin-left"]),
+    ("padding", ["padding-top", "padding-right", "padding-bottom", "padding-left"]),
+    ("inset", ["top", "right", "bottom", "left"]),
+    ("border-width", ["border-top-width", "border-right-width", "border-bottom-width", "border-left-width"]),
+    ("border-style", ["border-top-style", "border-right-style", "border-bottom-style", "border-left-style"]),
+    ("border-color", ["border-top-color", "border-right-color", "border-bottom-color", "border-left-color"]),
+];
+
+/// Shorthands expanding to two longhands from one or two values.
+const PAIR_SHORTHANDS: &[(&str, [&str; 2])] = &[
+    ("gap", ["row-gap", "column-gap"]),
+    ("overflow", ["overflow-x", "overflow-y"]),
+];
+
+impl Rule {
+    /// Returns the rule's final declarations as longhands, sorted by
+    /// property. Property names ignore ASCII case and come out lower-cased,
+    /// except custom properties.
+    ///
+    /// With `vars`, `var(--name)` and `var(--name, fallback)` references
+    /// are substituted from it (keys include the `--`); a declaration
+    /// referencing an unknown variable without a fallback is dropped.
+    /// Known shorthands (`margin`, `padding`, `inset`, `border-width`,
+    /// `border-style`, `border-color`, `gap`, `overflow`) are expanded.
+    ///
+    /// Among declarations setting the same longhand, directly or through a
+    /// shorthand, an `!important` one beats a normal one and otherwise the
+    /// later one in source order wins.
+    pub fn effective_declarations(
+        &self,
+        vars: Option<&HashMap<String, String>>,
+    ) -> impl Iterator<Item = Declaration> + use<> {
+        let mut resolved: BTreeMap<String, Declaration> = BTreeMap::new();
+
+        for Declaration { property, value, important } in &self.declarations {
+            let mut value = value.clone();
+            if let Some(vars) = vars {
+                match substitute_vars(&value, vars, 0) {
+                    Some(substituted) => value = substituted,
+                    None => continue,
+                }
+            }
+            let important = *important;
+            let property = lookup_key(property).into_owned();
+            let expanded = expand_shorthand(&property, &value).unwrap_or_else(|| vec![(property, value)]);
+            for (property, value) in expanded {
+                if resolved.get(&property).is_some_and(|existing| existing.important && !important) {
+                    continue;
+                }
+                let declaration = Declaration { property: property.clone(), value, important };
+                resolved.insert(property, declaration);
+            }
+        }
+
+        resolved.into_values()
+    }
+}
+
+/// Expands a known shorthand into `(longhand, value)` pairs, or returns
+/// `None` for other properties and for values with the wrong number of
+/// parts.
+fn expand_shorthand(property: &str, value: &str) -> Option<Vec<(String, String)>> {
+    let parts = split_top_level(value);
+
+    if let Some((_, longhands)) = BOX_SHORTHANDS.iter().find(|(name, _)| *name == property) {
+        let [top, right, bottom, left] = match parts[..] {
+            [all] => [all, all, all, all],
+            [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+            [top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+            [top, right, bottom, left] => [top, right, bottom, left],
+            _ => return None,
+        };
+        let values = [top, right, bottom, left];
+        return Some(longhands.iter().zip(values).map(|(l, v)| (l.to_string(), v.to_string())).collect());
+    }
+
+    if let Some((_, longhands)) = PAIR_SHORTHANDS.iter().find(|(name, _)| *name == property) {
+        let values = match parts[..] {
+            [both] => [both, both],
+            [first, second] => [first, second],
+            _ => return None,
+        };
+        return Some(longhands.iter().zip(values).map(|(l, v)| (l.to_string(), v.to_string())).collect());
+    }
+
+    None
+}
+
+/// Splits a value on whitespace outside parentheses, so `calc(1px + 2px)`
+/// stays one part.
+fn split_top_level(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (index, ch) in value.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if ch.is_whitespace() && depth == 0 => {
+                if let Some(begin) = start.take() {
+                    parts.push(&value[begin..index]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(begin) = start {
+        parts.push(&value[begin..]);
+    }
+    parts
+}
+
+/// Replaces every `var(...)` in `value`, or returns `None` if one names an
+/// unknown variable and has no fallback, or variables nest too deeply (as
+/// cyclic ones do).
+fn substitute_vars(value: &str, vars: &HashMap<String, String>, depth: usize) -> Option<String> {
+    const MAX_DEPTH: usize = 32;
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("var(") {
+        out.push_str(&rest[..start]);
+        let inner_start = start + "var(".len();
+
+        // Find the matching ')'
+        let mut open = 1;
+        let mut end = None;
+        for (index, ch) in rest[inner_start..].char_indices() {
+            match ch {
+                '(' => open += 1,
+                ')' => {
+                    open -= 1;
+                    if open == 0 {
+                        end = Some(inner_start + index);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let end = end?;
+
+        let inner = &rest[inner_start..end];
+        let (name, fallback) = match inner.split_once(',') {
+            Some((name, fallback)) => (name.trim(), Some(fallback.trim())),
+            None => (inner.trim(), None),
+        };
+        match (vars.get(name), fallback) {
+            (Some(replacement), _) => out.push_str(&substitute_vars(replacement, vars, depth + 1)?),
+            (None, Some(fallback)) => out.push_str(&substitute_vars(fallback, vars, depth + 1)?),
+            (None, None) => return None,
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+
+    fn effective(css: &str, vars: Option<&HashMap<String, String>>) -> Vec<(String, String)> {
+        CssParser::new(css).parse()[0]
+            .effective_declarations(vars)
+            .map(|declaration| (declaration.property, declaration.value))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(p, v)| (p.to_string(), v.to_string())).collect()
+    }
+
+    fn list(css: &str) -> DeclarationList {
+        CssParser::new(css).parse_inline_style()
+    }
+
+    #[test]
+    fn test_lookup_ignores_ascii_case() {
+        let declarations = list("Color: red; FONT-size: 2em; --Accent: teal");
+
+        assert_eq!(declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+        assert_eq!(declarations.get("COLOR").map(|d| d.property.as_str()), Some("Color"));
+        assert_eq!(declarations.get("font-Size").map(|d| d.value.as_str()), Some("2em"));
+        assert_eq!(declarations.get("--Accent").map(|d| d.value.as_str()), Some("teal"));
+        assert!(declarations.get("--accent").is_none());
+        assert!(declarations.get("margin").is_none());
+
+        let properties: Vec<_> = declarations.iter().map(|d| d.property.as_str()).collect();
+        assert_eq!(properties, vec!["Color", "FONT-size", "--Accent"]);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let declarations = list("display: flex; color: red; DISPLAY: grid; display: -webkit-box");
+
+        assert_eq!(declarations.get("display").map(|d| d.value.as_str()), Some("-webkit-box"));
+        let values: Vec<_> = declarations.get_all("Display").map(|d| d.value.as_str()).collect();
+        assert_eq!(values, vec!["flex", "grid", "-webkit-box"]);
+        assert_eq!(declarations.get_all("width").count(), 0);
+    }
+
+    #[test]
+    fn test_mutation_invalidates_index() {
+        let mut declarations = list("color: red; margin: 0");
+        assert_eq!(declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+
+        declarations.push(Declaration::new("COLOR", "blue"));
+        assert_eq!(declarations.get("color").map(|d| d.value.as_str()), Some("blue"));
+
+        assert_eq!(declarations.remove("Color"), 2);
+        assert!(declarations.get("color").is_none());
+        assert_eq!(declarations.get("margin").map(|d| d.value.as_str()), Some("0"));
+
+        for declaration in declarations.iter_mut() {
+            declaration.property = "padding".to_string();
+        }
+        assert!(declarations.get("margin").is_none());
+        assert_eq!(declarations.get("padding").map(|d| d.value.as_str()), Some("0"));
+        assert_eq!(declarations, vec![Declaration::new("padding", "0")]);
+    }
+
+    #[test]
+    fn test_shorthand_duplicate_and_var() {
+        let vars = HashMap::from([("--accent".to_string(), "teal".to_string())]);
+        let css = "a { margin: 1px 2px; color: red; color: blue; \
+                   border-color: var(--accent); padding: var(--pad, 4px); margin-left: 0 }";
+
+        assert_eq!(
+            effective(css, Some(&vars)),
+            pairs(&[
+                ("border-bottom-color", "teal"),
+                ("border-left-color", "teal"),
+                ("border-right-color", "teal"),
+                ("border-top-color", "teal"),
+                ("color", "blue"),
+                ("margin-bottom", "1px"),
+                ("margin-left", "0"),
+                ("margin-right", "2px"),
+                ("margin-top", "1px"),
+                ("padding-bottom", "4px"),
+                ("padding-left", "4px"),
+                ("padding-right", "4px"),
+                ("padding-top", "4px"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_effective_declarations_ignore_case() {
+        let css = "a { Color: red; color: blue; MARGIN: 1px; margin-top: 2px; --Accent: teal }";
+
+        assert_eq!(
+            effective(css, None),
+            pairs(&[
+                ("--Accent", "teal"),
+                ("color", "blue"),
+                ("margin-bottom", "1px"),
+                ("margin-left", "1px"),
+                ("margin-right", "1px"),
+                ("margin-top", "2px"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unresolved_var_and_no_map() {
+        let css = "a { width: var(--missing); gap: 1px calc(2px + 3px) }";
+
+        assert_eq!(
+            effective(css, Some(&HashMap::new())),
+            pairs(&[("column-gap", "calc(2px + 3px)"), ("row-gap", "1px")])
+        );
+        assert_eq!(
+            effective(css, None),
+            pairs(&[
+                ("column-gap", "calc(2px + 3px)"),
+                ("row-gap", "1px"),
+                ("width", "var(--missing)"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_cyclic_var_is_dropped() {
+        let vars = HashMap::from([("--a".to_string(), "var(--a)".to_string())]);
+
+        assert!(effective("a { color: var(--a) }", Some(&vars)).is_empty());
+    }
+
+    #[test]
+    fn test_later_declaration_wins() {
+        let css = "a { padding-top: 9px; padding: 1px 2px; color: red; color: blue }";
+
+        assert_eq!(
+            effective(css, None),
+            pairs(&[
+                ("color", "blue"),
+                ("padding-bottom", "1px"),
+                ("padding-left", "2px"),
+                ("padding-right", "2px"),
+                ("padding-top", "1px"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_set_replaces_in_place() {
+        let mut declarations = list("color: red; margin: 0; COLOR: blue !important");
+
+        declarations.set(Declaration::new("color", "green"));
+        declarations.set(Declaration::new("padding", "1px"));
+        assert_eq!(
+            declarations,
+            vec![Declaration::new("color", "green"), Declaration::new("margin", "0"), Declaration::new("padding", "1px")]
+        );
+    }
+
+    #[test]
+    fn test_important_shorthand_beats_longhand() {
+        let css = "a { padding: 0 !important; padding-top: 9px }";
+
+        assert_eq!(
+            effective(css, None),
+            pairs(&[
+                ("padding-bottom", "0"),
+                ("padding-left", "0"),
+                ("padding-right", "0"),
+                ("padding-top", "0"),
+            ])
+        );
+    }
+}
@@ -0,0 +1,940 @@
+use crate::css::parser::StylesheetItem;
+use crate::css::tokenizer::{unescape, CssToken, CssTokenizer};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::location::Span;
+
+/// A comma-separated list of media queries, as in `@media` or the `media`
+/// attribute. It matches when any query does; an empty list matches
+/// everything.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaQueryList {
+    pub queries: Vec<MediaQuery>,
+}
+
+/// One media query: `screen and (min-width: 600px)`, `not print` or a bare
+/// condition such as `(hover)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    pub modifier: Option<MediaModifier>,
+    /// The lower-cased media type (`screen`, `print`, `all`), if given.
+    pub media_type: Option<String>,
+    pub condition: Option<MediaCondition>,
+}
+
+/// The `not` or `only` before a media type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaModifier {
+    Not,
+    Only,
+}
+
+/// A media condition: features combined with `not`, `and` and `or`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+}
+
+/// A parenthesized media feature test. Names are lower-cased and keep any
+/// `min-` or `max-` prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaFeature {
+    /// `(hover)`: the feature has a value other than zero or `none`.
+    Boolean(String),
+    /// `(min-width: 600px)`.
+    Plain { name: String, value: MediaValue },
+    /// `(width >= 600px)`, always with the name on the left: `(600px <
+    /// width)` is `width > 600px`, and `(400px < width < 800px)` is the
+    /// `And` of two ranges.
+    Range { name: String, op: RangeOp, value: MediaValue },
+}
+
+/// The comparison of a range feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl RangeOp {
+    /// The operator with its operands swapped: `<` becomes `>`.
+    fn flipped(self) -> Self {
+        match self {
+            RangeOp::Lt => RangeOp::Gt,
+            RangeOp::Le => RangeOp::Ge,
+            RangeOp::Gt => RangeOp::Lt,
+            RangeOp::Ge => RangeOp::Le,
+            RangeOp::Eq => RangeOp::Eq,
+        }
+    }
+}
+
+/// The value of a media feature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaValue {
+    Number(f64),
+    /// A number with a unit: a length such as `600px`, or a resolution
+    /// such as `2dppx`.
+    Length(Length),
+    /// `16/9`.
+    Ratio(f64, f64),
+    /// A lower-cased keyword such as `landscape` or `dark`.
+    Ident(String),
+}
+
+/// A number with a lower-cased unit, such as `100vw`. A bare `0` has an
+/// empty unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Length {
+    pub value: f64,
+    pub unit: String,
+}
+
+/// What media queries are evaluated against: the device and the user's
+/// preferences.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Environment {
+    /// The lower-cased media type, such as `screen` or `print`.
+    pub media_type: String,
+    /// The viewport width in CSS pixels.
+    pub width: f64,
+    /// The viewport height in CSS pixels.
+    pub height: f64,
+    /// `prefers-color-scheme`.
+    pub prefers_color_scheme: ColorScheme,
+    /// `prefers-reduced-motion: reduce`.
+    pub prefers_reduced_motion: bool,
+    /// `prefers-reduced-transparency: reduce`.
+    pub prefers_reduced_transparency: bool,
+    /// `prefers-contrast`.
+    pub prefers_contrast: Contrast,
+    /// `forced-colors: active`.
+    pub forced_colors: bool,
+}
+
+impl Default for Environment {
+    /// A 1024 by 768 screen with no preferences set.
+    fn default() -> Self {
+        Self {
+            media_type: "screen".to_string(),
+            width: 1024.0,
+            height: 768.0,
+            prefers_color_scheme: ColorScheme::Light,
+            prefers_reduced_motion: false,
+            prefers_reduced_transparency: false,
+            prefers_contrast: Contrast::NoPreference,
+            forced_colors: false,
+        }
+    }
+}
+
+/// A value of `prefers-color-scheme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// A value of `prefers-contrast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Contrast {
+    #[default]
+    NoPreference,
+    More,
+    Less,
+    Custom,
+}
+
+/// The items of a stylesheet grouped by the color scheme they apply to;
+/// see `split_by_color_scheme`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ColorSchemeSplit {
+    pub light: Vec<StylesheetItem>,
+    pub dark: Vec<StylesheetItem>,
+    pub common: Vec<StylesheetItem>,
+}
+
+/// Parses a media query list such as `screen and (min-width: 600px), print`.
+/// Queries that do not parse are left out, as browsers treat them as
+/// `not all`.
+pub fn parse_media_query_list(input: &str) -> MediaQueryList {
+    parse_media_query_list_with_errors(input).0
+}
+
+/// Like `parse_media_query_list`, but also returns a `ParseError` for each
+/// query left out. Spans are relative to `input`.
+pub fn parse_media_query_list_with_errors(input: &str) -> (MediaQueryList, Vec<ParseError>) {
+    let tokens = tokenize(input);
+    let mut list = MediaQueryList::default();
+    let mut errors = Vec::new();
+    for entry in split_on_commas(&tokens) {
+        let mut parser = MediaParser::new(entry, end_span(&tokens));
+        match parser.parse_query() {
+            Some(query) if parser.at_end() => list.queries.push(query),
+            Some(_) => errors.push(parser.error_here("expected `,` or the end of the media query")),
+            None => errors.extend(parser.error),
+        }
+    }
+    (list, errors)
+}
+
+/// Parses a `sizes` attribute, such as `(max-width: 600px) 100vw, 50vw`,
+/// into its entries in source order. Each is a size, with the condition
+/// under which it applies; the last entry usually has none and is the
+/// default. Entries that do not parse, including `calc()` sizes and
+/// negative ones, are left out with a `ParseError` whose span is relative
+/// to `input`.
+pub fn parse_source_sizes(input: &str) -> (Vec<(Option<MediaCondition>, Length)>, Vec<ParseError>) {
+    let tokens = tokenize(input);
+    let mut sizes = Vec::new();
+    let mut errors = Vec::new();
+    for entry in split_on_commas(&tokens) {
+        let Some(((last, last_span), condition)) = entry.split_last() else {
+            errors.push(ParseError::new(end_span(&tokens), ParseErrorKind::UnexpectedToken, "empty `sizes` entry"));
+            continue;
+        };
+        let length = match *last {
+            CssToken::Dimension { value, ref unit, .. } if value >= 0.0 => {
+                Length { value, unit: unescape(unit).to_ascii_lowercase() }
+            }
+            CssToken::Number { value, .. } if value == 0.0 => Length { value, unit: String::new() },
+            _ => {
+                let message = "expected a non-negative length at the end of the `sizes` entry";
+                errors.push(ParseError::new(*last_span, ParseErrorKind::UnexpectedToken, message));
+                continue;
+            }
+        };
+        if condition.is_empty() {
+            sizes.push((None, length));
+            continue;
+        }
+        let mut parser = MediaParser::new(condition, *last_span);
+        match parser.parse_condition(true) {
+            Some(condition) if parser.at_end() => sizes.push((Some(condition), length)),
+            Some(_) => errors.push(parser.error_here("expected the size after the media condition")),
+            None => errors.extend(parser.error),
+        }
+    }
+    (sizes, errors)
+}
+
+/// Splits the items of a stylesheet by the color scheme they apply to.
+///
+/// `@media` blocks and `@import`s whose media query cannot match with a
+/// dark `prefers-color-scheme`, whatever the rest of the environment, go
+/// to `light`; those that cannot match with a light one go to `dark`.
+/// Everything else, including rules outside `@media`, goes to `common`.
+/// Items keep their source order and their media queries.
+pub fn split_by_color_scheme(items: &[StylesheetItem]) -> ColorSchemeSplit {
+    let mut split = ColorSchemeSplit::default();
+    for item in items {
+        let query = match item {
+            StylesheetItem::Media(media) => Some(media.query.as_str()),
+            StylesheetItem::Import(import) => import.media_query.as_deref(),
+            _ => None,
+        };
+        let list = query.map(parse_media_query_list).unwrap_or_default();
+        let in_light = list.could_match(ColorScheme::Light);
+        let in_dark = list.could_match(ColorScheme::Dark);
+        let group = match (in_light, in_dark) {
+            (true, false) => &mut split.light,
+            (false, true) => &mut split.dark,
+            _ => &mut split.common,
+        };
+        group.push(item.clone());
+    }
+    split
+}
+
+impl MediaQueryList {
+    /// Returns whether the list matches in `environment`: an empty list
+    /// always does, otherwise any of its queries must.
+    pub fn matches(&self, environment: &Environment) -> bool {
+        self.queries.is_empty() || self.queries.iter().any(|query| query.matches(environment))
+    }
+
+    /// Returns whether the list can match with the color scheme `scheme`,
+    /// for some values of the other features.
+    fn could_match(&self, scheme: ColorScheme) -> bool {
+        let feature = |feature: &MediaFeature| match feature {
+            MediaFeature::Plain { name, value: MediaValue::Ident(value) } if name == "prefers-color-scheme" => {
+                Some(value == scheme.keyword())
+            }
+            MediaFeature::Boolean(name) if name == "prefers-color-scheme" => Some(true),
+            _ => None,
+        };
+        self.queries.is_empty() || self.queries.iter().any(|query| query.evaluate(None, &feature) != Some(false))
+    }
+}
+
+impl MediaQuery {
+    /// Returns whether the query matches in `environment`. Features this
+    /// crate does not know, and lengths in units it cannot convert, do not
+    /// match.
+    pub fn matches(&self, environment: &Environment) -> bool {
+        let feature = |feature: &MediaFeature| Some(environment.matches_feature(feature));
+        self.evaluate(Some(&environment.media_type), &feature) == Some(true)
+    }
+
+    /// Evaluates the query with `feature` telling whether a feature
+    /// matches, `None` when that is not known, and the media type of the
+    /// environment if it is known. The result is `None` if it depends on
+    /// what is not known.
+    fn evaluate(&self, media_type: Option<&str>, feature: &dyn Fn(&MediaFeature) -> Option<bool>) -> Option<bool> {
+        let type_matches = match (self.media_type.as_deref(), media_type) {
+            (None | Some("all"), _) => Some(true),
+            (Some(expected), Some(actual)) => Some(expected == actual),
+            (Some(_), None) => None,
+        };
+        let condition = self.condition.as_ref().map_or(Some(true), |condition| condition.evaluate(feature));
+        let matches = all([type_matches, condition]);
+        if self.modifier == Some(MediaModifier::Not) {
+            matches.map(|matches| !matches)
+        } else {
+            matches
+        }
+    }
+}
+
+impl MediaCondition {
+    /// Returns whether the condition matches in `environment`.
+    pub fn matches(&self, environment: &Environment) -> bool {
+        self.evaluate(&|feature| Some(environment.matches_feature(feature))) == Some(true)
+    }
+
+    fn evaluate(&self, feature: &dyn Fn(&MediaFeature) -> Option<bool>) -> Option<bool> {
+        match self {
+            MediaCondition::Feature(test) => feature(test),
+            MediaCondition::Not(condition) => condition.evaluate(feature).map(|matches| !matches),
+            MediaCondition::And(conditions) => all(conditions.iter().map(|condition| condition.evaluate(feature))),
+            MediaCondition::Or(conditions) => {
+                let any = all(conditions.iter().map(|condition| condition.evaluate(feature).map(|matches| !matches)));
+                any.map(|none| !none)
+            }
+        }
+    }
+}
+
+/// Whether all of `values` are true: `Some(false)` if one is false, `None`
+/// if none is false but one is not known.
+fn all(values: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut result = Some(true);
+    for value in values {
+        match value {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => {}
+        }
+    }
+    result
+}
+
+impl ColorScheme {
+    fn keyword(self) -> &'static str {
+        match self {
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark",
+        }
+    }
+}
+
+impl Environment {
+    /// Returns whether `feature` matches: the viewport size features, in
+    /// any range syntax, `orientation`, and the user preferences.
+    fn matches_feature(&self, feature: &MediaFeature) -> bool {
+        match feature {
+            MediaFeature::Boolean(name) => match name.as_str() {
+                "width" => self.width != 0.0,
+                "height" => self.height != 0.0,
+                _ => self.keyword(name).is_some_and(|keyword| !matches!(keyword, "no-preference" | "none")),
+            },
+            MediaFeature::Plain { name, value } => {
+                if let Some(size) = name.strip_prefix("min-").and_then(|name| self.size(name)) {
+                    return self.compare(size, RangeOp::Ge, value);
+                }
+                if let Some(size) = name.strip_prefix("max-").and_then(|name| self.size(name)) {
+                    return self.compare(size, RangeOp::Le, value);
+                }
+                if let Some(size) = self.size(name) {
+                    return self.compare(size, RangeOp::Eq, value);
+                }
+                matches!(value, MediaValue::Ident(value) if self.keyword(name) == Some(value.as_str()))
+            }
+            MediaFeature::Range { name, op, value } => self.size(name).is_some_and(|size| self.compare(size, *op, value)),
+        }
+    }
+
+    /// The value of the viewport size feature `name`, in pixels.
+    fn size(&self, name: &str) -> Option<f64> {
+        match name {
+            "width" => Some(self.width),
+            "height" => Some(self.height),
+            _ => None,
+        }
+    }
+
+    /// The current keyword of the discrete feature `name`.
+    fn keyword(&self, name: &str) -> Option<&'static str> {
+        let reduce = |on: bool| if on { "reduce" } else { "no-preference" };
+        Some(match name {
+            "orientation" if self.height >= self.width => "portrait",
+            "orientation" => "landscape",
+            "prefers-color-scheme" => self.prefers_color_scheme.keyword(),
+            "prefers-reduced-motion" => reduce(self.prefers_reduced_motion),
+            "prefers-reduced-transparency" => reduce(self.prefers_reduced_transparency),
+            "prefers-contrast" => match self.prefers_contrast {
+                Contrast::NoPreference => "no-preference",
+                Contrast::More => "more",
+                Contrast::Less => "less",
+                Contrast::Custom => "custom",
+            },
+            "forced-colors" if self.forced_colors => "active",
+            "forced-colors" => "none",
+            _ => return None,
+        })
+    }
+
+    /// Compares `size` with the length `value`, as `size op value`.
+    fn compare(&self, size: f64, op: RangeOp, value: &MediaValue) -> bool {
+        let Some(value) = self.to_px(value) else {
+            return false;
+        };
+        match op {
+            RangeOp::Lt => size < value,
+            RangeOp::Le => size <= value,
+            RangeOp::Gt => size > value,
+            RangeOp::Ge => size >= value,
+            RangeOp::Eq => size == value,
+        }
+    }
+
+    /// Converts a length to pixels, taking `em` and `rem` as 16px.
+    fn to_px(&self, value: &MediaValue) -> Option<f64> {
+        let (value, unit) = match value {
+            MediaValue::Number(value) if *value == 0.0 => return Some(0.0),
+            MediaValue::Length(Length { value, unit }) => (*value, unit.as_str()),
+            _ => return None,
+        };
+        let scale = match unit {
+            "" | "px" => 1.0,
+            "em" | "rem" => 16.0,
+            "vw" => self.width / 100.0,
+            "vh" => self.height / 100.0,
+            "in" => 96.0,
+            "cm" => 96.0 / 2.54,
+            "mm" => 96.0 / 25.4,
+            "pt" => 96.0 / 72.0,
+            "pc" => 16.0,
+            _ => return None,
+        };
+        Some(value * scale)
+    }
+}
+
+/// The tokens of `input` with their spans, leaving out whitespace and
+/// comments, which never matter between the tokens of a media query.
+fn tokenize(input: &str) -> Vec<(CssToken<'_>, Span)> {
+    let mut tokenizer = CssTokenizer::new_with_locations(input);
+    let mut tokens = Vec::new();
+    while let Some(token) = tokenizer.next_token() {
+        if !token.is_trivia() {
+            tokens.push((token, tokenizer.token_span()));
+        }
+    }
+    tokens
+}
+
+/// Splits `tokens` on the commas outside parentheses.
+fn split_on_commas<'t, 'a>(tokens: &'t [(CssToken<'a>, Span)]) -> Vec<&'t [(CssToken<'a>, Span)]> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    let mut entries = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            CssToken::LeftParen | CssToken::Function(_) => depth += 1,
+            CssToken::RightParen => depth = depth.saturating_sub(1),
+            CssToken::Comma if depth == 0 => {
+                entries.push(&tokens[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&tokens[start..]);
+    entries
+}
+
+/// An empty span at the end of the tokens.
+fn end_span(tokens: &[(CssToken<'_>, Span)]) -> Span {
+    let end = tokens.last().map(|(_, span)| span.end).unwrap_or_default();
+    Span { start: end, end }
+}
+
+/// A recursive-descent parser over the tokens of one query or condition.
+/// Parsing methods return `None` after recording the first problem in
+/// `error`.
+struct MediaParser<'t, 'a> {
+    tokens: &'t [(CssToken<'a>, Span)],
+    position: usize,
+    /// Where errors at the end of the tokens are reported.
+    end: Span,
+    error: Option<ParseError>,
+}
+
+impl<'t, 'a> MediaParser<'t, 'a> {
+    fn new(tokens: &'t [(CssToken<'a>, Span)], end: Span) -> Self {
+        Self { tokens, position: 0, end, error: None }
+    }
+
+    fn parse_query(&mut self) -> Option<MediaQuery> {
+        if self.peek_is(CssToken::LeftParen) || (self.peek_keyword("not") && self.peek_at_is(1, CssToken::LeftParen)) {
+            let condition = self.parse_condition(true)?;
+            return Some(MediaQuery { modifier: None, media_type: None, condition: Some(condition) });
+        }
+
+        let modifier = if self.eat_keyword("not") {
+            Some(MediaModifier::Not)
+        } else if self.eat_keyword("only") {
+            Some(MediaModifier::Only)
+        } else {
+            None
+        };
+        let media_type = match self.peek() {
+            Some(CssToken::Ident(name)) if !is_reserved(name) => unescape(name).to_ascii_lowercase(),
+            _ => return self.fail("expected a media type or `(`"),
+        };
+        self.position += 1;
+
+        let condition = if self.eat_keyword("and") { Some(self.parse_condition(false)?) } else { None };
+        Some(MediaQuery { modifier, media_type: Some(media_type), condition })
+    }
+
+    /// Parses `not (...)`, or parenthesized tests joined by `and`, or by
+    /// `or` when `allow_or` is set. The two cannot be mixed without
+    /// parentheses.
+    fn parse_condition(&mut self, allow_or: bool) -> Option<MediaCondition> {
+        if self.eat_keyword("not") {
+            return Some(MediaCondition::Not(Box::new(self.parse_in_parens()?)));
+        }
+
+        let first = self.parse_in_parens()?;
+        let joiner = match self.peek() {
+            Some(CssToken::Ident(word)) if word.eq_ignore_ascii_case("and") => "and",
+            Some(CssToken::Ident(word)) if allow_or && word.eq_ignore_ascii_case("or") => "or",
+            _ => return Some(first),
+        };
+        let mut parts = vec![first];
+        while self.eat_keyword(joiner) {
+            parts.push(self.parse_in_parens()?);
+        }
+        if self.peek_keyword("and") || self.peek_keyword("or") {
+            return self.fail("`and` and `or` cannot be mixed without parentheses");
+        }
+        Some(if joiner == "and" { MediaCondition::And(parts) } else { MediaCondition::Or(parts) })
+    }
+
+    /// Parses `(condition)` or `(feature)`.
+    fn parse_in_parens(&mut self) -> Option<MediaCondition> {
+        if !self.eat(CssToken::LeftParen) {
+            return self.fail("expected `(`");
+        }
+        let condition = if self.peek_is(CssToken::LeftParen) || self.peek_keyword("not") {
+            self.parse_condition(true)?
+        } else {
+            self.parse_feature()?
+        };
+        if !self.eat(CssToken::RightParen) {
+            return self.fail("expected `)`");
+        }
+        Some(condition)
+    }
+
+    /// Parses the inside of `(hover)`, `(min-width: 600px)`, `(width >=
+    /// 600px)` or `(400px < width < 800px)`.
+    fn parse_feature(&mut self) -> Option<MediaCondition> {
+        if let Some(CssToken::Ident(name)) = self.peek() {
+            let name = unescape(name).to_ascii_lowercase();
+            let name_position = self.position;
+            self.position += 1;
+            let feature = if self.peek_is(CssToken::RightParen) {
+                MediaFeature::Boolean(name)
+            } else if self.eat(CssToken::Colon) {
+                let value_position = self.position;
+                let value = self.parse_value()?;
+                if let Some(values) = discrete_values(&name)
+                    && !matches!(&value, MediaValue::Ident(value) if values.contains(&value.as_str()))
+                {
+                    self.position = value_position;
+                    let expected: Vec<_> = values.iter().map(|value| format!("`{}`", value)).collect();
+                    return self.fail(&format!("expected {} after `{}:`", expected.join(" or "), name));
+                }
+                if let Some(base) = name.strip_prefix("min-").or_else(|| name.strip_prefix("max-"))
+                    && discrete_values(base).is_some()
+                {
+                    self.position = name_position;
+                    return self.fail(&format!("`{}` takes no `min-` or `max-` prefix", base));
+                }
+                MediaFeature::Plain { name, value }
+            } else {
+                if discrete_values(&name).is_some() {
+                    return self.fail(&format!("`{}` cannot be compared", name));
+                }
+                let op = self.parse_op()?;
+                MediaFeature::Range { name, op, value: self.parse_value()? }
+            };
+            return Some(MediaCondition::Feature(feature));
+        }
+
+        let low = self.parse_value()?;
+        let low_op = self.parse_op()?;
+        let name = match self.peek() {
+            Some(CssToken::Ident(name)) => unescape(name).to_ascii_lowercase(),
+            _ => return self.fail("expected a media feature name"),
+        };
+        if discrete_values(&name).is_some() {
+            return self.fail(&format!("`{}` cannot be compared", name));
+        }
+        self.position += 1;
+        let lower = MediaFeature::Range { name: name.clone(), op: low_op.flipped(), value: low };
+        if self.peek_is(CssToken::RightParen) {
+            return Some(MediaCondition::Feature(lower));
+        }
+        let high_op = self.parse_op()?;
+        let upper = MediaFeature::Range { name, op: high_op, value: self.parse_value()? };
+        Some(MediaCondition::And(vec![MediaCondition::Feature(lower), MediaCondition::Feature(upper)]))
+    }
+
+    fn parse_op(&mut self) -> Option<RangeOp> {
+        let op = match self.peek() {
+            Some(CssToken::Delim('<')) => RangeOp::Lt,
+            Some(CssToken::Delim('>')) => RangeOp::Gt,
+            Some(CssToken::Delim('=')) => RangeOp::Eq,
+            _ => return self.fail("expected `:` or a comparison"),
+        };
+        self.position += 1;
+        if op == RangeOp::Eq || !self.eat(CssToken::Delim('=')) {
+            return Some(op);
+        }
+        Some(if op == RangeOp::Lt { RangeOp::Le } else { RangeOp::Ge })
+    }
+
+    fn parse_value(&mut self) -> Option<MediaValue> {
+        let value = match self.peek() {
+            Some(&CssToken::Number { value, .. }) => {
+                self.position += 1;
+                if !self.eat(CssToken::Delim('/')) {
+                    return Some(MediaValue::Number(value));
+                }
+                match self.peek() {
+                    Some(&CssToken::Number { value: denominator, .. }) => MediaValue::Ratio(value, denominator),
+                    _ => return self.fail("expected the second number of the ratio"),
+                }
+            }
+            Some(&CssToken::Dimension { value, ref unit, .. }) => {
+                MediaValue::Length(Length { value, unit: unescape(unit).to_ascii_lowercase() })
+            }
+            Some(CssToken::Ident(name)) => MediaValue::Ident(unescape(name).to_ascii_lowercase()),
+            _ => return self.fail("expected a media feature value"),
+        };
+        self.position += 1;
+        Some(value)
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&CssToken<'a>> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn peek_is(&self, expected: CssToken<'_>) -> bool {
+        self.peek_at_is(0, expected)
+    }
+
+    fn peek_at_is(&self, offset: usize, expected: CssToken<'_>) -> bool {
+        self.tokens.get(self.position + offset).is_some_and(|(token, _)| *token == expected)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(CssToken::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat(&mut self, expected: CssToken<'_>) -> bool {
+        let found = self.peek_is(expected);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn error_here(&self, message: &str) -> ParseError {
+        let (span, kind) = match self.tokens.get(self.position) {
+            Some((_, span)) => (*span, ParseErrorKind::UnexpectedToken),
+            None => (self.end, ParseErrorKind::UnexpectedEof),
+        };
+        ParseError::new(span, kind, message)
+    }
+
+    fn fail<T>(&mut self, message: &str) -> Option<T> {
+        if self.error.is_none() {
+            self.error = Some(self.error_here(message));
+        }
+        None
+    }
+}
+
+/// Discrete media features and the keywords they take.
+const DISCRETE_FEATURES: &[(&str, &[&str])] = &[
+    ("orientation", &["portrait", "landscape"]),
+    ("prefers-color-scheme", &["light", "dark"]),
+    ("prefers-reduced-motion", &["no-preference", "reduce"]),
+    ("prefers-reduced-transparency", &["no-preference", "reduce"]),
+    ("prefers-contrast", &["no-preference", "more", "less", "custom"]),
+    ("forced-colors", &["none", "active"]),
+];
+
+/// Returns the keywords of the discrete feature `name`, or `None` if it is
+/// not one.
+fn discrete_values(name: &str) -> Option<&'static [&'static str]> {
+    DISCRETE_FEATURES.iter().find(|(feature, _)| *feature == name).map(|(_, values)| *values)
+}
+
+/// Keywords that cannot be a media type.
+fn is_reserved(name: &str) -> bool {
+    ["not", "only", "and", "or", "layer"].iter().any(|word| name.eq_ignore_ascii_case(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+
+    fn feature(name: &str, value: MediaValue) -> MediaCondition {
+        MediaCondition::Feature(MediaFeature::Plain { name: name.to_string(), value })
+    }
+
+    fn range(name: &str, op: RangeOp, value: MediaValue) -> MediaCondition {
+        MediaCondition::Feature(MediaFeature::Range { name: name.to_string(), op, value })
+    }
+
+    fn px(value: f64) -> MediaValue {
+        MediaValue::Length(Length { value, unit: "px".to_string() })
+    }
+
+    #[test]
+    fn test_media_query_list() {
+        let list =
+            parse_media_query_list("only Screen and (min-width: 600px) and (orientation: Landscape), not print, (hover)");
+
+        assert_eq!(
+            list.queries,
+            vec![
+                MediaQuery {
+                    modifier: Some(MediaModifier::Only),
+                    media_type: Some("screen".to_string()),
+                    condition: Some(MediaCondition::And(vec![
+                        feature("min-width", px(600.0)),
+                        feature("orientation", MediaValue::Ident("landscape".to_string())),
+                    ])),
+                },
+                MediaQuery {
+                    modifier: Some(MediaModifier::Not),
+                    media_type: Some("print".to_string()),
+                    condition: None,
+                },
+                MediaQuery {
+                    modifier: None,
+                    media_type: None,
+                    condition: Some(MediaCondition::Feature(MediaFeature::Boolean("hover".to_string()))),
+                },
+            ]
+        );
+        assert_eq!(parse_media_query_list(""), MediaQueryList::default());
+    }
+
+    #[test]
+    fn test_conditions_and_ranges() {
+        let list = parse_media_query_list(
+            "not (color), ((hover) or (aspect-ratio: 16/9)), (width >= 600px), (400px < width <= 800px)",
+        );
+        let conditions: Vec<_> = list.queries.into_iter().map(|query| query.condition.unwrap()).collect();
+
+        assert_eq!(
+            conditions,
+            vec![
+                MediaCondition::Not(Box::new(MediaCondition::Feature(MediaFeature::Boolean("color".to_string())))),
+                MediaCondition::Or(vec![
+                    MediaCondition::Feature(MediaFeature::Boolean("hover".to_string())),
+                    feature("aspect-ratio", MediaValue::Ratio(16.0, 9.0)),
+                ]),
+                range("width", RangeOp::Ge, px(600.0)),
+                MediaCondition::And(vec![
+                    range("width", RangeOp::Gt, px(400.0)),
+                    range("width", RangeOp::Le, px(800.0)),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_queries_are_dropped() {
+        let (list, errors) =
+            parse_media_query_list_with_errors("screen and (color) or (hover), print, (min-width 600px), screen and");
+
+        assert_eq!(list.queries.len(), 1);
+        assert_eq!(list.queries[0].media_type.as_deref(), Some("print"));
+        let messages: Vec<_> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["expected `,` or the end of the media query", "expected `:` or a comparison", "expected `(`"]
+        );
+        assert_eq!(errors[1].span.byte_range(), 49..54);
+        assert_eq!(errors[2].kind, ParseErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_source_sizes() {
+        let (sizes, errors) = parse_source_sizes("(max-width: 600px) 100vw, 50VW");
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            sizes,
+            vec![
+                (Some(feature("max-width", px(600.0))), Length { value: 100.0, unit: "vw".to_string() }),
+                (None, Length { value: 50.0, unit: "vw".to_string() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_source_sizes_are_skipped() {
+        let (sizes, errors) = parse_source_sizes(
+            "(max-width 600px) 100vw, (min-width: 40em) calc(100vw - 2em), , (min-width: 30em) 80vw, 0",
+        );
+
+        assert_eq!(
+            sizes,
+            vec![
+                (
+                    Some(feature("min-width", MediaValue::Length(Length { value: 30.0, unit: "em".to_string() }))),
+                    Length { value: 80.0, unit: "vw".to_string() },
+                ),
+                (None, Length { value: 0.0, unit: String::new() }),
+            ]
+        );
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].message, "expected `:` or a comparison");
+        assert_eq!(errors[1].message, "expected a non-negative length at the end of the `sizes` entry");
+    }
+
+    #[test]
+    fn test_discrete_features_take_known_values() {
+        let (list, errors) = parse_media_query_list_with_errors(
+            "(prefers-color-scheme: dark), (prefers-color-scheme: purple), (prefers-reduced-motion), \
+             (min-prefers-contrast: more), (forced-colors > none)",
+        );
+
+        assert_eq!(
+            list.queries.iter().map(|query| query.condition.clone().unwrap()).collect::<Vec<_>>(),
+            vec![
+                feature("prefers-color-scheme", MediaValue::Ident("dark".to_string())),
+                MediaCondition::Feature(MediaFeature::Boolean("prefers-reduced-motion".to_string())),
+            ]
+        );
+        let messages: Vec<_> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected `light` or `dark` after `prefers-color-scheme:`",
+                "`prefers-contrast` takes no `min-` or `max-` prefix",
+                "`forced-colors` cannot be compared",
+            ]
+        );
+        assert_eq!(errors[0].span.byte_range(), 53..59);
+    }
+
+    #[test]
+    fn test_evaluate_preferences() {
+        let dark_motion = parse_media_query_list("screen and (prefers-color-scheme: dark) and (prefers-reduced-motion: reduce)");
+        let no_motion = parse_media_query_list("not all and (prefers-reduced-motion)");
+        let contrast = parse_media_query_list("(prefers-contrast: more) or (forced-colors: active)");
+        let mut environment = Environment::default();
+
+        assert!(!dark_motion.matches(&environment));
+        assert!(no_motion.matches(&environment));
+        assert!(!contrast.matches(&environment));
+
+        environment.prefers_color_scheme = ColorScheme::Dark;
+        environment.prefers_reduced_motion = true;
+        environment.prefers_contrast = Contrast::More;
+        assert!(dark_motion.matches(&environment));
+        assert!(!no_motion.matches(&environment));
+        assert!(contrast.matches(&environment));
+
+        environment.media_type = "print".to_string();
+        assert!(!dark_motion.matches(&environment));
+    }
+
+    #[test]
+    fn test_evaluate_viewport() {
+        let environment = Environment { width: 800.0, height: 600.0, ..Default::default() };
+        let matches = |query: &str| parse_media_query_list(query).matches(&environment);
+
+        assert!(matches("(min-width: 50em) and (max-width: 800px)"));
+        assert!(matches("(400px < width <= 800px), print"));
+        assert!(matches("(orientation: landscape)"));
+        assert!(!matches("(width > 800px)"));
+        assert!(!matches("(min-width: 10foo)"));
+        assert!(!matches("(hover)"));
+        assert!(matches(""));
+    }
+
+    #[test]
+    fn test_split_by_color_scheme() {
+        let css = "a { color: black }\n\
+                   @media (prefers-color-scheme: dark) { a { color: white } }\n\
+                   @media screen and (prefers-color-scheme: light) { a { color: navy } }\n\
+                   @media not all and (prefers-color-scheme: dark) { b { color: gray } }\n\
+                   @media (min-width: 600px) { a { margin: 0 } }\n\
+                   @media (prefers-color-scheme: dark), print { c { color: silver } }\n\
+                   @import url(dark.css) (prefers-color-scheme: dark);";
+        let items = CssParser::new(css).parse_stylesheet();
+
+        let split = split_by_color_scheme(&items);
+
+        let queries = |items: &[StylesheetItem]| -> Vec<String> {
+            items
+                .iter()
+                .map(|item| match item {
+                    StylesheetItem::Media(media) => media.query.clone(),
+                    StylesheetItem::Import(import) => format!("import {}", import.media_query.as_deref().unwrap_or("")),
+                    _ => "rule".to_string(),
+                })
+                .collect()
+        };
+        assert_eq!(queries(&split.dark), ["(prefers-color-scheme: dark)", "import (prefers-color-scheme: dark)"]);
+        assert_eq!(
+            queries(&split.light),
+            ["screen and (prefers-color-scheme: light)", "not all and (prefers-color-scheme: dark)"]
+        );
+        assert_eq!(queries(&split.common), ["rule", "(min-width: 600px)", "(prefers-color-scheme: dark), print"]);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::css::report::{strip_vendor_prefix, DECLARATION_AT_RULES};
+use crate::css::tokenizer::{CssToken, CssTokenizer};
+use crate::output::{write_limited, OutputError};
+use std::fmt;
+
+/// What the tokens inside a block are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Block {
+    Rules,
+    Declarations,
+}
+
+/// What the tokens since the last rule in a `Block::Rules` belong to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prelude {
+    None,
+    Selector,
+    AtRule { declarations: bool },
+}
+
+/// Where the minifier is when it meets the next token.
+#[derive(Debug, Clone, Copy)]
+struct Context {
+    block: Block,
+    prelude: Prelude,
+    /// Inside a declaration value, after its `:`.
+    in_value: bool,
+    /// Open `(`, `[` and functions in the current prelude or value.
+    depth: usize,
+    /// Inside the `[...]` of an attribute selector.
+    in_attribute: bool,
+}
+
+/// Removes the whitespace and comments of the stylesheet `input` that do
+/// not change its meaning.
+///
+/// Tokens are copied as written, escapes included. Whitespace is kept, as
+/// a single space, only where it is significant: between compound
+/// selectors (`nav a`), in at-rule preludes (`screen and (...)`) and
+/// inside declaration values (`0 auto`), where the parser keeps it as
+/// part of the value. A space is also put back where dropping a comment
+/// would join two tokens, as in `a/**/b`. The output parses to the same
+/// rules as the input.
+pub fn minify_css(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let _ = minify(input, false, &mut out);
+    out
+}
+
+/// Writes `minify_css` of `input` into `out`, failing with
+/// `OutputError::LimitExceeded` instead of writing more than `limit`
+/// bytes. Each token is written whole, with the space or `;` before it.
+pub fn write_minified_css<W: fmt::Write + ?Sized>(
+    input: &str,
+    out: &mut W,
+    limit: Option<usize>,
+) -> Result<(), OutputError> {
+    write_limited(out, limit, |w| minify(input, false, w))
+}
+
+/// Like `minify_css`, but keeps `/*! ... */` comments, the convention for
+/// license headers that must survive minification.
+pub fn minify_css_preserve_comments(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let _ = minify(input, true, &mut out);
+    out
+}
+
+fn minify<W: fmt::Write + ?Sized>(input: &str, keep_bang_comments: bool, out: &mut W) -> fmt::Result {
+    let mut tokenizer = CssTokenizer::new(input);
+    let mut context =
+        Context { block: Block::Rules, prelude: Prelude::None, in_value: false, depth: 0, in_attribute: false };
+    let mut blocks = vec![Block::Rules];
+    // The last token written, with its source text
+    let mut previous: Option<(CssToken, &str)> = None;
+    // Whitespace, or a dropped comment, since the last token written
+    let mut whitespace = false;
+    let mut comment = false;
+    // The `:` that starts the current value was the last token written
+    let mut value_start = false;
+    // A `;` held back, as none is needed before `}`
+    let mut semicolon = false;
+
+    while let Some(token) = tokenizer.next_token() {
+        let raw = &input[tokenizer.token_span().byte_range()];
+        match token {
+            CssToken::Whitespace(_) => {
+                whitespace = true;
+                continue;
+            }
+            CssToken::Comment(text) if !(keep_bang_comments && text.starts_with('!')) => {
+                comment = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        // The token with the `;` and space before it
+        let mut piece = String::new();
+        if semicolon && token != CssToken::RightBrace {
+            piece.push(';');
+        }
+        if let Some((before, before_raw)) = &previous {
+            let space = if whitespace && !value_start && is_significant_space(before, &token, context) {
+                true
+            } else if whitespace && !comment && is_selector_combinator(before, &token, context) {
+                false
+            } else {
+                (whitespace || comment) && could_join(before_raw, raw)
+            };
+            if matches!(before, CssToken::BadString | CssToken::Delim('\\')) {
+                // These end at a newline that must stay one
+                piece.push('\n');
+            } else if space {
+                piece.push(' ');
+            }
+        }
+        semicolon = token == CssToken::Semicolon;
+        match token {
+            CssToken::Semicolon => {}
+            CssToken::Number { .. } => push_number(&mut piece, raw),
+            CssToken::Dimension { ref unit, .. } => {
+                push_number(&mut piece, &raw[..raw.len() - unit.len()]);
+                piece.push_str(unit);
+            }
+            CssToken::Percentage { .. } => {
+                push_number(&mut piece, &raw[..raw.len() - 1]);
+                piece.push('%');
+            }
+            _ => piece.push_str(raw),
+        }
+        out.write_str(&piece)?;
+        whitespace = false;
+        comment = false;
+        if matches!(token, CssToken::Comment(_)) {
+            continue;
+        }
+        value_start = false;
+
+        let starts_selector = !matches!(
+            token,
+            CssToken::AtKeyword(_) | CssToken::LeftBrace | CssToken::RightBrace | CssToken::Semicolon | CssToken::Cdo | CssToken::Cdc
+        );
+        if starts_selector && context.block == Block::Rules && context.prelude == Prelude::None {
+            context.prelude = Prelude::Selector;
+        }
+        match &token {
+            CssToken::AtKeyword(name) if context.block == Block::Rules => {
+                let name = name.to_ascii_lowercase();
+                let name = strip_vendor_prefix(&name);
+                context.prelude = Prelude::AtRule { declarations: DECLARATION_AT_RULES.contains(&name) };
+            }
+            CssToken::LeftBrace => {
+                let block = match context.prelude {
+                    Prelude::AtRule { declarations: false } => Block::Rules,
+                    _ => Block::Declarations,
+                };
+                blocks.push(block);
+                context = Context { block, prelude: Prelude::None, in_value: false, depth: 0, in_attribute: false };
+            }
+            CssToken::RightBrace => {
+                if blocks.len() > 1 {
+                    blocks.pop();
+                }
+                let block = *blocks.last().unwrap_or(&Block::Rules);
+                context = Context { block, prelude: Prelude::None, in_value: false, depth: 0, in_attribute: false };
+            }
+            CssToken::Semicolon if context.depth == 0 => {
+                context.prelude = Prelude::None;
+                context.in_value = false;
+            }
+            CssToken::Colon if context.block == Block::Declarations && !context.in_value => {
+                context.in_value = true;
+                value_start = true;
+            }
+            CssToken::LeftBracket if context.prelude == Prelude::Selector => {
+                context.depth += 1;
+                context.in_attribute = true;
+            }
+            CssToken::RightBracket if context.in_attribute => {
+                context.depth = context.depth.saturating_sub(1);
+                context.in_attribute = false;
+            }
+            CssToken::Function(_) | CssToken::LeftParen | CssToken::LeftBracket => context.depth += 1,
+            CssToken::RightParen | CssToken::RightBracket => context.depth = context.depth.saturating_sub(1),
+            _ => {}
+        }
+        previous = Some((token, raw));
+    }
+    Ok(())
+}
+
+/// Returns whether whitespace between `before` and `after` changes what
+/// the parser reads.
+fn is_significant_space(before: &CssToken, after: &CssToken, context: Context) -> bool {
+    let ends_statement = |token: &CssToken| {
+        matches!(token, CssToken::LeftBrace | CssToken::RightBrace | CssToken::Semicolon)
+    };
+    if ends_statement(before) || ends_statement(after) {
+        return false;
+    }
+    match context.block {
+        Block::Declarations => context.in_value,
+        Block::Rules => match context.prelude {
+            Prelude::None => false,
+            Prelude::AtRule { .. } => true,
+            // Whitespace inside `[...]` only separates tokens, which
+            // `could_join` already looks after
+            Prelude::Selector if context.in_attribute => false,
+            Prelude::Selector => context.depth > 0 || !is_selector_combinator(before, after, context),
+        },
+    }
+}
+
+/// Writes a number without the zeros that do not change its value:
+/// `0.50` is written `.5` and `2.0` is written `2`. Numbers with an
+/// exponent are written as they are.
+fn push_number(out: &mut String, number: &str) {
+    let (sign, digits) = match number.strip_prefix(['+', '-']) {
+        Some(digits) => (&number[..1], digits),
+        None => ("", number),
+    };
+    let Some((integer, fraction)) = digits.split_once('.').filter(|_| !digits.contains(['e', 'E'])) else {
+        out.push_str(number);
+        return;
+    };
+    let integer = integer.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    out.push_str(sign);
+    out.push_str(integer);
+    if !fraction.is_empty() {
+        out.push('.');
+        out.push_str(fraction);
+    } else if integer.is_empty() {
+        out.push('0');
+    }
+}
+
+/// Returns whether `before` or `after` is a `,`, `>`, `+` or `~` between
+/// selectors, which need no whitespace around them and never join with
+/// their neighbours.
+fn is_selector_combinator(before: &CssToken, after: &CssToken, context: Context) -> bool {
+    let combinator = |token: &CssToken| matches!(token, CssToken::Comma | CssToken::Delim('>' | '+' | '~'));
+    context.block == Block::Rules
+        && context.prelude == Prelude::Selector
+        && context.depth == 0
+        && (combinator(before) || combinator(after))
+}
+
+/// Returns whether writing `after` right after `before` could tokenize
+/// differently, as `1` and `px` would read as `1px`. Errs on the side of
+/// a space.
+fn could_join(before: &str, after: &str) -> bool {
+    // Attribute matchers never join with their neighbours, except that a
+    // `~` and a `=` written apart must not become `~=`
+    let is_matcher = |raw: &str| matches!(raw, "=" | "~=" | "|=" | "^=" | "$=" | "*=");
+    if is_matcher(after) {
+        return matches!(before, "~" | "|" | "^" | "$" | "*");
+    }
+    if is_matcher(before) {
+        return false;
+    }
+    let (Some(last), Some(first)) = (before.chars().last(), after.chars().next()) else {
+        return false;
+    };
+    !matches!(last, '{' | '}' | '(' | ')' | '[' | ']' | ';' | ':' | ',' | '"' | '\'')
+        && !matches!(first, '{' | '}' | ')' | '[' | ']' | ';' | ':' | ',' | '"' | '\'')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+
+    const LARGE_CSS: &str = include_str!("../../tests/fixtures/large.css");
+
+    fn assert_same_rules(css: &str) {
+        let minified = minify_css(css);
+        assert_eq!(CssParser::new(&minified).parse(), CssParser::new(css).parse(), "{}", minified);
+        assert_eq!(
+            CssParser::new(&minified).parse_stylesheet(),
+            CssParser::new(css).parse_stylesheet(),
+            "{}",
+            minified
+        );
+    }
+
+    #[test]
+    fn test_minify_css() {
+        let css = "/* nav */\n.nav  >  li , .nav a:hover ,\ndiv  .x\t[ data-x = \"y\" ] {\n  \
+                   color : red ;\n  margin: 0  auto !important;\n  width: calc( 100% - 2px );\n}\n";
+
+        assert_eq!(
+            minify_css(css),
+            ".nav>li,.nav a:hover,div .x [data-x=\"y\"]{color:red;margin:0 auto !important;width:calc( 100% - 2px )}"
+        );
+        assert_same_rules(css);
+    }
+
+    #[test]
+    fn test_attribute_selectors() {
+        let css = "[ lang |= en ] a, [ data-x = y i ], [x ~ = y], :not( [ a ] ) b { color: red }";
+
+        assert_eq!(minify_css(css), "[lang|=en] a,[data-x=y i],[x ~ =y],:not( [a] ) b{color:red}");
+        assert_same_rules(css);
+    }
+
+    #[test]
+    fn test_at_rules() {
+        let css = "@import url(base.css)  screen ;\n@media screen and (min-width: 768px) {\n  a { color: red }\n}\n\
+                   @font-face {\n  font-family: x;\n  src: url(x.woff2) format(\"woff2\");\n}\n\
+                   @keyframes fade {\n  0% , 100% { opacity: 1 }\n  50% { opacity: 0 }\n}\n";
+
+        assert_eq!(
+            minify_css(css),
+            "@import url(base.css) screen;@media screen and (min-width: 768px){a{color:red}}\
+             @font-face{font-family:x;src:url(x.woff2) format(\"woff2\")}\
+             @keyframes fade{0%,100%{opacity:1}50%{opacity:0}}"
+        );
+        assert_same_rules(css);
+    }
+
+    #[test]
+    fn test_numbers_lose_redundant_zeros() {
+        let css = "a { opacity: 0.50; margin: -0.5px +1.0em; width: 10.0%; line-height: 1.0e3; z-index: 0.0 }";
+
+        assert_eq!(minify_css(css), "a{opacity:.5;margin:-.5px +1em;width:10%;line-height:1.0e3;z-index:0}");
+        assert_same_rules(css);
+    }
+
+    #[test]
+    fn test_dropped_comments_do_not_join_tokens() {
+        assert_eq!(minify_css("a/**/b{margin:1px/**/2px}"), "a b{margin:1px 2px}");
+        assert_eq!(minify_css("a{color:/* x */red}"), "a{color:red}");
+        assert_eq!(minify_css("\"a\n{}"), "\"a\n{}");
+        assert_same_rules("a/**/b{margin:1px/**/2px}");
+        assert_same_rules("a{margin:1px /**/ 2px/**/!important}");
+    }
+
+    #[test]
+    fn test_preserve_bang_comments() {
+        let css = "/*! License: MIT */\n/* build note */\na { color: red; /*! keep */ }\n";
+
+        assert_eq!(minify_css_preserve_comments(css), "/*! License: MIT */a{color:red;/*! keep */}");
+        assert_eq!(minify_css(css), "a{color:red}");
+    }
+
+    #[test]
+    fn test_write_minified_css() {
+        let css = "a { color : red ; }\nb { margin : 0 }";
+        let mut out = String::new();
+
+        let result = write_minified_css(css, &mut out, Some(12));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 12 }));
+        assert_eq!(out, "a{color:red}");
+
+        let mut out = String::new();
+        assert_eq!(write_minified_css(LARGE_CSS, &mut out, None), Ok(()));
+        assert_eq!(out, minify_css(LARGE_CSS));
+    }
+
+    #[test]
+    fn test_large_fixture() {
+        let minified = minify_css(LARGE_CSS);
+
+        assert!(minified.len() * 100 <= LARGE_CSS.len() * 72, "{} of {} bytes", minified.len(), LARGE_CSS.len());
+        // A 30% cut needs more than whitespace and comments: with every
+        // space left in the output gone too, joining tokens, 70% remains
+        let without_spaces = minified.bytes().filter(|byte| *byte != b' ').count();
+        assert!(without_spaces * 10 > LARGE_CSS.len() * 7, "{} of {} bytes", without_spaces, LARGE_CSS.len());
+        assert_same_rules(LARGE_CSS);
+        assert_eq!(minify_css(&minified), minified);
+    }
+}
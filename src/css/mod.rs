@@ -1,5 +1,53 @@
 pub mod tokenizer;
+pub mod stream;
+pub mod trivia;
+#[cfg(feature = "css-ast")]
 pub mod parser;
+#[cfg(feature = "css-ast")]
+pub mod nth;
+#[cfg(feature = "css-ast")]
+pub mod ops;
+#[cfg(feature = "css-ast")]
+pub mod declaration;
+pub mod number;
+#[cfg(feature = "css-ast")]
+pub mod stylesheet;
+#[cfg(feature = "css-ast")]
+pub mod report;
+#[cfg(feature = "css-ast")]
+pub mod serializer;
+#[cfg(feature = "css-ast")]
+pub mod media;
+#[cfg(feature = "css-ast")]
+pub mod minifier;
 
-pub use tokenizer::{CssTokenizer, CssToken};
-pub use parser::{CssParser, Rule, Selector};
\ No newline at end of file
+pub use tokenizer::{CssTokenizer, CssToken, TokenizeError, TokenizeErrorKind};
+pub use stream::{CssStreamTokenizer, OwnedCssToken};
+pub use trivia::{SignificantTokens, TokensWithTrivia, Trivia};
+#[cfg(feature = "css-ast")]
+pub use parser::{CssParser, Rule, Selector, AttrOp, Specificity, specificity};
+#[cfg(feature = "css-ast")]
+pub use parser::{StylesheetItem, MediaRule, ImportRule, KeyframesRule, KeyframeStep, KeyframeSelector};
+#[cfg(feature = "css-ast")]
+pub use nth::{parse_nth, NthExpr};
+#[cfg(feature = "css-ast")]
+pub use declaration::{Declaration, DeclarationList};
+#[cfg(feature = "css-ast")]
+pub use ops::{CssOp, CssOpError, apply_css_ops};
+pub use number::{format_number, NumberFormat};
+#[cfg(feature = "css-ast")]
+pub use stylesheet::{OwnedStylesheet, RuleSource};
+#[cfg(feature = "css-ast")]
+pub use report::{feature_report, FeatureReport, FeatureUsage};
+#[cfg(feature = "css-ast")]
+pub use serializer::{
+    serialize_css, stylesheet_to_css, write_serialized_css, write_stylesheet_css, CssSerializerOptions,
+};
+#[cfg(feature = "css-ast")]
+pub use media::{parse_media_query_list, parse_media_query_list_with_errors, parse_source_sizes, split_by_color_scheme};
+#[cfg(feature = "css-ast")]
+pub use media::{Length, MediaCondition, MediaFeature, MediaModifier, MediaQuery, MediaQueryList, MediaValue, RangeOp};
+#[cfg(feature = "css-ast")]
+pub use media::{ColorScheme, ColorSchemeSplit, Contrast, Environment};
+#[cfg(feature = "css-ast")]
+pub use minifier::{minify_css, minify_css_preserve_comments, write_minified_css};
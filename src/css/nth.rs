@@ -0,0 +1,142 @@
+use std::fmt;
+
+/// An `An+B` expression, as in `:nth-child(2n+1)`: it selects the elements
+/// whose 1-based position is `a*n + b` for some `n >= 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NthExpr {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl NthExpr {
+    /// Returns whether the 1-based `position` is selected.
+    pub fn matches(self, position: usize) -> bool {
+        let offset = position as i64 - self.b as i64;
+        match self.a {
+            0 => offset == 0,
+            a => offset % a as i64 == 0 && offset / a as i64 >= 0,
+        }
+    }
+}
+
+/// Writes the shortest `An+B` form: `2n+1`, `-n+3`, `n`, `5`.
+impl fmt::Display for NthExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.a {
+            0 => return write!(f, "{}", self.b),
+            1 => f.write_str("n")?,
+            -1 => f.write_str("-n")?,
+            a => write!(f, "{}n", a)?,
+        }
+        match self.b {
+            0 => Ok(()),
+            b if b > 0 => write!(f, "+{}", b),
+            b => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Parses the `An+B` micro-syntax: `odd`, `even`, an integer (`3`, `-2`),
+/// or `An` with an optional `+B`/`-B` (`n`, `-n+3`, `2n`, `-3n-1`).
+///
+/// Letters are matched ignoring ASCII case. Whitespace is allowed around
+/// the expression and around the sign before `B` (`2n + 1`), but not
+/// inside `An` or between a sign and its digits. Returns `None` for
+/// anything else, including values that do not fit in an `i32`.
+pub fn parse_nth(s: &str) -> Option<NthExpr> {
+    let s = s.trim().to_ascii_lowercase();
+    match s.as_str() {
+        "odd" => return Some(NthExpr { a: 2, b: 1 }),
+        "even" => return Some(NthExpr { a: 2, b: 0 }),
+        _ => {}
+    }
+
+    let Some((a, rest)) = s.split_once('n') else {
+        return Some(NthExpr { a: 0, b: parse_integer(&s)? });
+    };
+    let a = match a {
+        "" | "+" => 1,
+        "-" => -1,
+        a => parse_integer(a)?,
+    };
+
+    let rest = rest.trim_start();
+    let b = if rest.is_empty() {
+        0
+    } else {
+        let (negative, digits) = match (rest.strip_prefix('+'), rest.strip_prefix('-')) {
+            (Some(digits), _) => (false, digits.trim_start()),
+            (_, Some(digits)) => (true, digits.trim_start()),
+            _ => return None,
+        };
+        if digits.starts_with(['+', '-']) {
+            return None;
+        }
+        let b = parse_integer(digits)?;
+        if negative { -b } else { b }
+    };
+    Some(NthExpr { a, b })
+}
+
+/// Parses an optionally signed run of ASCII digits.
+fn parse_integer(s: &str) -> Option<i32> {
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nth(a: i32, b: i32) -> Option<NthExpr> {
+        Some(NthExpr { a, b })
+    }
+
+    #[test]
+    fn test_parse_nth_forms() {
+        assert_eq!(parse_nth("odd"), nth(2, 1));
+        assert_eq!(parse_nth(" EVEN "), nth(2, 0));
+        assert_eq!(parse_nth("3"), nth(0, 3));
+        assert_eq!(parse_nth("+3"), nth(0, 3));
+        assert_eq!(parse_nth("-2"), nth(0, -2));
+        assert_eq!(parse_nth("n"), nth(1, 0));
+        assert_eq!(parse_nth("+n"), nth(1, 0));
+        assert_eq!(parse_nth("-n"), nth(-1, 0));
+        assert_eq!(parse_nth("2n"), nth(2, 0));
+        assert_eq!(parse_nth("2N+3"), nth(2, 3));
+        assert_eq!(parse_nth("-n+3"), nth(-1, 3));
+        assert_eq!(parse_nth("-3n-1"), nth(-3, -1));
+        assert_eq!(parse_nth("0n+5"), nth(0, 5));
+        assert_eq!(parse_nth("2n + 1"), nth(2, 1));
+        assert_eq!(parse_nth("2n- 1"), nth(2, -1));
+    }
+
+    #[test]
+    fn test_parse_nth_rejects_invalid() {
+        for invalid in ["", "n2", "2 n", "+ n", "2n+", "2n++1", "2n+-1", "2n 1", "3.0", "nn", "odd1", "2né", "99999999999"] {
+            assert_eq!(parse_nth(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_matches_positions() {
+        let selected = |expr: NthExpr| (1..=8).filter(|&position| expr.matches(position)).collect::<Vec<_>>();
+
+        assert_eq!(selected(NthExpr { a: 2, b: 1 }), vec![1, 3, 5, 7]);
+        assert_eq!(selected(NthExpr { a: 0, b: 3 }), vec![3]);
+        assert_eq!(selected(NthExpr { a: -1, b: 3 }), vec![1, 2, 3]);
+        assert_eq!(selected(NthExpr { a: 3, b: -1 }), vec![2, 5, 8]);
+        assert_eq!(selected(NthExpr { a: -3, b: -1 }), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_display() {
+        for css in ["2n+1", "-n+3", "n", "-n", "-3n-1", "5", "-2", "4n"] {
+            assert_eq!(parse_nth(css).unwrap().to_string(), css);
+        }
+    }
+}
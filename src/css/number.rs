@@ -0,0 +1,94 @@
+/// Options for `format_number`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NumberFormat {
+    /// Round to at most this many digits after the decimal point. `None`
+    /// keeps the shortest text that parses back to the same `f64`.
+    pub max_precision: Option<usize>,
+    /// Drop the zero before the decimal point (`.5` instead of `0.5`).
+    pub minify: bool,
+}
+
+/// Formats a CSS number.
+///
+/// Trailing zeros and a trailing `.` are never written, `-0` is written as
+/// `0`, and exponent notation is only used below `1e-6` or from `1e21` up,
+/// where plain digits would get unreasonably long.
+pub fn format_number(value: f64, format: NumberFormat) -> String {
+    if !value.is_finite() {
+        return "0".to_string();
+    }
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-6..1e21).contains(&magnitude) && format.max_precision.is_none() {
+        return format!("{:e}", value);
+    }
+
+    let mut text = match format.max_precision {
+        Some(precision) => {
+            let fixed = format!("{:.*}", precision, value);
+            if fixed.contains('.') {
+                fixed.trim_end_matches('0').trim_end_matches('.').to_string()
+            } else {
+                fixed
+            }
+        }
+        None => value.to_string(),
+    };
+
+    if text == "-0" {
+        text = "0".to_string();
+    }
+    if format.minify {
+        if let Some(rest) = text.strip_prefix("0.") {
+            text = format!(".{}", rest);
+        } else if let Some(rest) = text.strip_prefix("-0.") {
+            text = format!("-.{}", rest);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        let normal = NumberFormat::default();
+        let minified = NumberFormat { minify: true, ..normal };
+        let cases: &[(f64, &str, &str)] = &[
+            (16.0, "16", "16"),
+            (0.5, "0.5", ".5"),
+            (-0.5, "-0.5", "-.5"),
+            (-0.0, "0", "0"),
+            (0.1 + 0.2, "0.30000000000000004", ".30000000000000004"),
+            (1e3, "1000", "1000"),
+            (1e-7, "1e-7", "1e-7"),
+            (6.02e23, "6.02e23", "6.02e23"),
+            (123456.75, "123456.75", "123456.75"),
+        ];
+
+        for &(value, expected, expected_minified) in cases {
+            assert_eq!(format_number(value, normal), expected, "{}", value);
+            assert_eq!(format_number(value, minified), expected_minified, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_max_precision() {
+        let format = NumberFormat { max_precision: Some(4), minify: false };
+        let cases: &[(f64, &str)] = &[
+            (0.1 + 0.2, "0.3"),
+            (2.0 / 3.0, "0.6667"),
+            (-0.00001, "0"),
+            (1.5, "1.5"),
+            (100.0, "100"),
+            (1e-7, "0"),
+        ];
+
+        for &(value, expected) in cases {
+            assert_eq!(format_number(value, format), expected, "{}", value);
+        }
+        assert_eq!(format_number(2.0 / 3.0, NumberFormat { minify: true, ..format }), ".6667");
+        assert_eq!(format_number(0.4, NumberFormat { max_precision: Some(0), minify: false }), "0");
+    }
+}
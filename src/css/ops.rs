@@ -0,0 +1,109 @@
+use crate::css::declaration::Declaration;
+use crate::css::parser::Rule;
+use std::fmt;
+
+/// A machine-applicable change to a stylesheet, i.e. a list of rules.
+///
+/// `rule` and `index` address positions in that list. `SetDeclaration`
+/// writes a normal declaration, dropping any `!important` flag.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CssOp {
+    SetDeclaration { rule: usize, property: String, value: String },
+    RemoveDeclaration { rule: usize, property: String },
+    InsertRule { index: usize, rule: Rule },
+    RemoveRule { index: usize },
+}
+
+/// The error returned by `apply_css_ops`: op `op_index` addressed rule
+/// `index` in a stylesheet of `len` rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CssOpError {
+    pub op_index: usize,
+    pub index: usize,
+    pub len: usize,
+}
+
+impl fmt::Display for CssOpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "op {}: rule index {} out of range for {} rules",
+            self.op_index, self.index, self.len
+        )
+    }
+}
+
+impl std::error::Error for CssOpError {}
+
+/// Applies `ops` to `rules` in order.
+///
+/// Like `Element::apply_ops`, the batch is atomic: on error `rules` is left
+/// unchanged.
+pub fn apply_css_ops(rules: &mut Vec<Rule>, ops: &[CssOp]) -> Result<(), CssOpError> {
+    let mut working = rules.clone();
+    for (op_index, op) in ops.iter().enumerate() {
+        let len = working.len();
+        let out_of_range = |index| CssOpError { op_index, index, len };
+        match op {
+            CssOp::SetDeclaration { rule, property, value } => {
+                let target = working.get_mut(*rule).ok_or_else(|| out_of_range(*rule))?;
+                target.declarations.set(Declaration::new(property.clone(), value.clone()));
+            }
+            CssOp::RemoveDeclaration { rule, property } => {
+                let target = working.get_mut(*rule).ok_or_else(|| out_of_range(*rule))?;
+                target.declarations.remove(property);
+            }
+            CssOp::InsertRule { index, rule } => {
+                if *index > len {
+                    return Err(out_of_range(*index));
+                }
+                working.insert(*index, rule.clone());
+            }
+            CssOp::RemoveRule { index } => {
+                if *index >= len {
+                    return Err(out_of_range(*index));
+                }
+                working.remove(*index);
+            }
+        }
+    }
+    *rules = working;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+
+    #[test]
+    fn test_apply_batch() {
+        let mut rules = CssParser::new("a { color: red; } p { margin: 0; }").parse();
+        let inserted = CssParser::new("em { font-style: italic; }").parse().remove(0);
+
+        let ops = vec![
+            CssOp::SetDeclaration { rule: 0, property: "color".to_string(), value: "blue".to_string() },
+            CssOp::RemoveDeclaration { rule: 1, property: "margin".to_string() },
+            CssOp::InsertRule { index: 1, rule: inserted },
+            CssOp::RemoveRule { index: 2 },
+        ];
+
+        assert_eq!(apply_css_ops(&mut rules, &ops), Ok(()));
+        assert_eq!(rules, CssParser::new("a { color: blue; } em { font-style: italic; }").parse());
+    }
+
+    #[test]
+    fn test_out_of_range_is_atomic() {
+        let original = CssParser::new("a { color: red; }").parse();
+        let mut rules = original.clone();
+
+        let ops = vec![
+            CssOp::RemoveRule { index: 0 },
+            CssOp::SetDeclaration { rule: 0, property: "color".to_string(), value: "blue".to_string() },
+        ];
+
+        let error = apply_css_ops(&mut rules, &ops).unwrap_err();
+        assert_eq!(error, CssOpError { op_index: 1, index: 0, len: 0 });
+        assert_eq!(rules, original);
+    }
+}
@@ -1,79 +1,741 @@
-use crate::css::tokenizer::{CssTokenizer, CssToken};
-use std::collections::HashMap;
+use crate::css::declaration::{lookup_key, Declaration, DeclarationList};
+use crate::css::nth::{parse_nth, NthExpr};
+use crate::css::number::{format_number, NumberFormat};
+use crate::css::tokenizer::{preprocess, unescape, unescape_string, CssTokenizer, CssToken};
+use crate::error::{ParseError, ParseErrorKind};
+#[cfg(feature = "matching")]
+use crate::html::parser::Element;
+use crate::location::Span;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rule {
     pub selectors: Vec<Selector>,
-    pub declarations: HashMap<String, String>,
+    /// Declarations in source order, duplicates included. `get` returns
+    /// the last declaration of a property; an earlier `!important` one
+    /// still wins over it in the cascade.
+    pub declarations: DeclarationList,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Selector {
     Type(String),
     Class(String),
     Id(String),
     Universal,
+    /// `[name]`, or `[name=value]` and the other operators. `operator` and
+    /// `value` are both set or both `None`. Name and value are unescaped,
+    /// so `[xlink\:href]` selects the `xlink:href` attribute.
+    /// `case_insensitive` is set by a trailing `i` flag (`[type="a" i]`),
+    /// which compares the value ignoring ASCII case.
+    Attribute { name: String, operator: Option<AttrOp>, value: Option<String>, case_insensitive: bool },
+    /// `:name` or `:name(argument)`, with the name lower-cased and the
+    /// argument kept verbatim (`ltr` for `:dir(ltr)`).
+    PseudoClass { name: String, argument: Option<String> },
+    /// `:nth-child(An+B)`.
+    NthChild(NthExpr),
+    /// `:nth-of-type(An+B)`, counting only siblings with the same tag name.
+    NthOfType(NthExpr),
+    /// `:nth-last-child(An+B)`, counting from the last sibling.
+    NthLastChild(NthExpr),
+    /// `:nth-last-of-type(An+B)`.
+    NthLastOfType(NthExpr),
+    /// `:not(selector)`. The argument is a single selector, simple or
+    /// complex (`:not(div > p)`).
+    Not(Box<Selector>),
+    /// `::name`, lower-cased. The legacy one-colon forms (`:before`, `:after`,
+    /// `:first-line`, `:first-letter`) are parsed to this too.
+    PseudoElement(String),
+    /// Simple selectors that must all match the same element, such as
+    /// `a:hover`, `.nav.active` or `.badge::after`.
+    Compound(Vec<Selector>),
     Descendant(Box<Selector>, Box<Selector>),
     Child(Box<Selector>, Box<Selector>),
     Adjacent(Box<Selector>, Box<Selector>),
     GeneralSibling(Box<Selector>, Box<Selector>),
 }
 
+/// The operator of an attribute selector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttrOp {
+    /// `=`: exactly the value.
+    Equals,
+    /// `~=`: the value is one of the whitespace-separated words.
+    Includes,
+    /// `|=`: exactly the value, or the value followed by `-`.
+    DashMatch,
+    /// `^=`: starts with the value.
+    Prefix,
+    /// `$=`: ends with the value.
+    Suffix,
+    /// `*=`: contains the value.
+    Substring,
+}
+
+impl AttrOp {
+    /// The operator as written in CSS, e.g. `~=`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AttrOp::Equals => "=",
+            AttrOp::Includes => "~=",
+            AttrOp::DashMatch => "|=",
+            AttrOp::Prefix => "^=",
+            AttrOp::Suffix => "$=",
+            AttrOp::Substring => "*=",
+        }
+    }
+
+    /// Returns whether an attribute value `actual` matches `expected` with
+    /// this operator. As in browsers, `~=` with an empty or multi-word value
+    /// and `^=`, `$=` and `*=` with an empty value match nothing.
+    pub fn matches(self, actual: &str, expected: &str) -> bool {
+        match self {
+            AttrOp::Equals => actual == expected,
+            AttrOp::Includes => {
+                !expected.is_empty()
+                    && !expected.contains(|c: char| c.is_ascii_whitespace())
+                    && actual.split_ascii_whitespace().any(|word| word == expected)
+            }
+            AttrOp::DashMatch => {
+                actual == expected
+                    || actual.strip_prefix(expected).is_some_and(|rest| rest.starts_with('-'))
+            }
+            AttrOp::Prefix => !expected.is_empty() && actual.starts_with(expected),
+            AttrOp::Suffix => !expected.is_empty() && actual.ends_with(expected),
+            AttrOp::Substring => !expected.is_empty() && actual.contains(expected),
+        }
+    }
+}
+
+/// How specific a selector is: (id count, class-like count, type count).
+///
+/// The derived `Ord` compares the counts lexicographically, so a higher
+/// specificity wins the cascade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Specificity(pub u32, pub u32, pub u32);
+
+impl std::ops::Add for Specificity {
+    type Output = Specificity;
+
+    fn add(self, other: Specificity) -> Specificity {
+        Specificity(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+}
+
+impl From<Specificity> for (u32, u32, u32) {
+    fn from(Specificity(ids, classes, types): Specificity) -> Self {
+        (ids, classes, types)
+    }
+}
+
+/// Computes the specificity of `selector`. Combinators add up both sides.
+pub fn specificity(selector: &Selector) -> Specificity {
+    match selector {
+        Selector::Id(_) => Specificity(1, 0, 0),
+        Selector::Class(_)
+        | Selector::Attribute { .. }
+        | Selector::PseudoClass { .. }
+        | Selector::NthChild(_)
+        | Selector::NthOfType(_)
+        | Selector::NthLastChild(_)
+        | Selector::NthLastOfType(_) => Specificity(0, 1, 0),
+        Selector::Type(_) | Selector::PseudoElement(_) => Specificity(0, 0, 1),
+        Selector::Universal => Specificity(0, 0, 0),
+        Selector::Not(argument) => specificity(argument),
+        Selector::Compound(parts) => parts.iter().map(specificity).fold(Specificity::default(), |a, b| a + b),
+        Selector::Descendant(left, right)
+        | Selector::Child(left, right)
+        | Selector::Adjacent(left, right)
+        | Selector::GeneralSibling(left, right) => specificity(left) + specificity(right),
+    }
+}
+
+impl Selector {
+    /// The specificity of this selector; see `specificity`.
+    pub fn specificity(&self) -> Specificity {
+        specificity(self)
+    }
+
+    /// Returns whether the selector contains a combinator.
+    pub fn is_complex(&self) -> bool {
+        match self {
+            Selector::Descendant(..)
+            | Selector::Child(..)
+            | Selector::Adjacent(..)
+            | Selector::GeneralSibling(..) => true,
+            Selector::Compound(parts) => parts.iter().any(Selector::is_complex),
+            Selector::Not(argument) => argument.is_complex(),
+            _ => false,
+        }
+    }
+}
+
+/// A top-level item of a stylesheet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StylesheetItem {
+    Rule(Rule),
+    /// `@charset "name";`, holding the encoding name.
+    Charset(String),
+    Import(ImportRule),
+    Media(MediaRule),
+    Keyframes(KeyframesRule),
+    /// An `@font-face` block, with its declarations in source order.
+    FontFace(DeclarationList),
+}
+
+/// An `@import` statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRule {
+    pub url: String,
+    /// The media query list after the URL, e.g. `screen`, if any.
+    pub media_query: Option<String>,
+}
+
+/// An `@media` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRule {
+    /// The media query list as written between `@media` and `{`, e.g.
+    /// `(max-width: 768px)`.
+    pub query: String,
+    pub rules: Vec<Rule>,
+}
+
+/// An `@keyframes` block (or a vendor-prefixed one such as
+/// `@-webkit-keyframes`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyframesRule {
+    pub name: String,
+    pub steps: Vec<KeyframeStep>,
+}
+
+/// One `from`, `to` or `50%` block inside `@keyframes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyframeStep {
+    pub selectors: Vec<KeyframeSelector>,
+    /// Declarations of the step; `!important` ones are ignored, as in
+    /// browsers.
+    pub declarations: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyframeSelector {
+    From,
+    To,
+    Percentage(f64),
+}
+
+impl Rule {
+    /// Returns whether any declaration of `property` is `!important`,
+    /// which makes it the one that applies.
+    pub fn is_important(&self, property: &str) -> bool {
+        self.declarations.get_all(property).any(|declaration| declaration.important)
+    }
+
+    /// The highest specificity among the rule's selectors.
+    pub fn max_specificity(&self) -> Specificity {
+        self.selectors.iter().map(specificity).max().unwrap_or_default()
+    }
+
+    /// Returns the value that applies for each property, as a map from
+    /// property to value without `!important`: the last `!important`
+    /// declaration if there is one, or else the last declaration.
+    /// Properties are lower-cased, except custom properties.
+    pub fn declarations_as_strings(&self) -> HashMap<String, String> {
+        let mut values = HashMap::new();
+        let mut important = HashSet::new();
+        for declaration in &self.declarations {
+            let key = lookup_key(&declaration.property).into_owned();
+            if declaration.important {
+                important.insert(key.clone());
+            } else if important.contains(&key) {
+                continue;
+            }
+            values.insert(key, declaration.value.clone());
+        }
+        values
+    }
+}
+
+#[cfg(feature = "matching")]
+impl Selector {
+    /// Returns whether a simple or compound selector matches `element` on
+    /// its own.
+    ///
+    /// Combinators depend on the element's ancestors and siblings and never
+    /// match here, nor does a `:not()` of one; neither do pseudo-classes,
+    /// which depend on state or position in the tree, or pseudo-elements,
+    /// which are not elements.
+    pub fn matches_element(&self, element: &Element) -> bool {
+        match self {
+            Selector::Type(name) => element.tag_name.eq_ignore_ascii_case(name),
+            Selector::Class(class) => element
+                .attr("class")
+                .is_some_and(|value| value.split_ascii_whitespace().any(|token| token == class)),
+            Selector::Id(id) => element.attr("id") == Some(id.as_str()),
+            Selector::Universal => true,
+            Selector::Attribute { name, operator, value, case_insensitive } => match (element.attr(name), operator, value) {
+                (Some(actual), Some(operator), Some(expected)) if *case_insensitive => {
+                    operator.matches(&actual.to_ascii_lowercase(), &expected.to_ascii_lowercase())
+                }
+                (Some(actual), Some(operator), Some(expected)) => operator.matches(actual, expected),
+                (found, _, _) => found.is_some() && operator.is_none(),
+            },
+            Selector::Compound(parts) => parts.iter().all(|part| part.matches_element(element)),
+            Selector::Not(argument) => !argument.is_complex() && !argument.matches_element(element),
+            Selector::PseudoClass { .. }
+            | Selector::NthChild(_)
+            | Selector::NthOfType(_)
+            | Selector::NthLastChild(_)
+            | Selector::NthLastOfType(_)
+            | Selector::PseudoElement(_)
+            | Selector::Descendant(..)
+            | Selector::Child(..)
+            | Selector::Adjacent(..)
+            | Selector::GeneralSibling(..) => false,
+        }
+    }
+}
+
 pub struct CssParser<'a> {
+    input: &'a str,
     tokenizer: CssTokenizer<'a>,
     current_token: Option<CssToken<'a>>,
+    current_span: Span,
+    errors: Vec<ParseError>,
 }
 
 impl<'a> CssParser<'a> {
     pub fn new(input: &'a str) -> Self {
-        let mut tokenizer = CssTokenizer::new(input);
-        let current_token = tokenizer.next_token();
-        
-        Self {
-            tokenizer,
-            current_token,
-        }
+        let mut parser = Self {
+            input,
+            tokenizer: CssTokenizer::new_with_locations(input),
+            current_token: None,
+            current_span: Span::default(),
+            errors: Vec::new(),
+        };
+        parser.advance();
+        parser
     }
 
+    /// Parses the top-level style rules. Rules inside `@media` blocks are
+    /// left out; use `parse_stylesheet` to get them.
     pub fn parse(&mut self) -> Vec<Rule> {
-        let mut rules = Vec::new();
+        self.parse_stylesheet()
+            .into_iter()
+            .filter_map(|item| match item {
+                StylesheetItem::Rule(rule) => Some(rule),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Parses the input into items in source order: style rules
+    /// (`StylesheetItem::Rule`), `@charset` (`Charset`), `@import`
+    /// (`Import`), `@media` (`Media`), `@keyframes` and its vendor-prefixed
+    /// forms (`Keyframes`) and `@font-face` (`FontFace`). Other at-rules are
+    /// skipped.
+    pub fn parse_stylesheet(&mut self) -> Vec<StylesheetItem> {
+        self.parse_rule_list(true)
+    }
+
+    /// Like `parse_stylesheet`, but also returns every problem found; see
+    /// `parse_with_errors`.
+    pub fn parse_stylesheet_with_errors(&mut self) -> (Vec<StylesheetItem>, Vec<ParseError>) {
+        let items = self.parse_stylesheet();
+        (items, std::mem::take(&mut self.errors))
+    }
+
+    /// Parses rules up to the end of the input, or for a nested list up to
+    /// and including the closing `}`.
+    fn parse_rule_list(&mut self, top_level: bool) -> Vec<StylesheetItem> {
+        let mut items = Vec::new();
+        // Report a run of skipped tokens once, not once per token.
+        let mut skipping = false;
         
-        while self.current_token.is_some() {
-            self.skip_whitespace();
-            
-            if let Some(rule) = self.parse_rule() {
-                rules.push(rule);
-            } else {
-                // Skip invalid tokens
+        loop {
+            // `<!--` and `-->` are only allowed between top-level rules
+            while self.current_token.as_ref().is_some_and(CssToken::is_trivia)
+                || (top_level && matches!(self.current_token, Some(CssToken::Cdo) | Some(CssToken::Cdc)))
+            {
                 self.advance();
             }
+            match self.current_token {
+                None if top_level => break,
+                None => {
+                    let error = self.error_here(ParseErrorKind::UnexpectedEof, "expected `}` before the end of input");
+                    self.errors.push(error);
+                    break;
+                }
+                Some(CssToken::RightBrace) if !top_level => {
+                    self.advance(); // Skip '}'
+                    break;
+                }
+                Some(CssToken::AtKeyword(ref name)) => {
+                    let name = unescape(name).to_ascii_lowercase();
+                    items.extend(self.parse_at_rule(&name));
+                    skipping = false;
+                    continue;
+                }
+                _ => {}
+            }
+            
+            match self.parse_rule() {
+                Ok(rule) => {
+                    items.push(StylesheetItem::Rule(rule));
+                    skipping = false;
+                }
+                Err(error) => {
+                    if !skipping {
+                        self.errors.push(error);
+                    }
+                    skipping = true;
+                    // Skip invalid tokens, but not the end of a nested list
+                    if top_level || !matches!(self.current_token, Some(CssToken::RightBrace)) {
+                        self.advance();
+                    }
+                }
+            }
         }
         
-        rules
+        items
+    }
+
+    /// Parses the at-rule named `name`, unescaped and lower-cased.
+    fn parse_at_rule(&mut self, name: &str) -> Option<StylesheetItem> {
+        if name == "charset" {
+            self.parse_charset_rule()
+        } else if name == "import" {
+            self.parse_import_rule()
+        } else if name == "font-face" {
+            self.parse_font_face_rule()
+        } else if name == "media" {
+            self.parse_media_rule()
+        } else if name == "keyframes" || (name.starts_with('-') && name.ends_with("-keyframes")) {
+            self.parse_keyframes_rule()
+        } else {
+            self.skip_at_rule();
+            None
+        }
+    }
+
+    fn parse_charset_rule(&mut self) -> Option<StylesheetItem> {
+        self.advance(); // Skip '@charset'
+        self.skip_whitespace();
+
+        let Some(CssToken::String(encoding)) = self.current_token.clone() else {
+            let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected a string after `@charset`");
+            self.errors.push(error);
+            self.skip_at_rule_rest();
+            return None;
+        };
+        self.advance();
+        self.skip_whitespace();
+
+        match self.current_token {
+            Some(CssToken::Semicolon) => self.advance(),
+            None => {}
+            _ => {
+                let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected `;` after `@charset`");
+                self.errors.push(error);
+                self.skip_at_rule_rest();
+                return None;
+            }
+        }
+        Some(StylesheetItem::Charset(encoding.to_string()))
     }
 
-    fn parse_rule(&mut self) -> Option<Rule> {
+    fn parse_import_rule(&mut self) -> Option<StylesheetItem> {
+        self.advance(); // Skip '@import'
+        self.skip_whitespace();
+
+        let url = match &self.current_token {
+            Some(CssToken::Url(url)) => unescape(url).into_owned(),
+            Some(CssToken::String(url)) => unescape_string(url).into_owned(),
+            _ => {
+                let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected a URL after `@import`");
+                self.errors.push(error);
+                self.skip_at_rule_rest();
+                return None;
+            }
+        };
+        self.advance();
+        self.skip_whitespace();
+
+        let query_start = self.current_span.start.byte_offset;
+        while !matches!(
+            self.current_token,
+            Some(CssToken::Semicolon) | Some(CssToken::LeftBrace) | None
+        ) {
+            self.advance();
+        }
+        let query = preprocess(self.input[query_start..self.current_span.start.byte_offset].trim_end());
+        let media_query = (!query.is_empty()).then(|| query.into_owned());
+
+        match self.current_token {
+            Some(CssToken::Semicolon) => self.advance(),
+            // A missing `;` at the end of the input is fine
+            None => {}
+            _ => {
+                let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected `;` after `@import`");
+                self.errors.push(error);
+                self.skip_at_rule_rest();
+                return None;
+            }
+        }
+        Some(StylesheetItem::Import(ImportRule { url, media_query }))
+    }
+
+    fn parse_font_face_rule(&mut self) -> Option<StylesheetItem> {
+        self.advance(); // Skip '@font-face'
+        self.skip_whitespace();
+
+        if !matches!(self.current_token, Some(CssToken::LeftBrace)) {
+            let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected `{` after `@font-face`");
+            self.errors.push(error);
+            self.skip_at_rule_rest();
+            return None;
+        }
+        self.advance(); // Skip '{'
+
+        let declarations = self.parse_declarations(true);
+        if matches!(self.current_token, Some(CssToken::RightBrace)) {
+            self.advance(); // Skip '}'
+        } else {
+            let error = self.error_here(ParseErrorKind::UnexpectedEof, "expected `}` before the end of input");
+            self.errors.push(error);
+        }
+        Some(StylesheetItem::FontFace(declarations))
+    }
+
+    fn parse_media_rule(&mut self) -> Option<StylesheetItem> {
+        self.advance(); // Skip '@media'
+        self.skip_whitespace();
+
+        let query_start = self.current_span.start.byte_offset;
+        while !matches!(
+            self.current_token,
+            Some(CssToken::LeftBrace) | Some(CssToken::Semicolon) | None
+        ) {
+            self.advance();
+        }
+        let query = preprocess(self.input[query_start..self.current_span.start.byte_offset].trim_end()).into_owned();
+
+        if !matches!(self.current_token, Some(CssToken::LeftBrace)) {
+            let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected `{` after the media query");
+            self.errors.push(error);
+            self.advance();
+            return None;
+        }
+        self.advance(); // Skip '{'
+
+        // Nested at-rules are not represented and are dropped.
+        let rules = self
+            .parse_rule_list(false)
+            .into_iter()
+            .filter_map(|item| match item {
+                StylesheetItem::Rule(rule) => Some(rule),
+                _ => None,
+            })
+            .collect();
+        Some(StylesheetItem::Media(MediaRule { query, rules }))
+    }
+
+    fn parse_keyframes_rule(&mut self) -> Option<StylesheetItem> {
+        self.advance(); // Skip '@keyframes'
+        self.skip_whitespace();
+
+        let name = match &self.current_token {
+            Some(CssToken::Ident(name)) => unescape(name).into_owned(),
+            Some(CssToken::String(name)) => unescape_string(name).into_owned(),
+            _ => {
+                let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected a keyframes name");
+                self.errors.push(error);
+                self.skip_at_rule_rest();
+                return None;
+            }
+        };
+        self.advance();
+        self.skip_whitespace();
+
+        if !matches!(self.current_token, Some(CssToken::LeftBrace)) {
+            let error = self.error_here(ParseErrorKind::UnexpectedToken, "expected `{` after the keyframes name");
+            self.errors.push(error);
+            self.skip_at_rule_rest();
+            return None;
+        }
+        self.advance(); // Skip '{'
+
+        let mut steps = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.current_token {
+                None => {
+                    let error = self.error_here(ParseErrorKind::UnexpectedEof, "expected `}` before the end of input");
+                    self.errors.push(error);
+                    break;
+                }
+                Some(CssToken::RightBrace) => {
+                    self.advance(); // Skip '}'
+                    break;
+                }
+                _ => {}
+            }
+
+            match self.parse_keyframe_selectors() {
+                Some(selectors) => {
+                    self.advance(); // Skip '{'
+                    let list = self.parse_declaration_list();
+                    let important: HashSet<&str> =
+                        list.iter().filter(|d| d.important).map(|d| d.property.as_str()).collect();
+                    let declarations = list
+                        .iter()
+                        .filter(|d| !important.contains(d.property.as_str()))
+                        .map(|d| (d.property.clone(), d.value.clone()))
+                        .collect();
+                    if matches!(self.current_token, Some(CssToken::RightBrace)) {
+                        self.advance(); // Skip '}'
+                    }
+                    steps.push(KeyframeStep { selectors, declarations });
+                }
+                None => {
+                    let error = self.error_here(ParseErrorKind::InvalidSelector, "expected `from`, `to` or a percentage");
+                    self.errors.push(error);
+                    // Drop the step, block included
+                    while !matches!(
+                        self.current_token,
+                        Some(CssToken::LeftBrace) | Some(CssToken::RightBrace) | None
+                    ) {
+                        self.advance();
+                    }
+                    if matches!(self.current_token, Some(CssToken::LeftBrace)) {
+                        self.skip_block();
+                    }
+                }
+            }
+        }
+
+        Some(StylesheetItem::Keyframes(KeyframesRule { name, steps }))
+    }
+
+    /// Parses a keyframe selector list up to its `{`, leaving the `{` as
+    /// the current token.
+    fn parse_keyframe_selectors(&mut self) -> Option<Vec<KeyframeSelector>> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let selector = match &self.current_token {
+                Some(CssToken::Ident(name)) if name.eq_ignore_ascii_case("from") => KeyframeSelector::From,
+                Some(CssToken::Ident(name)) if name.eq_ignore_ascii_case("to") => KeyframeSelector::To,
+                Some(CssToken::Percentage { value, .. }) => KeyframeSelector::Percentage(*value),
+                _ => return None,
+            };
+            selectors.push(selector);
+            self.advance();
+            self.skip_whitespace();
+
+            match self.current_token {
+                Some(CssToken::Comma) => self.advance(),
+                Some(CssToken::LeftBrace) => return Some(selectors),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Skips an at-rule this parser does not represent: up to its `;`, or
+    /// past its `{ ... }` block.
+    fn skip_at_rule(&mut self) {
+        self.advance(); // Skip the at-keyword
+        self.skip_at_rule_rest();
+    }
+
+    /// Skips the rest of an at-rule's prelude and its `;` or block.
+    fn skip_at_rule_rest(&mut self) {
+        loop {
+            match self.current_token {
+                None => return,
+                Some(CssToken::Semicolon) => {
+                    self.advance();
+                    return;
+                }
+                Some(CssToken::LeftBrace) => {
+                    self.skip_block();
+                    return;
+                }
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Skips a `{ ... }` block, including nested blocks.
+    fn skip_block(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = &self.current_token {
+            match token {
+                CssToken::LeftBrace => depth += 1,
+                CssToken::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Like `parse`, but also returns every problem found in the input.
+    ///
+    /// Reported are unparsable selectors, declarations without a property
+    /// name, `:` or value, and blocks left open at the end of the input.
+    /// Invalid declarations are skipped up to the next `;` or `}`.
+    pub fn parse_with_errors(&mut self) -> (Vec<Rule>, Vec<ParseError>) {
+        let rules = self.parse();
+        (rules, std::mem::take(&mut self.errors))
+    }
+
+    /// Parses the whole input as the declarations of a `style` attribute,
+    /// such as `color: red; display: none !important`, in source order and
+    /// keeping duplicates. Parsing stops early at a stray `}`.
+    pub fn parse_inline_style(&mut self) -> DeclarationList {
+        self.parse_declaration_list()
+    }
+
+    /// Parses the whole input as a comma-separated selector list, as used by
+    /// `query_selector`. Returns `None` unless the input is exactly one
+    /// valid selector list.
+    pub fn parse_selector_list(&mut self) -> Option<Vec<Selector>> {
         let selectors = self.parse_selectors()?;
+        self.skip_whitespace();
+        self.current_token.is_none().then_some(selectors)
+    }
+
+    fn parse_rule(&mut self) -> Result<Rule, ParseError> {
+        let Some(selectors) = self.parse_selectors() else {
+            return Err(self.error_here(ParseErrorKind::InvalidSelector, "expected a selector"));
+        };
         
         self.skip_whitespace();
         
         // Expect '{'
         if !matches!(self.current_token, Some(CssToken::LeftBrace)) {
-            return None;
+            return Err(self.error_here(ParseErrorKind::InvalidSelector, "expected `{` after the selector"));
         }
         self.advance(); // Skip '{'
         
-        let declarations = self.parse_declarations();
+        let declarations = self.parse_declaration_list();
         
         // Expect '}'
         if matches!(self.current_token, Some(CssToken::RightBrace)) {
             self.advance(); // Skip '}'
+        } else {
+            let error = self.error_here(ParseErrorKind::UnexpectedEof, "expected `}` before the end of input");
+            self.errors.push(error);
         }
         
-        Some(Rule {
-            selectors,
-            declarations,
-        })
+        Ok(Rule { selectors, declarations })
     }
 
     fn parse_selectors(&mut self) -> Option<Vec<Selector>> {
@@ -108,7 +770,7 @@ impl<'a> CssParser<'a> {
     fn parse_selector(&mut self) -> Option<Selector> {
         self.skip_whitespace();
         
-        let mut selector = self.parse_simple_selector()?;
+        let mut selector = self.parse_compound_selector()?;
         
         loop {
             self.skip_whitespace();
@@ -118,27 +780,24 @@ impl<'a> CssParser<'a> {
                 Some(CssToken::Delim('>')) => {
                     self.advance(); // Skip '>'
                     self.skip_whitespace();
-                    if let Some(right) = self.parse_simple_selector() {
-                        selector = Selector::Child(Box::new(selector), Box::new(right));
-                    }
+                    let right = self.parse_compound_selector()?;
+                    selector = Selector::Child(Box::new(selector), Box::new(right));
                 }
                 Some(CssToken::Delim('+')) => {
                     self.advance(); // Skip '+'
                     self.skip_whitespace();
-                    if let Some(right) = self.parse_simple_selector() {
-                        selector = Selector::Adjacent(Box::new(selector), Box::new(right));
-                    }
+                    let right = self.parse_compound_selector()?;
+                    selector = Selector::Adjacent(Box::new(selector), Box::new(right));
                 }
                 Some(CssToken::Delim('~')) => {
                     self.advance(); // Skip '~'
                     self.skip_whitespace();
-                    if let Some(right) = self.parse_simple_selector() {
-                        selector = Selector::GeneralSibling(Box::new(selector), Box::new(right));
-                    }
+                    let right = self.parse_compound_selector()?;
+                    selector = Selector::GeneralSibling(Box::new(selector), Box::new(right));
                 }
                 _ => {
                     // Descendant combinator (whitespace)
-                    if let Some(right) = self.parse_simple_selector() {
+                    if let Some(right) = self.parse_compound_selector() {
                         selector = Selector::Descendant(Box::new(selector), Box::new(right));
                     } else {
                         break;
@@ -150,75 +809,233 @@ impl<'a> CssParser<'a> {
         Some(selector)
     }
 
+    /// Parses simple selectors written without whitespace between them,
+    /// such as `a.nav:hover::before`. A lone simple selector is returned as is.
+    fn parse_compound_selector(&mut self) -> Option<Selector> {
+        let mut parts = vec![self.parse_simple_selector()?];
+        while matches!(
+            self.current_token,
+            Some(CssToken::Delim('.')) | Some(CssToken::Hash { .. }) | Some(CssToken::LeftBracket) | Some(CssToken::Colon)
+        ) {
+            parts.push(self.parse_simple_selector()?);
+        }
+
+        if parts.len() == 1 {
+            parts.pop()
+        } else {
+            Some(Selector::Compound(parts))
+        }
+    }
+
     fn parse_simple_selector(&mut self) -> Option<Selector> {
         match &self.current_token {
             Some(CssToken::Ident(name)) => {
-                let selector = Selector::Type(name.to_string());
+                let selector = Selector::Type(unescape(name).into_owned());
                 self.advance();
                 Some(selector)
             }
-            Some(CssToken::Hash(id)) => {
-                let selector = Selector::Id(id.to_string());
+            Some(CssToken::Hash { value, is_id: true }) => {
+                let selector = Selector::Id(unescape(value).into_owned());
                 self.advance();
                 Some(selector)
             }
             Some(CssToken::Delim('.')) => {
                 self.advance(); // Skip '.'
-                if let Some(CssToken::Ident(class)) = &self.current_token {
-                    let selector = Selector::Class(class.to_string());
+                let class = match &self.current_token {
+                    Some(CssToken::Ident(class)) => unescape(class).into_owned(),
+                    Some(CssToken::CustomProperty(class)) => format!("--{}", unescape(class)),
+                    _ => return None,
+                };
+                self.advance();
+                Some(Selector::Class(class))
+            }
+            Some(CssToken::Delim('*')) => {
+                self.advance();
+                Some(Selector::Universal)
+            }
+            Some(CssToken::LeftBracket) => {
+                self.advance(); // Skip '['
+                self.parse_attribute_selector()
+            }
+            Some(CssToken::Colon) => {
+                self.advance(); // Skip ':'
+                if matches!(self.current_token, Some(CssToken::Colon)) {
+                    self.advance(); // Skip the second ':'
+                    let Some(CssToken::Ident(name)) = &self.current_token else {
+                        return None;
+                    };
+                    let selector = Selector::PseudoElement(unescape(name).to_ascii_lowercase());
                     self.advance();
                     Some(selector)
                 } else {
-                    None
+                    self.parse_pseudo_class()
                 }
             }
-            Some(CssToken::Delim('*')) => {
+            _ => None,
+        }
+    }
+
+    fn parse_pseudo_class(&mut self) -> Option<Selector> {
+        match &self.current_token {
+            Some(CssToken::Ident(name)) => {
+                let name = unescape(name).to_ascii_lowercase();
                 self.advance();
-                Some(Selector::Universal)
+                if matches!(name.as_str(), "before" | "after" | "first-line" | "first-letter") {
+                    Some(Selector::PseudoElement(name))
+                } else {
+                    Some(Selector::PseudoClass { name, argument: None })
+                }
+            }
+            Some(CssToken::Function(name)) if unescape(name).eq_ignore_ascii_case("not") => {
+                self.advance(); // Skip 'not('
+                let argument = self.parse_selector()?;
+                self.skip_whitespace();
+                if !matches!(self.current_token, Some(CssToken::RightParen)) {
+                    return None;
+                }
+                self.advance(); // Skip ')'
+                Some(Selector::Not(Box::new(argument)))
+            }
+            Some(CssToken::Function(name)) => {
+                let name = unescape(name).to_ascii_lowercase();
+                self.advance(); // Skip 'name('
+
+                // Capture the argument verbatim up to the matching ')'
+                let start = self.current_span.start.byte_offset;
+                let mut depth = 0;
+                loop {
+                    match self.current_token {
+                        None => return None,
+                        Some(CssToken::RightParen) if depth == 0 => break,
+                        Some(CssToken::RightParen) => depth -= 1,
+                        Some(CssToken::LeftParen) | Some(CssToken::Function(_)) => depth += 1,
+                        _ => {}
+                    }
+                    self.advance();
+                }
+                let argument = preprocess(self.input[start..self.current_span.start.byte_offset].trim()).into_owned();
+                self.advance(); // Skip ')'
+                let nth: Option<fn(NthExpr) -> Selector> = match name.as_str() {
+                    "nth-child" => Some(Selector::NthChild),
+                    "nth-of-type" => Some(Selector::NthOfType),
+                    "nth-last-child" => Some(Selector::NthLastChild),
+                    "nth-last-of-type" => Some(Selector::NthLastOfType),
+                    _ => None,
+                };
+                match nth {
+                    Some(nth) => parse_nth(&argument).map(nth),
+                    None => Some(Selector::PseudoClass { name, argument: Some(argument) }),
+                }
             }
             _ => None,
         }
     }
 
-    fn parse_declarations(&mut self) -> HashMap<String, String> {
-        let mut declarations = HashMap::new();
-        
+    fn parse_attribute_selector(&mut self) -> Option<Selector> {
+        self.skip_whitespace();
+        let name = match &self.current_token {
+            Some(CssToken::Ident(name)) => unescape(name).into_owned(),
+            _ => return None,
+        };
+        self.advance();
+        self.skip_whitespace();
+
+        let mut value = None;
+        let mut case_insensitive = false;
+        let operator = match self.current_token {
+            Some(CssToken::Delim('=')) => Some(AttrOp::Equals),
+            Some(CssToken::IncludeMatch) => Some(AttrOp::Includes),
+            Some(CssToken::DashMatch) => Some(AttrOp::DashMatch),
+            Some(CssToken::PrefixMatch) => Some(AttrOp::Prefix),
+            Some(CssToken::SuffixMatch) => Some(AttrOp::Suffix),
+            Some(CssToken::SubstringMatch) => Some(AttrOp::Substring),
+            _ => None,
+        };
+        if operator.is_some() {
+            self.advance(); // Skip the operator
+            self.skip_whitespace();
+            value = match &self.current_token {
+                Some(CssToken::Ident(text)) => Some(unescape(text).into_owned()),
+                Some(CssToken::String(text)) => Some(unescape_string(text).into_owned()),
+                _ => return None,
+            };
+            self.advance();
+            self.skip_whitespace();
+
+            // The `i` or `s` flag
+            if let Some(CssToken::Ident(flag)) = &self.current_token {
+                case_insensitive = match unescape(flag).to_ascii_lowercase().as_str() {
+                    "i" => true,
+                    "s" => false,
+                    _ => return None,
+                };
+                self.advance();
+                self.skip_whitespace();
+            }
+        }
+
+        if !matches!(self.current_token, Some(CssToken::RightBracket)) {
+            return None;
+        }
+        self.advance(); // Skip ']'
+        Some(Selector::Attribute { name, operator, value, case_insensitive })
+    }
+
+    /// Parses declarations up to `}` or the end of input, in source order
+    /// and keeping duplicates.
+    fn parse_declaration_list(&mut self) -> DeclarationList {
+        self.parse_declarations(false)
+    }
+
+    /// `parse_declaration_list`, reading `unicode-range` as the `@font-face`
+    /// descriptor if `font_face` is set.
+    fn parse_declarations(&mut self, font_face: bool) -> DeclarationList {
+        let mut declarations = DeclarationList::new();
         loop {
             self.skip_whitespace();
-            
             if matches!(self.current_token, Some(CssToken::RightBrace)) || self.current_token.is_none() {
                 break;
             }
-            
-            if let Some((property, value)) = self.parse_declaration() {
-                declarations.insert(property, value);
+            match self.parse_declaration(font_face) {
+                Ok((property, value, important)) => {
+                    declarations.push(Declaration { property, value, important });
+                }
+                Err(error) => {
+                    self.errors.push(error);
+                    self.skip_declaration();
+                }
             }
-            
-            // Skip semicolon if present
             if matches!(self.current_token, Some(CssToken::Semicolon)) {
                 self.advance();
             }
         }
-        
         declarations
     }
 
-    fn parse_declaration(&mut self) -> Option<(String, String)> {
+    fn parse_declaration(&mut self, font_face: bool) -> Result<(String, String, bool), ParseError> {
         // Parse property name
         let property = match &self.current_token {
             Some(CssToken::Ident(name)) => {
-                let prop = name.to_string();
+                let prop = unescape(name).into_owned();
                 self.advance();
                 prop
             }
-            _ => return None,
+            Some(CssToken::CustomProperty(name)) => {
+                let prop = format!("--{}", unescape(name));
+                self.advance();
+                prop
+            }
+            _ => return Err(self.error_here(ParseErrorKind::UnexpectedToken, "expected a property name")),
         };
         
         self.skip_whitespace();
         
         // Expect ':'
         if !matches!(self.current_token, Some(CssToken::Colon)) {
-            return None;
+            return Err(self.error_here(
+                ParseErrorKind::MissingColon,
+                format!("expected `:` after `{}`", property),
+            ));
         }
         self.advance(); // Skip ':'
         
@@ -226,54 +1043,174 @@ impl<'a> CssParser<'a> {
         
         // Parse value
         let mut value_parts = Vec::new();
+        // The source text of the value, from its first token to its last
+        let mut value_source = None;
         
         loop {
             match &self.current_token {
                 Some(CssToken::Semicolon) | Some(CssToken::RightBrace) | None => break,
-                Some(CssToken::Whitespace) => {
-                    if !value_parts.is_empty() {
+                // A comment separates tokens like whitespace does, so
+                // `1px/**/2px` is `1px 2px` and not `1px2px`
+                Some(CssToken::Whitespace(_)) | Some(CssToken::Comment(_)) => {
+                    if value_parts.last().is_some_and(|part| part != " ") {
                         value_parts.push(" ".to_string());
                     }
                     self.advance();
                 }
+                Some(CssToken::BadString) | Some(CssToken::BadUrl) => {
+                    return Err(self.error_here(
+                        ParseErrorKind::UnexpectedToken,
+                        format!("invalid string or url in the value of `{}`", property),
+                    ));
+                }
                 Some(token) => {
                     value_parts.push(self.token_to_string(token));
+                    let span = self.current_span.byte_range();
+                    value_source = Some(value_source.map_or(span.clone(), |source: Range<usize>| source.start..span.end));
                     self.advance();
                 }
             }
         }
         
+        if font_face && property.eq_ignore_ascii_case("unicode-range") {
+            let source = &self.input[value_source.unwrap_or_default()];
+            return match unicode_range_list(source) {
+                Some(value) => Ok((property, value, false)),
+                None => Err(self.error_here(ParseErrorKind::UnexpectedToken, "invalid `unicode-range` value")),
+            };
+        }
+
+        let important = strip_important(&mut value_parts);
         if value_parts.is_empty() {
-            None
+            Err(self.error_here(
+                ParseErrorKind::UnexpectedToken,
+                format!("expected a value for `{}`", property),
+            ))
         } else {
             let value = value_parts.join("").trim().to_string();
-            Some((property, value))
+            Ok((property, value, important))
+        }
+    }
+
+    /// Skips the rest of an invalid declaration, up to the next `;` or `}`.
+    fn skip_declaration(&mut self) {
+        while !matches!(
+            self.current_token,
+            Some(CssToken::Semicolon) | Some(CssToken::RightBrace) | None
+        ) {
+            self.advance();
         }
     }
 
+    /// Writes a token of a declaration value. Numbers are normalized but
+    /// keep an explicit `+`.
     fn token_to_string(&self, token: &CssToken) -> String {
         match token {
-            CssToken::Ident(s) => s.to_string(),
-            CssToken::String(s) => format!("\"{}\"", s),
-            CssToken::Number(n) => n.to_string(),
-            CssToken::Dimension { value, unit } => format!("{}{}", value, unit),
-            CssToken::Percentage(p) => format!("{}%", p),
-            CssToken::Hash(h) => format!("#{}", h),
-            CssToken::Delim(c) => c.to_string(),
-            CssToken::Url(url) => format!("url({})", url),
-            _ => String::new(),
+            CssToken::Number { value, .. } => self.format_signed(*value),
+            CssToken::Dimension { value, unit, .. } => format!("{}{}", self.format_signed(*value), unit),
+            CssToken::Percentage { value, .. } => format!("{}%", self.format_signed(*value)),
+            token => token.to_string(),
+        }
+    }
+
+    /// Formats the value of the current numeric token, keeping an explicit
+    /// `+` sign from the source (`counter-increment: c +1`).
+    fn format_signed(&self, value: f64) -> String {
+        let number = format_number(value, NumberFormat::default());
+        if self.input[self.current_span.byte_range()].starts_with('+') {
+            format!("+{}", number)
+        } else {
+            number
         }
     }
 
     fn skip_whitespace(&mut self) {
-        while matches!(self.current_token, Some(CssToken::Whitespace) | Some(CssToken::Comment(_))) {
+        while self.current_token.as_ref().is_some_and(CssToken::is_trivia) {
             self.advance();
         }
     }
 
     fn advance(&mut self) {
         self.current_token = self.tokenizer.next_token();
+        self.current_span = match self.current_token {
+            Some(_) => self.tokenizer.token_span(),
+            None => {
+                let end = self.tokenizer.token_span().end;
+                Span { start: end, end }
+            }
+        };
+    }
+
+    /// Builds an error located at the current token.
+    fn error_here(&self, kind: ParseErrorKind, message: impl Into<String>) -> ParseError {
+        ParseError::new(self.current_span, kind, message)
+    }
+}
+
+/// Reads a `unicode-range` descriptor, a comma-separated list of ranges,
+/// into its canonical form: `U+0025-00ff, u+4??` becomes `U+25-FF, U+400-4FF`.
+///
+/// The ranges are read from the source text, as their tokens (an ident,
+/// numbers and delims) cannot tell `U+1e3` from `U+1000`.
+fn unicode_range_list(source: &str) -> Option<String> {
+    let ranges = source
+        .split(',')
+        .map(|range| match parse_unicode_range(range.trim())? {
+            (start, end) if start == end => Some(format!("U+{:X}", start)),
+            (start, end) => Some(format!("U+{:X}-{:X}", start, end)),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(ranges.join(", "))
+}
+
+/// Parses one `U+` range by the CSS Syntax `<urange>` rules into the
+/// inclusive range of code points it covers: up to six hex digits, then
+/// either `?` wildcards or an optional `-` and up to six more hex digits.
+fn parse_unicode_range(text: &str) -> Option<(u32, u32)> {
+    let rest = text.strip_prefix(['u', 'U'])?.strip_prefix('+')?;
+    let digits = rest.bytes().take_while(u8::is_ascii_hexdigit).count();
+    let wildcards = rest[digits..].bytes().take_while(|&b| b == b'?').count();
+    let (first, rest) = rest.split_at(digits + wildcards);
+    if first.is_empty() || first.len() > 6 {
+        return None;
+    }
+
+    let (start, end) = if wildcards > 0 {
+        if !rest.is_empty() {
+            return None;
+        }
+        let start = u32::from_str_radix(&first.replace('?', "0"), 16).ok()?;
+        (start, u32::from_str_radix(&first.replace('?', "F"), 16).ok()?)
+    } else {
+        let start = u32::from_str_radix(first, 16).ok()?;
+        match rest.strip_prefix('-') {
+            None if rest.is_empty() => (start, start),
+            Some(last) if (1..=6).contains(&last.len()) && last.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                (start, u32::from_str_radix(last, 16).ok()?)
+            }
+            _ => return None,
+        }
+    };
+    (start <= end && end <= 0x10FFFF).then_some((start, end))
+}
+
+/// Removes a trailing `!important` (any case, whitespace allowed after the
+/// `!`) from the serialized value parts and returns whether it was there.
+fn strip_important(parts: &mut Vec<String>) -> bool {
+    let mut rest = parts.len();
+    let mut previous = || {
+        while rest > 0 && parts[rest - 1] == " " {
+            rest -= 1;
+        }
+        rest = rest.checked_sub(1)?;
+        Some(parts[rest].as_str())
+    };
+    let flagged = previous().is_some_and(|part| part.eq_ignore_ascii_case("important"))
+        && previous() == Some("!");
+    if flagged {
+        parts.truncate(rest);
     }
+    flagged
 }
 
 #[cfg(test)]
@@ -290,7 +1227,34 @@ mod tests {
         let rule = &rules[0];
         assert_eq!(rule.selectors.len(), 1);
         assert!(matches!(rule.selectors[0], Selector::Type(ref name) if name == "div"));
-        assert_eq!(rule.declarations.get("color"), Some(&"red".to_string()));
+        assert_eq!(rule.declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+    }
+
+    #[test]
+    fn test_nul_and_carriage_returns() {
+        let css = "a\0b { color: re\0d; }\r\n.x\r{\x0Cmargin:0\r\n}\r[title='it\\\r\ns']{top:0}";
+        let rules = CssParser::new(css).parse();
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].selectors, vec![Selector::Type("a\u{FFFD}b".to_string())]);
+        assert_eq!(rules[0].declarations.get("color").map(|d| d.value.as_str()), Some("re\u{FFFD}d"));
+        assert_eq!(rules[1].selectors, vec![Selector::Class("x".to_string())]);
+        assert_eq!(rules[1].declarations.get("margin").map(|d| d.value.as_str()), Some("0"));
+        assert!(matches!(&rules[2].selectors[0], Selector::Attribute { value: Some(value), .. } if !value.contains('\r')));
+        assert_eq!(rules[2].declarations.get("top").map(|d| d.value.as_str()), Some("0"));
+    }
+
+    #[test]
+    fn test_strings_are_decoded() {
+        let rules = CssParser::new("[title=\"a\\\"b\\\n c\"] { x: y }").parse();
+        assert!(matches!(
+            &rules[0].selectors[0],
+            Selector::Attribute { value: Some(value), .. } if value == "a\"b c"
+        ));
+
+        let items = CssParser::new("@import \"a\\20 b.css\"; @keyframes \"sp\\69n\" { to { x: y } }").parse_stylesheet();
+        assert!(matches!(&items[0], StylesheetItem::Import(import) if import.url == "a b.css"));
+        assert!(matches!(&items[1], StylesheetItem::Keyframes(keyframes) if keyframes.name == "spin"));
     }
 
     #[test]
@@ -317,7 +1281,7 @@ mod tests {
         let rule = &rules[0];
         assert_eq!(rule.selectors.len(), 1);
         assert!(matches!(rule.selectors[0], Selector::Class(ref name) if name == "container"));
-        assert_eq!(rule.declarations.get("width"), Some(&"100%".to_string()));
+        assert_eq!(rule.declarations.get("width").map(|d| d.value.as_str()), Some("100%"));
     }
 
     #[test]
@@ -330,7 +1294,7 @@ mod tests {
         let rule = &rules[0];
         assert_eq!(rule.selectors.len(), 1);
         assert!(matches!(rule.selectors[0], Selector::Id(ref name) if name == "main"));
-        assert_eq!(rule.declarations.get("display"), Some(&"block".to_string()));
+        assert_eq!(rule.declarations.get("display").map(|d| d.value.as_str()), Some("block"));
     }
 
     #[test]
@@ -343,7 +1307,7 @@ mod tests {
         let rule = &rules[0];
         assert_eq!(rule.selectors.len(), 1);
         assert!(matches!(rule.selectors[0], Selector::Universal));
-        assert_eq!(rule.declarations.get("box-sizing"), Some(&"border-box".to_string()));
+        assert_eq!(rule.declarations.get("box-sizing").map(|d| d.value.as_str()), Some("border-box"));
     }
 
     #[test]
@@ -391,9 +1355,9 @@ mod tests {
         
         let rule = &rules[0];
         assert_eq!(rule.declarations.len(), 3);
-        assert_eq!(rule.declarations.get("color"), Some(&"red".to_string()));
-        assert_eq!(rule.declarations.get("background"), Some(&"blue".to_string()));
-        assert_eq!(rule.declarations.get("font-size"), Some(&"16px".to_string()));
+        assert_eq!(rule.declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+        assert_eq!(rule.declarations.get("background").map(|d| d.value.as_str()), Some("blue"));
+        assert_eq!(rule.declarations.get("font-size").map(|d| d.value.as_str()), Some("16px"));
     }
 
     #[test]
@@ -413,4 +1377,802 @@ mod tests {
         assert!(matches!(rules[1].selectors[0], Selector::Class(ref name) if name == "container"));
         assert!(matches!(rules[2].selectors[0], Selector::Id(ref name) if name == "main"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_function_values() {
+        let css = r#"div {
+            color: rgb(255,0,0);
+            width: calc(100% - 2 * 10px);
+            background: linear-gradient(rgba(0, 0, 0, 0.5), red);
+            src: local("Arial");
+        }"#;
+        let rules = CssParser::new(css).parse();
+
+        let declarations = &rules[0].declarations;
+        assert_eq!(declarations.get("color").map(|d| d.value.as_str()), Some("rgb(255,0,0)"));
+        assert_eq!(declarations.get("width").map(|d| d.value.as_str()), Some("calc(100% - 2 * 10px)"));
+        assert_eq!(
+            declarations.get("background").map(|d| d.value.as_str()),
+            Some("linear-gradient(rgba(0, 0, 0, 0.5), red)")
+        );
+        assert_eq!(declarations.get("src").map(|d| d.value.as_str()), Some("local(\"Arial\")"));
+    }
+
+    #[test]
+    fn test_function_tokens_in_values() {
+        let css = "a { color: rgba(0,0,0,.5); width: calc(100% - 20px); }";
+        let tokens: Vec<_> = CssTokenizer::new(css).collect();
+        let rules = CssParser::new(css).parse();
+
+        assert!(tokens.contains(&CssToken::Function("rgba".into())));
+        assert!(tokens.contains(&CssToken::Function("calc".into())));
+        assert!(!tokens.contains(&CssToken::LeftParen));
+        let declarations = &rules[0].declarations;
+        assert_eq!(declarations.get("color").map(|d| d.value.as_str()), Some("rgba(0,0,0,0.5)"));
+        assert_eq!(declarations.get("width").map(|d| d.value.as_str()), Some("calc(100% - 20px)"));
+    }
+
+    #[test]
+    fn test_non_ascii_selectors() {
+        let css = ".кнопка { color: red; } .日本語 > #naïve { margin: 0; } .🎉 { content: \"🎉\"; }";
+        let rules = CssParser::new(css).parse();
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].selectors, vec![Selector::Class("кнопка".to_string())]);
+        assert_eq!(
+            rules[1].selectors,
+            vec![Selector::Child(
+                Box::new(Selector::Class("日本語".to_string())),
+                Box::new(Selector::Id("naïve".to_string())),
+            )]
+        );
+        assert_eq!(rules[2].selectors, vec![Selector::Class("🎉".to_string())]);
+        assert_eq!(rules[2].declarations.get("content").map(|d| d.value.as_str()), Some("\"🎉\""));
+    }
+
+    #[test]
+    fn test_escaped_selectors() {
+        let rules = CssParser::new(r".hover\:bg-red, #\26 foo, .\31 23 { color: red; }").parse();
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::Class("hover:bg-red".to_string()),
+                Selector::Id("&foo".to_string()),
+                Selector::Class("123".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attribute_selectors() {
+        let css = "[disabled], [type=\"text\"], [xlink\\:href], [class~=\"foo\"], \
+                   [lang|=en], [href^='https'], [src $= \".png\"], [title*=x] { display: none; }";
+        let rules = CssParser::new(css).parse();
+        let attribute = |name: &str, operator: Option<AttrOp>, value: Option<&str>| Selector::Attribute {
+            name: name.to_string(),
+            operator,
+            value: value.map(str::to_string),
+            case_insensitive: false,
+        };
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                attribute("disabled", None, None),
+                attribute("type", Some(AttrOp::Equals), Some("text")),
+                attribute("xlink:href", None, None),
+                attribute("class", Some(AttrOp::Includes), Some("foo")),
+                attribute("lang", Some(AttrOp::DashMatch), Some("en")),
+                attribute("href", Some(AttrOp::Prefix), Some("https")),
+                attribute("src", Some(AttrOp::Suffix), Some(".png")),
+                attribute("title", Some(AttrOp::Substring), Some("x")),
+            ]
+        );
+        assert!(CssParser::new("[a~b] {}").parse().is_empty());
+        assert!(CssParser::new("[a~ =b] {}").parse().is_empty());
+        assert!(matches!(
+            CssParser::new("a ~ [b~=c]").parse_selector_list().as_deref(),
+            Some([Selector::GeneralSibling(..)])
+        ));
+        assert!(CssParser::new("[a!=b] {}").parse().is_empty());
+        // A value must be an identifier or a string
+        assert!(CssParser::new("[src$=.png] {}").parse().is_empty());
+    }
+
+    #[test]
+    fn test_attribute_case_flag() {
+        let rules = CssParser::new("[data-id=\"3\" i], [type=a S], [lang|=EN I] { x: y }").parse();
+        let flags: Vec<_> = rules[0]
+            .selectors
+            .iter()
+            .map(|selector| match selector {
+                Selector::Attribute { case_insensitive, .. } => *case_insensitive,
+                _ => panic!("Expected an attribute selector, got {:?}", selector),
+            })
+            .collect();
+
+        assert_eq!(flags, vec![true, false, true]);
+        assert!(CssParser::new("[a=b x] {}").parse().is_empty());
+        assert!(CssParser::new("[a i] {}").parse().is_empty());
+    }
+
+    #[test]
+    fn test_attr_op_matches() {
+        assert!(AttrOp::Includes.matches("btn foo", "foo"));
+        assert!(!AttrOp::Includes.matches("btn foobar", "foo"));
+        assert!(!AttrOp::Includes.matches("a b", "a b"));
+        assert!(AttrOp::DashMatch.matches("en-US", "en"));
+        assert!(AttrOp::DashMatch.matches("en", "en"));
+        assert!(!AttrOp::DashMatch.matches("english", "en"));
+        assert!(AttrOp::Prefix.matches("https://x", "https"));
+        assert!(AttrOp::Suffix.matches("a.png", ".png"));
+        assert!(AttrOp::Substring.matches("abc", "b"));
+        assert!(!AttrOp::Substring.matches("abc", ""));
+    }
+
+    #[cfg(feature = "matching")]
+    #[test]
+    fn test_prefixed_svg_attribute() {
+        let nodes = crate::html::parser::HtmlParser::new(r##"<svg><use xlink:href="#icon"/></svg>"##).parse();
+        let crate::html::parser::Node::Element(svg) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        let crate::html::parser::Node::Element(use_element) = &svg.children[0] else {
+            panic!("Expected element node");
+        };
+        let rules = CssParser::new("[xlink\\:href] { fill: red; }").parse();
+
+        assert!(rules[0].selectors[0].matches_element(use_element));
+        assert_eq!(use_element.attr("xlink:href"), Some("#icon"));
+        assert_eq!(use_element.attr("href"), None);
+        assert_eq!(use_element.attr_local("href"), Some("#icon"));
+        assert!(!rules[0].selectors[0].matches_element(svg));
+    }
+
+    #[cfg(feature = "matching")]
+    #[test]
+    fn test_case_insensitive_attribute_matching() {
+        let document = crate::html::parser::HtmlParser::new(r#"<input type="TEXT"><a lang="EN-us">x</a>"#)
+            .parse_document();
+
+        assert_eq!(document.query_selector_all("[type=text]").len(), 0);
+        assert_eq!(document.query_selector_all("[type=text i]").len(), 1);
+        assert_eq!(document.query_selector_all("a[lang|=\"en\" i]").len(), 1);
+        assert_eq!(document.query_selector_all("[type=text s]").len(), 0);
+    }
+
+    #[test]
+    fn test_missing_colon_error() {
+        let (rules, errors) = CssParser::new("div { color }").parse_with_errors();
+
+        assert_eq!(rules.len(), 1);
+        assert!(rules[0].declarations.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::MissingColon);
+        assert_eq!(errors[0].span.byte_range(), 12..13);
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let css = "} p { 12px; color: red; margin: ; width: 1px }\na { b: c";
+        let (rules, errors) = CssParser::new(css).parse_with_errors();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].declarations.len(), 2);
+        assert_eq!(rules[0].declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+        assert_eq!(rules[1].declarations.get("b").map(|d| d.value.as_str()), Some("c"));
+        let kinds: Vec<_> = errors.iter().map(|error| error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParseErrorKind::InvalidSelector,
+                ParseErrorKind::UnexpectedToken,
+                ParseErrorKind::UnexpectedToken,
+                ParseErrorKind::UnexpectedEof,
+            ]
+        );
+        assert_eq!(errors[3].span.start.line, 2);
+    }
+
+    #[test]
+    fn test_scientific_notation_value() {
+        let rules = CssParser::new("g { stroke-width: 1e3; opacity: 2.5E-2; margin: 1e3px }").parse();
+
+        assert_eq!(rules[0].declarations.get("stroke-width").map(|d| d.value.as_str()), Some("1000"));
+        assert_eq!(rules[0].declarations.get("opacity").map(|d| d.value.as_str()), Some("0.025"));
+        assert_eq!(rules[0].declarations.get("margin").map(|d| d.value.as_str()), Some("1000px"));
+    }
+
+    #[test]
+    fn test_cdo_cdc_between_rules() {
+        let rules = CssParser::new("<!-- body { color: red } -->").parse();
+
+        assert_eq!(rules.len(), 1);
+        assert!(matches!(rules[0].selectors[0], Selector::Type(ref name) if name == "body"));
+
+        let rules = CssParser::new("<!--\np { x: 1 --> 2; content: \"-->\"; color: red }\n-->\na { b: c }").parse();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].declarations.get("x").map(|d| d.value.as_str()), Some("1 --> 2"));
+        assert_eq!(rules[0].declarations.get("content").map(|d| d.value.as_str()), Some("\"-->\""));
+        assert_eq!(rules[0].declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+    }
+
+    #[test]
+    fn test_specificity_ordering() {
+        let rules = CssParser::new("#id .class div {} .container p {} div {} [href] > *, p {}").parse();
+        let specificities: Vec<_> = rules.iter().map(Rule::max_specificity).collect();
+
+        assert_eq!(
+            specificities,
+            vec![
+                Specificity(1, 1, 1),
+                Specificity(0, 1, 1),
+                Specificity(0, 0, 1),
+                Specificity(0, 1, 0),
+            ]
+        );
+        assert!(specificities[0] > specificities[1]);
+        assert!(specificities[1] > specificities[2]);
+        assert!(specificities[3] > specificities[2]);
+    }
+
+    #[test]
+    fn test_selector_specificity() {
+        let cases = [
+            ("#id", Specificity(1, 0, 0)),
+            ("div.foo", Specificity(0, 1, 1)),
+            ("ul li a:hover", Specificity(0, 1, 3)),
+            ("*", Specificity(0, 0, 0)),
+            ("p::before + [type=text]", Specificity(0, 1, 2)),
+        ];
+
+        for (selector, expected) in cases {
+            let selectors = CssParser::new(selector).parse_selector_list().unwrap();
+            assert_eq!(selectors[0].specificity(), expected, "{}", selector);
+        }
+        assert_eq!(<(u32, u32, u32)>::from(Specificity(1, 2, 3)), (1, 2, 3));
+    }
+
+    #[test]
+    fn test_important_flag() {
+        let css = "a { color:red!important } b { color: red ! IMPORTANT; } c { color: red } d { color: red !important; color: blue }";
+        let rules = CssParser::new(css).parse();
+
+        for rule in &rules[..3] {
+            assert_eq!(rule.declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+        }
+        assert!(rules[0].is_important("color"));
+        assert!(rules[1].is_important("color"));
+        assert!(!rules[2].is_important("color"));
+        // Both declarations are kept; the important one still applies.
+        assert_eq!(rules[3].declarations.get("color").map(|d| d.value.as_str()), Some("blue"));
+        assert!(rules[3].is_important("color"));
+    }
+
+    #[test]
+    fn test_declarations_keep_order_and_duplicates() {
+        let rules = CssParser::new("a { width: 1px; display: flex; color: red; display: grid }").parse();
+        let declarations = &rules[0].declarations;
+
+        let written: Vec<_> = declarations.iter().map(|d| (d.property.as_str(), d.value.as_str())).collect();
+        assert_eq!(written, vec![("width", "1px"), ("display", "flex"), ("color", "red"), ("display", "grid")]);
+        assert_eq!(declarations.get("display").map(|d| d.value.as_str()), Some("grid"));
+        assert_eq!(declarations.get_all("display").count(), 2);
+        assert_eq!(declarations.len(), 4);
+    }
+
+    #[test]
+    fn test_important_declarations_in_order() {
+        let declarations = CssParser::new("color: red !important; color: blue; margin: 0 ! important").parse_inline_style();
+
+        assert_eq!(
+            declarations,
+            vec![
+                Declaration { property: "color".to_string(), value: "red".to_string(), important: true },
+                Declaration::new("color", "blue"),
+                Declaration { property: "margin".to_string(), value: "0".to_string(), important: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_declarations_as_strings() {
+        let rules = CssParser::new("a { color: red !important; Color: blue; MARGIN: 0; --Gap: 1px; margin: 2px }").parse();
+
+        let values = rules[0].declarations_as_strings();
+
+        let expected: HashMap<String, String> = [("color", "red"), ("margin", "2px"), ("--Gap", "1px")]
+            .into_iter()
+            .map(|(property, value)| (property.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(values, expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let rules = CssParser::new(
+            "ul > li:nth-child(2n+1), a[href^='https:' i]:not(.x) { color: red !important; margin: 0 } *::before {}",
+        )
+        .parse();
+
+        for rule in &rules {
+            let json = serde_json::to_string(rule).unwrap();
+            assert_eq!(serde_json::from_str::<Rule>(&json).unwrap(), *rule);
+        }
+    }
+
+    #[test]
+    fn test_media_rule() {
+        // The responsive block of the benchmark stylesheet
+        let css = "/* Responsive design */
+@media (max-width: 768px) {
+    .hero h1 {
+        font-size: 2.5rem;
+    }
+    
+    .nav-list {
+        flex-direction: column;
+        gap: 1rem;
+    }
+    
+    .testimonial-list {
+        flex-direction: column;
+    }
+    
+    .feature-grid {
+        grid-template-columns: 1fr;
+    }
+}
+";
+        let items = CssParser::new(css).parse_stylesheet();
+
+        let expected_selector = Selector::Descendant(
+            Box::new(Selector::Class("hero".to_string())),
+            Box::new(Selector::Type("h1".to_string())),
+        );
+        match &items[..] {
+            [StylesheetItem::Media(media)] => {
+                assert_eq!(media.query, "(max-width: 768px)");
+                assert_eq!(media.rules.len(), 4);
+                assert_eq!(media.rules[0].selectors, vec![expected_selector]);
+                assert_eq!(media.rules[0].declarations.get("font-size").map(|d| d.value.as_str()), Some("2.5rem"));
+                assert_eq!(media.rules[1].declarations.get("gap").map(|d| d.value.as_str()), Some("1rem"));
+                assert_eq!(media.rules[3].selectors, vec![Selector::Class("feature-grid".to_string())]);
+            }
+            _ => panic!("Expected one media rule, got {:?}", items),
+        }
+        assert!(CssParser::new(css).parse().is_empty());
+    }
+
+    #[test]
+    fn test_stylesheet_item_order_and_unknown_at_rules() {
+        let css = "@namespace svg url(x); a {} @media print { b {} } @page { margin: 0 } c {}";
+        let (items, errors) = CssParser::new(css).parse_stylesheet_with_errors();
+
+        assert!(errors.is_empty());
+        assert_eq!(items.len(), 3);
+        assert!(matches!(&items[0], StylesheetItem::Rule(rule) if rule.selectors == vec![Selector::Type("a".to_string())]));
+        assert!(matches!(&items[1], StylesheetItem::Media(media) if media.query == "print" && media.rules.len() == 1));
+        assert!(matches!(&items[2], StylesheetItem::Rule(rule) if rule.selectors == vec![Selector::Type("c".to_string())]));
+    }
+
+    #[test]
+    fn test_unclosed_media_block() {
+        let (rules, errors) = CssParser::new("@media screen { a { b: c }").parse_with_errors();
+
+        assert!(rules.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_pseudo_classes() {
+        let rules = CssParser::new("a:hover, li:nth-child(2n+1), input:not(.disabled), :focus { x: y }").parse();
+        let pseudo = |name: &str, argument: Option<&str>| Selector::PseudoClass {
+            name: name.to_string(),
+            argument: argument.map(str::to_string),
+        };
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::Compound(vec![Selector::Type("a".to_string()), pseudo("hover", None)]),
+                Selector::Compound(vec![Selector::Type("li".to_string()), Selector::NthChild(NthExpr { a: 2, b: 1 })]),
+                Selector::Compound(vec![
+                    Selector::Type("input".to_string()),
+                    Selector::Not(Box::new(Selector::Class("disabled".to_string()))),
+                ]),
+                pseudo("focus", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pseudo_class_arguments() {
+        let rules = CssParser::new(":dir(ltr), p:lang(en) { x: y }").parse();
+        let pseudo = |name: &str, argument: &str| Selector::PseudoClass {
+            name: name.to_string(),
+            argument: Some(argument.to_string()),
+        };
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                pseudo("dir", "ltr"),
+                Selector::Compound(vec![Selector::Type("p".to_string()), pseudo("lang", "en")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nth_selectors() {
+        let rules = CssParser::new(
+            ":nth-child(odd), :NTH-OF-TYPE(-n+3), :nth-last-child( 2n - 1 ), :nth-last-of-type(5) { x: y }",
+        )
+        .parse();
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::NthChild(NthExpr { a: 2, b: 1 }),
+                Selector::NthOfType(NthExpr { a: -1, b: 3 }),
+                Selector::NthLastChild(NthExpr { a: 2, b: -1 }),
+                Selector::NthLastOfType(NthExpr { a: 0, b: 5 }),
+            ]
+        );
+        assert_eq!(rules[0].selectors[1].specificity(), Specificity(0, 1, 0));
+        assert!(CssParser::new("li:nth-child(2n)").parse_selector_list().is_some());
+        assert!(CssParser::new("li:nth-child(2x)").parse_selector_list().is_none());
+    }
+
+    #[test]
+    fn test_hash_selectors_must_be_identifiers() {
+        let rules = CssParser::new("#main, a#nav { color: #fff } #1a2b3c { color: red } p { background: #1a2b3c }").parse();
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::Id("main".to_string()),
+                Selector::Compound(vec![Selector::Type("a".to_string()), Selector::Id("nav".to_string())]),
+            ]
+        );
+        assert_eq!(rules[0].declarations.get("color").map(|d| d.value.as_str()), Some("#fff"));
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].declarations.get("background").map(|d| d.value.as_str()), Some("#1a2b3c"));
+
+        assert!(CssParser::new("#1a2b3c").parse_selector_list().is_none());
+        assert!(CssParser::new("div#9").parse_selector_list().is_none());
+        assert!(CssParser::new(r"#\31 a").parse_selector_list().is_some());
+    }
+
+    #[test]
+    fn test_not_selector() {
+        let css = ":not(.disabled), :NOT( #sidebar ), input:not([type=submit]), li:not(div > p) { x: y }";
+        let rules = CssParser::new(css).parse();
+        let not = |selector: Selector| Selector::Not(Box::new(selector));
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                not(Selector::Class("disabled".to_string())),
+                not(Selector::Id("sidebar".to_string())),
+                Selector::Compound(vec![
+                    Selector::Type("input".to_string()),
+                    not(Selector::Attribute {
+                        name: "type".to_string(),
+                        operator: Some(AttrOp::Equals),
+                        value: Some("submit".to_string()),
+                        case_insensitive: false,
+                    }),
+                ]),
+                Selector::Compound(vec![
+                    Selector::Type("li".to_string()),
+                    not(Selector::Child(
+                        Box::new(Selector::Type("div".to_string())),
+                        Box::new(Selector::Type("p".to_string())),
+                    )),
+                ]),
+            ]
+        );
+        assert_eq!(rules[0].selectors[1].specificity(), Specificity(1, 0, 0));
+        assert_eq!(rules[0].selectors[3].specificity(), Specificity(0, 0, 3));
+        assert!(CssParser::new(":not() {}").parse().is_empty());
+        assert!(CssParser::new(":not(a b {}").parse().is_empty());
+    }
+
+    #[test]
+    fn test_compound_selectors() {
+        let rules = CssParser::new("div.container, #main.active, div.container#main.active, .solo { x: y }").parse();
+        let (div, container) = (Selector::Type("div".to_string()), Selector::Class("container".to_string()));
+        let (main, active) = (Selector::Id("main".to_string()), Selector::Class("active".to_string()));
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::Compound(vec![div.clone(), container.clone()]),
+                Selector::Compound(vec![main.clone(), active.clone()]),
+                Selector::Compound(vec![div, container, main, active]),
+                Selector::Class("solo".to_string()),
+            ]
+        );
+        assert_eq!(rules[0].selectors[0].specificity(), Specificity(0, 1, 1));
+        assert_eq!(rules[0].selectors[1].specificity(), Specificity(1, 1, 0));
+        assert_eq!(rules[0].selectors[2].specificity(), Specificity(1, 2, 1));
+        assert!(CssParser::new("div .container").parse_selector_list().is_some_and(|list| {
+            matches!(list[0], Selector::Descendant(..))
+        }));
+    }
+
+    #[test]
+    fn test_compound_in_complex_selector() {
+        let rules = CssParser::new(".nav-list li a:hover { color: red }").parse();
+
+        let Selector::Descendant(left, right) = &rules[0].selectors[0] else {
+            panic!("Expected descendant selector");
+        };
+        assert!(matches!(**left, Selector::Descendant(..)));
+        assert!(matches!(**right, Selector::Compound(ref parts) if parts.len() == 2));
+        assert_eq!(rules[0].max_specificity(), Specificity(0, 2, 2));
+    }
+
+    #[test]
+    fn test_unicode_range_descriptor() {
+        let (items, errors) = CssParser::new(
+            "@font-face { unicode-range: U+0025-00FF, u+4??, U+1e3 } \
+             @font-face { unicode-range: U+GGGG; font-display: swap } \
+             @font-face { unicode-range: U+110000 }",
+        )
+        .parse_stylesheet_with_errors();
+
+        let ranges: Vec<_> = items
+            .iter()
+            .map(|item| match item {
+                StylesheetItem::FontFace(declarations) => declarations.get("unicode-range").map(|d| d.value.as_str()),
+                _ => panic!("expected @font-face, got {:?}", item),
+            })
+            .collect();
+        assert_eq!(ranges, vec![Some("U+25-FF, U+400-4FF, U+1E3"), None, None]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|error| error.message == "invalid `unicode-range` value"));
+    }
+
+    #[test]
+    fn test_u_plus_selectors() {
+        for css in ["u+a {}", "u + a {}"] {
+            let rules = CssParser::new(css).parse();
+
+            assert_eq!(rules.len(), 1, "{}", css);
+            assert_eq!(
+                rules[0].selectors,
+                vec![Selector::Adjacent(
+                    Box::new(Selector::Type("u".to_string())),
+                    Box::new(Selector::Type("a".to_string()))
+                )],
+                "{}",
+                css
+            );
+        }
+    }
+
+    #[test]
+    fn test_bad_string_only_drops_its_declaration() {
+        let (rules, errors) = CssParser::new("p { content: \"oops\n; color: red }").parse_with_errors();
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].declarations.len(), 1);
+        assert_eq!(rules[0].declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span.start.byte_offset, 13);
+    }
+
+    #[test]
+    fn test_bad_url_only_drops_its_declaration() {
+        let css = "a { background: url(a b) no-repeat; color: red; cursor: url( data:x;y ) }";
+        let (rules, errors) = CssParser::new(css).parse_with_errors();
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].declarations.len(), 2);
+        assert_eq!(rules[0].declarations.get("color").map(|d| d.value.as_str()), Some("red"));
+        assert_eq!(rules[0].declarations.get("cursor").map(|d| d.value.as_str()), Some("url(data:x;y)"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::UnexpectedToken);
+    }
+
+    #[test]
+    fn test_keyframes() {
+        let css = "@keyframes slide { from { left: 0; } 50% { left: 100px; } to { left: 200px; } } a { b: c }";
+        let items = CssParser::new(css).parse_stylesheet();
+
+        assert_eq!(items.len(), 2);
+        let StylesheetItem::Keyframes(keyframes) = &items[0] else {
+            panic!("Expected keyframes, got {:?}", items[0]);
+        };
+        assert_eq!(keyframes.name, "slide");
+        let steps: Vec<_> = keyframes
+            .steps
+            .iter()
+            .map(|step| (step.selectors.clone(), step.declarations["left"].as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (vec![KeyframeSelector::From], "0"),
+                (vec![KeyframeSelector::Percentage(50.0)], "100px"),
+                (vec![KeyframeSelector::To], "200px"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyframes_recovery() {
+        let css = "@-webkit-keyframes \"fade\" { 0%, 100% { opacity: 1 !important; color: red } bad { x: y } }";
+        let (items, errors) = CssParser::new(css).parse_stylesheet_with_errors();
+
+        let StylesheetItem::Keyframes(keyframes) = &items[0] else {
+            panic!("Expected keyframes, got {:?}", items[0]);
+        };
+        assert_eq!(keyframes.name, "fade");
+        assert_eq!(keyframes.steps.len(), 1);
+        assert_eq!(
+            keyframes.steps[0].selectors,
+            vec![KeyframeSelector::Percentage(0.0), KeyframeSelector::Percentage(100.0)]
+        );
+        assert_eq!(keyframes.steps[0].declarations.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::InvalidSelector);
+    }
+
+    #[test]
+    fn test_pseudo_elements() {
+        let rules = CssParser::new("a::before, p:first-line, li:AFTER, .x::marker:hover, ::selection { x: y }").parse();
+        let element = |name: &str| Selector::PseudoElement(name.to_string());
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::Compound(vec![Selector::Type("a".to_string()), element("before")]),
+                Selector::Compound(vec![Selector::Type("p".to_string()), element("first-line")]),
+                Selector::Compound(vec![Selector::Type("li".to_string()), element("after")]),
+                Selector::Compound(vec![
+                    Selector::Class("x".to_string()),
+                    element("marker"),
+                    Selector::PseudoClass { name: "hover".to_string(), argument: None },
+                ]),
+                element("selection"),
+            ]
+        );
+        assert_eq!(specificity(&rules[0].selectors[0]), Specificity(0, 0, 2));
+
+        let rules = CssParser::new("p::before, span::after, input::placeholder, p::first-letter { x: y }").parse();
+        let names: Vec<_> = rules[0]
+            .selectors
+            .iter()
+            .map(|selector| match selector {
+                Selector::Compound(parts) => parts.last().cloned(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            names,
+            ["before", "after", "placeholder", "first-letter"].map(|name| Some(element(name))).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_import_rules() {
+        let css = r#"@import "base.css"; @import url(reset.css) print; @IMPORT url("x.css") screen and (min-width: 600px);
+                     @import 12; a { color: red }"#;
+        let (items, errors) = CssParser::new(css).parse_stylesheet_with_errors();
+
+        let import = |url: &str, media_query: Option<&str>| {
+            StylesheetItem::Import(ImportRule {
+                url: url.to_string(),
+                media_query: media_query.map(str::to_string),
+            })
+        };
+        assert_eq!(items[..3], [
+            import("base.css", None),
+            import("reset.css", Some("print")),
+            import("x.css", Some("screen and (min-width: 600px)")),
+        ]);
+        assert!(matches!(&items[3], StylesheetItem::Rule(rule) if rule.selectors == vec![Selector::Type("a".to_string())]));
+        assert_eq!(items.len(), 4);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::UnexpectedToken);
+    }
+
+    #[test]
+    fn test_custom_properties() {
+        let rules = CssParser::new(":root { --gap: 1rem; --Brand-Color: #6200ea } .--x { padding: var(--gap); margin: var(--m, 2px) }").parse();
+
+        assert_eq!(rules[0].declarations.get("--gap").map(|d| d.value.as_str()), Some("1rem"));
+        assert_eq!(rules[0].declarations.get("--Brand-Color").map(|d| d.value.as_str()), Some("#6200ea"));
+        assert_eq!(rules[1].selectors, vec![Selector::Class("--x".to_string())]);
+        assert_eq!(rules[1].declarations.get("padding").map(|d| d.value.as_str()), Some("var(--gap)"));
+        assert_eq!(rules[1].declarations.get("margin").map(|d| d.value.as_str()), Some("var(--m, 2px)"));
+
+        let rules = CssParser::new(":root { --main-color: #333; --1x: 2 }").parse();
+        assert_eq!(rules[0].declarations.get("--main-color").map(|d| d.value.as_str()), Some("#333"));
+        assert_eq!(rules[0].declarations.get("--1x").map(|d| d.value.as_str()), Some("2"));
+    }
+
+    #[test]
+    fn test_pseudo_class_case_and_precedence() {
+        let rules = CssParser::new("li:First-Child, div:HOVER > p, a:NOT(.X)::Before { x: y }").parse();
+        let pseudo = |name: &str, argument: Option<&str>| Selector::PseudoClass {
+            name: name.to_string(),
+            argument: argument.map(str::to_string),
+        };
+
+        assert_eq!(
+            rules[0].selectors,
+            vec![
+                Selector::Compound(vec![Selector::Type("li".to_string()), pseudo("first-child", None)]),
+                Selector::Child(
+                    Box::new(Selector::Compound(vec![Selector::Type("div".to_string()), pseudo("hover", None)])),
+                    Box::new(Selector::Type("p".to_string())),
+                ),
+                Selector::Compound(vec![
+                    Selector::Type("a".to_string()),
+                    Selector::Not(Box::new(Selector::Class("X".to_string()))),
+                    Selector::PseudoElement("before".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_charset_and_font_face() {
+        let css = "@charset \"utf-8\";
+                   @import url(\"foo.css\");
+                   @import \"print.css\" print and (orientation: landscape);
+                   @font-face { font-family: \"Inter\"; src: url(inter.woff2) format(\"woff2\"); font-weight: 100 900; }
+                   a { b: c }";
+        let (items, errors) = CssParser::new(css).parse_stylesheet_with_errors();
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(items[0], StylesheetItem::Charset("utf-8".to_string()));
+        assert_eq!(
+            items[1..3],
+            [
+                StylesheetItem::Import(ImportRule { url: "foo.css".to_string(), media_query: None }),
+                StylesheetItem::Import(ImportRule {
+                    url: "print.css".to_string(),
+                    media_query: Some("print and (orientation: landscape)".to_string()),
+                }),
+            ]
+        );
+        assert_eq!(
+            items[3],
+            StylesheetItem::FontFace(vec![
+                Declaration::new("font-family", "\"Inter\""),
+                Declaration::new("src", "url(inter.woff2) format(\"woff2\")"),
+                Declaration::new("font-weight", "100 900"),
+            ].into())
+        );
+        assert!(matches!(&items[4], StylesheetItem::Rule(_)));
+        assert_eq!(items.len(), 5);
+    }
+
+    #[test]
+    fn test_invalid_charset() {
+        let (items, errors) = CssParser::new("@charset utf-8; @font-face { src: x").parse_stylesheet_with_errors();
+
+        assert_eq!(items, vec![StylesheetItem::FontFace(vec![Declaration::new("src", "x")].into())]);
+        let kinds: Vec<_> = errors.iter().map(|error| error.kind).collect();
+        assert_eq!(kinds, vec![ParseErrorKind::UnexpectedToken, ParseErrorKind::UnexpectedEof]);
+    }
+
+    #[test]
+    fn test_plus_sign_survives_in_values() {
+        let rules = CssParser::new("li { counter-increment: c +1; margin: +2px -0.5em; z-index: +0 }").parse();
+
+        assert_eq!(rules[0].declarations.get("counter-increment").map(|d| d.value.as_str()), Some("c +1"));
+        assert_eq!(rules[0].declarations.get("margin").map(|d| d.value.as_str()), Some("+2px -0.5em"));
+        assert_eq!(rules[0].declarations.get("z-index").map(|d| d.value.as_str()), Some("+0"));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::css::tokenizer::{CssToken, CssTokenizer};
+use crate::location::SourceLocation;
+use std::collections::BTreeMap;
+
+/// How often a feature occurs and where it first does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureUsage {
+    pub count: usize,
+    /// Where the first occurrence starts.
+    pub example: SourceLocation,
+}
+
+/// The CSS features a stylesheet uses. Names are lower-cased and have
+/// escapes decoded; maps are sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureReport {
+    /// At-rules by name without the `@`, e.g. `media`, `-webkit-keyframes`.
+    pub at_rules: BTreeMap<String, FeatureUsage>,
+    /// Pseudo-classes in selectors, e.g. `hover`, `nth-child`.
+    pub pseudo_classes: BTreeMap<String, FeatureUsage>,
+    /// Pseudo-elements in selectors, including the legacy one-colon forms.
+    pub pseudo_elements: BTreeMap<String, FeatureUsage>,
+    /// Functions in declaration values, e.g. `calc`, `var`, `url`.
+    pub functions: BTreeMap<String, FeatureUsage>,
+    /// Vendor prefixes such as `-webkit-` on at-rules, properties, values,
+    /// functions and pseudo-classes.
+    pub vendor_prefixes: BTreeMap<String, FeatureUsage>,
+}
+
+/// At-rules whose block holds declarations rather than rules.
+pub(crate) const DECLARATION_AT_RULES: &[&str] =
+    &["font-face", "page", "counter-style", "property", "font-palette-values", "viewport"];
+
+/// What the tokens inside a block are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Block {
+    Rules,
+    Declarations,
+}
+
+/// What the tokens since the last rule in a `Block::Rules` belong to.
+#[derive(Debug, Clone, PartialEq)]
+enum Prelude {
+    None,
+    Selector,
+    AtRule(String),
+}
+
+/// Reports the at-rules, pseudo-classes, pseudo-elements, value functions
+/// and vendor prefixes used by the stylesheet `css`.
+///
+/// This walks the tokens rather than the parsed rules, since the AST keeps
+/// neither locations nor the at-rules it does not model.
+pub fn feature_report(css: &str) -> FeatureReport {
+    let mut report = FeatureReport::default();
+    let mut tokenizer = CssTokenizer::new_with_locations(css);
+    let mut blocks = vec![Block::Rules];
+    let mut prelude = Prelude::None;
+    // Colons seen just before the current token in a selector
+    let mut colons = 0;
+
+    while let Some(token) = tokenizer.next_token() {
+        let location = tokenizer.token_span().start;
+        let block = *blocks.last().unwrap_or(&Block::Rules);
+
+        if let Some(name) = token.unescaped()
+            && !matches!(token, CssToken::Hash { .. } | CssToken::CustomProperty(_))
+            && let Some(prefix) = vendor_prefix(&name)
+        {
+            record(&mut report.vendor_prefixes, prefix, location);
+        }
+
+        match &token {
+            CssToken::AtKeyword(_) => {
+                let name = token.unescaped().unwrap_or_default().to_ascii_lowercase();
+                record(&mut report.at_rules, &name, location);
+                if block == Block::Rules {
+                    prelude = Prelude::AtRule(name);
+                }
+            }
+            CssToken::LeftBrace => {
+                blocks.push(match &prelude {
+                    Prelude::AtRule(name) if !DECLARATION_AT_RULES.contains(&strip_vendor_prefix(name)) => {
+                        Block::Rules
+                    }
+                    _ => Block::Declarations,
+                });
+                prelude = Prelude::None;
+            }
+            CssToken::RightBrace => {
+                if blocks.len() > 1 {
+                    blocks.pop();
+                }
+                prelude = Prelude::None;
+            }
+            CssToken::Semicolon if matches!(prelude, Prelude::AtRule(_)) => prelude = Prelude::None,
+            CssToken::Function(_) | CssToken::Url(_) if block == Block::Declarations => {
+                let name = match token {
+                    CssToken::Url(_) => "url".to_string(),
+                    _ => token.unescaped().unwrap_or_default().to_ascii_lowercase(),
+                };
+                record(&mut report.functions, &name, location);
+            }
+            CssToken::Whitespace(_) | CssToken::Comment(_) | CssToken::Cdo | CssToken::Cdc => {}
+            _ if block == Block::Rules && prelude == Prelude::None => prelude = Prelude::Selector,
+            _ => {}
+        }
+
+        if prelude == Prelude::Selector {
+            match &token {
+                CssToken::Colon => {
+                    colons += 1;
+                    continue;
+                }
+                CssToken::Ident(_) | CssToken::Function(_) if colons > 0 => {
+                    let name = token.unescaped().unwrap_or_default().to_ascii_lowercase();
+                    let legacy_element = matches!(name.as_str(), "before" | "after" | "first-line" | "first-letter");
+                    if colons > 1 || legacy_element {
+                        record(&mut report.pseudo_elements, &name, location);
+                    } else {
+                        record(&mut report.pseudo_classes, &name, location);
+                    }
+                }
+                _ => {}
+            }
+        }
+        colons = 0;
+    }
+    report
+}
+
+fn record(map: &mut BTreeMap<String, FeatureUsage>, name: &str, location: SourceLocation) {
+    map.entry(name.to_string())
+        .and_modify(|usage| usage.count += 1)
+        .or_insert(FeatureUsage { count: 1, example: location });
+}
+
+/// Returns the vendor prefix of a name, such as `-webkit-` for
+/// `-webkit-box-shadow`.
+fn vendor_prefix(name: &str) -> Option<&str> {
+    let rest = name.strip_prefix('-')?;
+    let end = rest.find('-')?;
+    if end == 0 || !rest[..end].bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(&name[..end + 2])
+}
+
+pub(crate) fn strip_vendor_prefix(name: &str) -> &str {
+    match vendor_prefix(name) {
+        Some(prefix) => &name[prefix.len()..],
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "@charset \"utf-8\";
+@import url(base.css) screen;
+a:hover, li:NTH-CHILD(2n+1)::marker, p:first-line { width: calc(100% - var(--gap)); }
+@media (min-width: 600px) {
+  .card:not(:focus) { background: url(bg.png), -webkit-linear-gradient(red, blue); }
+}
+@font-face { font-family: x; src: url(x.woff2) format(\"woff2\"); }
+@-webkit-keyframes spin { from { -webkit-transform: rotate(0deg) } }
+@supports (display: grid) { @layer base { input::-moz-placeholder { color: color-mix(in srgb, red, blue) } } }
+";
+
+    fn usage(count: usize, line: usize, column: usize) -> FeatureUsage {
+        let offset = FIXTURE
+            .split_inclusive('\n')
+            .take(line - 1)
+            .map(str::len)
+            .sum::<usize>()
+            + column
+            - 1;
+        FeatureUsage { count, example: SourceLocation { line, column, byte_offset: offset } }
+    }
+
+    fn map(entries: &[(&str, FeatureUsage)]) -> BTreeMap<String, FeatureUsage> {
+        entries.iter().map(|(name, usage)| (name.to_string(), *usage)).collect()
+    }
+
+    #[test]
+    fn test_feature_report() {
+        let report = feature_report(FIXTURE);
+
+        assert_eq!(
+            report.at_rules,
+            map(&[
+                ("-webkit-keyframes", usage(1, 8, 1)),
+                ("charset", usage(1, 1, 1)),
+                ("font-face", usage(1, 7, 1)),
+                ("import", usage(1, 2, 1)),
+                ("layer", usage(1, 9, 29)),
+                ("media", usage(1, 4, 1)),
+                ("supports", usage(1, 9, 1)),
+            ])
+        );
+        assert_eq!(
+            report.pseudo_classes,
+            map(&[
+                ("focus", usage(1, 5, 14)),
+                ("hover", usage(1, 3, 3)),
+                ("not", usage(1, 5, 9)),
+                ("nth-child", usage(1, 3, 13)),
+            ])
+        );
+        assert_eq!(
+            report.pseudo_elements,
+            map(&[
+                ("-moz-placeholder", usage(1, 9, 50)),
+                ("first-line", usage(1, 3, 40)),
+                ("marker", usage(1, 3, 30)),
+            ])
+        );
+        assert_eq!(
+            report.functions,
+            map(&[
+                ("-webkit-linear-gradient", usage(1, 5, 48)),
+                ("calc", usage(1, 3, 60)),
+                ("color-mix", usage(1, 9, 76)),
+                ("format", usage(1, 7, 48)),
+                ("rotate", usage(1, 8, 53)),
+                ("url", usage(2, 5, 35)),
+                ("var", usage(1, 3, 72)),
+            ])
+        );
+        assert_eq!(
+            report.vendor_prefixes,
+            map(&[("-moz-", usage(1, 9, 50)), ("-webkit-", usage(3, 5, 48))])
+        );
+    }
+
+    #[test]
+    fn test_vendor_prefix() {
+        assert_eq!(vendor_prefix("-webkit-box-shadow"), Some("-webkit-"));
+        assert_eq!(vendor_prefix("-ms-grid"), Some("-ms-"));
+        assert_eq!(vendor_prefix("--custom"), None);
+        assert_eq!(vendor_prefix("-x"), None);
+        assert_eq!(vendor_prefix("margin-top"), None);
+    }
+}
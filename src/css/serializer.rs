@@ -0,0 +1,607 @@
+use crate::css::parser::{Rule, Selector};
+use crate::output::{write_limited, OutputError};
+use std::fmt::{self, Write};
+
+impl Selector {
+    /// Serializes the selector as CSS, escaping names and quoting
+    /// attribute values so the text parses back to the same selector.
+    ///
+    /// Combinators are written with exactly one space on each side (`a b`,
+    /// `a > b`), compound parts with none (`a.nav:hover`), and there is no
+    /// leading or trailing whitespace. Combinator trees nest to the left
+    /// as the parser builds them, so `Child(Descendant(a, b), c)` is
+    /// `a b > c`; a combinator on the right-hand side cannot be written
+    /// without parentheses and comes back left-nested when parsed.
+    pub fn to_css(&self) -> String {
+        let mut out = String::new();
+        let _ = write_selector(self, false, &mut out);
+        out
+    }
+
+    /// Writes the selector as `to_css` does into `out`, failing with
+    /// `OutputError::LimitExceeded` instead of writing more than `limit`
+    /// bytes. The selector is written whole or not at all.
+    pub fn write_css<W: fmt::Write + ?Sized>(&self, out: &mut W, limit: Option<usize>) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_whole(w, |piece| write_selector(self, false, piece)))
+    }
+}
+
+/// Writes the selector as `to_css` does, so `format!("{}", selector)`
+/// parses back to the same selector.
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_selector(self, false, f)
+    }
+}
+
+impl Rule {
+    /// Serializes the rule as `selectors { property: value; ... }`, with
+    /// declarations in source order, duplicates and `!important` kept.
+    pub fn to_css(&self) -> String {
+        let mut out = String::new();
+        let _ = write_rule(self, &mut out);
+        out
+    }
+
+    /// Writer-based form of `to_css`; see `Selector::write_css`.
+    pub fn write_css<W: fmt::Write + ?Sized>(&self, out: &mut W, limit: Option<usize>) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_whole(w, |piece| write_rule(self, piece)))
+    }
+}
+
+/// Writes the rule as `to_css` does.
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_rule(self, f)
+    }
+}
+
+/// Serializes rules as a stylesheet, one rule per line.
+pub fn stylesheet_to_css(rules: &[Rule]) -> String {
+    let mut out = String::new();
+    let _ = write_stylesheet(rules, &mut out);
+    out
+}
+
+/// Writes rules as `stylesheet_to_css` does into `out`, failing with
+/// `OutputError::LimitExceeded` instead of writing more than `limit`
+/// bytes. Output is cut only between whole rules.
+pub fn write_stylesheet_css<W: fmt::Write + ?Sized>(
+    rules: &[Rule],
+    out: &mut W,
+    limit: Option<usize>,
+) -> Result<(), OutputError> {
+    write_limited(out, limit, |w| write_stylesheet(rules, w))
+}
+
+/// Layout options for `serialize_css`. The default is minified output:
+/// no indent, no newlines and no optional spaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CssSerializerOptions {
+    /// Spaces before each declaration.
+    pub indent: usize,
+    /// Written after `{`, after each declaration and after each rule.
+    pub newline: &'static str,
+    /// Leave out the `;` after the last declaration of a rule.
+    pub omit_last_semicolon: bool,
+}
+
+/// Serializes rules as a stylesheet laid out by `options`, with
+/// declarations in source order, duplicates and `!important` kept.
+///
+/// With an indent or a newline, a space also follows `,` between selectors
+/// and `:` in declarations, comes before `{` and `!important`, and goes on
+/// each side of the `>`, `+` and `~` combinators, so
+/// `CssSerializerOptions { indent: 2, newline: "\n", .. }` gives the usual
+/// hand-written layout.
+///
+/// Formatting is idempotent: parsing the output and serializing it again
+/// with the same options gives the same text, byte for byte. The same
+/// holds for `stylesheet_to_css`.
+pub fn serialize_css(rules: &[Rule], options: &CssSerializerOptions) -> String {
+    let mut out = String::new();
+    let _ = write_serialized(rules, options, &mut out);
+    out
+}
+
+/// Writer-based form of `serialize_css`; see `write_stylesheet_css`.
+pub fn write_serialized_css<W: fmt::Write + ?Sized>(
+    rules: &[Rule],
+    options: &CssSerializerOptions,
+    out: &mut W,
+    limit: Option<usize>,
+) -> Result<(), OutputError> {
+    write_limited(out, limit, |w| write_serialized(rules, options, w))
+}
+
+/// Builds a piece of output in a buffer and writes it with one call, so a
+/// capped writer takes all of it or none.
+fn write_whole<W: fmt::Write + ?Sized>(out: &mut W, build: impl FnOnce(&mut String) -> fmt::Result) -> fmt::Result {
+    let mut piece = String::new();
+    build(&mut piece)?;
+    out.write_str(&piece)
+}
+
+fn write_stylesheet<W: fmt::Write + ?Sized>(rules: &[Rule], out: &mut W) -> fmt::Result {
+    for rule in rules {
+        write_whole(out, |piece| {
+            write_rule(rule, piece)?;
+            piece.write_char('\n')
+        })?;
+    }
+    Ok(())
+}
+
+fn write_rule<W: fmt::Write + ?Sized>(rule: &Rule, out: &mut W) -> fmt::Result {
+    write_selector_list(&rule.selectors, ", ", false, out)?;
+    out.write_str(" {")?;
+    for declaration in &rule.declarations {
+        write!(out, " {}: {}", declaration.property, declaration.value)?;
+        if declaration.important {
+            out.write_str(" !important")?;
+        }
+        out.write_char(';')?;
+    }
+    out.write_str(if rule.declarations.is_empty() { "}" } else { " }" })
+}
+
+fn write_serialized<W: fmt::Write + ?Sized>(rules: &[Rule], options: &CssSerializerOptions, out: &mut W) -> fmt::Result {
+    let space = if options.indent == 0 && options.newline.is_empty() { "" } else { " " };
+    for rule in rules {
+        write_whole(out, |out| {
+            let compact = space.is_empty();
+            write_selector_list(&rule.selectors, if compact { "," } else { ", " }, compact, out)?;
+            write!(out, "{}{{{}", space, options.newline)?;
+            for (index, declaration) in rule.declarations.iter().enumerate() {
+                let (property, value) = (&declaration.property, &declaration.value);
+                write!(out, "{:indent$}{}:{}{}", "", property, space, value, indent = options.indent)?;
+                if declaration.important {
+                    write!(out, "{}!important", space)?;
+                }
+                if !(options.omit_last_semicolon && index + 1 == rule.declarations.len()) {
+                    out.write_char(';')?;
+                }
+                out.write_str(options.newline)?;
+            }
+            out.write_char('}')?;
+            out.write_str(options.newline)
+        })?;
+    }
+    Ok(())
+}
+
+fn write_selector_list<W: fmt::Write + ?Sized>(
+    selectors: &[Selector],
+    separator: &str,
+    compact: bool,
+    out: &mut W,
+) -> fmt::Result {
+    for (index, selector) in selectors.iter().enumerate() {
+        if index > 0 {
+            out.write_str(separator)?;
+        }
+        write_selector(selector, compact, out)?;
+    }
+    Ok(())
+}
+
+/// Writes `selector`; `compact` leaves out the spaces around `>`, `+`
+/// and `~`.
+fn write_selector<W: fmt::Write + ?Sized>(selector: &Selector, compact: bool, out: &mut W) -> fmt::Result {
+    match selector {
+        Selector::Type(name) => write_identifier(name, out),
+        Selector::Class(name) => {
+            out.write_char('.')?;
+            write_identifier(name, out)
+        }
+        Selector::Id(name) => {
+            out.write_char('#')?;
+            write_identifier(name, out)
+        }
+        Selector::Universal => out.write_char('*'),
+        Selector::Attribute { name, operator, value, case_insensitive } => {
+            out.write_char('[')?;
+            write_identifier(name, out)?;
+            if let (Some(operator), Some(value)) = (operator, value) {
+                out.write_str(operator.as_str())?;
+                write_string(value, out)?;
+                if *case_insensitive {
+                    out.write_str(" i")?;
+                }
+            }
+            out.write_char(']')
+        }
+        Selector::PseudoClass { name, argument } => {
+            out.write_char(':')?;
+            write_identifier(name, out)?;
+            match argument {
+                Some(argument) => write!(out, "({})", argument),
+                None => Ok(()),
+            }
+        }
+        Selector::NthChild(expr) => write!(out, ":nth-child({})", expr),
+        Selector::NthOfType(expr) => write!(out, ":nth-of-type({})", expr),
+        Selector::NthLastChild(expr) => write!(out, ":nth-last-child({})", expr),
+        Selector::NthLastOfType(expr) => write!(out, ":nth-last-of-type({})", expr),
+        Selector::Not(argument) => {
+            out.write_str(":not(")?;
+            write_selector(argument, compact, out)?;
+            out.write_char(')')
+        }
+        Selector::PseudoElement(name) => {
+            out.write_str("::")?;
+            write_identifier(name, out)
+        }
+        Selector::Compound(parts) => {
+            // A type or `*` must come first or it would run into the name
+            // before it: `.a` then `div` is `div.a`, not `.adiv`.
+            let first = parts
+                .iter()
+                .position(|part| matches!(part, Selector::Type(_) | Selector::Universal))
+                .unwrap_or(0);
+            if let Some(part) = parts.get(first) {
+                write_selector(part, compact, out)?;
+            }
+            for (index, part) in parts.iter().enumerate() {
+                if index != first {
+                    write_selector(part, compact, out)?;
+                }
+            }
+            Ok(())
+        }
+        Selector::Descendant(left, right) => write_combinator(left, ' ', right, compact, out),
+        Selector::Child(left, right) => write_combinator(left, '>', right, compact, out),
+        Selector::Adjacent(left, right) => write_combinator(left, '+', right, compact, out),
+        Selector::GeneralSibling(left, right) => write_combinator(left, '~', right, compact, out),
+    }
+}
+
+fn write_combinator<W: fmt::Write + ?Sized>(
+    left: &Selector,
+    combinator: char,
+    right: &Selector,
+    compact: bool,
+    out: &mut W,
+) -> fmt::Result {
+    write_selector(left, compact, out)?;
+    if compact || combinator == ' ' {
+        out.write_char(combinator)?;
+    } else {
+        write!(out, " {} ", combinator)?;
+    }
+    write_selector(right, compact, out)
+}
+
+/// Writes `name` as a CSS identifier, escaping what would not tokenize as
+/// part of one.
+fn write_identifier<W: fmt::Write + ?Sized>(name: &str, out: &mut W) -> fmt::Result {
+    if name == "-" {
+        return out.write_str("\\-");
+    }
+    let starts_with_dash = name.starts_with('-');
+    for (index, ch) in name.chars().enumerate() {
+        let leading_digit =
+            ch.is_ascii_digit() && (index == 0 || (index == 1 && starts_with_dash));
+        match ch {
+            '\0' => out.write_char('\u{FFFD}')?,
+            '\x01'..='\x1F' | '\x7F' => write!(out, "\\{:x} ", ch as u32)?,
+            _ if leading_digit => write!(out, "\\{:x} ", ch as u32)?,
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => out.write_char(ch)?,
+            _ if !ch.is_ascii() => out.write_char(ch)?,
+            _ => {
+                out.write_char('\\')?;
+                out.write_char(ch)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes `value` as a double-quoted CSS string.
+fn write_string<W: fmt::Write + ?Sized>(value: &str, out: &mut W) -> fmt::Result {
+    out.write_char('"')?;
+    for ch in value.chars() {
+        match ch {
+            '\0' => out.write_char('\u{FFFD}')?,
+            '\x01'..='\x1F' | '\x7F' => write!(out, "\\{:x} ", ch as u32)?,
+            '"' | '\\' => {
+                out.write_char('\\')?;
+                out.write_char(ch)?;
+            }
+            _ => out.write_char(ch)?,
+        }
+    }
+    out.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+
+    const SMALL_CSS: &str = include_str!("../../tests/fixtures/small.css");
+
+    fn selector(css: &str) -> Selector {
+        let rules = CssParser::new(&format!("{} {{ a: b }}", css)).parse();
+        rules[0].selectors[0].clone()
+    }
+
+    #[test]
+    fn test_selector_to_css() {
+        let cases = [
+            "div > p",
+            "ul li + li ~ a",
+            "a.nav:hover::before",
+            "input[type=\"text\"]",
+            "[lang|=\"en\"][data-x]",
+            "[type=\"a\" i]",
+            "li:nth-child(2n+1)",
+            "input:not([type=\"submit\"]):not(div > p)",
+            "#main *",
+        ];
+        for css in cases {
+            assert_eq!(selector(css).to_css(), css);
+        }
+    }
+
+    #[test]
+    fn test_names_and_values_are_escaped() {
+        let cases = [
+            (Selector::Class("1col".to_string()), ".\\31 col"),
+            (Selector::Class("-2x".to_string()), ".-\\32 x"),
+            (Selector::Id("-".to_string()), "#\\-"),
+            (Selector::Class("w-1/2".to_string()), ".w-1\\/2"),
+            (Selector::Type("café".to_string()), "café"),
+            (
+                Selector::Attribute {
+                    name: "xlink:href".to_string(),
+                    operator: Some(crate::css::parser::AttrOp::Equals),
+                    value: Some("say \"hi\"\n".to_string()),
+                    case_insensitive: false,
+                },
+                "[xlink\\:href=\"say \\\"hi\\\"\\a \"]",
+            ),
+        ];
+        for (selector, expected) in cases {
+            let css = selector.to_css();
+            assert_eq!(css, expected);
+            let reparsed = CssParser::new(&format!("{} {{ a: b }}", css)).parse();
+            assert_eq!(reparsed[0].selectors, vec![selector]);
+        }
+    }
+
+    #[test]
+    fn test_rule_to_css() {
+        let rules = CssParser::new("h1, .title { margin: 0 auto; color: red !important } p {}").parse();
+
+        assert_eq!(rules[0].to_css(), "h1, .title { margin: 0 auto; color: red !important; }");
+        assert_eq!(rules[1].to_css(), "p {}");
+
+        let rules = CssParser::new("a { display: -webkit-box; display: flex }").parse();
+        assert_eq!(rules[0].to_css(), "a { display: -webkit-box; display: flex; }");
+    }
+
+    #[test]
+    fn test_rule_display() {
+        let rules = CssParser::new("a > b{width:16px;height:50%;color:red!important}").parse();
+        let css = format!("{}", rules[0]);
+
+        assert_eq!(css, "a > b { width: 16px; height: 50%; color: red !important; }");
+        assert_eq!(CssParser::new(&css).parse(), rules);
+    }
+
+    #[test]
+    fn test_stylesheet_round_trip() {
+        let rules = CssParser::new(SMALL_CSS).parse();
+        let css = stylesheet_to_css(&rules);
+
+        assert_eq!(
+            css,
+            ".container { max-width: 1200px; margin: 0 auto; padding: 20px; }\n\
+             h1 { color: #333; font-size: 2rem; }\n\
+             p { line-height: 1.6; color: #666; }\n"
+        );
+        assert_eq!(CssParser::new(&css).parse(), rules);
+    }
+
+    #[test]
+    fn test_serialize_css_layouts() {
+        let rules = CssParser::new("h1, .title { margin: 0 auto; color: red !important } div > p {}").parse();
+
+        assert_eq!(
+            serialize_css(&rules, &CssSerializerOptions::default()),
+            "h1,.title{margin:0 auto;color:red!important;}div>p{}"
+        );
+        let minified = CssSerializerOptions { omit_last_semicolon: true, ..Default::default() };
+        assert_eq!(serialize_css(&rules, &minified), "h1,.title{margin:0 auto;color:red!important}div>p{}");
+        let pretty = CssSerializerOptions { indent: 2, newline: "\n", omit_last_semicolon: false };
+        assert_eq!(
+            serialize_css(&rules, &pretty),
+            "h1, .title {\n  margin: 0 auto;\n  color: red !important;\n}\ndiv > p {\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_css_round_trip_large() {
+        let rules = CssParser::new(include_str!("../../tests/fixtures/large.css")).parse();
+        assert!(!rules.is_empty());
+
+        for options in [
+            CssSerializerOptions::default(),
+            CssSerializerOptions { omit_last_semicolon: true, ..Default::default() },
+            CssSerializerOptions { indent: 4, newline: "\n", omit_last_semicolon: false },
+        ] {
+            let css = serialize_css(&rules, &options);
+            assert_eq!(CssParser::new(&css).parse(), rules, "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let tricky = concat!(
+            "/* head */\n.a>.b ,  .c~d{color : red ;;margin:0  auto!important;\n",
+            "  background: url( 'x y.png' )\n    no-repeat}\n@media (max-width: 600px) { .a { b: c } }\np{}",
+        );
+        let sources = [SMALL_CSS, include_str!("../../tests/fixtures/large.css"), tricky];
+
+        for source in sources {
+            let once = stylesheet_to_css(&CssParser::new(source).parse());
+            assert_eq!(stylesheet_to_css(&CssParser::new(&once).parse()), once);
+
+            for indent in [0, 2, 4] {
+                for newline in ["", "\n", "\r\n"] {
+                    for omit_last_semicolon in [false, true] {
+                        let options = CssSerializerOptions { indent, newline, omit_last_semicolon };
+                        let once = serialize_css(&CssParser::new(source).parse(), &options);
+                        let twice = serialize_css(&CssParser::new(&once).parse(), &options);
+                        assert_eq!(twice, once, "{:?}", options);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_css_matches_to_css() {
+        let rules = CssParser::new(include_str!("../../tests/fixtures/large.css")).parse();
+        let options = CssSerializerOptions { indent: 2, newline: "\n", omit_last_semicolon: true };
+        let (mut sheet, mut serialized, mut rule) = (String::new(), String::new(), String::new());
+
+        write_stylesheet_css(&rules, &mut sheet, None).unwrap();
+        write_serialized_css(&rules, &options, &mut serialized, None).unwrap();
+        rules[0].write_css(&mut rule, None).unwrap();
+
+        assert_eq!(sheet, stylesheet_to_css(&rules));
+        assert_eq!(serialized, serialize_css(&rules, &options));
+        assert_eq!(rule, rules[0].to_css());
+        for rule in &rules {
+            assert_eq!(rule.to_string(), rule.to_css());
+            for selector in &rule.selectors {
+                assert_eq!(selector.to_string(), selector.to_css());
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_css_limit_cuts_between_rules() {
+        let rules = CssParser::new("a { color: red } b { color: blue } c { color: green }").parse();
+        let mut out = String::new();
+
+        let result = write_stylesheet_css(&rules, &mut out, Some(40));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 40 }));
+        assert_eq!(out, "a { color: red; }\nb { color: blue; }\n");
+        assert_eq!(CssParser::new(&out).parse(), rules[..2]);
+
+        let mut out = String::new();
+        let selector = &rules[0].selectors[0];
+        assert_eq!(rules[0].write_css(&mut out, Some(5)), Err(OutputError::LimitExceeded { limit: 5 }));
+        assert_eq!(out, "");
+        assert_eq!(selector.write_css(&mut out, Some(5)), Ok(()));
+        assert_eq!(out, "a");
+    }
+
+    #[test]
+    fn test_combinator_whitespace() {
+        let (a, b, c) = (
+            Box::new(Selector::Type("a".to_string())),
+            Box::new(Selector::Type("b".to_string())),
+            Box::new(Selector::Type("c".to_string())),
+        );
+        let nested = Selector::Child(Box::new(Selector::Descendant(a.clone(), b.clone())), c.clone());
+        let compound = Selector::Compound(vec![Selector::Class("x".to_string()), *a.clone()]);
+
+        assert_eq!(nested.to_css(), "a b > c");
+        assert_eq!(Selector::Adjacent(a.clone(), b.clone()).to_css(), "a + b");
+        assert_eq!(Selector::GeneralSibling(a, Box::new(compound.clone())).to_css(), "a ~ a.x");
+        assert_eq!(compound.to_css(), "a.x");
+        assert_eq!(selector("  a\n\t>b   c~d+e  ").to_css(), "a > b c ~ d + e");
+    }
+
+    #[test]
+    fn test_selector_display() {
+        let name = |name: &str| Box::new(Selector::Type(name.to_string()));
+        let cases = [
+            (Selector::Type("div".to_string()), "div"),
+            (Selector::Class("foo".to_string()), ".foo"),
+            (Selector::Id("bar".to_string()), "#bar"),
+            (Selector::Universal, "*"),
+            (Selector::Descendant(name("a"), name("b")), "a b"),
+            (Selector::Child(name("a"), name("b")), "a > b"),
+            (Selector::Adjacent(name("a"), name("b")), "a + b"),
+            (Selector::GeneralSibling(name("a"), name("b")), "a ~ b"),
+        ];
+        for (selector, css) in cases {
+            assert_eq!(format!("{}", selector), css);
+        }
+
+        let parsed = selector("div > .container p");
+        assert_eq!(parsed.to_string(), "div > .container p");
+        assert_eq!(selector(&parsed.to_string()), parsed);
+    }
+
+    #[test]
+    fn test_selector_round_trip_corpus() {
+        let corpus = [
+            "*",
+            "div",
+            ".nav",
+            "#main",
+            "a.nav",
+            "div#main.wide",
+            ".a.b.c",
+            "*.x",
+            "[hidden]",
+            "[type=\"text\"]",
+            "[class~=\"big\"]",
+            "[lang|=\"en\"]",
+            "[href^=\"https:\"]",
+            "[src$=\".png\"]",
+            "[title*=\"foo bar\"]",
+            "[type=\"A\" i]",
+            "input[type=\"checkbox\"][checked]",
+            "a:hover",
+            "a:visited:focus",
+            ":dir(ltr)",
+            "li:nth-child(2n+1)",
+            "li:nth-child(-n+3)",
+            "tr:nth-of-type(odd)",
+            "p:nth-last-child(1)",
+            "p:nth-last-of-type(3n-1)",
+            "p::before",
+            "a.nav:hover::after",
+            "::placeholder",
+            ":not(.disabled)",
+            "input:not([type=\"submit\"])",
+            "p:not(div > p)",
+            "li:not(:nth-child(2n)):not(.x)",
+            "div p",
+            "div > p",
+            "h1 + p",
+            "h1 ~ p",
+            "ul li a",
+            "ul > li > a",
+            "a b > c",
+            "a > b c",
+            "a + b ~ c",
+            "a ~ b + c > d e",
+            "nav.main > ul li:first-child + li",
+            "#header .logo img",
+            ".grid > .col:nth-child(3n) ~ .col",
+            "section > h2 + p::first-line",
+            "form input[type=\"text\" i]:focus",
+            ".\\31 col > .w-1\\/2",
+            "café > naïve",
+            "body * p",
+        ];
+        assert_eq!(corpus.len(), 50);
+
+        for css in corpus {
+            let parsed = selector(css);
+            let serialized = parsed.to_css();
+            assert_eq!(serialized.trim(), serialized, "{}", css);
+            assert!(!serialized.contains("  "), "{}", serialized);
+            assert_eq!(selector(&serialized), parsed, "{} -> {}", css, serialized);
+        }
+    }
+}
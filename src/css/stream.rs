@@ -0,0 +1,287 @@
+use crate::css::tokenizer::{CssToken, CssTokenizer, TokenizeError};
+
+/// A `CssToken` that owns its text, as produced by `CssStreamTokenizer`.
+///
+/// With the `serde` feature both serialize the same way, and this is the
+/// type to deserialize tokens into, as a `CssToken` borrows its text.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OwnedCssToken {
+    Ident(String),
+    String(String),
+    Number { value: f64, is_integer: bool },
+    Dimension { value: f64, unit: String, is_integer: bool },
+    Percentage { value: f64, is_integer: bool },
+    Hash { value: String, is_id: bool },
+    Delim(char),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
+    Semicolon,
+    Comma,
+    Whitespace(String),
+    Comment(String),
+    AtKeyword(String),
+    Url(String),
+    Function(String),
+    CustomProperty(String),
+    Cdo,
+    Cdc,
+    BadString,
+    BadUrl,
+    IncludeMatch,
+    DashMatch,
+    PrefixMatch,
+    SuffixMatch,
+    SubstringMatch,
+}
+
+impl OwnedCssToken {
+    /// Borrows the token as a `CssToken`.
+    pub fn as_token(&self) -> CssToken<'_> {
+        match self {
+            OwnedCssToken::Ident(text) => CssToken::Ident(text.into()),
+            OwnedCssToken::String(text) => CssToken::String(text.into()),
+            OwnedCssToken::Number { value, is_integer } => CssToken::Number { value: *value, is_integer: *is_integer },
+            OwnedCssToken::Dimension { value, unit, is_integer } => {
+                CssToken::Dimension { value: *value, unit: unit.into(), is_integer: *is_integer }
+            }
+            OwnedCssToken::Percentage { value, is_integer } => {
+                CssToken::Percentage { value: *value, is_integer: *is_integer }
+            }
+            OwnedCssToken::Hash { value, is_id } => CssToken::Hash { value: value.into(), is_id: *is_id },
+            OwnedCssToken::Delim(ch) => CssToken::Delim(*ch),
+            OwnedCssToken::LeftParen => CssToken::LeftParen,
+            OwnedCssToken::RightParen => CssToken::RightParen,
+            OwnedCssToken::LeftBrace => CssToken::LeftBrace,
+            OwnedCssToken::RightBrace => CssToken::RightBrace,
+            OwnedCssToken::LeftBracket => CssToken::LeftBracket,
+            OwnedCssToken::RightBracket => CssToken::RightBracket,
+            OwnedCssToken::Colon => CssToken::Colon,
+            OwnedCssToken::Semicolon => CssToken::Semicolon,
+            OwnedCssToken::Comma => CssToken::Comma,
+            OwnedCssToken::Whitespace(text) => CssToken::Whitespace(text.into()),
+            OwnedCssToken::Comment(text) => CssToken::Comment(text.into()),
+            OwnedCssToken::AtKeyword(text) => CssToken::AtKeyword(text.into()),
+            OwnedCssToken::Url(text) => CssToken::Url(text.into()),
+            OwnedCssToken::Function(text) => CssToken::Function(text.into()),
+            OwnedCssToken::CustomProperty(text) => CssToken::CustomProperty(text.into()),
+            OwnedCssToken::Cdo => CssToken::Cdo,
+            OwnedCssToken::Cdc => CssToken::Cdc,
+            OwnedCssToken::BadString => CssToken::BadString,
+            OwnedCssToken::BadUrl => CssToken::BadUrl,
+            OwnedCssToken::IncludeMatch => CssToken::IncludeMatch,
+            OwnedCssToken::DashMatch => CssToken::DashMatch,
+            OwnedCssToken::PrefixMatch => CssToken::PrefixMatch,
+            OwnedCssToken::SuffixMatch => CssToken::SuffixMatch,
+            OwnedCssToken::SubstringMatch => CssToken::SubstringMatch,
+        }
+    }
+}
+
+impl From<CssToken<'_>> for OwnedCssToken {
+    fn from(token: CssToken<'_>) -> Self {
+        match token {
+            CssToken::Ident(text) => OwnedCssToken::Ident(text.into_owned()),
+            CssToken::String(text) => OwnedCssToken::String(text.into_owned()),
+            CssToken::Number { value, is_integer } => OwnedCssToken::Number { value, is_integer },
+            CssToken::Dimension { value, unit, is_integer } => {
+                OwnedCssToken::Dimension { value, unit: unit.into_owned(), is_integer }
+            }
+            CssToken::Percentage { value, is_integer } => OwnedCssToken::Percentage { value, is_integer },
+            CssToken::Hash { value, is_id } => OwnedCssToken::Hash { value: value.into_owned(), is_id },
+            CssToken::Delim(ch) => OwnedCssToken::Delim(ch),
+            CssToken::LeftParen => OwnedCssToken::LeftParen,
+            CssToken::RightParen => OwnedCssToken::RightParen,
+            CssToken::LeftBrace => OwnedCssToken::LeftBrace,
+            CssToken::RightBrace => OwnedCssToken::RightBrace,
+            CssToken::LeftBracket => OwnedCssToken::LeftBracket,
+            CssToken::RightBracket => OwnedCssToken::RightBracket,
+            CssToken::Colon => OwnedCssToken::Colon,
+            CssToken::Semicolon => OwnedCssToken::Semicolon,
+            CssToken::Comma => OwnedCssToken::Comma,
+            CssToken::Whitespace(text) => OwnedCssToken::Whitespace(text.into_owned()),
+            CssToken::Comment(text) => OwnedCssToken::Comment(text.into_owned()),
+            CssToken::AtKeyword(text) => OwnedCssToken::AtKeyword(text.into_owned()),
+            CssToken::Url(text) => OwnedCssToken::Url(text.into_owned()),
+            CssToken::Function(text) => OwnedCssToken::Function(text.into_owned()),
+            CssToken::CustomProperty(text) => OwnedCssToken::CustomProperty(text.into_owned()),
+            CssToken::Cdo => OwnedCssToken::Cdo,
+            CssToken::Cdc => OwnedCssToken::Cdc,
+            CssToken::BadString => OwnedCssToken::BadString,
+            CssToken::BadUrl => OwnedCssToken::BadUrl,
+            CssToken::IncludeMatch => OwnedCssToken::IncludeMatch,
+            CssToken::DashMatch => OwnedCssToken::DashMatch,
+            CssToken::PrefixMatch => OwnedCssToken::PrefixMatch,
+            CssToken::SuffixMatch => OwnedCssToken::SuffixMatch,
+            CssToken::SubstringMatch => OwnedCssToken::SubstringMatch,
+        }
+    }
+}
+
+/// How many characters past the end of a token the tokenizer may look
+/// before deciding where the next token starts: `<!--`, and the exponent
+/// in `1e+5` after a number, need three.
+const LOOKAHEAD: usize = 4;
+
+/// A push-based `CssTokenizer` for input that arrives in chunks.
+///
+/// `feed` returns the tokens that more input can no longer change, and
+/// keeps the rest, such as an identifier or string that may continue in
+/// the next chunk, buffered until a later `feed` or `finish`. The tokens
+/// of all calls together are the same as tokenizing the concatenated input
+/// at once, wherever the chunks are split.
+///
+/// Buffered input is tokenized again on each `feed`, so a long unclosed
+/// comment or string fed in many small chunks costs quadratic time.
+#[derive(Debug, Default)]
+pub struct CssStreamTokenizer {
+    /// Input not yet returned as tokens.
+    buffer: String,
+    /// Bytes of input already returned as tokens.
+    consumed: usize,
+    errors: Vec<TokenizeError>,
+}
+
+impl CssStreamTokenizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `chunk` to the input and returns the tokens completed so far.
+    pub fn feed(&mut self, chunk: &str) -> Vec<OwnedCssToken> {
+        self.buffer.push_str(chunk);
+        self.drain(false)
+    }
+
+    /// Ends the input and returns the remaining tokens.
+    pub fn finish(&mut self) -> Vec<OwnedCssToken> {
+        self.drain(true)
+    }
+
+    /// Returns the errors found since the last call, with offsets counted
+    /// from the start of the whole input; see `CssTokenizer::take_errors`.
+    /// Unterminated constructs are only reported by `finish`.
+    pub fn take_errors(&mut self) -> Vec<TokenizeError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn drain(&mut self, at_end: bool) -> Vec<OwnedCssToken> {
+        let mut tokens = Vec::new();
+        let mut done = 0;
+        let mut tokenizer = CssTokenizer::new(&self.buffer);
+        while let Some(token) = tokenizer.next_token() {
+            let end = tokenizer.token_end_offset();
+            if !at_end && self.buffer[end..].chars().nth(LOOKAHEAD - 1).is_none() {
+                break;
+            }
+            tokens.push(token.into());
+            done = end;
+        }
+        if at_end {
+            let consumed = self.consumed;
+            self.errors.extend(tokenizer.take_errors().into_iter().map(|error| TokenizeError {
+                offset: error.offset + consumed,
+                ..error
+            }));
+        }
+
+        self.buffer.drain(..done);
+        self.consumed += done;
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::tokenizer::TokenizeErrorKind;
+
+    const LARGE_CSS: &str = include_str!("../../tests/fixtures/large.css");
+
+    fn all_at_once(css: &str) -> Vec<OwnedCssToken> {
+        CssTokenizer::new(css).map(OwnedCssToken::from).collect()
+    }
+
+    fn streamed(chunks: &[&str]) -> Vec<OwnedCssToken> {
+        let mut stream = CssStreamTokenizer::new();
+        let mut tokens: Vec<_> = chunks.iter().flat_map(|chunk| stream.feed(chunk)).collect();
+        tokens.extend(stream.finish());
+        tokens
+    }
+
+    #[test]
+    fn test_large_css_split_everywhere() {
+        let expected = all_at_once(LARGE_CSS);
+        for split in (0..=LARGE_CSS.len()).filter(|&at| LARGE_CSS.is_char_boundary(at)) {
+            let (head, tail) = LARGE_CSS.split_at(split);
+            assert_eq!(streamed(&[head, tail]), expected, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn test_tokens_straddling_chunks() {
+        let cases: &[&[&str]] = &[
+            &["a { content: \"x\\", "\" }"],
+            &["a { width: 12", "px }"],
+            &["a { width: 1e", "+3 }"],
+            &["/", "* note */ a {}"],
+            &["<!-", "- a {} -", "->"],
+            &["url(a", ".png) U+0", "0-FF"],
+            &[".caf", "é", " #x\\", "31 y"],
+            &["", "a", "", "b", ""],
+        ];
+        for chunks in cases {
+            assert_eq!(streamed(chunks), all_at_once(&chunks.concat()), "{:?}", chunks);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_reads_tokens_as_owned() {
+        for token in CssTokenizer::new(".a\\:b { width: 1.5em; content: \"\\\"\" } /* x */ @media") {
+            let json = serde_json::to_string(&token).unwrap();
+            let owned: OwnedCssToken = serde_json::from_str(&json).unwrap();
+            assert_eq!(owned, OwnedCssToken::from(token.clone()));
+            assert_eq!(serde_json::to_string(&owned).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_feed_holds_back_unfinished_tokens() {
+        let mut stream = CssStreamTokenizer::new();
+
+        assert_eq!(stream.feed("color"), vec![]);
+        assert_eq!(
+            stream.feed(": red;    "),
+            vec![
+                OwnedCssToken::Ident("color".to_string()),
+                OwnedCssToken::Colon,
+                OwnedCssToken::Whitespace(" ".to_string()),
+                OwnedCssToken::Ident("red".to_string()),
+                OwnedCssToken::Semicolon,
+            ]
+        );
+        assert_eq!(stream.finish(), vec![OwnedCssToken::Whitespace("    ".to_string())]);
+        assert_eq!(OwnedCssToken::Ident("red".to_string()).as_token(), CssToken::Ident("red".into()));
+    }
+
+    #[test]
+    fn test_errors_are_reported_on_finish() {
+        let mut stream = CssStreamTokenizer::new();
+        stream.feed("a {} ");
+        stream.feed("/* never");
+        assert!(stream.take_errors().is_empty());
+
+        stream.feed(" closed");
+        assert_eq!(stream.finish(), vec![OwnedCssToken::Comment(" never closed".to_string())]);
+        assert_eq!(
+            stream.take_errors(),
+            vec![TokenizeError { kind: TokenizeErrorKind::UnterminatedComment, offset: 5 }]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::css::parser::{CssParser, Rule};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Read access to a list of rules, so the matching APIs accept a plain
+/// `[Rule]` slice as well as an `OwnedStylesheet`.
+pub trait RuleSource {
+    fn rules(&self) -> &[Rule];
+}
+
+impl RuleSource for [Rule] {
+    fn rules(&self) -> &[Rule] {
+        self
+    }
+}
+
+impl RuleSource for Vec<Rule> {
+    fn rules(&self) -> &[Rule] {
+        self
+    }
+}
+
+/// A parsed, immutable stylesheet that owns all its data.
+///
+/// It is `Send + Sync`, so one parse can be shared behind an `Arc` by every
+/// thread and request that uses the same stylesheet, e.g. from a cache keyed
+/// by `content_hash`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedStylesheet {
+    rules: Vec<Rule>,
+    content_hash: u64,
+}
+
+impl OwnedStylesheet {
+    /// Parses `css` into a shareable stylesheet.
+    pub fn parse(css: &str) -> Arc<Self> {
+        Arc::new(Self {
+            rules: CssParser::new(css).parse(),
+            content_hash: Self::hash_source(css),
+        })
+    }
+
+    /// The hash of the source text this stylesheet was parsed from.
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash
+    }
+
+    /// Hashes `css` the same way `content_hash` does, to look up a cached
+    /// stylesheet before parsing. The value is stable within one build of
+    /// the crate, not across versions.
+    pub fn hash_source(css: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        css.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl RuleSource for OwnedStylesheet {
+    fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "matching")]
+    use crate::html::parser::HtmlParser;
+    #[cfg(feature = "matching")]
+    use crate::lint::find_near_miss_selectors;
+    #[cfg(feature = "matching")]
+    use std::thread;
+
+    const CSS: &str = ".Nav, #Main { color: red; } p { margin: 0; }";
+
+    #[cfg(feature = "matching")]
+    #[test]
+    fn test_shared_across_threads() {
+        let sheet = OwnedStylesheet::parse(CSS);
+        let document = HtmlParser::new(r#"<div id="main"><a class="nav">x</a></div>"#).parse_document();
+        let expected = find_near_miss_selectors(&document, &CssParser::new(CSS).parse());
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..2)
+                .map(|_| {
+                    let sheet = Arc::clone(&sheet);
+                    let document = &document;
+                    scope.spawn(move || find_near_miss_selectors(document, &*sheet))
+                })
+                .collect();
+            for worker in workers {
+                assert_eq!(worker.join().unwrap(), expected);
+            }
+        });
+        assert_eq!(expected.len(), 2);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let sheet = OwnedStylesheet::parse(CSS);
+
+        assert_eq!(sheet.content_hash(), OwnedStylesheet::hash_source(CSS));
+        assert_ne!(sheet.content_hash(), OwnedStylesheet::hash_source("p {}"));
+        assert_eq!(sheet.rules(), CssParser::new(CSS).parse().as_slice());
+    }
+}
@@ -1,11 +1,39 @@
+#[cfg(any(feature = "css-ast", feature = "locations"))]
+use crate::location::Span;
+use crate::css::number::{format_number, NumberFormat};
+use crate::location::{LineCounter, SourceLocation};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// A CSS token. Text payloads hold the input after CSS preprocessing: a
+/// NUL reads as U+FFFD, and `\r\n`, `\r` and form feed as `\n`. They
+/// borrow the source unless preprocessing changed something; the source as
+/// written is at the token's span. Escapes are left undecoded; `unescaped`
+/// and `decoded_string` decode them.
+///
+/// With the `serde` feature, tokens deserialize with owned payloads, so a
+/// `CssToken<'static>` can be read back from any input.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CssToken<'a> {
-    Ident(&'a str),
-    String(&'a str),
-    Number(f64),
-    Dimension { value: f64, unit: &'a str },
-    Percentage(f64),
-    Hash(&'a str),
+    Ident(Cow<'a, str>),
+    /// The content between the quotes, escapes undecoded; `decoded_string`
+    /// decodes its escapes and line continuations.
+    String(Cow<'a, str>),
+    /// A number with an optional `+` or `-` sign. `is_integer` is false if
+    /// it was written with a `.` or an exponent, even when the value is
+    /// whole (`1.0`), as CSS tells `<integer>` and `<number>` apart.
+    Number { value: f64, is_integer: bool },
+    /// A number followed by a unit. Units are letters and digits only, so
+    /// they never need preprocessing and always borrow the source.
+    Dimension { value: f64, unit: Cow<'a, str>, is_integer: bool },
+    Percentage { value: f64, is_integer: bool },
+    /// `#name`, holding the name. `is_id` is set when the name would also
+    /// be a valid identifier (`#main`, `#-x`, `#\31 a`), the "id" type in
+    /// CSS Syntax; only those can be id selectors, while `#123abc` is
+    /// still fine as a color.
+    Hash { value: Cow<'a, str>, is_id: bool },
     Delim(char),
     LeftParen,
     RightParen,
@@ -16,23 +44,542 @@ pub enum CssToken<'a> {
     Colon,
     Semicolon,
     Comma,
-    Whitespace,
-    Comment(&'a str),
-    AtKeyword(&'a str),
-    Url(&'a str),
+    /// A run of whitespace, holding its text so formatters can keep the
+    /// author's newlines and indentation.
+    Whitespace(Cow<'a, str>),
+    Comment(Cow<'a, str>),
+    AtKeyword(Cow<'a, str>),
+    /// The URL of a `url(...)` without the quotes and surrounding
+    /// whitespace. Escapes are kept raw; `unescape` decodes them.
+    Url(Cow<'a, str>),
+    /// An identifier immediately followed by `(`, such as `rgb(`. The
+    /// parenthesis is consumed; arguments follow as ordinary tokens up to
+    /// the matching `RightParen`.
+    Function(Cow<'a, str>),
+    /// An identifier starting with `--`, such as a custom property name.
+    /// Holds the name without the `--`: `--primary-color` is
+    /// `CustomProperty("primary-color")`.
+    CustomProperty(Cow<'a, str>),
+    /// `<!--`, allowed around rules for stylesheets embedded in HTML.
+    Cdo,
+    /// `-->`, the counterpart of `Cdo`.
+    Cdc,
+    /// A string cut short by an unescaped newline. The newline is not part
+    /// of the token.
+    BadString,
+    /// A `url(` containing whitespace inside the URL, a quote, `(`, a
+    /// control character or an escaped newline, or with anything but
+    /// whitespace between a quoted URL and `)`. Everything up to the next
+    /// unescaped `)` is part of the token.
+    BadUrl,
+    /// `~=`. The attribute-selector operators are only single tokens when
+    /// written without a space before the `=`; a lone `~` is a `Delim`.
+    IncludeMatch,
+    /// `|=`.
+    DashMatch,
+    /// `^=`.
+    PrefixMatch,
+    /// `$=`.
+    SuffixMatch,
+    /// `*=`.
+    SubstringMatch,
+}
+
+impl<'a> CssToken<'a> {
+    /// Returns the name of an `Ident`, `Hash`, `AtKeyword`, `Function` or
+    /// `CustomProperty` token with CSS escapes decoded. The variants
+    /// themselves hold the name with its escapes.
+    pub fn unescaped(&self) -> Option<Cow<'a, str>> {
+        match self {
+            CssToken::Ident(raw)
+            | CssToken::Hash { value: raw, .. }
+            | CssToken::AtKeyword(raw)
+            | CssToken::Function(raw)
+            | CssToken::CustomProperty(raw) => Some(decode_payload(raw, unescape)),
+            _ => None,
+        }
+    }
+
+    /// Returns the text of a `String` token with its escapes decoded; see
+    /// `unescape_string`. The variant itself holds the text with its
+    /// escapes.
+    pub fn decoded_string(&self) -> Option<Cow<'a, str>> {
+        match self {
+            CssToken::String(raw) => Some(decode_payload(raw, unescape_string)),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the token is `Whitespace` or a `Comment`, which only
+    /// separate the tokens that carry meaning.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, CssToken::Whitespace(_) | CssToken::Comment(_))
+    }
+}
+
+/// Decodes `raw` with `decode`, still borrowing the source if `raw` does
+/// and there is nothing to decode.
+fn decode_payload<'a>(raw: &Cow<'a, str>, decode: fn(&str) -> Cow<'_, str>) -> Cow<'a, str> {
+    match raw {
+        Cow::Borrowed(raw) => decode(raw),
+        Cow::Owned(raw) => Cow::Owned(decode(raw).into_owned()),
+    }
+}
+
+/// Writes the token as CSS that tokenizes back to the same token.
+///
+/// Names, strings and urls are held in their source form, so their escapes
+/// are written as they are. Characters that need escaping but are not
+/// escaped, as in tokens built by hand, are escaped: `Ident("1 a")` is
+/// written `\31 \20 a`. Strings and urls are quoted with `"`, or with `'` when
+/// that avoids escaping a quote; urls are only quoted when they must be.
+/// Numbers are written in their shortest form, plus `.0` if not an integer
+/// (`1e3` is written `1000.0`); whitespace and comments are written as
+/// they are.
+/// `BadString` and `BadUrl` keep no text and are written as `"` plus a
+/// newline and as `url(()`.
+impl fmt::Display for CssToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CssToken::Ident(name) => write_name(f, name, true),
+            CssToken::String(text) => write_quoted(f, text),
+            CssToken::Number { value, is_integer } => write_number(f, *value, *is_integer),
+            CssToken::Dimension { value, unit, is_integer } => {
+                write_number(f, *value, *is_integer)?;
+                write_name(f, unit, true)
+            }
+            CssToken::Percentage { value, is_integer } => {
+                write_number(f, *value, *is_integer)?;
+                f.write_str("%")
+            }
+            CssToken::Hash { value, .. } => {
+                f.write_str("#")?;
+                write_name(f, value, false)
+            }
+            CssToken::Delim('\\') => f.write_str("\\\n"),
+            CssToken::Delim(ch) => write!(f, "{}", ch),
+            CssToken::LeftParen => f.write_str("("),
+            CssToken::RightParen => f.write_str(")"),
+            CssToken::LeftBrace => f.write_str("{"),
+            CssToken::RightBrace => f.write_str("}"),
+            CssToken::LeftBracket => f.write_str("["),
+            CssToken::RightBracket => f.write_str("]"),
+            CssToken::Colon => f.write_str(":"),
+            CssToken::Semicolon => f.write_str(";"),
+            CssToken::Comma => f.write_str(","),
+            CssToken::Whitespace(text) => f.write_str(text),
+            CssToken::Comment(text) => write!(f, "/*{}*/", text),
+            CssToken::AtKeyword(name) => {
+                f.write_str("@")?;
+                write_name(f, name, true)
+            }
+            CssToken::Url(url) => {
+                f.write_str("url(")?;
+                if needs_quotes_in_url(url) {
+                    write_quoted(f, url)?;
+                } else {
+                    f.write_str(url)?;
+                }
+                f.write_str(")")
+            }
+            CssToken::Function(name) => {
+                write_name(f, name, true)?;
+                f.write_str("(")
+            }
+            CssToken::CustomProperty(name) => {
+                f.write_str("--")?;
+                write_name(f, name, false)
+            }
+            CssToken::Cdo => f.write_str("<!--"),
+            CssToken::Cdc => f.write_str("-->"),
+            CssToken::BadString => f.write_str("\"\n"),
+            CssToken::BadUrl => f.write_str("url(()"),
+            CssToken::IncludeMatch => f.write_str("~="),
+            CssToken::DashMatch => f.write_str("|="),
+            CssToken::PrefixMatch => f.write_str("^="),
+            CssToken::SuffixMatch => f.write_str("$="),
+            CssToken::SubstringMatch => f.write_str("*="),
+        }
+    }
+}
+
+fn write_number(f: &mut fmt::Formatter<'_>, value: f64, is_integer: bool) -> fmt::Result {
+    let text = format_number(value, NumberFormat::default());
+    f.write_str(&text)?;
+    if !is_integer && !text.contains(['.', 'e']) {
+        f.write_str(".0")?;
+    }
+    Ok(())
+}
+
+/// Writes a name in source form: escapes are copied, other characters that
+/// are not name code points are escaped, and so is a digit where an
+/// identifier cannot have one (first, or after a single leading `-`) if
+/// `identifier` is set.
+fn write_name(f: &mut fmt::Formatter<'_>, raw: &str, identifier: bool) -> fmt::Result {
+    let mut chars = raw.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        if ch == '\\' {
+            // Copy the escape: up to six hex digits and one whitespace
+            // character, or any one character.
+            f.write_str("\\")?;
+            let mut hex_digits = 0;
+            while hex_digits < 6 && let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                write!(f, "{}", digit)?;
+                hex_digits += 1;
+            }
+            let next = match hex_digits {
+                0 => chars.next(),
+                _ => chars.next_if(|&(_, c)| c == ' ' || c == '\t' || is_newline(c)),
+            };
+            if let Some((_, next)) = next {
+                write!(f, "{}", next)?;
+                if next == '\r' && chars.next_if(|&(_, c)| c == '\n').is_some() {
+                    f.write_str("\n")?;
+                }
+            }
+            continue;
+        }
+
+        let digit_not_allowed = identifier && (index == 0 || (index == 1 && raw.starts_with('-')));
+        if is_name_char(ch) && !(digit_not_allowed && ch.is_ascii_digit()) {
+            write!(f, "{}", ch)?;
+        } else if ch.is_ascii_hexdigit() || ch.is_whitespace() || ch.is_control() {
+            write!(f, "\\{:x} ", ch as u32)?;
+        } else {
+            write!(f, "\\{}", ch)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `raw`, the source form of a string's content, in quotes.
+fn write_quoted(f: &mut fmt::Formatter<'_>, raw: &str) -> fmt::Result {
+    // Find the quotes that are not escaped
+    let (mut double, mut single) = (false, false);
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '"' => double = true,
+            '\'' => single = true,
+            _ => {}
+        }
+    }
+    let quote = if double && !single { '\'' } else { '"' };
+
+    write!(f, "{}", quote)?;
+    let mut chars = raw.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                f.write_str("\\")?;
+                if let Some(escaped) = chars.next() {
+                    write!(f, "{}", escaped)?;
+                    if escaped == '\r' && chars.next_if_eq(&'\n').is_some() {
+                        f.write_str("\n")?;
+                    }
+                }
+            }
+            '\n' | '\r' | '\x0C' => write!(f, "\\{:x} ", ch as u32)?,
+            ch if ch == quote => write!(f, "\\{}", ch)?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "{}", quote)
+}
+
+/// Returns whether `raw` can only be written as a quoted url: it has
+/// whitespace, quotes, parentheses or control characters outside escapes,
+/// or ends in a lone `\`.
+fn needs_quotes_in_url(raw: &str) -> bool {
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            // Consumes the escaped character; an escape is fine unless the
+            // backslash ends the input or a line
+            '\\' if chars.next().is_none_or(|escaped| matches!(escaped, '\n' | '\r' | '\x0C')) => return true,
+            '"' | '\'' | '(' | ')' => return true,
+            ch if ch.is_whitespace() || is_non_printable(ch) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Returns whether `ch` is a control character that may not appear in an
+/// unquoted url. NUL is not one: it stands for U+FFFD.
+fn is_non_printable(ch: char) -> bool {
+    matches!(ch, '\x01'..='\x08' | '\x0B' | '\x0E'..='\x1F' | '\x7F')
+}
+
+/// Returns whether `ch` ends a line. CSS reads `\r`, `\r\n` and form feed
+/// as `\n`.
+fn is_newline(ch: char) -> bool {
+    matches!(ch, '\n' | '\r' | '\x0C')
+}
+
+/// Decodes CSS escapes in a name: `\` followed by up to six hex digits and
+/// one optional whitespace character, or by any other character. Invalid
+/// code points and a trailing `\` decode to U+FFFD.
+///
+/// Also applies the input preprocessing of CSS Syntax, for text taken from
+/// the source rather than from a token: NUL becomes U+FFFD, and `\r\n`,
+/// `\r` and form feed become `\n`. Borrows when there is nothing to
+/// decode.
+pub fn unescape(raw: &str) -> Cow<'_, str> {
+    decode(raw, false)
+}
+
+/// Decodes the content of a CSS string, as `unescape` does for names,
+/// except that a `\` before a newline is a line continuation and is
+/// dropped along with the newline, as is a `\` at the very end:
+/// `"a\` + newline + `b"` is `ab`.
+pub fn unescape_string(raw: &str) -> Cow<'_, str> {
+    decode(raw, true)
+}
+
+fn decode(raw: &str, in_string: bool) -> Cow<'_, str> {
+    if !raw.contains(['\\', '\0', '\r', '\x0C']) {
+        return Cow::Borrowed(raw);
+    }
+
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            push_preprocessed(&mut out, ch, &mut chars);
+            continue;
+        }
+        match chars.peek() {
+            None if in_string => {}
+            None => out.push('\u{FFFD}'),
+            Some(c) if c.is_ascii_hexdigit() => {
+                let mut value = 0;
+                for _ in 0..6 {
+                    match chars.peek().and_then(|c| c.to_digit(16)) {
+                        Some(digit) => {
+                            value = value * 16 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                if chars.next_if(|&c| c == ' ' || c == '\t' || is_newline(c)) == Some('\r') {
+                    chars.next_if_eq(&'\n');
+                }
+                out.push(char::from_u32(value).filter(|&c| c != '\0').unwrap_or('\u{FFFD}'));
+            }
+            Some(&c) if in_string && is_newline(c) => {
+                if chars.next() == Some('\r') {
+                    chars.next_if_eq(&'\n');
+                }
+            }
+            Some(_) => {
+                if let Some(escaped) = chars.next() {
+                    push_preprocessed(&mut out, escaped, &mut chars);
+                }
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Applies the input preprocessing of CSS Syntax to `raw`: NUL becomes
+/// U+FFFD, and `\r\n`, `\r` and form feed become `\n`. Borrows when
+/// there is nothing to change.
+pub(crate) fn preprocess(raw: &str) -> Cow<'_, str> {
+    if !raw.contains(['\0', '\r', '\x0C']) {
+        return Cow::Borrowed(raw);
+    }
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(ch) = chars.next() {
+        push_preprocessed(&mut out, ch, &mut chars);
+    }
+    Cow::Owned(out)
+}
+
+/// Pushes `ch` as CSS preprocessing reads it, skipping the `\n` of a
+/// `\r\n` pair.
+fn push_preprocessed(out: &mut String, ch: char, rest: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    match ch {
+        '\0' => out.push('\u{FFFD}'),
+        '\r' => {
+            rest.next_if_eq(&'\n');
+            out.push('\n');
+        }
+        '\x0C' => out.push('\n'),
+        ch => out.push(ch),
+    }
+}
+
+/// Name code points per CSS Syntax: ASCII letters, digits, `-`, `_`, and
+/// every non-ASCII code point, which includes the U+FFFD a NUL stands for.
+fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == '\0' || !ch.is_ascii()
+}
+
+/// Name-start code points: name code points other than digits and `-`.
+fn is_name_start_char(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_' || ch == '\0' || !ch.is_ascii()
+}
+
+/// Returns whether `name` starts the way an identifier does: with a
+/// name-start code point or an escape, optionally after one `-`, or with
+/// `--`.
+fn starts_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let mut first = chars.next();
+    if first == Some('-') {
+        first = chars.next();
+        if first == Some('-') {
+            return true;
+        }
+    }
+    match first {
+        Some('\\') => chars.next().is_some_and(|ch| !is_newline(ch)),
+        Some(ch) => is_name_start_char(ch),
+        None => false,
+    }
+}
+
+/// Which construct a `TokenizeError` reports as unterminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenizeErrorKind {
+    /// A `/*` comment with no closing `*/`.
+    UnterminatedComment,
+    /// A quoted string that reaches the end of the input.
+    UnterminatedString,
+    /// A `url(` that reaches the end of the input before its `)`.
+    UnterminatedUrl,
+}
+
+/// A construct the tokenizer ran off the end of the input in. The token is
+/// still emitted with everything up to the end of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenizeError {
+    pub kind: TokenizeErrorKind,
+    /// Byte offset of the start of the construct: the `/` of `/*`, the
+    /// opening quote, or the `u` of `url(`.
+    pub offset: usize,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            TokenizeErrorKind::UnterminatedComment => "comment",
+            TokenizeErrorKind::UnterminatedString => "string",
+            TokenizeErrorKind::UnterminatedUrl => "url",
+        };
+        write!(f, "unterminated {} at byte {}", what, self.offset)
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// A token read ahead by `peek_n`, with where it starts and ends.
+#[derive(Debug, Clone)]
+struct Lookahead<'a> {
+    token: CssToken<'a>,
+    start: SourceLocation,
+    end: SourceLocation,
 }
 
 pub struct CssTokenizer<'a> {
     input: &'a str,
+    /// Byte offset of the next unconsumed character; always on a char
+    /// boundary. Ahead of the emitted tokens while some are peeked.
     position: usize,
+    /// Line/column bookkeeping; only kept by `new_with_locations`.
+    lines: Option<LineCounter>,
+    /// Tokens peeked but not yet returned by `next_token`, in order.
+    lookahead: VecDeque<Lookahead<'a>>,
+    token_start: SourceLocation,
+    token_end: SourceLocation,
+    /// Errors found so far and not yet taken by `take_errors`.
+    errors: Vec<TokenizeError>,
 }
 
 impl<'a> CssTokenizer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Self { input, position: 0 }
+        Self {
+            input,
+            position: 0,
+            lines: None,
+            lookahead: VecDeque::new(),
+            token_start: SourceLocation::START,
+            token_end: SourceLocation::START,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Creates a tokenizer that also tracks line and column numbers, so
+    /// `location()` can report where each token starts.
+    pub fn new_with_locations(input: &'a str) -> Self {
+        Self { lines: Some(LineCounter::new()), ..Self::new(input) }
+    }
+
+    /// Returns where the most recently emitted token starts, or `None` if
+    /// the tokenizer was not created with `new_with_locations`.
+    pub fn location(&self) -> Option<SourceLocation> {
+        self.lines.is_some().then_some(self.token_start)
+    }
+
+    /// Byte offset just past the most recently emitted token.
+    pub(crate) fn token_end_offset(&self) -> usize {
+        self.token_end.byte_offset
     }
 
     pub fn next_token(&mut self) -> Option<CssToken<'a>> {
+        let next = match self.lookahead.pop_front() {
+            Some(next) => next,
+            None => self.lex()?,
+        };
+        self.token_start = next.start;
+        self.token_end = next.end;
+        Some(next.token)
+    }
+
+    /// Returns the token the next call to `next_token` will return, without
+    /// consuming it.
+    pub fn peek(&mut self) -> Option<&CssToken<'a>> {
+        self.peek_n(0)
+    }
+
+    /// Returns the token `n` places after the next one without consuming
+    /// anything: `peek_n(0)` is the same as `peek()`. Peeked tokens are
+    /// kept, so later calls and `next_token` do not tokenize them again.
+    pub fn peek_n(&mut self, n: usize) -> Option<&CssToken<'a>> {
+        while self.lookahead.len() <= n {
+            let next = self.lex()?;
+            self.lookahead.push_back(next);
+        }
+        self.lookahead.get(n).map(|next| &next.token)
+    }
+
+    /// Returns the errors found since the last call, in input order.
+    ///
+    /// Errors are recorded when a token is tokenized, which includes
+    /// tokens only peeked so far.
+    pub fn take_errors(&mut self) -> Vec<TokenizeError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn error(&mut self, kind: TokenizeErrorKind, offset: usize) {
+        self.errors.push(TokenizeError { kind, offset });
+    }
+
+    /// Tokenizes the next token from the input, past any peeked ones.
+    fn lex(&mut self) -> Option<Lookahead<'a>> {
+        let start = self.current_location();
+        let token = self.consume_token()?;
+        Some(Lookahead { token, start, end: self.current_location() })
+    }
+
+    fn consume_token(&mut self) -> Option<CssToken<'a>> {
         if self.position >= self.input.len() {
             return None;
         }
@@ -40,7 +587,7 @@ impl<'a> CssTokenizer<'a> {
         let current_char = self.current_char()?;
 
         match current_char {
-            ' ' | '\t' | '\n' | '\r' => self.consume_whitespace(),
+            ' ' | '\t' | '\n' | '\r' | '\x0C' => self.consume_whitespace(),
             '/' if self.peek_char(1) == Some('*') => self.consume_comment(),
             '{' => {
                 self.advance();
@@ -78,13 +625,33 @@ impl<'a> CssTokenizer<'a> {
                 self.advance();
                 Some(CssToken::Comma)
             }
+            '~' | '|' | '^' | '$' | '*' if self.peek_char(1) == Some('=') => {
+                self.advance_by(2);
+                Some(match current_char {
+                    '~' => CssToken::IncludeMatch,
+                    '|' => CssToken::DashMatch,
+                    '^' => CssToken::PrefixMatch,
+                    '$' => CssToken::SuffixMatch,
+                    _ => CssToken::SubstringMatch,
+                })
+            }
             '"' | '\'' => self.consume_string(current_char),
             '#' => self.consume_hash(),
             '@' => self.consume_at_keyword(),
+            '<' if self.input[self.position..].starts_with("<!--") => {
+                self.advance_by(4);
+                Some(CssToken::Cdo)
+            }
+            '-' if self.input[self.position..].starts_with("-->") => {
+                self.advance_by(3);
+                Some(CssToken::Cdc)
+            }
             '0'..='9' => self.consume_number(),
-            '.' if self.peek_char(1).map_or(false, |c| c.is_ascii_digit()) => self.consume_number(),
-            '-' if self.is_number_start() => self.consume_number(),
+            '.' if self.peek_char(1).is_some_and(|c| c.is_ascii_digit()) => self.consume_number(),
+            '+' | '-' if self.is_number_start() => self.consume_number(),
             'a'..='z' | 'A'..='Z' | '_' | '-' => self.consume_ident_or_url(),
+            c if is_name_start_char(c) => self.consume_ident_or_url(),
+            '\\' if self.is_valid_escape() => self.consume_ident_or_url(),
             _ => {
                 self.advance();
                 Some(CssToken::Delim(current_char))
@@ -93,20 +660,65 @@ impl<'a> CssTokenizer<'a> {
     }
 
     fn current_char(&self) -> Option<char> {
-        self.input.chars().nth(self.position)
+        self.input[self.position..].chars().next()
     }
 
     fn peek_char(&self, offset: usize) -> Option<char> {
-        self.input.chars().nth(self.position + offset)
+        self.input[self.position..].chars().nth(offset)
     }
 
     fn advance(&mut self) {
-        if self.position < self.input.len() {
-            self.position += 1;
+        if let Some(ch) = self.current_char() {
+            self.position += ch.len_utf8();
+            if let Some(lines) = &mut self.lines {
+                lines.advance(ch);
+            }
+        }
+    }
+
+    fn advance_by(&mut self, chars: usize) {
+        for _ in 0..chars {
+            self.advance();
         }
     }
 
+    fn advance_to_end(&mut self) {
+        if self.lines.is_some() {
+            while self.position < self.input.len() {
+                self.advance();
+            }
+        } else {
+            self.position = self.input.len();
+        }
+    }
+
+    /// Where the next character is. Without `new_with_locations` only the
+    /// byte offset is tracked, and the line and column stay at 1.
+    fn current_location(&self) -> SourceLocation {
+        match self.lines {
+            Some(lines) => lines.location(self.position),
+            None => SourceLocation { byte_offset: self.position, ..SourceLocation::START },
+        }
+    }
+
+    /// The span of the most recently emitted token; lines and columns are
+    /// only tracked by `new_with_locations`.
+    #[cfg(any(feature = "css-ast", feature = "locations"))]
+    pub(crate) fn token_span(&self) -> Span {
+        Span {
+            start: self.token_start,
+            end: self.token_end,
+        }
+    }
+
+    /// The input from `start` to `end` as CSS preprocessing reads it: NUL
+    /// becomes U+FFFD, and `\r\n`, `\r` and form feed become `\n`.
+    fn slice(&self, start: usize, end: usize) -> Cow<'a, str> {
+        preprocess(&self.input[start..end])
+    }
+
     fn consume_whitespace(&mut self) -> Option<CssToken<'a>> {
+        let start = self.position;
         while let Some(ch) = self.current_char() {
             if ch.is_whitespace() {
                 self.advance();
@@ -114,10 +726,11 @@ impl<'a> CssTokenizer<'a> {
                 break;
             }
         }
-        Some(CssToken::Whitespace)
+        Some(CssToken::Whitespace(self.slice(start, self.position)))
     }
 
     fn consume_comment(&mut self) -> Option<CssToken<'a>> {
+        let comment_start = self.position;
         self.advance(); // Skip '/'
         self.advance(); // Skip '*'
         
@@ -125,7 +738,7 @@ impl<'a> CssTokenizer<'a> {
         
         while self.position + 1 < self.input.len() {
             if self.current_char() == Some('*') && self.peek_char(1) == Some('/') {
-                let content = &self.input[start..self.position];
+                let content = self.slice(start, self.position);
                 self.advance(); // Skip '*'
                 self.advance(); // Skip '/'
                 return Some(CssToken::Comment(content));
@@ -134,22 +747,30 @@ impl<'a> CssTokenizer<'a> {
         }
 
         // Unclosed comment
-        let content = &self.input[start..];
-        self.position = self.input.len();
+        let content = self.slice(start, self.input.len());
+        self.advance_to_end();
+        self.error(TokenizeErrorKind::UnterminatedComment, comment_start);
         Some(CssToken::Comment(content))
     }
 
     fn consume_string(&mut self, quote: char) -> Option<CssToken<'a>> {
+        let quote_start = self.position;
         self.advance(); // Skip opening quote
         let start = self.position;
 
         while let Some(ch) = self.current_char() {
             if ch == quote {
-                let content = &self.input[start..self.position];
+                let content = self.slice(start, self.position);
                 self.advance(); // Skip closing quote
                 return Some(CssToken::String(content));
+            } else if is_newline(ch) {
+                // Leave the newline for the next token
+                return Some(CssToken::BadString);
             } else if ch == '\\' {
                 self.advance(); // Skip backslash
+                if self.current_char() == Some('\r') && self.peek_char(1) == Some('\n') {
+                    self.advance(); // An escaped `\r\n` is one newline
+                }
                 if self.current_char().is_some() {
                     self.advance(); // Skip escaped character
                 }
@@ -159,8 +780,9 @@ impl<'a> CssTokenizer<'a> {
         }
 
         // Unclosed string
-        let content = &self.input[start..];
-        self.position = self.input.len();
+        let content = self.slice(start, self.input.len());
+        self.advance_to_end();
+        self.error(TokenizeErrorKind::UnterminatedString, quote_start);
         Some(CssToken::String(content))
     }
 
@@ -168,19 +790,14 @@ impl<'a> CssTokenizer<'a> {
         self.advance(); // Skip '#'
         let start = self.position;
 
-        while let Some(ch) = self.current_char() {
-            if ch.is_alphanumeric() || ch == '-' || ch == '_' {
-                self.advance();
-            } else {
-                break;
-            }
-        }
+        self.consume_name();
 
         if start == self.position {
             Some(CssToken::Delim('#'))
         } else {
-            let content = &self.input[start..self.position];
-            Some(CssToken::Hash(content))
+            let value = self.slice(start, self.position);
+            let is_id = starts_identifier(&value);
+            Some(CssToken::Hash { value, is_id })
         }
     }
 
@@ -188,19 +805,48 @@ impl<'a> CssTokenizer<'a> {
         self.advance(); // Skip '@'
         let start = self.position;
 
+        self.consume_name();
+
+        if start == self.position {
+            Some(CssToken::Delim('@'))
+        } else {
+            let content = self.slice(start, self.position);
+            Some(CssToken::AtKeyword(content))
+        }
+    }
+
+    /// Consumes name code points and escapes, leaving escapes undecoded in
+    /// the input slice; see `unescape`.
+    fn consume_name(&mut self) {
         while let Some(ch) = self.current_char() {
-            if ch.is_alphanumeric() || ch == '-' || ch == '_' {
+            if is_name_char(ch) {
                 self.advance();
+            } else if self.is_valid_escape() {
+                self.consume_escape();
             } else {
                 break;
             }
         }
+    }
 
-        if start == self.position {
-            Some(CssToken::Delim('@'))
-        } else {
-            let content = &self.input[start..self.position];
-            Some(CssToken::AtKeyword(content))
+    fn is_valid_escape(&self) -> bool {
+        self.current_char() == Some('\\') && !self.peek_char(1).is_some_and(is_newline)
+    }
+
+    fn consume_escape(&mut self) {
+        self.advance(); // Skip '\\'
+        let mut hex_digits = 0;
+        while hex_digits < 6 && self.current_char().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.advance();
+            hex_digits += 1;
+        }
+        if hex_digits == 0 {
+            self.advance(); // Escaped character
+        } else if self.current_char().is_some_and(|ch| ch == ' ' || ch == '\t' || is_newline(ch)) {
+            if self.current_char() == Some('\r') && self.peek_char(1) == Some('\n') {
+                self.advance();
+            }
+            self.advance(); // Whitespace terminating a hex escape
         }
     }
 
@@ -208,8 +854,8 @@ impl<'a> CssTokenizer<'a> {
         let start = self.position;
         let mut has_dot = false;
 
-        // Handle optional minus sign
-        if self.current_char() == Some('-') {
+        // Handle optional sign
+        if matches!(self.current_char(), Some('+' | '-')) {
             self.advance();
         }
 
@@ -217,7 +863,7 @@ impl<'a> CssTokenizer<'a> {
         while let Some(ch) = self.current_char() {
             if ch.is_ascii_digit() {
                 self.advance();
-            } else if ch == '.' && !has_dot {
+            } else if ch == '.' && !has_dot && self.peek_char(1).is_some_and(|ch| ch.is_ascii_digit()) {
                 has_dot = true;
                 self.advance();
             } else {
@@ -225,13 +871,30 @@ impl<'a> CssTokenizer<'a> {
             }
         }
 
+        // Optional exponent; `1e` or `1em` leave the `e` for the unit
+        let mut has_exponent = false;
+        if matches!(self.current_char(), Some('e' | 'E')) {
+            let digits_at = match self.peek_char(1) {
+                Some('+' | '-') => 2,
+                _ => 1,
+            };
+            if self.peek_char(digits_at).is_some_and(|ch| ch.is_ascii_digit()) {
+                has_exponent = true;
+                self.advance_by(digits_at);
+                while self.current_char().is_some_and(|ch| ch.is_ascii_digit()) {
+                    self.advance();
+                }
+            }
+        }
+
         let number_str = &self.input[start..self.position];
         let value = number_str.parse::<f64>().unwrap_or(0.0);
+        let is_integer = !has_dot && !has_exponent;
 
         // Check for unit or percentage
         if self.current_char() == Some('%') {
             self.advance();
-            Some(CssToken::Percentage(value))
+            Some(CssToken::Percentage { value, is_integer })
         } else if let Some(ch) = self.current_char() {
             if ch.is_alphabetic() {
                 let unit_start = self.position;
@@ -242,44 +905,37 @@ impl<'a> CssTokenizer<'a> {
                         break;
                     }
                 }
-                let unit = &self.input[unit_start..self.position];
-                Some(CssToken::Dimension { value, unit })
+                let unit = self.input[unit_start..self.position].into();
+                Some(CssToken::Dimension { value, unit, is_integer })
             } else {
-                Some(CssToken::Number(value))
+                Some(CssToken::Number { value, is_integer })
             }
         } else {
-            Some(CssToken::Number(value))
+            Some(CssToken::Number { value, is_integer })
         }
     }
 
     fn consume_ident_or_url(&mut self) -> Option<CssToken<'a>> {
         let start = self.position;
 
-        while let Some(ch) = self.current_char() {
-            if ch.is_alphanumeric() || ch == '-' || ch == '_' {
-                self.advance();
-            } else {
-                break;
-            }
-        }
+        self.consume_name();
 
-        let ident = &self.input[start..self.position];
+        let ident = self.slice(start, self.position);
 
         // Check if this is a url() function
         if ident == "url" && self.current_char() == Some('(') {
             self.advance(); // Skip '('
             self.skip_whitespace();
 
-            let _url_start = self.position;
             let mut in_quotes = false;
             let mut quote_char = None;
 
-            if let Some(ch) = self.current_char() {
-                if ch == '"' || ch == '\'' {
-                    in_quotes = true;
-                    quote_char = Some(ch);
-                    self.advance();
-                }
+            if let Some(ch) = self.current_char()
+                && (ch == '"' || ch == '\'')
+            {
+                in_quotes = true;
+                quote_char = Some(ch);
+                self.advance();
             }
 
             let url_content_start = self.position;
@@ -287,31 +943,83 @@ impl<'a> CssTokenizer<'a> {
             while let Some(ch) = self.current_char() {
                 if in_quotes {
                     if Some(ch) == quote_char {
-                        let url = &self.input[url_content_start..self.position];
+                        let url = self.slice(url_content_start, self.position);
                         self.advance(); // Skip closing quote
                         self.skip_whitespace();
-                        if self.current_char() == Some(')') {
-                            self.advance(); // Skip ')'
-                        }
-                        return Some(CssToken::Url(url));
+                        return match self.current_char() {
+                            Some(')') => {
+                                self.advance(); // Skip ')'
+                                Some(CssToken::Url(url))
+                            }
+                            None => {
+                                self.error(TokenizeErrorKind::UnterminatedUrl, start);
+                                Some(CssToken::Url(url))
+                            }
+                            Some(_) => Some(self.consume_bad_url()),
+                        };
+                    } else if is_newline(ch) {
+                        return Some(self.consume_bad_url());
+                    } else if ch == '\\' {
+                        self.advance(); // Skip backslash; the escaped character follows
                     }
                 } else if ch == ')' {
-                    let url = &self.input[url_content_start..self.position].trim();
+                    let url = self.slice(url_content_start, self.position);
                     self.advance(); // Skip ')'
                     return Some(CssToken::Url(url));
+                } else if ch.is_whitespace() {
+                    let url = self.slice(url_content_start, self.position);
+                    self.skip_whitespace();
+                    match self.current_char() {
+                        Some(')') => {
+                            self.advance(); // Skip ')'
+                            return Some(CssToken::Url(url));
+                        }
+                        None => {
+                            self.error(TokenizeErrorKind::UnterminatedUrl, start);
+                            return Some(CssToken::Url(url));
+                        }
+                        Some(_) => return Some(self.consume_bad_url()),
+                    }
+                } else if matches!(ch, '"' | '\'' | '(') || is_non_printable(ch) {
+                    return Some(self.consume_bad_url());
+                } else if ch == '\\' {
+                    // A backslash before a newline is not a valid escape
+                    if self.peek_char(1).is_some_and(is_newline) {
+                        return Some(self.consume_bad_url());
+                    }
+                    self.advance(); // Skip backslash; the escaped character follows
                 }
                 self.advance();
             }
 
             // Unclosed url
-            let url = &self.input[url_content_start..];
-            self.position = self.input.len();
+            let url = self.slice(url_content_start, self.input.len());
+            self.advance_to_end();
+            self.error(TokenizeErrorKind::UnterminatedUrl, start);
             Some(CssToken::Url(url))
+        } else if self.current_char() == Some('(') {
+            self.advance(); // Skip '('
+            Some(CssToken::Function(ident))
+        } else if ident.len() > 2 && ident.starts_with("--") {
+            Some(CssToken::CustomProperty(self.slice(start + 2, self.position)))
         } else {
             Some(CssToken::Ident(ident))
         }
     }
 
+    /// Consumes the rest of a bad url up to and including `)`.
+    fn consume_bad_url(&mut self) -> CssToken<'a> {
+        while let Some(ch) = self.current_char() {
+            self.advance();
+            if ch == ')' {
+                break;
+            } else if ch == '\\' {
+                self.advance(); // Skip escaped character
+            }
+        }
+        CssToken::BadUrl
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(ch) = self.current_char() {
             if ch.is_whitespace() {
@@ -322,15 +1030,28 @@ impl<'a> CssTokenizer<'a> {
         }
     }
 
+    /// Returns whether the `+` or `-` at the cursor starts a number.
     fn is_number_start(&self) -> bool {
-        if let Some(next) = self.peek_char(1) {
-            next.is_ascii_digit() || next == '.'
-        } else {
-            false
+        match self.peek_char(1) {
+            Some(next) if next.is_ascii_digit() => true,
+            Some('.') => self.peek_char(2).is_some_and(|ch| ch.is_ascii_digit()),
+            _ => false,
         }
     }
 }
 
+#[cfg(feature = "locations")]
+impl<'a> CssTokenizer<'a> {
+    /// Like `next_token`, but also returns the source span of the token,
+    /// covering its full text (quotes of strings, `#` of hashes and so on).
+    /// Byte offsets are always set; lines and columns only with
+    /// `new_with_locations`, and stay at 1 otherwise.
+    pub fn next_token_spanned(&mut self) -> Option<(CssToken<'a>, Span)> {
+        let token = self.next_token()?;
+        Some((token, self.token_span()))
+    }
+}
+
 impl<'a> Iterator for CssTokenizer<'a> {
     type Item = CssToken<'a>;
 
@@ -350,13 +1071,13 @@ mod tests {
         let tokens: Vec<_> = tokenizer.collect();
         
         assert!(matches!(tokens[0], CssToken::LeftBrace));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
         assert!(matches!(tokens[2], CssToken::RightBrace));
-        assert!(matches!(tokens[3], CssToken::Whitespace));
+        assert!(matches!(tokens[3], CssToken::Whitespace(_)));
         assert!(matches!(tokens[4], CssToken::LeftParen));
-        assert!(matches!(tokens[5], CssToken::Whitespace));
+        assert!(matches!(tokens[5], CssToken::Whitespace(_)));
         assert!(matches!(tokens[6], CssToken::RightParen));
-        assert!(matches!(tokens[7], CssToken::Whitespace));
+        assert!(matches!(tokens[7], CssToken::Whitespace(_)));
         assert!(matches!(tokens[8], CssToken::LeftBracket));
     }
 
@@ -366,28 +1087,226 @@ mod tests {
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::Ident("div")));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::Ident("class-name")));
-        assert!(matches!(tokens[3], CssToken::Whitespace));
-        assert!(matches!(tokens[4], CssToken::Ident("_private")));
+        assert_eq!(tokens[0], CssToken::Ident("div".into()));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert_eq!(tokens[2], CssToken::Ident("class-name".into()));
+        assert!(matches!(tokens[3], CssToken::Whitespace(_)));
+        assert_eq!(tokens[4], CssToken::Ident("_private".into()));
     }
 
     #[test]
     fn test_numbers() {
-        let tokenizer = CssTokenizer::new("42 3.14 -10 50% 16px");
+        let tokenizer = CssTokenizer::new("42 2.75 -10 50% 16px");
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::Number(42.0)));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::Number(3.14)));
-        assert!(matches!(tokens[3], CssToken::Whitespace));
-        assert!(matches!(tokens[4], CssToken::Number(-10.0)));
-        assert!(matches!(tokens[5], CssToken::Whitespace));
-        assert!(matches!(tokens[6], CssToken::Percentage(50.0)));
-        assert!(matches!(tokens[7], CssToken::Whitespace));
-        assert!(matches!(tokens[8], CssToken::Dimension { value: 16.0, unit: "px" }));
+        assert!(matches!(tokens[0], CssToken::Number { value: 42.0, is_integer: true }));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert!(matches!(tokens[2], CssToken::Number { value: 2.75, is_integer: false }));
+        assert!(matches!(tokens[3], CssToken::Whitespace(_)));
+        assert!(matches!(tokens[4], CssToken::Number { value: -10.0, is_integer: true }));
+        assert!(matches!(tokens[5], CssToken::Whitespace(_)));
+        assert!(matches!(tokens[6], CssToken::Percentage { value: 50.0, is_integer: true }));
+        assert!(matches!(tokens[7], CssToken::Whitespace(_)));
+        assert_eq!(tokens[8], CssToken::Dimension { value: 16.0, unit: "px".into(), is_integer: true });
+    }
+
+    #[test]
+    fn test_custom_properties() {
+        let tokens: Vec<_> = CssTokenizer::new("--primary-color: var(--gap) --a\\62 -x --").collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::CustomProperty("primary-color".into()),
+                CssToken::Colon,
+                CssToken::Whitespace(" ".into()),
+                CssToken::Function("var".into()),
+                CssToken::CustomProperty("gap".into()),
+                CssToken::RightParen,
+                CssToken::Whitespace(" ".into()),
+                CssToken::CustomProperty("a\\62 -x".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::Ident("--".into()),
+            ]
+        );
+        assert_eq!(tokens[7].unescaped().as_deref(), Some("ab-x"));
+
+        // A digit may follow the dashes, unlike in other identifiers
+        let tokens: Vec<_> = CssTokenizer::new("--1x:-1x").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::CustomProperty("1x".into()),
+                CssToken::Colon,
+                CssToken::Dimension { value: -1.0, unit: "x".into(), is_integer: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cdo_cdc() {
+        let tokens: Vec<_> = CssTokenizer::new("<!-- a --> <! -- --x").collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Cdo,
+                CssToken::Whitespace(" ".into()),
+                CssToken::Ident("a".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::Cdc,
+                CssToken::Whitespace(" ".into()),
+                CssToken::Delim('<'),
+                CssToken::Delim('!'),
+                CssToken::Whitespace(" ".into()),
+                CssToken::Ident("--".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::CustomProperty("x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bad_strings_and_urls() {
+        let tokens: Vec<_> = CssTokenizer::new("\"oops\n'ok\\\nstill' url( a.png ) url(a b) url(a\"b) url(a(b)) x")
+            .filter(|token| !matches!(token, CssToken::Whitespace(_)))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::BadString,
+                CssToken::String("ok\\\nstill".into()),
+                CssToken::Url("a.png".into()),
+                CssToken::BadUrl,
+                CssToken::BadUrl,
+                CssToken::BadUrl,
+                CssToken::RightParen,
+                CssToken::Ident("x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_url_edge_cases() {
+        let input = "url( data:image/png;base64,iVBORw0KGgo= ) url(foo\\).png) url( ) url(\"a\\\"b\" ) \
+                     url(\"a\" x) url(a\u{7}b) url(a\\\nb) y";
+        let tokens: Vec<_> = CssTokenizer::new(input)
+            .filter(|token| !matches!(token, CssToken::Whitespace(_)))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Url("data:image/png;base64,iVBORw0KGgo=".into()),
+                CssToken::Url("foo\\).png".into()),
+                CssToken::Url("".into()),
+                CssToken::Url("a\\\"b".into()),
+                CssToken::BadUrl,
+                CssToken::BadUrl,
+                CssToken::BadUrl,
+                CssToken::Ident("y".into()),
+            ]
+        );
+        assert_eq!(unescape("foo\\).png"), "foo).png");
+    }
+
+    #[test]
+    fn test_peek_matches_next_token() {
+        let css = "a/* x */url( b.png )/* y */c";
+        let mut tokenizer = CssTokenizer::new_with_locations(css);
+
+        assert_eq!(tokenizer.peek_n(2), Some(&CssToken::Url("b.png".into())));
+        assert_eq!(tokenizer.peek(), Some(&CssToken::Ident("a".into())));
+        assert_eq!(tokenizer.location().unwrap().column, 1);
+        assert_eq!(tokenizer.next_token(), Some(CssToken::Ident("a".into())));
+        assert_eq!(tokenizer.peek_n(2), Some(&CssToken::Comment(" y ".into())));
+        assert_eq!(tokenizer.next_token(), Some(CssToken::Comment(" x ".into())));
+        assert_eq!(tokenizer.location().unwrap().column, 2);
+        assert_eq!(tokenizer.peek_n(2), Some(&CssToken::Ident("c".into())));
+        assert_eq!(tokenizer.peek_n(3), None);
+        assert_eq!(tokenizer.next_token(), Some(CssToken::Url("b.png".into())));
+        assert_eq!(tokenizer.location().unwrap().column, 9);
+
+        let rest: Vec<_> = tokenizer.collect();
+        assert_eq!(rest, vec![CssToken::Comment(" y ".into()), CssToken::Ident("c".into())]);
+
+        let mut peeking = CssTokenizer::new(css);
+        let mut peeked = Vec::new();
+        while let Some(token) = peeking.peek().cloned() {
+            peeking.peek_n(2);
+            assert_eq!(peeking.next_token(), Some(token.clone()));
+            peeked.push(token);
+        }
+        assert_eq!(peeked, CssTokenizer::new(css).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_unicode_ranges_are_ordinary_tokens() {
+        // The parser rebuilds ranges for `unicode-range` in `@font-face`;
+        // elsewhere `u+a` is a selector.
+        let tokens: Vec<_> = CssTokenizer::new("U+26 u+a U+4??")
+            .filter(|token| !matches!(token, CssToken::Whitespace(_)))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Ident("U".into()),
+                CssToken::Number { value: 26.0, is_integer: true },
+                CssToken::Ident("u".into()),
+                CssToken::Delim('+'),
+                CssToken::Ident("a".into()),
+                CssToken::Ident("U".into()),
+                CssToken::Number { value: 4.0, is_integer: true },
+                CssToken::Delim('?'),
+                CssToken::Delim('?'),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_flag_and_plus_sign() {
+        let tokens: Vec<_> = CssTokenizer::new("+5px 1.0 +.5 -3% 1. a+b + 2")
+            .filter(|token| !matches!(token, CssToken::Whitespace(_)))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Dimension { value: 5.0, unit: "px".into(), is_integer: true },
+                CssToken::Number { value: 1.0, is_integer: false },
+                CssToken::Number { value: 0.5, is_integer: false },
+                CssToken::Percentage { value: -3.0, is_integer: true },
+                CssToken::Number { value: 1.0, is_integer: true },
+                CssToken::Delim('.'),
+                CssToken::Ident("a".into()),
+                CssToken::Delim('+'),
+                CssToken::Ident("b".into()),
+                CssToken::Delim('+'),
+                CssToken::Number { value: 2.0, is_integer: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        let tokens: Vec<_> = CssTokenizer::new("1e3 2.5E-2 6.02e+23 1e3px 1e 2em")
+            .filter(|token| !matches!(token, CssToken::Whitespace(_)))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Number { value: 1000.0, is_integer: false },
+                CssToken::Number { value: 0.025, is_integer: false },
+                CssToken::Number { value: 6.02e23, is_integer: false },
+                CssToken::Dimension { value: 1000.0, unit: "px".into(), is_integer: false },
+                CssToken::Dimension { value: 1.0, unit: "e".into(), is_integer: true },
+                CssToken::Dimension { value: 2.0, unit: "em".into(), is_integer: true },
+            ]
+        );
     }
 
     #[test]
@@ -396,9 +1315,9 @@ mod tests {
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::String("hello")));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::String("world")));
+        assert_eq!(tokens[0], CssToken::String("hello".into()));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert_eq!(tokens[2], CssToken::String("world".into()));
     }
 
     #[test]
@@ -407,9 +1326,36 @@ mod tests {
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::Hash("main")));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::Hash("ff0000")));
+        assert_eq!(tokens[0], CssToken::Hash { value: "main".into(), is_id: true });
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert_eq!(tokens[2], CssToken::Hash { value: "ff0000".into(), is_id: true });
+    }
+
+    #[test]
+    fn test_hash_id_flag() {
+        let hashes: Vec<_> = CssTokenizer::new(r"#main #1a2b3c #-x #-1 #--y #_z #\31 a #é #fff #123")
+            .filter_map(|token| match token {
+                CssToken::Hash { value, is_id } => Some((value.into_owned(), is_id)),
+                _ => None,
+            })
+            .collect();
+        let hashes: Vec<_> = hashes.iter().map(|(value, is_id)| (value.as_str(), *is_id)).collect();
+
+        assert_eq!(
+            hashes,
+            vec![
+                ("main", true),
+                ("1a2b3c", false),
+                ("-x", true),
+                ("-1", false),
+                ("--y", true),
+                ("_z", true),
+                (r"\31 a", true),
+                ("é", true),
+                ("fff", true),
+                ("123", false),
+            ]
+        );
     }
 
     #[test]
@@ -418,9 +1364,9 @@ mod tests {
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::AtKeyword("media")));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::AtKeyword("import")));
+        assert_eq!(tokens[0], CssToken::AtKeyword("media".into()));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert_eq!(tokens[2], CssToken::AtKeyword("import".into()));
     }
 
     #[test]
@@ -429,9 +1375,9 @@ mod tests {
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::Url("image.png")));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::Url("path/to/file.jpg")));
+        assert_eq!(tokens[0], CssToken::Url("image.png".into()));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert_eq!(tokens[2], CssToken::Url("path/to/file.jpg".into()));
     }
 
     #[test]
@@ -440,8 +1386,467 @@ mod tests {
         
         let tokens: Vec<_> = tokenizer.collect();
         
-        assert!(matches!(tokens[0], CssToken::Comment(" comment ")));
-        assert!(matches!(tokens[1], CssToken::Whitespace));
-        assert!(matches!(tokens[2], CssToken::Ident("div")));
+        assert_eq!(tokens[0], CssToken::Comment(" comment ".into()));
+        assert!(matches!(tokens[1], CssToken::Whitespace(_)));
+        assert_eq!(tokens[2], CssToken::Ident("div".into()));
+    }
+
+    #[test]
+    fn test_functions() {
+        let tokenizer = CssTokenizer::new("rgb(255, 0, 0) calc(100% - min(2px, 1em)) url(a.png)");
+
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert_eq!(tokens[0], CssToken::Function("rgb".into()));
+        assert!(matches!(tokens[1], CssToken::Number { value: 255.0, is_integer: true }));
+        assert!(matches!(tokens[8], CssToken::RightParen));
+        assert_eq!(tokens[10], CssToken::Function("calc".into()));
+        assert_eq!(tokens[15], CssToken::Function("min".into()));
+        assert_eq!(tokens[tokens.len() - 1], CssToken::Url("a.png".into()));
+    }
+
+    #[test]
+    fn test_ident_before_spaced_paren_is_not_function() {
+        let tokens: Vec<_> = CssTokenizer::new("and (").collect();
+
+        assert_eq!(tokens[0], CssToken::Ident("and".into()));
+        assert!(matches!(tokens[2], CssToken::LeftParen));
+    }
+
+    #[test]
+    fn test_multibyte_content() {
+        let css = "/* café */ \"日本語\" url(ñ/图.png) url(\"ü.png\") .naïve #ß → é";
+        let tokens: Vec<_> = CssTokenizer::new(css).filter(|t| !matches!(t, CssToken::Whitespace(_))).collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Comment(" café ".into()),
+                CssToken::String("日本語".into()),
+                CssToken::Url("ñ/图.png".into()),
+                CssToken::Url("ü.png".into()),
+                CssToken::Delim('.'),
+                CssToken::Ident("naïve".into()),
+                CssToken::Hash { value: "ß".into(), is_id: true },
+                CssToken::Ident("→".into()),
+                CssToken::Ident("é".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_stylesheet() {
+        let rule = ".item-é { margin: 0 auto; color: #ff0000; width: calc(100% - 10px); }\n";
+        let count = 100 * 1024 / rule.len() + 1;
+        let css = rule.repeat(count);
+
+        let tokens: Vec<_> = CssTokenizer::new(&css).collect();
+
+        assert_eq!(tokens.len(), CssTokenizer::new(rule).count() * count);
+        let braces = tokens.iter().filter(|t| matches!(t, CssToken::LeftBrace)).count();
+        assert_eq!(braces, count);
+    }
+
+    #[test]
+    fn test_locations() {
+        let mut tokenizer = CssTokenizer::new_with_locations(".foo\n{ color: red;\n}");
+
+        let mut find = |wanted: CssToken| loop {
+            let token = tokenizer.next_token().unwrap();
+            if token == wanted {
+                return tokenizer.location().unwrap();
+            }
+        };
+
+        assert_eq!(find(CssToken::LeftBrace), SourceLocation { line: 2, column: 1, byte_offset: 5 });
+        assert_eq!(find(CssToken::Semicolon), SourceLocation { line: 2, column: 13, byte_offset: 17 });
+        assert_eq!(find(CssToken::RightBrace), SourceLocation { line: 3, column: 1, byte_offset: 19 });
+    }
+
+    #[test]
+    fn test_locations_multibyte() {
+        let mut tokenizer = CssTokenizer::new_with_locations("\"日本\" \"e\u{301}👍🏽\" x");
+
+        let columns: Vec<_> = std::iter::from_fn(|| {
+            tokenizer.next_token().map(|_| tokenizer.location().unwrap().column)
+        })
+        .collect();
+
+        assert_eq!(columns, vec![1, 5, 6, 10, 11]);
+    }
+
+    #[test]
+    fn test_no_locations_by_default() {
+        let mut tokenizer = CssTokenizer::new("a");
+
+        tokenizer.next_token();
+        assert_eq!(tokenizer.location(), None);
+    }
+
+    #[cfg(feature = "locations")]
+    #[test]
+    fn test_spans_after_peeking() {
+        let css = "a { b: url(c) }";
+        let mut tokenizer = CssTokenizer::new(css);
+
+        tokenizer.peek_n(8);
+        let (token, span) = tokenizer.next_token_spanned().unwrap();
+        assert_eq!((token, &css[span.byte_range()]), (CssToken::Ident("a".into()), "a"));
+        tokenizer.peek_n(6);
+        let spans: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_spanned())
+            .map(|(_, span)| &css[span.byte_range()])
+            .collect();
+        assert_eq!(spans, vec![" ", "{", " ", "b", ":", " ", "url(c)", " ", "}"]);
+    }
+
+    #[cfg(feature = "locations")]
+    #[test]
+    fn test_spanned_tokens_across_lines() {
+        let css = ".a {\r\n  color: café;\r\n}";
+        let mut tokenizer = CssTokenizer::new_with_locations(css);
+        let at = |line, column, byte_offset| SourceLocation { line, column, byte_offset };
+
+        let spans: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_spanned())
+            .filter(|(token, _)| !matches!(token, CssToken::Whitespace(_)))
+            .map(|(_, span)| span)
+            .collect();
+
+        assert_eq!(spans[3], Span { start: at(2, 3, 8), end: at(2, 8, 13) });
+        assert_eq!(spans[4], Span { start: at(2, 8, 13), end: at(2, 9, 14) });
+        assert_eq!(spans[5], Span { start: at(2, 10, 15), end: at(2, 14, 20) });
+        assert_eq!(spans[6], Span { start: at(2, 14, 20), end: at(2, 15, 21) });
+        assert_eq!(spans[7], Span { start: at(3, 1, 23), end: at(3, 2, 24) });
+    }
+
+    #[cfg(feature = "locations")]
+    #[test]
+    fn test_spanned_tokens() {
+        let css = ".a { color: red; content: \"x\"; } #b";
+        let mut tokenizer = CssTokenizer::new(css);
+
+        let spans: Vec<_> = std::iter::from_fn(|| tokenizer.next_token_spanned())
+            .filter(|(token, _)| !matches!(token, CssToken::Whitespace(_)))
+            .map(|(token, span)| (token, &css[span.byte_range()]))
+            .collect();
+
+        assert_eq!(spans[0], (CssToken::Delim('.'), "."));
+        assert_eq!(spans[1], (CssToken::Ident("a".into()), "a"));
+        assert_eq!(spans[2], (CssToken::LeftBrace, "{"));
+        assert_eq!(spans[3], (CssToken::Ident("color".into()), "color"));
+        assert_eq!(spans[5], (CssToken::Ident("red".into()), "red"));
+        assert_eq!(spans[6], (CssToken::Semicolon, ";"));
+        assert_eq!(spans[9], (CssToken::String("x".into()), "\"x\""));
+        assert_eq!(spans[12], (CssToken::Hash { value: "b".into(), is_id: true }, "#b"));
+    }
+
+    #[test]
+    fn test_escaped_identifiers() {
+        let tokens: Vec<_> = CssTokenizer::new(r".hover\:bg-red #\26 foo .\31 23 x").collect();
+
+        assert_eq!(tokens[1], CssToken::Ident(r"hover\:bg-red".into()));
+        assert_eq!(tokens[1].unescaped().unwrap(), "hover:bg-red");
+        assert_eq!(tokens[3], CssToken::Hash { value: r"\26 foo".into(), is_id: true });
+        assert_eq!(tokens[3].unescaped().unwrap(), "&foo");
+        assert_eq!(tokens[6], CssToken::Ident(r"\31 23".into()));
+        assert_eq!(tokens[6].unescaped().unwrap(), "123");
+        assert_eq!(tokens[8], CssToken::Ident("x".into()));
+    }
+
+    #[test]
+    fn test_escape_at_end_of_input() {
+        let tokens: Vec<_> = CssTokenizer::new(r"foo\").collect();
+
+        assert_eq!(tokens, vec![CssToken::Ident(r"foo\".into())]);
+        assert_eq!(tokens[0].unescaped().unwrap(), "foo\u{FFFD}");
+    }
+
+    #[test]
+    fn test_nul_in_names_and_strings() {
+        let tokens: Vec<_> = CssTokenizer::new("fo\0o #a\0 '\0' \0x").collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Ident("fo\u{FFFD}o".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::Hash { value: "a\u{FFFD}".into(), is_id: true },
+                CssToken::Whitespace(" ".into()),
+                CssToken::String("\u{FFFD}".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::Ident("\u{FFFD}x".into()),
+            ]
+        );
+        assert_eq!(tokens[0].unescaped().unwrap(), "fo\u{FFFD}o");
+        assert_eq!(tokens[6].unescaped().unwrap(), "\u{FFFD}x");
+        assert_eq!(unescape("\\0\0"), "\u{FFFD}\u{FFFD}");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_carriage_returns_and_form_feeds_are_newlines() {
+        let tokens: Vec<_> = CssTokenizer::new("a\rb\x0Cc 'x\ry").collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Ident("a".into()),
+                CssToken::Whitespace("\n".into()),
+                CssToken::Ident("b".into()),
+                CssToken::Whitespace("\n".into()),
+                CssToken::Ident("c".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::BadString,
+                CssToken::Whitespace("\n".into()),
+                CssToken::Ident("y".into()),
+            ]
+        );
+
+        let tokens: Vec<_> = CssTokenizer::new("/* 1\r\n2 */\\31\r\nd \"p\\\r\nq\" \\\rx").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::Comment(" 1\n2 ".into()),
+                CssToken::Ident("\\31\nd".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::String("p\\\nq".into()),
+                CssToken::Whitespace(" ".into()),
+                CssToken::Delim('\\'),
+                CssToken::Whitespace("\n".into()),
+                CssToken::Ident("x".into()),
+            ]
+        );
+        assert_eq!(tokens[1].unescaped().unwrap(), "1d");
+        assert_eq!(unescape("a\r\nb\rc\x0Cd"), "a\nb\nc\nd");
+
+        let written: String = tokens.iter().map(|token| token.to_string()).collect();
+        assert_eq!(CssTokenizer::new(&written).collect::<Vec<_>>()[..4], tokens[..4]);
+    }
+
+    #[test]
+    fn test_decoded_string() {
+        let css = "\"\\201C quoted \\201D\" 'Fira\\ Sans' \"say \\\"hi\\\"\" '\\\n' 'a\\\r\nb' \"\\110000\\0\" plain";
+        let strings: Vec<_> = CssTokenizer::new(css).filter_map(|token| token.decoded_string()).collect();
+
+        assert_eq!(strings, ["\u{201C}quoted \u{201D}", "Fira Sans", "say \"hi\"", "", "ab", "\u{FFFD}\u{FFFD}"]);
+        assert!(matches!(CssToken::String("plain".into()).decoded_string(), Some(Cow::Borrowed("plain"))));
+        assert_eq!(CssToken::Ident("plain".into()).decoded_string(), None);
+        assert_eq!(unescape_string("end\\"), "end");
+        assert_eq!(unescape("end\\"), "end\u{FFFD}");
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert!(matches!(unescape("plain"), Cow::Borrowed("plain")));
+        assert_eq!(unescape(r"\0 x"), "\u{FFFD}x");
+        assert_eq!(unescape(r"\110000"), "\u{FFFD}");
+        assert_eq!(unescape(r"a\\b"), r"a\b");
+    }
+
+    #[test]
+    fn test_non_ascii_identifiers() {
+        let tokens: Vec<_> = CssTokenizer::new("日本語 #naïve @мedia 🎉x").collect();
+
+        assert_eq!(tokens[0], CssToken::Ident("日本語".into()));
+        assert_eq!(tokens[2], CssToken::Hash { value: "naïve".into(), is_id: true });
+        assert_eq!(tokens[4], CssToken::AtKeyword("мedia".into()));
+        assert_eq!(tokens[6], CssToken::Ident("🎉x".into()));
+        assert_eq!(tokens.len(), 7);
+    }
+
+    #[test]
+    fn test_unterminated_comment_error() {
+        let mut tokenizer = CssTokenizer::new("a { } /* never closed");
+        let tokens: Vec<_> = tokenizer.by_ref().collect();
+
+        assert_eq!(tokens.last(), Some(&CssToken::Comment(" never closed".into())));
+        assert_eq!(
+            tokenizer.take_errors(),
+            vec![TokenizeError { kind: TokenizeErrorKind::UnterminatedComment, offset: 6 }]
+        );
+        assert!(tokenizer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_string_and_url_errors() {
+        let mut tokenizer = CssTokenizer::new("a { b: url(x.png");
+        let tokens: Vec<_> = tokenizer.by_ref().collect();
+        assert_eq!(tokens.last(), Some(&CssToken::Url("x.png".into())));
+        assert_eq!(
+            tokenizer.take_errors(),
+            vec![TokenizeError { kind: TokenizeErrorKind::UnterminatedUrl, offset: 7 }]
+        );
+
+        let mut tokenizer = CssTokenizer::new("'ok' \"open");
+        tokenizer.by_ref().for_each(drop);
+        assert_eq!(
+            tokenizer.take_errors(),
+            vec![TokenizeError { kind: TokenizeErrorKind::UnterminatedString, offset: 5 }]
+        );
+
+        let mut tokenizer = CssTokenizer::new("/* a */ 'b' url(c) \"d\ne");
+        tokenizer.by_ref().for_each(drop);
+        assert!(tokenizer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_match_operators() {
+        let tokens: Vec<_> = CssTokenizer::new("~= |= ^= $= *= ~ ^ | $ * ~ =").collect();
+        let tokens: Vec<_> = tokens.into_iter().filter(|token| !matches!(token, CssToken::Whitespace(_))).collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                CssToken::IncludeMatch,
+                CssToken::DashMatch,
+                CssToken::PrefixMatch,
+                CssToken::SuffixMatch,
+                CssToken::SubstringMatch,
+                CssToken::Delim('~'),
+                CssToken::Delim('^'),
+                CssToken::Delim('|'),
+                CssToken::Delim('$'),
+                CssToken::Delim('*'),
+                CssToken::Delim('~'),
+                CssToken::Delim('='),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sibling_combinator_is_a_delim() {
+        let tokens: Vec<_> = CssTokenizer::new("a ~ b a~b [c~=d]").collect();
+
+        assert_eq!(tokens[2], CssToken::Delim('~'));
+        assert_eq!(tokens[7], CssToken::Delim('~'));
+        assert_eq!(tokens[12], CssToken::IncludeMatch);
+    }
+
+    /// Writes the tokens of `css` and returns the text with the tokens.
+    /// Whitespace text is left out of the tokens, as the newline written
+    /// after a `\` delim or a bad string joins the whitespace after it.
+    fn round_trip(css: &str) -> (String, Vec<CssToken<'_>>) {
+        let written: String = CssTokenizer::new(css).map(|token| token.to_string()).collect();
+        (written, blank_whitespace(CssTokenizer::new(css)))
+    }
+
+    fn blank_whitespace<'a>(tokens: impl Iterator<Item = CssToken<'a>>) -> Vec<CssToken<'a>> {
+        tokens
+            .map(|token| match token {
+                CssToken::Whitespace(_) => CssToken::Whitespace("".into()),
+                token => token,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_display_round_trips_large_css() {
+        let css = include_str!("../../tests/fixtures/large.css");
+        let (written, tokens) = round_trip(css);
+
+        assert_eq!(blank_whitespace(CssTokenizer::new(&written)), tokens);
+    }
+
+    #[test]
+    fn test_whitespace_keeps_its_text() {
+        let css = "/* header */\r\n\n.a ,\t.b {\n    color : red ;\n\tmargin:0  auto /* x */}\n\n\n@media print {\r\n  p { }\n}\n";
+
+        let written: String = CssTokenizer::new(css).map(|token| token.to_string()).collect();
+        assert_eq!(written, css.replace("\r\n", "\n"));
+
+        let whitespace: Vec<_> = CssTokenizer::new(css)
+            .filter_map(|token| match token {
+                CssToken::Whitespace(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(whitespace[..4], ["\n\n", " ", "\t", " "]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "css-ast", feature = "locations"))]
+    fn test_token_spans_reproduce_the_source() {
+        let sources = [
+            include_str!("../../tests/fixtures/large.css"),
+            "/* c */\r\n.a\\:b>c{x:1.50E+1PX /* d */ url( a.png ) 'q\\\r\n'}\u{0}\x0C\"bad\n@x;<!--",
+        ];
+        for css in sources {
+            let mut tokenizer = CssTokenizer::new(css);
+            let mut rebuilt = String::new();
+            while tokenizer.next_token().is_some() {
+                let span = tokenizer.token_span();
+                assert_eq!(span.start.byte_offset, rebuilt.len());
+                rebuilt.push_str(&css[span.byte_range()]);
+            }
+            assert_eq!(rebuilt, css);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips_every_variant() {
+        let css = r#"@media screen{a.b#c:hover>d[e~="f"][g|=h][i^='j'][k$=l][m*=n]{o:1 +2.5 1e3 -0 12px 1.5E2em 50%}}
+            --x: y; url(a.png) url( "a b.png" ) url('q"uote') "it's" 'say "hi"' "a\"b" 'a\'b' "line\
+            two" <!-- --> /* c */ \31 23 #\26 x .hover\:bg-red @\40x f\(x( x\ \ y rgb( 1 , 2 ) ~ | ^ $ * \
+            "bad
+            url(a b) url(a"b) [ ] ; :"#;
+        let (written, tokens) = round_trip(css);
+
+        assert_eq!(blank_whitespace(CssTokenizer::new(&written)), tokens);
+    }
+
+    #[test]
+    fn test_display_forms() {
+        let cases: &[(CssToken, &str)] = &[
+            (CssToken::String("it's".into()), r#""it's""#),
+            (CssToken::String(r#"say "hi""#.into()), r#"'say "hi"'"#),
+            (CssToken::Url("a.png".into()), "url(a.png)"),
+            (CssToken::Url("a b.png".into()), r#"url("a b.png")"#),
+            (CssToken::Number { value: 1000.0, is_integer: false }, "1000.0"),
+            (CssToken::Number { value: -0.5, is_integer: false }, "-0.5"),
+            (CssToken::Dimension { value: 12.0, unit: "px".into(), is_integer: true }, "12px"),
+            (CssToken::Percentage { value: 50.0, is_integer: true }, "50%"),
+            (CssToken::AtKeyword("media".into()), "@media"),
+            (CssToken::Hash { value: "1a".into(), is_id: false }, "#1a"),
+            (CssToken::Ident(r"hover\:bg".into()), r"hover\:bg"),
+            (CssToken::Function("rgb".into()), "rgb("),
+            (CssToken::CustomProperty("main-color".into()), "--main-color"),
+            (CssToken::Comment(" note ".into()), "/* note */"),
+        ];
+        for (token, expected) in cases {
+            assert_eq!(token.to_string(), *expected);
+            assert_eq!(CssTokenizer::new(expected).collect::<Vec<_>>(), vec![token.clone()], "{}", expected);
+        }
+    }
+
+    #[test]
+    fn test_display_escapes_hand_built_tokens() {
+        // These come back with escapes in their source form, so compare
+        // the decoded text.
+        let cases: &[(CssToken, &str, &str)] = &[
+            (CssToken::String(r#"both ' and ""#.into()), r#""both ' and \"""#, r#"both ' and ""#),
+            (CssToken::String("two\nlines".into()), r#""two\a lines""#, "two\nlines"),
+            (CssToken::Ident("1 a".into()), r"\31 \20 a", "1 a"),
+            (CssToken::Ident("-2x".into()), r"-\32 x", "-2x"),
+            (CssToken::Hash { value: "a:b".into(), is_id: true }, r"#a\:b", "a:b"),
+        ];
+        for (token, expected, text) in cases {
+            assert_eq!(token.to_string(), *expected);
+            let decoded = match CssTokenizer::new(expected).next().unwrap() {
+                CssToken::String(raw) => unescape(&raw).into_owned().into(),
+                reparsed => reparsed.unescaped().unwrap(),
+            };
+            assert_eq!(decoded, *text, "{}", expected);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        fn read_back(json: String) -> CssToken<'static> {
+            serde_json::from_str(&json).unwrap()
+        }
+
+        let css = ".a\\:b { width: 1.5em; content: \"\\\"\" } /* x */ @media url(a.png) #x --y";
+        for token in CssTokenizer::new(css) {
+            assert_eq!(read_back(serde_json::to_string(&token).unwrap()), token);
+        }
+    }
+}
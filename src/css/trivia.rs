@@ -0,0 +1,125 @@
+use crate::css::tokenizer::{CssToken, CssTokenizer};
+use std::borrow::Cow;
+
+/// Whitespace or a comment, as attached to a token by
+/// `CssTokenizer::tokens_with_trivia`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivia<'a> {
+    /// A run of whitespace, as in `CssToken::Whitespace`.
+    Whitespace(Cow<'a, str>),
+    /// The text between `/*` and `*/`.
+    Comment(Cow<'a, str>),
+}
+
+impl<'a> Trivia<'a> {
+    fn from_token(token: &CssToken<'a>) -> Option<Self> {
+        match token {
+            CssToken::Whitespace(text) => Some(Trivia::Whitespace(text.clone())),
+            CssToken::Comment(text) => Some(Trivia::Comment(text.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> CssTokenizer<'a> {
+    /// Returns an iterator over the tokens that carry meaning, leaving out
+    /// whitespace and comments.
+    ///
+    /// Whitespace can matter, as between `a b` in a selector, so parsers
+    /// that need to see it should use the tokenizer itself.
+    pub fn significant(self) -> SignificantTokens<'a> {
+        SignificantTokens { tokenizer: self }
+    }
+
+    /// Returns an iterator over the tokens that carry meaning, each with
+    /// the whitespace and comments just before it. The trivia after the
+    /// last token is kept in `TokensWithTrivia::trailing_trivia`.
+    pub fn tokens_with_trivia(self) -> TokensWithTrivia<'a> {
+        TokensWithTrivia { tokenizer: self, trailing: Vec::new() }
+    }
+}
+
+/// The iterator returned by `CssTokenizer::significant`.
+pub struct SignificantTokens<'a> {
+    tokenizer: CssTokenizer<'a>,
+}
+
+impl<'a> Iterator for SignificantTokens<'a> {
+    type Item = CssToken<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokenizer.by_ref().find(|token| !token.is_trivia())
+    }
+}
+
+/// The iterator returned by `CssTokenizer::tokens_with_trivia`.
+pub struct TokensWithTrivia<'a> {
+    tokenizer: CssTokenizer<'a>,
+    trailing: Vec<Trivia<'a>>,
+}
+
+impl<'a> TokensWithTrivia<'a> {
+    /// The whitespace and comments after the last token, once the iterator
+    /// has returned `None`.
+    pub fn trailing_trivia(&self) -> &[Trivia<'a>] {
+        &self.trailing
+    }
+}
+
+impl<'a> Iterator for TokensWithTrivia<'a> {
+    type Item = (CssToken<'a>, Vec<Trivia<'a>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut trivia = Vec::new();
+        for token in self.tokenizer.by_ref() {
+            match Trivia::from_token(&token) {
+                Some(item) => trivia.push(item),
+                None => return Some((token, trivia)),
+            }
+        }
+        self.trailing.extend(trivia);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL_CSS: &str = include_str!("../../tests/fixtures/small.css");
+
+    #[test]
+    fn test_significant_small_css() {
+        let tokens: Vec<_> = CssTokenizer::new(SMALL_CSS).significant().collect();
+
+        assert!(tokens.iter().all(|token| !token.is_trivia()));
+        // The selectors and braces of the three rules are 10 tokens; the
+        // seven declarations (name, `:`, value, `;`) are 29, as
+        // `margin: 0 auto` has two value tokens.
+        assert_eq!(tokens.len(), 10 + 29);
+        assert_eq!(
+            tokens[..4],
+            [CssToken::Delim('.'), CssToken::Ident("container".into()), CssToken::LeftBrace, CssToken::Ident("max-width".into())]
+        );
+        let all: Vec<_> = CssTokenizer::new(SMALL_CSS).filter(|token| !token.is_trivia()).collect();
+        assert_eq!(tokens, all);
+    }
+
+    #[test]
+    fn test_tokens_with_trivia() {
+        let mut tokens = CssTokenizer::new("/* a */ b /* c */{ d:e }\n/* end */ ").tokens_with_trivia();
+
+        assert_eq!(tokens.next(), Some((CssToken::Ident("b".into()), vec![Trivia::Comment(" a ".into()), Trivia::Whitespace(" ".into())])));
+        assert_eq!(tokens.next(), Some((CssToken::LeftBrace, vec![Trivia::Whitespace(" ".into()), Trivia::Comment(" c ".into())])));
+        assert_eq!(tokens.next(), Some((CssToken::Ident("d".into()), vec![Trivia::Whitespace(" ".into())])));
+        assert_eq!(tokens.next(), Some((CssToken::Colon, vec![])));
+        assert_eq!(tokens.next(), Some((CssToken::Ident("e".into()), vec![])));
+        assert_eq!(tokens.next(), Some((CssToken::RightBrace, vec![Trivia::Whitespace(" ".into())])));
+        assert_eq!(tokens.trailing_trivia(), []);
+        assert_eq!(tokens.next(), None);
+        assert_eq!(
+            tokens.trailing_trivia(),
+            [Trivia::Whitespace("\n".into()), Trivia::Comment(" end ".into()), Trivia::Whitespace(" ".into())]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use crate::location::Span;
+use std::fmt;
+
+/// What kind of problem a `ParseError` reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseErrorKind {
+    /// A token that does not fit where it appears, such as a stray end tag
+    /// or a declaration that does not start with a property name.
+    UnexpectedToken,
+    /// An element whose end tag never appears.
+    UnclosedTag,
+    /// A declaration whose property name is not followed by `:`.
+    MissingColon,
+    /// A rule whose selector could not be parsed.
+    InvalidSelector,
+    /// The input ended inside a construct, such as a rule's `{ ... }` block.
+    UnexpectedEof,
+    /// Reading the input failed; the message is the I/O error's.
+    Io,
+    /// The input is not valid UTF-8.
+    InvalidUtf8,
+}
+
+/// A recoverable problem found while parsing.
+///
+/// The parsers never stop at an error; `parse_with_errors` returns whatever
+/// could be parsed together with every error found on the way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The source range of the offending token or construct.
+    pub span: Span,
+    pub kind: ParseErrorKind,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(span: Span, kind: ParseErrorKind, message: impl Into<String>) -> Self {
+        Self { span, kind, message: message.into() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.start.line, self.span.start.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,489 @@
+//! Random HTML/CSS fixture generation for property tests.
+//!
+//! Generators build a model (`Vec<Node>` / `Vec<Rule>`) and render it to
+//! source text that the parsers are expected to read back into exactly that
+//! model. Generation is driven by a seeded `Rng`, so a failing case is
+//! reproducible from its seed, and models are plain trees so `check` can
+//! shrink a failure by dropping children and rules.
+
+use crate::css::declaration::{Declaration, DeclarationList};
+use crate::css::parser::{Rule, Selector};
+use crate::html::parser::{Element, Node};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+pub const TAGS: &[&str] = &["div", "p", "span", "ul", "li", "a", "section", "em"];
+pub const VOID_TAGS: &[&str] = &["br", "img", "hr"];
+pub const CLASSES: &[&str] = &["nav", "item", "active", "card", "title", "x1"];
+pub const IDS: &[&str] = &["main", "header", "footer"];
+pub const WORDS: &[&str] = &["hello", "world", "lorem", "ipsum", "42", "a-b"];
+pub const PROPERTIES: &[&str] = &["color", "margin", "display", "width", "font-size"];
+pub const VALUES: &[&str] = &["red", "0", "block", "10px", "50%", "auto", "#fff"];
+
+/// A small xorshift generator; deterministic for a given seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+/// Bounds for generated documents and stylesheets.
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    pub max_depth: usize,
+    pub max_children: usize,
+    pub max_rules: usize,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self { max_depth: 4, max_children: 4, max_rules: 6 }
+    }
+}
+
+pub fn gen_nodes(rng: &mut Rng, bounds: Bounds) -> Vec<Node> {
+    gen_children(rng, bounds, bounds.max_depth, &[])
+}
+
+/// Returns whether `tag` may be a child of the innermost of `ancestors`
+/// without an implied end tag closing it, so that the tree parses back
+/// unchanged.
+fn allowed_child(ancestors: &[&str], tag: &str) -> bool {
+    match ancestors.last() {
+        Some(&("p" | "span" | "a" | "em")) => matches!(tag, "span" | "a" | "em" | "br" | "img"),
+        // An `li` closes an enclosing `li` across a `div` too, but not
+        // across a `ul` or `section`
+        _ if tag == "li" => {
+            let mut item_scope = ancestors.iter().rev().take_while(|name| !matches!(**name, "ul" | "section"));
+            !item_scope.any(|name| *name == "li")
+        }
+        _ => true,
+    }
+}
+
+fn gen_children(rng: &mut Rng, bounds: Bounds, depth: usize, ancestors: &[&str]) -> Vec<Node> {
+    let allowed = |tags: &[&'static str]| -> Vec<&'static str> {
+        tags.iter().copied().filter(|tag| allowed_child(ancestors, tag)).collect()
+    };
+    let (tags, void_tags) = (allowed(TAGS), allowed(VOID_TAGS));
+    let mut children = Vec::new();
+    for _ in 0..rng.below(bounds.max_children + 1) {
+        // The parser merges adjacent text, so never emit two in a row.
+        let after_text = matches!(children.last(), Some(Node::Text(_)));
+        let node = match rng.below(10) {
+            0..=2 if !after_text => Node::Text(gen_text(rng).into()),
+            3 => Node::Comment(format!(" {} ", rng.pick(WORDS))),
+            4 => {
+                let tag = rng.pick(&void_tags);
+                Node::Element(gen_element(rng, tag, bounds, 0, ancestors))
+            }
+            _ => {
+                let tag = rng.pick(&tags);
+                Node::Element(gen_element(rng, tag, bounds, depth.saturating_sub(1), ancestors))
+            }
+        };
+        children.push(node);
+    }
+    children
+}
+
+fn gen_text(rng: &mut Rng) -> String {
+    let words: Vec<&str> = (0..=rng.below(3)).map(|_| rng.pick(WORDS)).collect();
+    words.join(" ")
+}
+
+fn gen_element(rng: &mut Rng, tag: &str, bounds: Bounds, depth: usize, ancestors: &[&str]) -> Element {
+    let mut attributes = HashMap::new();
+    if rng.chance(50) {
+        let mut classes: Vec<&str> = Vec::new();
+        for _ in 0..=rng.below(2) {
+            let class = rng.pick(CLASSES);
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        attributes.insert("class".to_string(), classes.join(" "));
+    }
+    if rng.chance(20) {
+        attributes.insert("id".to_string(), rng.pick(IDS).to_string());
+    }
+    if rng.chance(20) {
+        attributes.insert("title".to_string(), gen_text(rng));
+    }
+
+    let children = if VOID_TAGS.contains(&tag) || depth == 0 {
+        Vec::new()
+    } else {
+        gen_children(rng, bounds, depth, &[ancestors, &[tag]].concat())
+    };
+
+    Element { tag_name: tag.to_string(), attributes, children, ..Default::default() }
+}
+
+/// Renders generated nodes as HTML source.
+pub fn render_nodes(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        render_node(node, &mut out);
+    }
+    out
+}
+
+fn render_node(node: &Node, out: &mut String) {
+    match node {
+        Node::Element(element) => {
+            out.push('<');
+            out.push_str(&element.tag_name);
+            let mut names: Vec<&String> = element.attributes.keys().collect();
+            names.sort();
+            for name in names {
+                out.push_str(&format!(" {}=\"{}\"", name, element.attributes[name]));
+            }
+            out.push('>');
+            if !VOID_TAGS.contains(&element.tag_name.as_str()) {
+                for child in &element.children {
+                    render_node(child, out);
+                }
+                out.push_str(&format!("</{}>", element.tag_name));
+            }
+        }
+        Node::Text(text) => out.push_str(text),
+        Node::Comment(comment) => out.push_str(&format!("<!--{}-->", comment)),
+        Node::Doctype(doctype) => out.push_str(&format!("<!DOCTYPE {}>", doctype)),
+    }
+}
+
+pub fn gen_rules(rng: &mut Rng, bounds: Bounds) -> Vec<Rule> {
+    (0..rng.below(bounds.max_rules + 1)).map(|_| gen_rule(rng)).collect()
+}
+
+fn gen_rule(rng: &mut Rng) -> Rule {
+    let selectors = (0..=rng.below(2)).map(|_| gen_selector(rng)).collect();
+    let mut declarations = DeclarationList::new();
+    for _ in 0..=rng.below(3) {
+        declarations.push(Declaration::new(rng.pick(PROPERTIES), rng.pick(VALUES)));
+    }
+    Rule { selectors, declarations }
+}
+
+fn gen_simple_selector(rng: &mut Rng) -> Selector {
+    match rng.below(4) {
+        0 => Selector::Type(rng.pick(TAGS).to_string()),
+        1 => Selector::Id(rng.pick(IDS).to_string()),
+        2 if rng.chance(30) => Selector::Universal,
+        _ => Selector::Class(rng.pick(CLASSES).to_string()),
+    }
+}
+
+fn gen_selector(rng: &mut Rng) -> Selector {
+    let mut selector = gen_simple_selector(rng);
+    for _ in 0..rng.below(3) {
+        let left = Box::new(selector);
+        let right = Box::new(gen_simple_selector(rng));
+        selector = match rng.below(4) {
+            0 => Selector::Child(left, right),
+            1 => Selector::Adjacent(left, right),
+            2 => Selector::GeneralSibling(left, right),
+            _ => Selector::Descendant(left, right),
+        };
+    }
+    selector
+}
+
+/// Renders generated rules as CSS source.
+pub fn render_rules(rules: &[Rule]) -> String {
+    let mut out = String::new();
+    for rule in rules {
+        let selectors: Vec<String> = rule.selectors.iter().map(render_selector).collect();
+        out.push_str(&selectors.join(", "));
+        out.push_str(" {");
+        for declaration in &rule.declarations {
+            out.push_str(&format!(" {}: {};", declaration.property, declaration.value));
+        }
+        out.push_str(" }\n");
+    }
+    out
+}
+
+fn render_selector(selector: &Selector) -> String {
+    match selector {
+        Selector::Type(name) => name.clone(),
+        Selector::Class(name) => format!(".{}", name),
+        Selector::Id(name) => format!("#{}", name),
+        Selector::Universal => "*".to_string(),
+        Selector::Attribute { name, operator: Some(operator), value: Some(value), .. } => {
+            format!("[{}{}\"{}\"]", name, operator.as_str(), value)
+        }
+        Selector::Attribute { name, .. } => format!("[{}]", name),
+        Selector::PseudoClass { name, argument: None } => format!(":{}", name),
+        Selector::PseudoClass { name, argument: Some(argument) } => format!(":{}({})", name, argument),
+        Selector::NthChild(expr) => format!(":nth-child({})", expr),
+        Selector::NthOfType(expr) => format!(":nth-of-type({})", expr),
+        Selector::NthLastChild(expr) => format!(":nth-last-child({})", expr),
+        Selector::NthLastOfType(expr) => format!(":nth-last-of-type({})", expr),
+        Selector::Not(argument) => format!(":not({})", render_selector(argument)),
+        Selector::PseudoElement(name) => format!("::{}", name),
+        Selector::Compound(parts) => parts.iter().map(render_selector).collect(),
+        Selector::Descendant(l, r) => format!("{} {}", render_selector(l), render_selector(r)),
+        Selector::Child(l, r) => format!("{} > {}", render_selector(l), render_selector(r)),
+        Selector::Adjacent(l, r) => format!("{} + {}", render_selector(l), render_selector(r)),
+        Selector::GeneralSibling(l, r) => format!("{} ~ {}", render_selector(l), render_selector(r)),
+    }
+}
+
+/// Candidate simplifications of a node list: each child removed, and each
+/// element replaced by its children.
+pub fn shrink_nodes(nodes: &[Node]) -> Vec<Vec<Node>> {
+    let mut candidates = Vec::new();
+    for i in 0..nodes.len() {
+        let mut removed = nodes.to_vec();
+        removed.remove(i);
+        candidates.push(removed);
+
+        if let Node::Element(element) = &nodes[i] {
+            for smaller in shrink_nodes(&element.children) {
+                let mut replaced = nodes.to_vec();
+                let mut element = element.clone();
+                element.children = smaller;
+                replaced[i] = Node::Element(element);
+                candidates.push(replaced);
+            }
+        }
+    }
+    candidates
+}
+
+/// Candidate simplifications of a rule list: each rule removed.
+pub fn shrink_rules(rules: &[Rule]) -> Vec<Vec<Rule>> {
+    (0..rules.len())
+        .map(|i| {
+            let mut removed = rules.to_vec();
+            removed.remove(i);
+            removed
+        })
+        .collect()
+}
+
+/// Runs `property` on `iterations` generated cases. On failure, greedily
+/// shrinks the case with `shrink` and panics with the seed and the
+/// smallest failing input found.
+pub fn check<T, G, S, P>(iterations: u64, generate: G, shrink: S, property: P)
+where
+    T: Debug,
+    G: Fn(&mut Rng) -> T,
+    S: Fn(&T) -> Vec<T>,
+    P: Fn(&T) -> Result<(), String>,
+{
+    for seed in 0..iterations {
+        let case = generate(&mut Rng::new(seed));
+        let Err(mut message) = property(&case) else {
+            continue;
+        };
+
+        let mut smallest = case;
+        'shrinking: loop {
+            for candidate in shrink(&smallest) {
+                if let Err(candidate_message) = property(&candidate) {
+                    smallest = candidate;
+                    message = candidate_message;
+                    continue 'shrinking;
+                }
+            }
+            break;
+        }
+        panic!("property failed for seed {}: {}\nshrunk input: {:?}", seed, message, smallest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+    use crate::css::serializer::stylesheet_to_css;
+    use crate::html::parser::HtmlParser;
+    use crate::html::query::matches;
+    use crate::html::serializer::Serializer;
+    use crate::lint::find_near_miss_selectors;
+
+    const ITERATIONS: u64 = 300;
+
+    #[test]
+    fn prop_html_parses_to_generated_tree() {
+        check(
+            ITERATIONS,
+            |rng| gen_nodes(rng, Bounds::default()),
+            |nodes| shrink_nodes(nodes),
+            |nodes| {
+                let source = render_nodes(nodes);
+                let parsed = HtmlParser::new(&source).parse();
+                if parsed == *nodes {
+                    Ok(())
+                } else {
+                    Err(format!("{:?} parsed as {:?}", source, parsed))
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn prop_html_serialize_round_trip() {
+        check(
+            ITERATIONS,
+            |rng| HtmlParser::new(&render_nodes(&gen_nodes(rng, Bounds::default()))).parse(),
+            |nodes| shrink_nodes(nodes),
+            |nodes| {
+                let serialized = Serializer::default().serialize(nodes);
+                let reparsed = HtmlParser::new(&serialized).parse();
+                if reparsed == *nodes {
+                    Ok(())
+                } else {
+                    Err(format!("{:?} reparsed as {:?}", serialized, reparsed))
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn prop_css_parses_to_generated_rules() {
+        check(
+            ITERATIONS,
+            |rng| gen_rules(rng, Bounds::default()),
+            |rules| shrink_rules(rules),
+            |rules| {
+                let source = render_rules(rules);
+                let parsed = CssParser::new(&source).parse();
+                if parsed == *rules {
+                    Ok(())
+                } else {
+                    Err(format!("{:?} parsed as {:?}", source, parsed))
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn prop_css_serialize_round_trip() {
+        check(
+            ITERATIONS,
+            |rng| gen_rules(rng, Bounds::default()),
+            |rules| shrink_rules(rules),
+            |rules| {
+                let serialized = stylesheet_to_css(rules);
+                let reparsed = CssParser::new(&serialized).parse();
+                if reparsed == *rules {
+                    Ok(())
+                } else {
+                    Err(format!("{:?} reparsed as {:?}", serialized, reparsed))
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn prop_generated_class_selectors_have_no_near_misses() {
+        // Generated markup and selectors share one lowercase vocabulary and
+        // tidy class lists, so the lint must stay silent.
+        check(
+            ITERATIONS,
+            |rng| {
+                let bounds = Bounds::default();
+                (gen_nodes(rng, bounds), gen_rules(rng, bounds))
+            },
+            |(nodes, rules)| {
+                let mut candidates: Vec<_> = shrink_nodes(nodes)
+                    .into_iter()
+                    .map(|n| (n, rules.clone()))
+                    .collect();
+                candidates.extend(shrink_rules(rules).into_iter().map(|r| (nodes.clone(), r)));
+                candidates
+            },
+            |(nodes, rules)| {
+                let document = HtmlParser::new(&render_nodes(nodes)).parse_document();
+                let misses = find_near_miss_selectors(&document, rules);
+                if misses.is_empty() {
+                    Ok(())
+                } else {
+                    Err(format!("unexpected near misses {:?}", misses))
+                }
+            },
+        );
+    }
+
+    /// Every element under `element` that `selector` matches, found by
+    /// asking `matches` about each one with its ancestors.
+    fn brute_force_matches<'e>(
+        selector: &Selector,
+        element: &'e Element,
+        ancestors: &mut Vec<&'e Element>,
+        found: &mut Vec<&'e Element>,
+    ) {
+        ancestors.push(element);
+        for child in &element.children {
+            if let Node::Element(child) = child {
+                if matches(selector, child, ancestors) {
+                    found.push(child);
+                }
+                brute_force_matches(selector, child, ancestors, found);
+            }
+        }
+        ancestors.pop();
+    }
+
+    #[test]
+    fn prop_query_agrees_with_brute_force_matching() {
+        // query_selector_all walks the tree once, carrying each element's
+        // position among its siblings; matches() is handed one element and
+        // its ancestors and finds the siblings itself. The generated nodes
+        // sit in a root element so both see the same top-level siblings.
+        check(
+            ITERATIONS,
+            |rng| {
+                let bounds = Bounds::default();
+                (gen_nodes(rng, bounds), gen_rules(rng, bounds))
+            },
+            |(nodes, rules)| {
+                let mut candidates: Vec<_> = shrink_nodes(nodes)
+                    .into_iter()
+                    .map(|n| (n, rules.clone()))
+                    .collect();
+                candidates.extend(shrink_rules(rules).into_iter().map(|r| (nodes.clone(), r)));
+                candidates
+            },
+            |(nodes, rules)| {
+                let root = Element { tag_name: "body".to_string(), children: nodes.clone(), ..Default::default() };
+                for selector in rules.iter().flat_map(|rule| &rule.selectors) {
+                    let queried = root.query_selector_all(&selector.to_css());
+                    let mut found = Vec::new();
+                    brute_force_matches(selector, &root, &mut Vec::new(), &mut found);
+                    let same = queried.len() == found.len()
+                        && queried.iter().zip(&found).all(|(a, b)| std::ptr::eq(*a, *b));
+                    if !same {
+                        return Err(format!("{} queried {:?} but matches() found {:?}", selector, queried, found));
+                    }
+                }
+                Ok(())
+            },
+        );
+    }
+}
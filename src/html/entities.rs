@@ -0,0 +1,510 @@
+use std::borrow::Cow;
+
+/// Decodes the character references in HTML text: named ones (`&amp;`,
+/// `&nbsp;`, `&copy;`), decimal ones (`&#169;`) and hexadecimal ones
+/// (`&#xA9;`).
+///
+/// Follows the HTML rules for malformed input: the semicolon may be left
+/// off numeric references and the legacy named ones such as `&amp` and
+/// `&copy` (so `&copy2024` is `©2024`), references to U+0000, surrogates
+/// or beyond U+10FFFF become U+FFFD, and 0x80 to 0x9F map to their
+/// windows-1252 characters (`&#150;` is `–`). Anything else, such as an
+/// unknown name, is kept as written.
+///
+/// Only the more common named references are known (the HTML 4 set plus
+/// some HTML5 additions); `&` followed by other names stays as it is.
+pub fn decode_html_entities(input: &str) -> String {
+    decode(input, false).into_owned()
+}
+
+/// Like `decode_html_entities`, for an attribute value: a named reference
+/// without its semicolon is kept as written when a letter, digit or `=`
+/// follows, so URLs like `?a=1&copy=2` survive.
+#[cfg(feature = "html-dom")]
+pub(crate) fn decode_attribute_value(input: &str) -> Cow<'_, str> {
+    decode(input, true)
+}
+
+fn decode(input: &str, in_attribute: bool) -> Cow<'_, str> {
+    if !input.contains('&') {
+        return Cow::Borrowed(input);
+    }
+    let mut out = String::with_capacity(input.len());
+    decode_with(input, in_attribute, |piece| out.push_str(piece));
+    Cow::Owned(out)
+}
+
+/// Decodes `input` into `out` a piece at a time, so large text can go
+/// straight into a chunked `Text` without a decoded copy in between.
+#[cfg(feature = "html-dom")]
+pub(crate) fn decode_text_into(input: &str, out: &mut crate::html::Text) {
+    decode_with(input, false, |piece| out.push_str(piece));
+}
+
+fn decode_with(input: &str, in_attribute: bool, mut push: impl FnMut(&str)) {
+    let mut rest = input;
+    while let Some(amp) = rest.find('&') {
+        push(&rest[..amp]);
+        rest = &rest[amp..];
+        match decode_reference(&rest[1..], in_attribute) {
+            Some((decoded, length)) => {
+                push(&decoded);
+                rest = &rest[1 + length..];
+            }
+            None => {
+                push("&");
+                rest = &rest[1..];
+            }
+        }
+    }
+    push(rest);
+}
+
+/// Decodes the reference at the start of `text`, which follows an `&`.
+/// Returns the characters and how many bytes of `text` they replace.
+fn decode_reference(text: &str, in_attribute: bool) -> Option<(Cow<'static, str>, usize)> {
+    if let Some(number) = text.strip_prefix('#') {
+        let (digits, radix, prefix) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 2),
+            None => (number, 10, 1),
+        };
+        let count = digits.bytes().take_while(|b| (*b as char).is_digit(radix)).count();
+        if count == 0 {
+            return None;
+        }
+        let value = u32::from_str_radix(&digits[..count], radix).unwrap_or(u32::MAX);
+        let semicolon = digits[count..].starts_with(';') as usize;
+        let ch = numeric_character(value);
+        return Some((Cow::Owned(ch.to_string()), prefix + count + semicolon));
+    }
+
+    let name_length = text.bytes().take_while(u8::is_ascii_alphanumeric).count();
+    let name = &text[..name_length];
+    if text[name_length..].starts_with(';')
+        && let Ok(index) = NAMED_REFERENCES.binary_search_by_key(&name, |(name, _)| name)
+    {
+        return Some((Cow::Borrowed(NAMED_REFERENCES[index].1), name_length + 1));
+    }
+
+    // Without a semicolon only the legacy names count, longest first.
+    let legacy = (1..=name_length)
+        .rev()
+        .map(|length| &name[..length])
+        .find(|prefix| LEGACY_REFERENCES.binary_search(prefix).is_ok())?;
+    let next = text[legacy.len()..].bytes().next();
+    if in_attribute && next.is_some_and(|b| b.is_ascii_alphanumeric() || b == b'=') {
+        return None;
+    }
+    let index = NAMED_REFERENCES.binary_search_by_key(&legacy, |(name, _)| name).ok()?;
+    Some((Cow::Borrowed(NAMED_REFERENCES[index].1), legacy.len()))
+}
+
+/// The character a numeric reference to `value` stands for.
+fn numeric_character(value: u32) -> char {
+    if let Some(offset) = value.checked_sub(0x80).filter(|offset| *offset < 0x20) {
+        // 0x81, 0x8D, 0x8F, 0x90 and 0x9D are undefined and stay as they are
+        return match WINDOWS_1252[offset as usize] {
+            '\0' => char::from_u32(value).unwrap(),
+            ch => ch,
+        };
+    }
+    match value {
+        0 => '\u{FFFD}',
+        value => char::from_u32(value).unwrap_or('\u{FFFD}'),
+    }
+}
+
+/// What the code points 0x80 to 0x9F mean in windows-1252, which numeric
+/// references in that range are read as; `\0` where it has no character.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0',
+    '\0', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\0', 'ž', 'Ÿ',
+];
+
+/// Named references without their `&` and `;`, sorted by name.
+const NAMED_REFERENCES: &[(&str, &str)] = &[
+    ("AElig", "\u{c6}"),
+    ("AMP", "&"),
+    ("Aacute", "\u{c1}"),
+    ("Acirc", "\u{c2}"),
+    ("Agrave", "\u{c0}"),
+    ("Alpha", "\u{391}"),
+    ("Aring", "\u{c5}"),
+    ("Atilde", "\u{c3}"),
+    ("Auml", "\u{c4}"),
+    ("Beta", "\u{392}"),
+    ("COPY", "\u{a9}"),
+    ("Ccedil", "\u{c7}"),
+    ("Chi", "\u{3a7}"),
+    ("Dagger", "\u{2021}"),
+    ("Delta", "\u{394}"),
+    ("ETH", "\u{d0}"),
+    ("Eacute", "\u{c9}"),
+    ("Ecirc", "\u{ca}"),
+    ("Egrave", "\u{c8}"),
+    ("Epsilon", "\u{395}"),
+    ("Eta", "\u{397}"),
+    ("Euml", "\u{cb}"),
+    ("GT", ">"),
+    ("Gamma", "\u{393}"),
+    ("Iacute", "\u{cd}"),
+    ("Icirc", "\u{ce}"),
+    ("Igrave", "\u{cc}"),
+    ("Iota", "\u{399}"),
+    ("Iuml", "\u{cf}"),
+    ("Kappa", "\u{39a}"),
+    ("LT", "<"),
+    ("Lambda", "\u{39b}"),
+    ("Mu", "\u{39c}"),
+    ("NewLine", "\n"),
+    ("NotEqual", "\u{2260}"),
+    ("Ntilde", "\u{d1}"),
+    ("Nu", "\u{39d}"),
+    ("OElig", "\u{152}"),
+    ("Oacute", "\u{d3}"),
+    ("Ocirc", "\u{d4}"),
+    ("Ograve", "\u{d2}"),
+    ("Omega", "\u{3a9}"),
+    ("Omicron", "\u{39f}"),
+    ("Oslash", "\u{d8}"),
+    ("Otilde", "\u{d5}"),
+    ("Ouml", "\u{d6}"),
+    ("Phi", "\u{3a6}"),
+    ("Pi", "\u{3a0}"),
+    ("Prime", "\u{2033}"),
+    ("Psi", "\u{3a8}"),
+    ("QUOT", "\""),
+    ("REG", "\u{ae}"),
+    ("Rho", "\u{3a1}"),
+    ("Scaron", "\u{160}"),
+    ("Sigma", "\u{3a3}"),
+    ("THORN", "\u{de}"),
+    ("TRADE", "\u{2122}"),
+    ("Tab", "\t"),
+    ("Tau", "\u{3a4}"),
+    ("Theta", "\u{398}"),
+    ("ThinSpace", "\u{2009}"),
+    ("Uacute", "\u{da}"),
+    ("Ucirc", "\u{db}"),
+    ("Ugrave", "\u{d9}"),
+    ("Upsilon", "\u{3a5}"),
+    ("Uuml", "\u{dc}"),
+    ("Xi", "\u{39e}"),
+    ("Yacute", "\u{dd}"),
+    ("Yuml", "\u{178}"),
+    ("ZeroWidthSpace", "\u{200b}"),
+    ("Zeta", "\u{396}"),
+    ("aacute", "\u{e1}"),
+    ("acirc", "\u{e2}"),
+    ("acute", "\u{b4}"),
+    ("aelig", "\u{e6}"),
+    ("agrave", "\u{e0}"),
+    ("alefsym", "\u{2135}"),
+    ("alpha", "\u{3b1}"),
+    ("amp", "&"),
+    ("and", "\u{2227}"),
+    ("ang", "\u{2220}"),
+    ("angle", "\u{2220}"),
+    ("apos", "'"),
+    ("aring", "\u{e5}"),
+    ("ast", "*"),
+    ("asymp", "\u{2248}"),
+    ("atilde", "\u{e3}"),
+    ("auml", "\u{e4}"),
+    ("bdquo", "\u{201e}"),
+    ("beta", "\u{3b2}"),
+    ("bigstar", "\u{2605}"),
+    ("blank", "\u{2423}"),
+    ("block", "\u{2588}"),
+    ("brvbar", "\u{a6}"),
+    ("bsol", "\\"),
+    ("bull", "\u{2022}"),
+    ("cap", "\u{2229}"),
+    ("caret", "\u{2041}"),
+    ("ccedil", "\u{e7}"),
+    ("cedil", "\u{b8}"),
+    ("cent", "\u{a2}"),
+    ("check", "\u{2713}"),
+    ("chi", "\u{3c7}"),
+    ("circ", "\u{2c6}"),
+    ("clubs", "\u{2663}"),
+    ("colon", ":"),
+    ("comma", ","),
+    ("commat", "@"),
+    ("cong", "\u{2245}"),
+    ("copy", "\u{a9}"),
+    ("crarr", "\u{21b5}"),
+    ("cross", "\u{2717}"),
+    ("cup", "\u{222a}"),
+    ("curren", "\u{a4}"),
+    ("dArr", "\u{21d3}"),
+    ("dagger", "\u{2020}"),
+    ("darr", "\u{2193}"),
+    ("dash", "\u{2010}"),
+    ("deg", "\u{b0}"),
+    ("delta", "\u{3b4}"),
+    ("diams", "\u{2666}"),
+    ("divide", "\u{f7}"),
+    ("dollar", "$"),
+    ("eacute", "\u{e9}"),
+    ("ecirc", "\u{ea}"),
+    ("egrave", "\u{e8}"),
+    ("empty", "\u{2205}"),
+    ("emsp", "\u{2003}"),
+    ("ensp", "\u{2002}"),
+    ("epsilon", "\u{3b5}"),
+    ("equals", "="),
+    ("equiv", "\u{2261}"),
+    ("eta", "\u{3b7}"),
+    ("eth", "\u{f0}"),
+    ("euml", "\u{eb}"),
+    ("euro", "\u{20ac}"),
+    ("excl", "!"),
+    ("exist", "\u{2203}"),
+    ("female", "\u{2640}"),
+    ("flat", "\u{266d}"),
+    ("fnof", "\u{192}"),
+    ("forall", "\u{2200}"),
+    ("frac12", "\u{bd}"),
+    ("frac13", "\u{2153}"),
+    ("frac14", "\u{bc}"),
+    ("frac18", "\u{215b}"),
+    ("frac23", "\u{2154}"),
+    ("frac34", "\u{be}"),
+    ("frac38", "\u{215c}"),
+    ("frac58", "\u{215d}"),
+    ("frac78", "\u{215e}"),
+    ("frasl", "\u{2044}"),
+    ("gamma", "\u{3b3}"),
+    ("ge", "\u{2265}"),
+    ("grave", "`"),
+    ("gt", ">"),
+    ("hArr", "\u{21d4}"),
+    ("hairsp", "\u{200a}"),
+    ("half", "\u{bd}"),
+    ("harr", "\u{2194}"),
+    ("hearts", "\u{2665}"),
+    ("hellip", "\u{2026}"),
+    ("hyphen", "\u{2010}"),
+    ("iacute", "\u{ed}"),
+    ("icirc", "\u{ee}"),
+    ("iexcl", "\u{a1}"),
+    ("igrave", "\u{ec}"),
+    ("image", "\u{2111}"),
+    ("incare", "\u{2105}"),
+    ("infin", "\u{221e}"),
+    ("int", "\u{222b}"),
+    ("iota", "\u{3b9}"),
+    ("iquest", "\u{bf}"),
+    ("isin", "\u{2208}"),
+    ("iuml", "\u{ef}"),
+    ("kappa", "\u{3ba}"),
+    ("lArr", "\u{21d0}"),
+    ("lambda", "\u{3bb}"),
+    ("lang", "\u{27e8}"),
+    ("laquo", "\u{ab}"),
+    ("larr", "\u{2190}"),
+    ("lceil", "\u{2308}"),
+    ("lcub", "{"),
+    ("ldquo", "\u{201c}"),
+    ("le", "\u{2264}"),
+    ("lfloor", "\u{230a}"),
+    ("lowast", "\u{2217}"),
+    ("lowbar", "_"),
+    ("loz", "\u{25ca}"),
+    ("lpar", "("),
+    ("lrm", "\u{200e}"),
+    ("lsaquo", "\u{2039}"),
+    ("lsqb", "["),
+    ("lsquo", "\u{2018}"),
+    ("lt", "<"),
+    ("macr", "\u{af}"),
+    ("male", "\u{2642}"),
+    ("mdash", "\u{2014}"),
+    ("micro", "\u{b5}"),
+    ("middot", "\u{b7}"),
+    ("minus", "\u{2212}"),
+    ("mu", "\u{3bc}"),
+    ("nabla", "\u{2207}"),
+    ("natural", "\u{266e}"),
+    ("nbsp", "\u{a0}"),
+    ("ndash", "\u{2013}"),
+    ("ne", "\u{2260}"),
+    ("nexist", "\u{2204}"),
+    ("ni", "\u{220b}"),
+    ("not", "\u{ac}"),
+    ("notin", "\u{2209}"),
+    ("nsub", "\u{2284}"),
+    ("ntilde", "\u{f1}"),
+    ("nu", "\u{3bd}"),
+    ("num", "#"),
+    ("oacute", "\u{f3}"),
+    ("ocirc", "\u{f4}"),
+    ("oelig", "\u{153}"),
+    ("ograve", "\u{f2}"),
+    ("oline", "\u{203e}"),
+    ("omega", "\u{3c9}"),
+    ("omicron", "\u{3bf}"),
+    ("oplus", "\u{2295}"),
+    ("or", "\u{2228}"),
+    ("ordf", "\u{aa}"),
+    ("ordm", "\u{ba}"),
+    ("oslash", "\u{f8}"),
+    ("otilde", "\u{f5}"),
+    ("otimes", "\u{2297}"),
+    ("ouml", "\u{f6}"),
+    ("para", "\u{b6}"),
+    ("part", "\u{2202}"),
+    ("percnt", "%"),
+    ("period", "."),
+    ("permil", "\u{2030}"),
+    ("perp", "\u{22a5}"),
+    ("phi", "\u{3c6}"),
+    ("phone", "\u{260e}"),
+    ("pi", "\u{3c0}"),
+    ("piv", "\u{3d6}"),
+    ("plus", "+"),
+    ("plusmn", "\u{b1}"),
+    ("pound", "\u{a3}"),
+    ("prime", "\u{2032}"),
+    ("prod", "\u{220f}"),
+    ("prop", "\u{221d}"),
+    ("psi", "\u{3c8}"),
+    ("quest", "?"),
+    ("quot", "\""),
+    ("rArr", "\u{21d2}"),
+    ("radic", "\u{221a}"),
+    ("rang", "\u{27e9}"),
+    ("raquo", "\u{bb}"),
+    ("rarr", "\u{2192}"),
+    ("rceil", "\u{2309}"),
+    ("rcub", "}"),
+    ("rdquo", "\u{201d}"),
+    ("real", "\u{211c}"),
+    ("reg", "\u{ae}"),
+    ("rfloor", "\u{230b}"),
+    ("rho", "\u{3c1}"),
+    ("rlm", "\u{200f}"),
+    ("rpar", ")"),
+    ("rsaquo", "\u{203a}"),
+    ("rsqb", "]"),
+    ("rsquo", "\u{2019}"),
+    ("sbquo", "\u{201a}"),
+    ("scaron", "\u{161}"),
+    ("sdot", "\u{22c5}"),
+    ("sect", "\u{a7}"),
+    ("semi", ";"),
+    ("sharp", "\u{266f}"),
+    ("shy", "\u{ad}"),
+    ("sigma", "\u{3c3}"),
+    ("sigmaf", "\u{3c2}"),
+    ("sim", "\u{223c}"),
+    ("sol", "/"),
+    ("spades", "\u{2660}"),
+    ("squ", "\u{25a1}"),
+    ("square", "\u{25a1}"),
+    ("star", "\u{2606}"),
+    ("starf", "\u{2605}"),
+    ("sub", "\u{2282}"),
+    ("sube", "\u{2286}"),
+    ("sum", "\u{2211}"),
+    ("sung", "\u{266a}"),
+    ("sup", "\u{2283}"),
+    ("sup1", "\u{b9}"),
+    ("sup2", "\u{b2}"),
+    ("sup3", "\u{b3}"),
+    ("supe", "\u{2287}"),
+    ("szlig", "\u{df}"),
+    ("tau", "\u{3c4}"),
+    ("there4", "\u{2234}"),
+    ("theta", "\u{3b8}"),
+    ("thetasym", "\u{3d1}"),
+    ("thinsp", "\u{2009}"),
+    ("thorn", "\u{fe}"),
+    ("tilde", "\u{2dc}"),
+    ("times", "\u{d7}"),
+    ("trade", "\u{2122}"),
+    ("uArr", "\u{21d1}"),
+    ("uacute", "\u{fa}"),
+    ("uarr", "\u{2191}"),
+    ("ucirc", "\u{fb}"),
+    ("udarr", "\u{21c5}"),
+    ("ugrave", "\u{f9}"),
+    ("uml", "\u{a8}"),
+    ("upsih", "\u{3d2}"),
+    ("upsilon", "\u{3c5}"),
+    ("uuml", "\u{fc}"),
+    ("verbar", "|"),
+    ("vert", "|"),
+    ("weierp", "\u{2118}"),
+    ("xi", "\u{3be}"),
+    ("yacute", "\u{fd}"),
+    ("yen", "\u{a5}"),
+    ("yuml", "\u{ff}"),
+    ("zeta", "\u{3b6}"),
+    ("zwj", "\u{200d}"),
+    ("zwnj", "\u{200c}"),
+];
+
+/// Names that are decoded without a semicolon too, sorted. All of them are
+/// in `NAMED_REFERENCES`.
+const LEGACY_REFERENCES: &[&str] = &[
+    "AElig", "AMP", "Aacute", "Acirc", "Agrave", "Aring", "Atilde", "Auml", "COPY", "Ccedil", "ETH",
+    "Eacute", "Ecirc", "Egrave", "Euml", "GT", "Iacute", "Icirc", "Igrave", "Iuml", "LT", "Ntilde",
+    "Oacute", "Ocirc", "Ograve", "Oslash", "Otilde", "Ouml", "QUOT", "REG", "THORN", "Uacute",
+    "Ucirc", "Ugrave", "Uuml", "Yacute", "aacute", "acirc", "acute", "aelig", "agrave", "amp",
+    "aring", "atilde", "auml", "brvbar", "ccedil", "cedil", "cent", "copy", "curren", "deg",
+    "divide", "eacute", "ecirc", "egrave", "eth", "euml", "frac12", "frac14", "frac34", "gt",
+    "iacute", "icirc", "iexcl", "igrave", "iquest", "iuml", "laquo", "lt", "macr", "micro",
+    "middot", "nbsp", "not", "ntilde", "oacute", "ocirc", "ograve", "ordf", "ordm", "oslash",
+    "otilde", "ouml", "para", "plusmn", "pound", "quot", "raquo", "reg", "sect", "shy", "sup1",
+    "sup2", "sup3", "szlig", "thorn", "times", "uacute", "ucirc", "ugrave", "uml", "uuml", "yacute",
+    "yen", "yuml",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_references() {
+        assert_eq!(decode_html_entities("fish &amp; chips"), "fish & chips");
+        assert_eq!(decode_html_entities("&lt;&gt;"), "<>");
+        assert_eq!(decode_html_entities("a&nbsp;b"), "a\u{a0}b");
+        assert_eq!(decode_html_entities("&quot;&apos;&hellip;&mdash;&euro;&AMP;"), "\"'…—€&");
+        assert_eq!(decode_html_entities("&alpha;&Omega;&rarr;&hearts;"), "αΩ→♥");
+    }
+
+    #[test]
+    fn test_numeric_references() {
+        assert_eq!(decode_html_entities("&#169;"), "©");
+        assert_eq!(decode_html_entities("&#x00A9;"), "©");
+        assert_eq!(decode_html_entities("&#XA9;&#60;&#x3C;"), "©<<");
+        assert_eq!(decode_html_entities("&#128512;"), "😀");
+        assert_eq!(decode_html_entities("&#169 and &#x41"), "© and A");
+        assert_eq!(decode_html_entities("&#0;&#xD800;&#x110000;&#99999999999;"), "\u{FFFD}".repeat(4));
+        assert_eq!(decode_html_entities("&#150;&#x80;&#x81;"), "–€\u{81}");
+    }
+
+    #[test]
+    fn test_malformed_references_are_kept() {
+        assert_eq!(decode_html_entities("AT&T & co"), "AT&T & co");
+        assert_eq!(decode_html_entities("&unknown; &; &# &#x; &"), "&unknown; &; &# &#x; &");
+        assert_eq!(decode_html_entities("no references"), "no references");
+    }
+
+    #[test]
+    fn test_legacy_references_without_semicolon() {
+        assert_eq!(decode_html_entities("&copy 2024 &amp &lt"), "© 2024 & <");
+        assert_eq!(decode_html_entities("&copy2024 &notit;"), "©2024 ¬it;");
+        assert_eq!(decode_html_entities("&notin; &hellip"), "∉ &hellip");
+    }
+
+    #[test]
+    #[cfg(feature = "html-dom")]
+    fn test_attribute_values() {
+        assert_eq!(decode_attribute_value("?a=1&copy=2&lang=en"), "?a=1&copy=2&lang=en");
+        assert_eq!(decode_attribute_value("&copy 2024 &amp;"), "© 2024 &");
+        assert!(matches!(decode_attribute_value("plain"), Cow::Borrowed("plain")));
+    }
+}
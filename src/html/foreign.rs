@@ -0,0 +1,159 @@
+/// The namespace an element is parsed in. The parser lower-cases tag and
+/// attribute names; inside `<svg>` and `<math>` the camelCase names of SVG
+/// and MathML are then restored from the HTML spec's adjustment tables, so
+/// `<SVG VIEWBOX="0 0 1 1">` gives a `viewBox` attribute as in a browser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+impl Namespace {
+    /// The namespace of an element named `name` (lower-cased) whose parent
+    /// is `parent`, as written, with its namespace, or the top level if
+    /// `None`.
+    pub(crate) fn of_child(parent: Option<(&str, Namespace)>, name: &str) -> Namespace {
+        let is_one_of = |parent: &str, names: &[&str]| names.iter().any(|n| parent.eq_ignore_ascii_case(n));
+        match (name, parent) {
+            ("svg", _) => Namespace::Svg,
+            ("math", _) => Namespace::MathMl,
+            // Integration points, whose children are HTML again
+            (_, Some((parent, Namespace::Svg))) if is_one_of(parent, &["foreignobject", "desc", "title"]) => {
+                Namespace::Html
+            }
+            (_, Some((parent, Namespace::MathMl)))
+                if is_one_of(parent, &["mi", "mo", "mn", "ms", "mtext"]) && !matches!(name, "mglyph" | "malignmark") =>
+            {
+                Namespace::Html
+            }
+            (_, Some((_, namespace))) => namespace,
+            (_, None) => Namespace::Html,
+        }
+    }
+
+    /// Adjusts a lower-cased tag name for this namespace.
+    pub(crate) fn tag_name(self, name: String) -> String {
+        match self {
+            Namespace::Svg => svg_tag_name(&name).map_or(name, str::to_string),
+            Namespace::Html | Namespace::MathMl => name,
+        }
+    }
+
+    /// Adjusts a lower-cased attribute name for this namespace.
+    pub(crate) fn attribute_name(self, name: String) -> String {
+        let adjusted = match self {
+            Namespace::Svg => svg_attribute_name(&name),
+            Namespace::MathMl if name == "definitionurl" => Some("definitionURL"),
+            Namespace::Html | Namespace::MathMl => None,
+        };
+        adjusted.map_or(name, str::to_string)
+    }
+}
+
+fn svg_tag_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "altglyph" => "altGlyph",
+        "altglyphdef" => "altGlyphDef",
+        "altglyphitem" => "altGlyphItem",
+        "animatecolor" => "animateColor",
+        "animatemotion" => "animateMotion",
+        "animatetransform" => "animateTransform",
+        "clippath" => "clipPath",
+        "feblend" => "feBlend",
+        "fecolormatrix" => "feColorMatrix",
+        "fecomponenttransfer" => "feComponentTransfer",
+        "fecomposite" => "feComposite",
+        "feconvolvematrix" => "feConvolveMatrix",
+        "fediffuselighting" => "feDiffuseLighting",
+        "fedisplacementmap" => "feDisplacementMap",
+        "fedistantlight" => "feDistantLight",
+        "fedropshadow" => "feDropShadow",
+        "feflood" => "feFlood",
+        "fefunca" => "feFuncA",
+        "fefuncb" => "feFuncB",
+        "fefuncg" => "feFuncG",
+        "fefuncr" => "feFuncR",
+        "fegaussianblur" => "feGaussianBlur",
+        "feimage" => "feImage",
+        "femerge" => "feMerge",
+        "femergenode" => "feMergeNode",
+        "femorphology" => "feMorphology",
+        "feoffset" => "feOffset",
+        "fepointlight" => "fePointLight",
+        "fespecularlighting" => "feSpecularLighting",
+        "fespotlight" => "feSpotLight",
+        "fetile" => "feTile",
+        "feturbulence" => "feTurbulence",
+        "foreignobject" => "foreignObject",
+        "glyphref" => "glyphRef",
+        "lineargradient" => "linearGradient",
+        "radialgradient" => "radialGradient",
+        "textpath" => "textPath",
+        _ => return None,
+    })
+}
+
+fn svg_attribute_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "attributename" => "attributeName",
+        "attributetype" => "attributeType",
+        "basefrequency" => "baseFrequency",
+        "baseprofile" => "baseProfile",
+        "calcmode" => "calcMode",
+        "clippathunits" => "clipPathUnits",
+        "diffuseconstant" => "diffuseConstant",
+        "edgemode" => "edgeMode",
+        "filterunits" => "filterUnits",
+        "glyphref" => "glyphRef",
+        "gradienttransform" => "gradientTransform",
+        "gradientunits" => "gradientUnits",
+        "kernelmatrix" => "kernelMatrix",
+        "kernelunitlength" => "kernelUnitLength",
+        "keypoints" => "keyPoints",
+        "keysplines" => "keySplines",
+        "keytimes" => "keyTimes",
+        "lengthadjust" => "lengthAdjust",
+        "limitingconeangle" => "limitingConeAngle",
+        "markerheight" => "markerHeight",
+        "markerunits" => "markerUnits",
+        "markerwidth" => "markerWidth",
+        "maskcontentunits" => "maskContentUnits",
+        "maskunits" => "maskUnits",
+        "numoctaves" => "numOctaves",
+        "pathlength" => "pathLength",
+        "patterncontentunits" => "patternContentUnits",
+        "patterntransform" => "patternTransform",
+        "patternunits" => "patternUnits",
+        "pointsatx" => "pointsAtX",
+        "pointsaty" => "pointsAtY",
+        "pointsatz" => "pointsAtZ",
+        "preservealpha" => "preserveAlpha",
+        "preserveaspectratio" => "preserveAspectRatio",
+        "primitiveunits" => "primitiveUnits",
+        "refx" => "refX",
+        "refy" => "refY",
+        "repeatcount" => "repeatCount",
+        "repeatdur" => "repeatDur",
+        "requiredextensions" => "requiredExtensions",
+        "requiredfeatures" => "requiredFeatures",
+        "specularconstant" => "specularConstant",
+        "specularexponent" => "specularExponent",
+        "spreadmethod" => "spreadMethod",
+        "startoffset" => "startOffset",
+        "stddeviation" => "stdDeviation",
+        "stitchtiles" => "stitchTiles",
+        "surfacescale" => "surfaceScale",
+        "systemlanguage" => "systemLanguage",
+        "tablevalues" => "tableValues",
+        "targetx" => "targetX",
+        "targety" => "targetY",
+        "textlength" => "textLength",
+        "viewbox" => "viewBox",
+        "viewtarget" => "viewTarget",
+        "xchannelselector" => "xChannelSelector",
+        "ychannelselector" => "yChannelSelector",
+        "zoomandpan" => "zoomAndPan",
+        _ => return None,
+    })
+}
@@ -0,0 +1,219 @@
+use crate::html::parser::{Document, Node};
+use std::collections::HashSet;
+
+/// How `ensure_heading_ids` turns heading text into an id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlugStyle {
+    /// Letters and digits of any script are kept, lower-cased:
+    /// `Über uns` becomes `über-uns`, as on GitHub.
+    #[default]
+    Unicode,
+    /// Only ASCII letters and digits are kept. Accented Latin letters lose
+    /// their accents (`Über uns` becomes `uber-uns`) and other characters
+    /// are dropped.
+    Ascii,
+}
+
+/// Gives every `<h1>` to `<h6>` in `document` an `id`, for deep links, and
+/// returns each heading's text and id in document order, for a table of
+/// contents.
+///
+/// Headings that already have a non-empty `id` keep it. For the others the
+/// id is a slug of the text: lower-cased, with runs of whitespace turned
+/// into `-` and punctuation dropped. A slug already taken by any element
+/// of the document, or by an earlier heading, gets `-1`, `-2` and so on
+/// appended. Text with nothing left to slug gives `section`.
+pub fn ensure_heading_ids(document: &mut Document, style: SlugStyle) -> Vec<(String, String)> {
+    let mut taken = HashSet::new();
+    collect_ids(&document.children, &mut taken);
+
+    let mut headings = Vec::new();
+    assign_ids(&mut document.children, style, &mut taken, &mut headings);
+    headings
+}
+
+fn collect_ids(nodes: &[Node], taken: &mut HashSet<String>) {
+    for node in nodes {
+        if let Node::Element(element) = node {
+            if let Some(id) = element.attr("id") {
+                taken.insert(id.to_string());
+            }
+            collect_ids(&element.children, taken);
+        }
+    }
+}
+
+fn assign_ids(
+    nodes: &mut [Node],
+    style: SlugStyle,
+    taken: &mut HashSet<String>,
+    headings: &mut Vec<(String, String)>,
+) {
+    for node in nodes {
+        let Node::Element(element) = node else {
+            continue;
+        };
+        if !is_heading(&element.tag_name) {
+            assign_ids(&mut element.children, style, taken, headings);
+            continue;
+        }
+
+        let text = element.text_content().split_whitespace().collect::<Vec<_>>().join(" ");
+        let id = match element.attr("id") {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => {
+                let id = unique_id(&slugify(&text, style), taken);
+                element.attributes.insert("id".to_string(), id.clone());
+                id
+            }
+        };
+        headings.push((text, id));
+    }
+}
+
+fn is_heading(tag_name: &str) -> bool {
+    matches!(tag_name.to_ascii_lowercase().as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+/// Returns `slug`, or `slug-1`, `slug-2`... if taken, and marks it taken.
+fn unique_id(slug: &str, taken: &mut HashSet<String>) -> String {
+    let mut id = slug.to_string();
+    let mut suffix = 0;
+    while taken.contains(&id) {
+        suffix += 1;
+        id = format!("{}-{}", slug, suffix);
+    }
+    taken.insert(id.clone());
+    id
+}
+
+fn slugify(text: &str, style: SlugStyle) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut dash = false;
+    for ch in text.chars() {
+        if ch.is_whitespace() || ch == '-' || ch == '_' {
+            dash = !slug.is_empty();
+            continue;
+        }
+        let kept = match style {
+            SlugStyle::Unicode if ch.is_alphanumeric() => ch.to_lowercase().collect(),
+            SlugStyle::Ascii if ch.is_ascii_alphanumeric() => ch.to_ascii_lowercase().to_string(),
+            SlugStyle::Ascii => fold_latin(ch).unwrap_or("").to_string(),
+            SlugStyle::Unicode => String::new(),
+        };
+        if kept.is_empty() {
+            continue;
+        }
+        if dash {
+            slug.push('-');
+            dash = false;
+        }
+        slug.push_str(&kept);
+    }
+
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+    slug
+}
+
+/// The lower-case ASCII spelling of an accented Latin letter.
+fn fold_latin(ch: char) -> Option<&'static str> {
+    let folded = match ch.to_lowercase().next()? {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(folded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(text, id)| (text.to_string(), id.to_string())).collect()
+    }
+
+    #[test]
+    fn test_duplicate_titles() {
+        let mut document =
+            HtmlParser::new("<main><h1>Setup</h1><section><h2>Setup</h2><h3>Setup!</h3></section></main>").parse_document();
+
+        let headings = ensure_heading_ids(&mut document, SlugStyle::Unicode);
+
+        assert_eq!(headings, pairs(&[("Setup", "setup"), ("Setup", "setup-1"), ("Setup!", "setup-2")]));
+        let Some(Node::Element(main)) = document.children.first() else {
+            panic!("Expected element node");
+        };
+        let Node::Element(section) = &main.children[1] else {
+            panic!("Expected element node");
+        };
+        let Node::Element(h3) = &section.children[1] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(h3.attr("id"), Some("setup-2"));
+    }
+
+    #[test]
+    fn test_existing_ids_are_kept_and_reserved() {
+        let mut document = HtmlParser::new(
+            r#"<div id="faq"></div><h2>  Getting   <em>started</em>, quickly!</h2><h2 id="faq">Questions</h2><h2>FAQ</h2><h2 id="">Empty</h2>"#,
+        )
+        .parse_document();
+
+        let headings = ensure_heading_ids(&mut document, SlugStyle::Unicode);
+
+        assert_eq!(
+            headings,
+            pairs(&[
+                ("Getting started, quickly!", "getting-started-quickly"),
+                ("Questions", "faq"),
+                ("FAQ", "faq-1"),
+                ("Empty", "empty"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_text() {
+        let html = "<h1>Über uns — Café Crème</h1><h2>日本語の見出し</h2><h2>C++ &amp; Rust_2024</h2><h3>!?</h3>";
+
+        let mut document = HtmlParser::new(html).parse_document();
+        assert_eq!(
+            ensure_heading_ids(&mut document, SlugStyle::Unicode),
+            pairs(&[
+                ("Über uns — Café Crème", "über-uns-café-crème"),
+                ("日本語の見出し", "日本語の見出し"),
+                ("C++ & Rust_2024", "c-rust-2024"),
+                ("!?", "section"),
+            ])
+        );
+
+        let mut document = HtmlParser::new(html).parse_document();
+        let ids: Vec<_> = ensure_heading_ids(&mut document, SlugStyle::Ascii).into_iter().map(|(_, id)| id).collect();
+        assert_eq!(ids, vec!["uber-uns-cafe-creme", "section", "c-rust-2024", "section-1"]);
+    }
+}
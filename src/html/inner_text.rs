@@ -0,0 +1,317 @@
+use crate::css::parser::Rule;
+use crate::css::stylesheet::RuleSource;
+use crate::html::parser::{Element, Node, BLOCK_ELEMENTS};
+use crate::html::style::cascade;
+use crate::output::{write_limited, OutputError};
+use std::fmt;
+
+impl Element {
+    /// Approximates the DOM's `innerText`: the text of the descendants as
+    /// it would render, given the rules of `stylesheet` if any.
+    ///
+    /// - Elements with `display: none` are left out, from their `style`
+    ///   attribute, from `stylesheet`, or by default (`<head>`, `<script>`,
+    ///   `<style>`, `<template>`, elements with a `hidden` attribute).
+    /// - Block elements (`div`, `li`, `h1` and so on, or any `display` other
+    ///   than `none` and the `inline` kinds) start and end on their own
+    ///   line; `<p>` leaves a blank line before and after.
+    /// - `<br>` is a line break.
+    /// - Runs of ASCII whitespace collapse to one space, and whitespace at
+    ///   the start and end of each line is dropped. Non-breaking spaces
+    ///   (U+00A0) and other non-ASCII spaces are kept.
+    ///
+    /// Only `display` is computed. `white-space` is always `normal`, so
+    /// `<pre>` collapses too, and table cells are not separated by tabs.
+    /// For `!important`, inline styles and specificity the usual cascade
+    /// order applies, with later rules winning ties. This element counts
+    /// as the outermost ancestor when matching selectors, and its own
+    /// `display` is ignored; see `outer_text`.
+    ///
+    /// `stylesheet` is a `[Rule]` list or an `OwnedStylesheet`. It is taken
+    /// as `&dyn RuleSource` rather than through a type parameter so that
+    /// `None` needs no annotation.
+    pub fn inner_text(&self, stylesheet: Option<&dyn RuleSource>) -> String {
+        let mut out = String::new();
+        let _ = write_inner_text(self, rules(stylesheet), &mut out);
+        out
+    }
+
+    /// Writes `inner_text` into `out`, failing with
+    /// `OutputError::LimitExceeded` instead of writing more than `limit`
+    /// bytes. The text of each text node, or of each chunk of a large one,
+    /// with the line breaks before it, is written whole or not at all.
+    pub fn write_inner_text<W: fmt::Write + ?Sized>(
+        &self,
+        stylesheet: Option<&dyn RuleSource>,
+        out: &mut W,
+        limit: Option<usize>,
+    ) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_inner_text(self, rules(stylesheet), w))
+    }
+
+    /// Like `inner_text`, but also applies this element's own `display`:
+    /// the result is empty if the element itself is hidden.
+    pub fn outer_text(&self, stylesheet: Option<&dyn RuleSource>) -> String {
+        let mut out = String::new();
+        let _ = write_outer_text(self, rules(stylesheet), &mut out);
+        out
+    }
+
+    /// Writer-based form of `outer_text`; see `write_inner_text`.
+    pub fn write_outer_text<W: fmt::Write + ?Sized>(
+        &self,
+        stylesheet: Option<&dyn RuleSource>,
+        out: &mut W,
+        limit: Option<usize>,
+    ) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_outer_text(self, rules(stylesheet), w))
+    }
+}
+
+fn rules(stylesheet: Option<&dyn RuleSource>) -> &[Rule] {
+    stylesheet.map(RuleSource::rules).unwrap_or(&[])
+}
+
+fn write_inner_text<W: fmt::Write + ?Sized>(element: &Element, stylesheet: &[Rule], out: &mut W) -> fmt::Result {
+    let mut text = TextBuilder::new(out);
+    let mut ancestors = vec![element];
+    write_children(&element.children, stylesheet, &mut ancestors, &mut text)
+}
+
+fn write_outer_text<W: fmt::Write + ?Sized>(element: &Element, stylesheet: &[Rule], out: &mut W) -> fmt::Result {
+    match display(element, &[], stylesheet) {
+        Display::None => Ok(()),
+        _ => write_inner_text(element, stylesheet, out),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Display {
+    None,
+    Inline,
+    Block,
+}
+
+/// Elements with `display: none` in the default stylesheet.
+const HIDDEN_ELEMENTS: &[&str] = &["base", "head", "link", "meta", "noscript", "script", "style", "template", "title"];
+
+fn write_children<'n, W: fmt::Write + ?Sized>(
+    nodes: &'n [Node],
+    stylesheet: &[Rule],
+    ancestors: &mut Vec<&'n Element>,
+    text: &mut TextBuilder<'_, W>,
+) -> fmt::Result {
+    for node in nodes {
+        match node {
+            Node::Text(content) => content.chunks().try_for_each(|chunk| text.push_text(chunk))?,
+            Node::Element(element) if element.tag_name.eq_ignore_ascii_case("br") => text.push_line_break()?,
+            Node::Element(element) => {
+                let breaks = match display(element, ancestors, stylesheet) {
+                    Display::None => continue,
+                    Display::Inline => 0,
+                    Display::Block if element.tag_name.eq_ignore_ascii_case("p") => 2,
+                    Display::Block => 1,
+                };
+                text.require_breaks(breaks);
+                ancestors.push(element);
+                write_children(&element.children, stylesheet, ancestors, text)?;
+                ancestors.pop();
+                text.require_breaks(breaks);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the `display` of `element` from its `style` attribute, the
+/// matching rules of `stylesheet` and the default stylesheet.
+fn display(element: &Element, ancestors: &[&Element], stylesheet: &[Rule]) -> Display {
+    let winner = cascade(element, ancestors, stylesheet, |property| property == "display").remove("display");
+    let value = winner.map(|value| value.trim().to_ascii_lowercase());
+    match value.as_deref() {
+        Some("none") => Display::None,
+        Some("inline" | "inline-block" | "inline-flex" | "inline-grid" | "inline-table" | "contents") => {
+            Display::Inline
+        }
+        Some(value) if !matches!(value, "inherit" | "initial" | "unset" | "revert" | "") => Display::Block,
+        _ => default_display(element),
+    }
+}
+
+fn default_display(element: &Element) -> Display {
+    let is = |names: &[&str]| names.iter().any(|name| element.tag_name.eq_ignore_ascii_case(name));
+    if is(HIDDEN_ELEMENTS) || element.attr("hidden").is_some() {
+        Display::None
+    } else if is(BLOCK_ELEMENTS) {
+        Display::Block
+    } else {
+        Display::Inline
+    }
+}
+
+/// Writes rendered text, collapsing whitespace and holding back line
+/// breaks required by block boundaries until more text follows, so none
+/// appear at the start or end.
+struct TextBuilder<'w, W: fmt::Write + ?Sized> {
+    out: &'w mut W,
+    /// Whether anything has been written yet.
+    started: bool,
+    /// How many line breaks the output written so far ends with.
+    trailing_breaks: usize,
+    pending_breaks: usize,
+    pending_space: bool,
+}
+
+impl<'w, W: fmt::Write + ?Sized> TextBuilder<'w, W> {
+    fn new(out: &'w mut W) -> Self {
+        Self { out, started: false, trailing_breaks: 0, pending_breaks: 0, pending_space: false }
+    }
+
+    fn push_text(&mut self, text: &str) -> fmt::Result {
+        let mut piece = String::new();
+        for ch in text.chars() {
+            // Only ASCII whitespace collapses; U+00A0 and the like render.
+            if ch.is_ascii_whitespace() {
+                self.pending_space = !piece.is_empty() || (self.started && self.trailing_breaks == 0);
+                continue;
+            }
+            if self.pending_breaks > 0 {
+                self.flush_breaks(&mut piece);
+            } else if self.pending_space {
+                piece.push(' ');
+            }
+            self.pending_space = false;
+            piece.push(ch);
+        }
+        if piece.is_empty() {
+            return Ok(());
+        }
+        self.out.write_str(&piece)?;
+        self.started = true;
+        self.trailing_breaks = 0;
+        Ok(())
+    }
+
+    /// A `<br>`: always a new line, even with no text around it.
+    fn push_line_break(&mut self) -> fmt::Result {
+        let mut piece = String::new();
+        self.flush_breaks(&mut piece);
+        self.pending_space = false;
+        piece.push('\n');
+        self.out.write_str(&piece)?;
+        self.started = true;
+        self.trailing_breaks += piece.len();
+        Ok(())
+    }
+
+    /// Asks for at least `count` line breaks before the next text.
+    fn require_breaks(&mut self, count: usize) {
+        if count > 0 {
+            self.pending_breaks = self.pending_breaks.max(count);
+            self.pending_space = false;
+        }
+    }
+
+    /// Adds the pending line breaks to `piece`, the output about to be
+    /// written, counting those a `<br>` already wrote at the end.
+    fn flush_breaks(&mut self, piece: &mut String) {
+        if !piece.is_empty() || self.started {
+            let written = if piece.is_empty() { self.trailing_breaks } else { 0 };
+            for _ in written..self.pending_breaks {
+                piece.push('\n');
+            }
+        }
+        self.pending_breaks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+    use crate::css::stylesheet::OwnedStylesheet;
+    use crate::html::parser::{HtmlParser, WhitespacePolicy};
+
+    fn root(html: &str) -> Element {
+        match HtmlParser::with_whitespace_policy(html, WhitespacePolicy::Preserve).parse().remove(0) {
+            Node::Element(element) => element,
+            _ => panic!("Expected element node"),
+        }
+    }
+
+    #[test]
+    fn test_stylesheet_hides_elements() {
+        let element = root(r#"<main><div class="ad">Buy now</div><div>Kept <span class="x">here</span></div></main>"#);
+        let rules = CssParser::new(".ad, main .x { display: none }").parse();
+
+        assert_eq!(element.inner_text(None), "Buy now\nKept here");
+        assert_eq!(element.inner_text(Some(&rules)), "Kept");
+    }
+
+    #[test]
+    fn test_cascade_order() {
+        let element = root(
+            r#"<div><p id="a" class="c">A</p><p class="c" style="display: block">B</p><b hidden>C</b><i style="display:none">D</i></div>"#,
+        );
+        let rules = CssParser::new("#a { display: block } .c { display: none } [hidden] { display: inline }").parse();
+        assert_eq!(element.inner_text(Some(&rules)), "A\n\nB\n\nC");
+
+        let rules = CssParser::new("p { display: none !important } b { display: inline }").parse();
+        assert_eq!(element.inner_text(Some(&rules)), "C");
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        let element = root("<div>one<br>two<br><br>three<br><div>four</div></div>");
+        assert_eq!(element.inner_text(None), "one\ntwo\n\nthree\nfour");
+
+        let element = root("<div>  a <br>  b  </div>");
+        assert_eq!(element.inner_text(None), "a\nb");
+    }
+
+    #[test]
+    fn test_non_breaking_spaces_are_kept() {
+        let element = root("<div>a&nbsp;&nbsp;b <span>\u{a0}c\u{3000}</span>\n</div>");
+        assert_eq!(element.inner_text(None), "a\u{a0}\u{a0}b \u{a0}c\u{3000}");
+    }
+
+    #[test]
+    fn test_block_and_inline() {
+        let element = root(
+            "<article>\n  <h1>Title</h1>\n  <p>First <em>para</em>\n  graph.</p><p>Second.</p>\n  <ul><li>x</li><li>y</li></ul>\n</article>",
+        );
+        assert_eq!(element.inner_text(None), "Title\n\nFirst para graph.\n\nSecond.\n\nx\ny");
+
+        let element = root(r#"<div><span>a</span> <span style="display: block">b</span><script>c()</script></div>"#);
+        assert_eq!(element.inner_text(None), "a\nb");
+    }
+
+    #[test]
+    fn test_outer_text() {
+        let element = root(r#"<div class="hide">text</div>"#);
+        let rules = CssParser::new(".hide { display: none }").parse();
+
+        assert_eq!(element.inner_text(Some(&rules)), "text");
+        assert_eq!(element.outer_text(Some(&rules)), "");
+        assert_eq!(element.outer_text(None), "text");
+
+        let sheet = OwnedStylesheet::parse(".hide { display: none }");
+        assert_eq!(element.outer_text(Some(&*sheet)), "");
+    }
+
+    #[test]
+    fn test_inner_text_limit() {
+        let element = root("<div><p>one</p><p>two</p><p>three</p></div>");
+        let mut out = String::new();
+
+        let result = element.write_inner_text(None, &mut out, Some(11));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 11 }));
+        assert_eq!(out, "one\n\ntwo");
+
+        let mut out = String::new();
+        assert_eq!(element.write_outer_text(None, &mut out, None), Ok(()));
+        assert_eq!(out, element.inner_text(None));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::html::parser::{Element, Node};
+
+/// One relation of a `<link>` element, with the attributes that matter for
+/// it. A `rel` listing several relations yields one `LinkRel` for each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkRel {
+    Stylesheet { href: String, media: Option<String> },
+    /// `rel="preload"`; `as_type` is the `as` attribute, e.g. `font`.
+    Preload { href: String, as_type: Option<String> },
+    Prefetch { href: String },
+    /// `rel="icon"`. `sizes` holds the `WxH` entries of the `sizes`
+    /// attribute in order; `scalable` is set when it lists `any`.
+    Icon { href: String, sizes: Vec<(u32, u32)>, scalable: bool, media_type: Option<String> },
+    Manifest { href: String },
+    /// `rel="alternate"`, e.g. a translation (`hreflang`) or a feed
+    /// (`type`).
+    Alternate { href: String, hreflang: Option<String>, media_type: Option<String>, media: Option<String> },
+    Canonical { href: String },
+    /// Any other relation, lower-cased, such as `preconnect` or `author`.
+    Other { rel: String, href: String },
+}
+
+/// Returns the relations of every `<link>` element among `nodes` and their
+/// descendants, in document order.
+///
+/// `rel` values are matched case-insensitively; the legacy `shortcut` in
+/// `rel="shortcut icon"` is dropped. Links without an `href` are skipped.
+pub fn link_relations(nodes: &[Node]) -> Vec<LinkRel> {
+    let mut relations = Vec::new();
+    collect_links(nodes, &mut relations);
+    relations
+}
+
+fn collect_links(nodes: &[Node], relations: &mut Vec<LinkRel>) {
+    for node in nodes {
+        let Node::Element(element) = node else {
+            continue;
+        };
+        if element.tag_name.eq_ignore_ascii_case("link") {
+            link_relations_of(element, relations);
+        }
+        collect_links(&element.children, relations);
+    }
+}
+
+fn link_relations_of(element: &Element, relations: &mut Vec<LinkRel>) {
+    let Some(href) = attribute(element, "href") else {
+        return;
+    };
+    let href = href.to_string();
+    let owned = |name: &str| attribute(element, name).map(str::to_string);
+
+    for rel in attribute(element, "rel").unwrap_or_default().split_ascii_whitespace() {
+        let rel = rel.to_ascii_lowercase();
+        relations.push(match rel.as_str() {
+            "shortcut" => continue,
+            "stylesheet" => LinkRel::Stylesheet { href: href.clone(), media: owned("media") },
+            "preload" => LinkRel::Preload { href: href.clone(), as_type: owned("as") },
+            "prefetch" => LinkRel::Prefetch { href: href.clone() },
+            "icon" => {
+                let (sizes, scalable) = parse_sizes(attribute(element, "sizes").unwrap_or_default());
+                LinkRel::Icon { href: href.clone(), sizes, scalable, media_type: owned("type") }
+            }
+            "manifest" => LinkRel::Manifest { href: href.clone() },
+            "alternate" => LinkRel::Alternate {
+                href: href.clone(),
+                hreflang: owned("hreflang"),
+                media_type: owned("type"),
+                media: owned("media"),
+            },
+            "canonical" => LinkRel::Canonical { href: href.clone() },
+            _ => LinkRel::Other { rel, href: href.clone() },
+        });
+    }
+}
+
+/// Returns the value of the attribute `name`, compared case-insensitively.
+fn attribute<'e>(element: &'e Element, name: &str) -> Option<&'e str> {
+    element.attr(name).or_else(|| {
+        element
+            .attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    })
+}
+
+/// Parses a `sizes` attribute such as `16x16 32X32` or `any`. Malformed
+/// entries are skipped.
+fn parse_sizes(value: &str) -> (Vec<(u32, u32)>, bool) {
+    let mut sizes = Vec::new();
+    let mut scalable = false;
+    for entry in value.split_ascii_whitespace() {
+        if entry.eq_ignore_ascii_case("any") {
+            scalable = true;
+        } else if let Some((width, height)) = entry.split_once(['x', 'X'])
+            && let (Ok(width), Ok(height)) = (width.parse(), height.parse())
+        {
+            sizes.push((width, height));
+        }
+    }
+    (sizes, scalable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    #[test]
+    fn test_link_relations() {
+        let html = r#"<head>
+            <link rel="stylesheet" href="/main.css" media="screen and (min-width: 600px)">
+            <link rel="preload" href="/font.woff2" as="font" crossorigin>
+            <link rel="prefetch" href="/next.html">
+            <link rel="Shortcut Icon" href="/favicon.png" sizes="32x32 64X64 bogus" type="image/png">
+            <link rel="icon" href="/icon.svg" sizes="any">
+            <link rel="manifest" href="/app.webmanifest">
+            <link rel="alternate" href="/de/" hreflang="de">
+            <link rel="alternate" href="/feed.xml" type="application/rss+xml">
+            <link rel="canonical" href="https://example.com/">
+            <link rel="preconnect" href="https://cdn.example.com">
+            <link rel="stylesheet">
+        </head>"#;
+        let relations = link_relations(&HtmlParser::new(html).parse());
+
+        assert_eq!(
+            relations,
+            vec![
+                LinkRel::Stylesheet {
+                    href: "/main.css".to_string(),
+                    media: Some("screen and (min-width: 600px)".to_string()),
+                },
+                LinkRel::Preload { href: "/font.woff2".to_string(), as_type: Some("font".to_string()) },
+                LinkRel::Prefetch { href: "/next.html".to_string() },
+                LinkRel::Icon {
+                    href: "/favicon.png".to_string(),
+                    sizes: vec![(32, 32), (64, 64)],
+                    scalable: false,
+                    media_type: Some("image/png".to_string()),
+                },
+                LinkRel::Icon { href: "/icon.svg".to_string(), sizes: vec![], scalable: true, media_type: None },
+                LinkRel::Manifest { href: "/app.webmanifest".to_string() },
+                LinkRel::Alternate {
+                    href: "/de/".to_string(),
+                    hreflang: Some("de".to_string()),
+                    media_type: None,
+                    media: None,
+                },
+                LinkRel::Alternate {
+                    href: "/feed.xml".to_string(),
+                    hreflang: None,
+                    media_type: Some("application/rss+xml".to_string()),
+                    media: None,
+                },
+                LinkRel::Canonical { href: "https://example.com/".to_string() },
+                LinkRel::Other { rel: "preconnect".to_string(), href: "https://cdn.example.com".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_relations_on_one_link() {
+        let relations = link_relations(&HtmlParser::new(r#"<LINK REL="alternate stylesheet" HREF="/dark.css">"#).parse());
+
+        assert_eq!(
+            relations,
+            vec![
+                LinkRel::Alternate { href: "/dark.css".to_string(), hreflang: None, media_type: None, media: None },
+                LinkRel::Stylesheet { href: "/dark.css".to_string(), media: None },
+            ]
+        );
+    }
+}
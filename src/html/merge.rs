@@ -0,0 +1,232 @@
+use crate::html::parser::{Document, Element, Node};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Where `merge_into_body` inserts fragments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergePosition {
+    /// Before the body's first child.
+    Prepend,
+    /// After the body's last child.
+    Append,
+    /// Before the first element inside the body matching the selector.
+    Before(String),
+    /// After the first element inside the body matching the selector.
+    After(String),
+}
+
+/// A problem in the merged fragments that did not stop the merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeWarning {
+    /// An `id` in fragment `fragment` is already used by the document or an
+    /// earlier fragment.
+    DuplicateId { fragment: usize, id: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// The document has no `<body>` element.
+    NoBody,
+    /// No element inside the body matches the anchor selector, or the
+    /// selector is invalid.
+    AnchorNotFound(String),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::NoBody => write!(f, "document has no body element"),
+            MergeError::AnchorNotFound(selector) => {
+                write!(f, "no element in the body matches `{}`", selector)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Inserts the nodes of `fragments` into the first `<body>` of `document`
+/// at `position`, keeping the fragments and their nodes in order.
+///
+/// Anchor selectors are resolved with `query_selector` inside the body. On
+/// error the document is left unchanged. Ids of fragment elements that
+/// collide with the document or an earlier fragment are returned as
+/// warnings; the nodes are inserted anyway.
+pub fn merge_into_body(
+    document: &mut Document,
+    fragments: &[Vec<Node>],
+    position: MergePosition,
+) -> Result<Vec<MergeWarning>, MergeError> {
+    let mut body_path = Vec::new();
+    let body = find_element(&document.children, &mut body_path, &|element| {
+        element.tag_name.eq_ignore_ascii_case("body")
+    })
+    .ok_or(MergeError::NoBody)?;
+
+    // The parent to insert into and the index in its children; `None`
+    // appends.
+    let (parent_path, index) = match &position {
+        MergePosition::Prepend => (body_path, Some(0)),
+        MergePosition::Append => (body_path, None),
+        MergePosition::Before(selector) | MergePosition::After(selector) => {
+            let anchor = body
+                .query_selector(selector)
+                .ok_or_else(|| MergeError::AnchorNotFound(selector.clone()))?;
+            let mut path = body_path;
+            find_element(&body.children, &mut path, &|element| std::ptr::eq(element, anchor))
+                .expect("the anchor is inside the body");
+            let index = path.pop().expect("the anchor path is not empty");
+            let offset = usize::from(matches!(position, MergePosition::After(_)));
+            (path, Some(index + offset))
+        }
+    };
+
+    let warnings = duplicate_ids(&document.children, fragments);
+
+    let parent = element_at_mut(&mut document.children, &parent_path).expect("the parent path is valid");
+    let index = index.unwrap_or(parent.children.len());
+    parent.children.splice(index..index, fragments.iter().flatten().cloned());
+    Ok(warnings)
+}
+
+/// Returns the first element in document order satisfying `predicate`,
+/// pushing the child indices leading to it onto `path`.
+fn find_element<'n>(
+    nodes: &'n [Node],
+    path: &mut Vec<usize>,
+    predicate: &dyn Fn(&Element) -> bool,
+) -> Option<&'n Element> {
+    for (index, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            path.push(index);
+            if predicate(element) {
+                return Some(element);
+            }
+            if let Some(found) = find_element(&element.children, path, predicate) {
+                return Some(found);
+            }
+            path.pop();
+        }
+    }
+    None
+}
+
+fn element_at_mut<'n>(nodes: &'n mut [Node], path: &[usize]) -> Option<&'n mut Element> {
+    let (&first, rest) = path.split_first()?;
+    let Node::Element(element) = nodes.get_mut(first)? else {
+        return None;
+    };
+    if rest.is_empty() {
+        Some(element)
+    } else {
+        element_at_mut(&mut element.children, rest)
+    }
+}
+
+fn duplicate_ids(document: &[Node], fragments: &[Vec<Node>]) -> Vec<MergeWarning> {
+    let mut seen = HashSet::new();
+    collect_ids(document, &mut |id| {
+        seen.insert(id.to_string());
+    });
+
+    let mut warnings = Vec::new();
+    for (fragment, nodes) in fragments.iter().enumerate() {
+        let mut ids = Vec::new();
+        collect_ids(nodes, &mut |id| ids.push(id.to_string()));
+        for id in &ids {
+            if seen.contains(id) {
+                warnings.push(MergeWarning::DuplicateId { fragment, id: id.clone() });
+            }
+        }
+        seen.extend(ids);
+    }
+    warnings
+}
+
+fn collect_ids(nodes: &[Node], found: &mut impl FnMut(&str)) {
+    for node in nodes {
+        if let Node::Element(element) = node {
+            if let Some(id) = element.attr("id") {
+                found(id);
+            }
+            collect_ids(&element.children, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    const PAGE: &str = r#"<html><head></head><body><main id="main"></main><footer id="footer"></footer></body></html>"#;
+
+    fn body_ids(document: &Document) -> Vec<&str> {
+        document
+            .query_selector_all("body > *")
+            .iter()
+            .filter_map(|element| element.attr("id"))
+            .collect()
+    }
+
+    #[test]
+    fn test_append_fragments_in_order() {
+        let mut document = HtmlParser::new(PAGE).parse_document();
+        let fragments = vec![
+            HtmlParser::new(r#"<p id="a"></p><p id="b"></p>"#).parse(),
+            HtmlParser::new(r#"<p id="c"></p>"#).parse(),
+        ];
+
+        let warnings = merge_into_body(&mut document, &fragments, MergePosition::Append).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(body_ids(&document), vec!["main", "footer", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_insert_around_anchor() {
+        let mut document = HtmlParser::new(PAGE).parse_document();
+        let fragment = vec![HtmlParser::new(r#"<aside id="x"></aside>"#).parse()];
+
+        merge_into_body(&mut document, &fragment, MergePosition::Before("#footer".to_string())).unwrap();
+        merge_into_body(&mut document, &fragment, MergePosition::Prepend).unwrap();
+        merge_into_body(&mut document, &fragment, MergePosition::After("main".to_string())).unwrap();
+
+        assert_eq!(body_ids(&document), vec!["x", "main", "x", "x", "footer"]);
+    }
+
+    #[test]
+    fn test_duplicate_id_warning() {
+        let mut document = HtmlParser::new(PAGE).parse_document();
+        let fragments = vec![
+            HtmlParser::new(r#"<div id="new"><span id="main"></span></div>"#).parse(),
+            HtmlParser::new(r#"<p id="new"></p>"#).parse(),
+        ];
+
+        let warnings = merge_into_body(&mut document, &fragments, MergePosition::Append).unwrap();
+
+        assert_eq!(
+            warnings,
+            vec![
+                MergeWarning::DuplicateId { fragment: 0, id: "main".to_string() },
+                MergeWarning::DuplicateId { fragment: 1, id: "new".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_leave_document_unchanged() {
+        let mut document = HtmlParser::new(PAGE).parse_document();
+        let before = document.children.clone();
+        let fragment = vec![HtmlParser::new("<p></p>").parse()];
+
+        assert_eq!(
+            merge_into_body(&mut document, &fragment, MergePosition::After("#missing".to_string())),
+            Err(MergeError::AnchorNotFound("#missing".to_string()))
+        );
+        assert_eq!(document.children, before);
+
+        let mut bodiless = HtmlParser::new("<div></div>").parse_document();
+        assert_eq!(merge_into_body(&mut bodiless, &fragment, MergePosition::Append), Err(MergeError::NoBody));
+    }
+}
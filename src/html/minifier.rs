@@ -0,0 +1,388 @@
+use crate::html::parser::{closes_implicitly, has_optional_end_tag, OpenElements, BLOCK_ELEMENTS};
+use crate::html::tokenizer::{HtmlToken, HtmlTokenizer};
+use crate::output::{write_limited, OutputError};
+use std::fmt;
+
+/// Which passes `minify_html_with_options` runs. All are on by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtmlMinifierOptions {
+    /// Drop whitespace-only text next to block elements and table parts,
+    /// at the top level and in `<html>` and `<head>`, where it is never
+    /// rendered. What is left of it is written as a single space. Text in
+    /// `<pre>`, `<textarea>` and the raw-text elements is kept as it is.
+    pub collapse_whitespace: bool,
+    /// Drop comments.
+    pub remove_comments: bool,
+    /// Keep `<!--[if IE]> ... <![endif]-->` conditional comments when
+    /// removing comments.
+    pub keep_conditional_comments: bool,
+    /// Leave out the end tags of `li`, `p`, `td` and the other elements
+    /// whose end tag is optional, where the next tag closes them anyway.
+    pub remove_optional_tags: bool,
+}
+
+impl Default for HtmlMinifierOptions {
+    fn default() -> Self {
+        Self {
+            collapse_whitespace: true,
+            remove_comments: true,
+            keep_conditional_comments: true,
+            remove_optional_tags: true,
+        }
+    }
+}
+
+/// Table parts whose surrounding whitespace is not rendered, in addition to
+/// `BLOCK_ELEMENTS`.
+const TABLE_PARTS: &[&str] = &["colgroup", "col", "tbody", "thead", "tfoot", "td", "th"];
+
+/// Elements whose whitespace-only text is content.
+const PREFORMATTED_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style", "title"];
+
+/// Elements in which a `p` must keep its end tag when nothing follows it,
+/// as browsers would otherwise leave the `p` open past their end. Custom
+/// elements, whose names contain a `-`, count too.
+const P_END_TAG_REQUIRED_IN: &[&str] = &["a", "audio", "del", "ins", "map", "noscript", "video"];
+
+/// A token of the input with what the minifier knows about it.
+struct Item<'a> {
+    token: HtmlToken<'a>,
+    raw: &'a str,
+    /// The element the token is in.
+    parent: Option<String>,
+    /// Inside an element of `PREFORMATTED_ELEMENTS`.
+    preformatted: bool,
+    /// For an end tag that closes the current element, which has an
+    /// optional end tag, the elements still open after it.
+    closes_optional: Option<Vec<String>>,
+    keep: bool,
+}
+
+/// Minifies the HTML `input` with the default options: see
+/// `minify_html_with_options`.
+pub fn minify_html(input: &str) -> String {
+    minify_html_with_options(input, &HtmlMinifierOptions::default())
+}
+
+/// Writer-based form of `minify_html`; see `write_minified_html_with_options`.
+pub fn write_minified_html<W: fmt::Write + ?Sized>(
+    input: &str,
+    out: &mut W,
+    limit: Option<usize>,
+) -> Result<(), OutputError> {
+    write_minified_html_with_options(input, &HtmlMinifierOptions::default(), out, limit)
+}
+
+/// Removes the whitespace, comments and end tags of the HTML `input` that
+/// `options` allows to go.
+///
+/// Everything that is kept is copied as written, attributes and character
+/// references included. The output parses, with `HtmlParser::new`, to the
+/// same nodes as the input, apart from the comments that were removed and
+/// the text on either side of them, which becomes one text node.
+pub fn minify_html_with_options(input: &str, options: &HtmlMinifierOptions) -> String {
+    let mut out = String::with_capacity(input.len());
+    let _ = minify(input, options, &mut out);
+    out
+}
+
+/// Writes `minify_html_with_options` of `input` into `out`, failing with
+/// `OutputError::LimitExceeded` instead of writing more than `limit`
+/// bytes. Each tag, text and comment is written whole or not at all.
+pub fn write_minified_html_with_options<W: fmt::Write + ?Sized>(
+    input: &str,
+    options: &HtmlMinifierOptions,
+    out: &mut W,
+    limit: Option<usize>,
+) -> Result<(), OutputError> {
+    write_limited(out, limit, |w| minify(input, options, w))
+}
+
+fn minify<W: fmt::Write + ?Sized>(input: &str, options: &HtmlMinifierOptions, out: &mut W) -> fmt::Result {
+    let mut items = read_items(input);
+
+    if options.remove_comments {
+        for item in &mut items {
+            if let HtmlToken::Comment(comment) = item.token {
+                item.keep = options.keep_conditional_comments && is_conditional_comment(comment);
+            }
+        }
+    }
+    if options.collapse_whitespace {
+        collapse_whitespace(&mut items);
+    }
+    if options.remove_optional_tags {
+        remove_optional_end_tags(&mut items);
+    }
+
+    for item in items.iter().filter(|item| item.keep) {
+        match item.token {
+            HtmlToken::Text(text) if options.collapse_whitespace && is_whitespace(text) && !item.preformatted => {
+                out.write_char(' ')?
+            }
+            _ => out.write_str(item.raw)?,
+        }
+    }
+    Ok(())
+}
+
+/// Tokenizes `input`, following the open elements as `HtmlParser` does.
+fn read_items(input: &str) -> Vec<Item<'_>> {
+    let mut tokenizer = HtmlTokenizer::preserving_whitespace(input);
+    let mut open = OpenElements::default();
+    let mut items = Vec::new();
+
+    while let Some(token) = tokenizer.next_token() {
+        let raw = &input[tokenizer.token_span().byte_range()];
+        let mut closes_optional = None;
+        match token {
+            HtmlToken::StartTag { name, self_closing, .. } => {
+                open.close_before(name);
+                items.push(item(token, raw, open.names(), None));
+                open.open(name, self_closing);
+                continue;
+            }
+            HtmlToken::EndTag { name } if open.close(name) && has_optional_end_tag(name) => {
+                closes_optional = Some(open.names().to_vec());
+            }
+            _ => {}
+        }
+        items.push(item(token, raw, open.names(), closes_optional));
+    }
+    items
+}
+
+fn item<'a>(token: HtmlToken<'a>, raw: &'a str, open: &[String], closes_optional: Option<Vec<String>>) -> Item<'a> {
+    Item {
+        token,
+        raw,
+        parent: open.last().cloned(),
+        preformatted: open.iter().any(|name| PREFORMATTED_ELEMENTS.contains(&name.as_str())),
+        closes_optional,
+        keep: true,
+    }
+}
+
+/// Drops whitespace-only text that is not rendered, and whitespace-only
+/// text that would follow other whitespace once comments are gone.
+fn collapse_whitespace(items: &mut [Item]) {
+    for index in 0..items.len() {
+        let item = &items[index];
+        if item.preformatted || !matches!(item.token, HtmlToken::Text(text) if is_whitespace(text)) {
+            continue;
+        }
+        let outside_body = item.parent.as_deref().is_none_or(|parent| matches!(parent, "html" | "head"));
+        let before = items[..index].iter().rev().find(|item| item.keep);
+        let after = items[index + 1..].iter().find(|item| item.keep);
+        let follows_whitespace = before.is_some_and(|item| matches!(item.token, HtmlToken::Text(text) if is_whitespace(text)));
+        if outside_body || follows_whitespace || is_block_boundary(before) || is_block_boundary(after) {
+            items[index].keep = false;
+        }
+    }
+}
+
+/// Returns whether whitespace next to `item` is not rendered: `item` is
+/// a block element's tag, a table part's tag, a doctype or the start or
+/// end of the input.
+fn is_block_boundary(item: Option<&Item>) -> bool {
+    let Some(item) = item else {
+        return true;
+    };
+    match item.token {
+        HtmlToken::StartTag { name, .. } | HtmlToken::EndTag { name } => {
+            let name = name.to_ascii_lowercase();
+            BLOCK_ELEMENTS.contains(&name.as_str()) || TABLE_PARTS.contains(&name.as_str())
+        }
+        HtmlToken::Doctype(_) => true,
+        HtmlToken::Text(_) | HtmlToken::Comment(_) => false,
+    }
+}
+
+/// Drops the end tags that the next kept token closes anyway. Tags are
+/// visited from the end, so `</td></tr><tr>` loses both end tags.
+fn remove_optional_end_tags(items: &mut [Item]) {
+    for index in (0..items.len()).rev() {
+        let (HtmlToken::EndTag { name }, Some(open)) = (&items[index].token, &items[index].closes_optional) else {
+            continue;
+        };
+        if !items[index].keep {
+            continue;
+        }
+        let name = name.to_ascii_lowercase();
+        let next = items[index + 1..].iter().find(|item| item.keep);
+        let required = name == "p"
+            && open.last().is_some_and(|parent| P_END_TAG_REQUIRED_IN.contains(&parent.as_str()) || parent.contains('-'));
+        let omit = match next.map(|item| &item.token) {
+            Some(HtmlToken::StartTag { name: next, .. }) => closes_implicitly(&name, next),
+            Some(HtmlToken::EndTag { name: next }) => !required && open.iter().any(|open| open.eq_ignore_ascii_case(next)),
+            Some(_) => false,
+            None => !required,
+        };
+        if omit {
+            items[index].keep = false;
+        }
+    }
+}
+
+/// Returns whether a comment is an Internet Explorer conditional comment,
+/// `<!--[if IE]> ... <![endif]-->`, or the `<!--<![endif]-->` that ends a
+/// revealed one.
+fn is_conditional_comment(comment: &str) -> bool {
+    comment.starts_with("[if") || comment.trim_end().ends_with("<![endif]")
+}
+
+/// Returns whether `text` is only ASCII whitespace, the only kind HTML
+/// collapses; U+00A0 and other spaces are content.
+fn is_whitespace(text: &str) -> bool {
+    text.bytes().all(|byte| byte.is_ascii_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::{HtmlParser, Node, WhitespacePolicy};
+
+    const LARGE_HTML: &str = include_str!("../../tests/fixtures/large.html");
+
+    /// `nodes` without comments, with the text on either side of each one
+    /// joined, as the minified output parses.
+    fn without_comments(nodes: &[Node]) -> Vec<Node> {
+        let mut result: Vec<Node> = Vec::new();
+        for node in nodes {
+            match node {
+                Node::Comment(_) => {}
+                Node::Text(text) => match result.last_mut() {
+                    Some(Node::Text(previous)) => previous.push_str(text),
+                    _ => result.push(node.clone()),
+                },
+                Node::Element(element) => {
+                    let mut element = element.clone();
+                    element.children = without_comments(&element.children);
+                    result.push(Node::Element(element));
+                }
+                Node::Doctype(_) => result.push(node.clone()),
+            }
+        }
+        result
+    }
+
+    /// Checks that the minified `html` parses to the same tree, whitespace
+    /// included, as `expected`: the same document written out with every
+    /// end tag, and without the comments and whitespace that should go.
+    fn assert_same_tree(html: &str, expected: &str) {
+        let minified = minify_html(html);
+        let parse = |html| HtmlParser::with_whitespace_policy(html, WhitespacePolicy::Preserve).parse();
+        assert_eq!(parse(&minified), parse(expected), "{}", minified);
+    }
+
+    #[test]
+    fn test_minify_html() {
+        let html = "<!DOCTYPE html>\n<html>\n<head>\n  <title> T </title>\n</head>\n<body>\n  <!-- nav -->\n  \
+                    <ul>\n    <li><a href=\"#a\">A</a></li>\n    <li>B</li>\n  </ul>\n  \
+                    <p>One <b>two</b> <i>three</i></p>\n  <p>Four</p>\n</body>\n</html>\n";
+
+        assert_eq!(
+            minify_html(html),
+            "<!DOCTYPE html><html><head><title> T </title></head><body><ul><li><a href=\"#a\">A</a><li>B</ul>\
+             <p>One <b>two</b> <i>three</i><p>Four</body></html>"
+        );
+        assert_same_tree(
+            html,
+            "<!DOCTYPE html><html><head><title> T </title></head><body><ul><li><a href=\"#a\">A</a></li><li>B</li></ul>\
+             <p>One <b>two</b> <i>three</i></p><p>Four</p></body></html>",
+        );
+    }
+
+    #[test]
+    fn test_optional_end_tags() {
+        let table = "<table>\n <tr><td>a</td><td>b</td></tr>\n <tr><td>c</td><td>d</td></tr>\n</table>";
+        assert_eq!(minify_html(table), "<table><tr><td>a<td>b<tr><td>c<td>d</table>");
+        assert_same_tree(table, "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td><td>d</td></tr></table>");
+
+        // `<li>` closes a `p` in the previous item, but a `p` at the end of
+        // an `a` keeps its end tag
+        let html = "<ul><li><p>x</p></li><li>y</li></ul><a href=x><p>y</p></a><div><p>z</p></div>";
+        assert_eq!(minify_html(html), "<ul><li><p>x<li>y</ul><a href=x><p>y</p></a><div><p>z</div>");
+        assert_same_tree(html, html);
+
+        // An end tag is left if text follows it
+        assert_eq!(minify_html("<div><p>a</p> b</div>"), "<div><p>a</p> b</div>");
+
+        // As in `a`, a `p` at the end of a custom element keeps it
+        let html = "<my-el><p>x</p></my-el><section><p>y</p></section>";
+        assert_eq!(minify_html(html), "<my-el><p>x</p></my-el><section><p>y</section>");
+        assert_same_tree(html, html);
+    }
+
+    #[test]
+    fn test_write_minified_html() {
+        let html = "<ul>\n  <li>one</li>\n  <li>two</li>\n</ul>";
+        let mut out = String::new();
+
+        let result = write_minified_html(html, &mut out, Some(14));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 14 }));
+        assert_eq!(out, "<ul><li>one");
+
+        let options = HtmlMinifierOptions { remove_optional_tags: false, ..Default::default() };
+        let mut out = String::new();
+        assert_eq!(write_minified_html_with_options(LARGE_HTML, &options, &mut out, None), Ok(()));
+        assert_eq!(out, minify_html_with_options(LARGE_HTML, &options));
+    }
+
+    #[test]
+    fn test_whitespace_is_kept_where_it_renders() {
+        let html = "<div><span>a</span> <!-- c --> <span>b</span>\n<pre>\n  a\n  </pre>\n<textarea>  </textarea> </div>";
+
+        assert_eq!(minify_html(html), "<div><span>a</span> <span>b</span><pre>\n  a\n  </pre><textarea>  </textarea></div>");
+    }
+
+    #[test]
+    fn test_non_ascii_spaces_are_content() {
+        let html = "<p><span>a</span>\u{a0}<span>b</span></p> <div>\u{3000}</div>\n<div> \u{a0} </div>";
+
+        assert_eq!(minify_html(html), "<p><span>a</span>\u{a0}<span>b</span><div>\u{3000}</div><div> \u{a0} </div>");
+        assert_same_tree(html, "<p><span>a</span>\u{a0}<span>b</span></p><div>\u{3000}</div><div> \u{a0} </div>");
+    }
+
+    #[test]
+    fn test_conditional_comments() {
+        let html = "<head><!--[if IE]><script src=ie.js></script><![endif]--><!-- x --></head>";
+
+        assert_eq!(minify_html(html), "<head><!--[if IE]><script src=ie.js></script><![endif]--></head>");
+        let options = HtmlMinifierOptions { keep_conditional_comments: false, ..Default::default() };
+        assert_eq!(minify_html_with_options(html, &options), "<head></head>");
+    }
+
+    #[test]
+    fn test_options_are_independent() {
+        let html = "<ul>\n  <li>a</li> <!-- x -->\n</ul>";
+        let none = HtmlMinifierOptions {
+            collapse_whitespace: false,
+            remove_comments: false,
+            keep_conditional_comments: false,
+            remove_optional_tags: false,
+        };
+
+        assert_eq!(minify_html_with_options(html, &none), html);
+        let options = HtmlMinifierOptions { collapse_whitespace: true, ..none };
+        assert_eq!(minify_html_with_options(html, &options), "<ul><li>a</li><!-- x --></ul>");
+        let options = HtmlMinifierOptions { remove_comments: true, ..none };
+        assert_eq!(minify_html_with_options(html, &options), "<ul>\n  <li>a</li> \n</ul>");
+        // Whitespace after `</li>` keeps it
+        let options = HtmlMinifierOptions { remove_optional_tags: true, ..none };
+        assert_eq!(minify_html_with_options(html, &options), html);
+        let options = HtmlMinifierOptions { remove_comments: true, ..options };
+        assert_eq!(minify_html_with_options("<ul><li>a</li><!-- x --></ul>", &options), "<ul><li>a</ul>");
+    }
+
+    #[test]
+    fn test_large_fixture() {
+        let minified = minify_html(LARGE_HTML);
+
+        assert!(minified.len() * 4 <= LARGE_HTML.len() * 3, "{} of {} bytes", minified.len(), LARGE_HTML.len());
+        let parse = |html| HtmlParser::new(html).parse();
+        assert_eq!(parse(&minified), without_comments(&parse(LARGE_HTML)));
+        assert_eq!(minify_html(&minified), minified);
+    }
+}
@@ -1,5 +1,67 @@
 pub mod tokenizer;
+pub mod entities;
+#[cfg(feature = "html-dom")]
 pub mod parser;
+#[cfg(feature = "html-dom")]
+pub mod ops;
+#[cfg(feature = "html-dom")]
+pub mod visit;
+#[cfg(feature = "html-dom")]
+mod rope;
+#[cfg(feature = "html-dom")]
+mod tree;
+#[cfg(feature = "html-dom")]
+mod foreign;
+#[cfg(feature = "html-dom")]
+pub mod minifier;
+#[cfg(feature = "html-dom")]
+pub mod reader;
+#[cfg(feature = "serialize")]
+pub mod serializer;
+#[cfg(feature = "matching")]
+pub mod query;
+#[cfg(feature = "matching")]
+pub mod merge;
+#[cfg(feature = "matching")]
+mod style;
+#[cfg(feature = "extract")]
+mod text;
+#[cfg(feature = "extract")]
+mod links;
+#[cfg(feature = "extract")]
+mod headings;
+#[cfg(all(feature = "extract", feature = "matching"))]
+mod inner_text;
+#[cfg(all(feature = "html-dom", feature = "css-ast"))]
+mod responsive;
 
-pub use tokenizer::{HtmlTokenizer, HtmlToken};
-pub use parser::{HtmlParser, Element, Node};
\ No newline at end of file
+pub use tokenizer::{HtmlTokenizer, HtmlToken, QuoteStyle, TemplateDelims};
+pub use entities::decode_html_entities;
+#[cfg(feature = "html-dom")]
+pub use parser::{HtmlParser, Element, Node, Document, WhitespacePolicy, SourceStyle};
+#[cfg(feature = "html-dom")]
+pub use rope::Text;
+#[cfg(feature = "html-dom")]
+pub use ops::{DomOp, DomOpError, DomOpErrorKind};
+#[cfg(feature = "html-dom")]
+pub use visit::NodeVisitor;
+#[cfg(feature = "html-dom")]
+pub use minifier::{
+    minify_html, minify_html_with_options, write_minified_html, write_minified_html_with_options, HtmlMinifierOptions,
+};
+#[cfg(feature = "html-dom")]
+pub use reader::HtmlReaderParser;
+#[cfg(feature = "serialize")]
+pub use serializer::{Serializer, SerializeOpts};
+#[cfg(feature = "matching")]
+pub use query::{matches, query_selector, query_selector_all};
+#[cfg(feature = "matching")]
+pub use merge::{merge_into_body, MergePosition, MergeWarning, MergeError};
+#[cfg(feature = "matching")]
+pub use style::{compute_style, diff_computed_styles, ComputedStyle, ElementStyleDiff, PseudoElement};
+#[cfg(feature = "extract")]
+pub use text::{find_text, FindOptions, TextMatch};
+#[cfg(feature = "extract")]
+pub use links::{link_relations, LinkRel};
+#[cfg(feature = "extract")]
+pub use headings::{ensure_heading_ids, SlugStyle};
@@ -0,0 +1,193 @@
+use crate::html::parser::{Element, Node};
+use std::fmt;
+
+/// A machine-applicable change to an element tree.
+///
+/// Every op carries a `path` of child indices leading from the element the
+/// ops are applied to down to the target element; an empty path targets the
+/// element itself. Each step of the path must land on an element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DomOp {
+    SetAttribute { path: Vec<usize>, name: String, value: String },
+    RemoveAttribute { path: Vec<usize>, name: String },
+    InsertChild { path: Vec<usize>, index: usize, node: Node },
+    RemoveChild { path: Vec<usize>, index: usize },
+    ReplaceText { path: Vec<usize>, index: usize, text: String },
+}
+
+impl DomOp {
+    pub fn path(&self) -> &[usize] {
+        match self {
+            DomOp::SetAttribute { path, .. }
+            | DomOp::RemoveAttribute { path, .. }
+            | DomOp::InsertChild { path, .. }
+            | DomOp::RemoveChild { path, .. }
+            | DomOp::ReplaceText { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomOpErrorKind {
+    /// A path step is out of range or does not land on an element.
+    InvalidPath { depth: usize },
+    /// The child index of an insert, remove or replace is out of range.
+    IndexOutOfRange { index: usize, len: usize },
+    /// `ReplaceText` addressed a child that is not a text node.
+    NotText { index: usize },
+}
+
+/// The error returned by `Element::apply_ops`, naming the op that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomOpError {
+    pub op_index: usize,
+    pub kind: DomOpErrorKind,
+}
+
+impl fmt::Display for DomOpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "op {}: ", self.op_index)?;
+        match &self.kind {
+            DomOpErrorKind::InvalidPath { depth } => {
+                write!(f, "path step {} does not address an element", depth)
+            }
+            DomOpErrorKind::IndexOutOfRange { index, len } => {
+                write!(f, "child index {} out of range for {} children", index, len)
+            }
+            DomOpErrorKind::NotText { index } => write!(f, "child {} is not a text node", index),
+        }
+    }
+}
+
+impl std::error::Error for DomOpError {}
+
+impl Element {
+    /// Applies `ops` in order.
+    ///
+    /// The batch is atomic: if any op fails, the element is left exactly as
+    /// it was before the call and the error names the failing op.
+    pub fn apply_ops(&mut self, ops: &[DomOp]) -> Result<(), DomOpError> {
+        let mut working = self.clone();
+        for (op_index, op) in ops.iter().enumerate() {
+            apply_op(&mut working, op).map_err(|kind| DomOpError { op_index, kind })?;
+        }
+        *self = working;
+        Ok(())
+    }
+}
+
+fn apply_op(root: &mut Element, op: &DomOp) -> Result<(), DomOpErrorKind> {
+    let target = resolve(root, op.path())?;
+    match op {
+        DomOp::SetAttribute { name, value, .. } => {
+            target.attributes.insert(name.clone(), value.clone());
+        }
+        DomOp::RemoveAttribute { name, .. } => {
+            target.attributes.remove(name);
+        }
+        DomOp::InsertChild { index, node, .. } => {
+            check_index(*index, target.children.len(), true)?;
+            target.children.insert(*index, node.clone());
+        }
+        DomOp::RemoveChild { index, .. } => {
+            check_index(*index, target.children.len(), false)?;
+            target.children.remove(*index);
+        }
+        DomOp::ReplaceText { index, text, .. } => {
+            check_index(*index, target.children.len(), false)?;
+            match &mut target.children[*index] {
+                Node::Text(existing) => *existing = text.as_str().into(),
+                _ => return Err(DomOpErrorKind::NotText { index: *index }),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve<'e>(root: &'e mut Element, path: &[usize]) -> Result<&'e mut Element, DomOpErrorKind> {
+    let mut current = root;
+    for (depth, &index) in path.iter().enumerate() {
+        current = match current.children.get_mut(index) {
+            Some(Node::Element(child)) => child,
+            _ => return Err(DomOpErrorKind::InvalidPath { depth }),
+        };
+    }
+    Ok(current)
+}
+
+fn check_index(index: usize, len: usize, allow_end: bool) -> Result<(), DomOpErrorKind> {
+    if index < len || (allow_end && index == len) {
+        Ok(())
+    } else {
+        Err(DomOpErrorKind::IndexOutOfRange { index, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    fn parse_element(html: &str) -> Element {
+        match HtmlParser::new(html).parse().remove(0) {
+            Node::Element(element) => element,
+            _ => panic!("Expected element node"),
+        }
+    }
+
+    #[test]
+    fn test_apply_batch() {
+        let mut element = parse_element("<ul><li>One</li><li>Two</li></ul>");
+
+        let ops = vec![
+            DomOp::SetAttribute { path: vec![], name: "class".to_string(), value: "list".to_string() },
+            DomOp::ReplaceText { path: vec![1], index: 0, text: "Second".to_string() },
+            DomOp::InsertChild {
+                path: vec![],
+                index: 2,
+                node: Node::Element(parse_element("<li>Three</li>")),
+            },
+            DomOp::RemoveChild { path: vec![], index: 0 },
+            DomOp::SetAttribute { path: vec![0], name: "id".to_string(), value: "b".to_string() },
+            DomOp::RemoveAttribute { path: vec![], name: "missing".to_string() },
+        ];
+
+        assert_eq!(element.apply_ops(&ops), Ok(()));
+        assert_eq!(
+            element,
+            parse_element(r#"<ul class="list"><li id="b">Second</li><li>Three</li></ul>"#)
+        );
+    }
+
+    #[test]
+    fn test_invalid_path_is_atomic() {
+        let original = parse_element("<div><p>Text</p></div>");
+        let mut element = original.clone();
+
+        let ops = vec![
+            DomOp::SetAttribute { path: vec![], name: "id".to_string(), value: "x".to_string() },
+            DomOp::RemoveChild { path: vec![0, 3], index: 0 },
+            DomOp::RemoveChild { path: vec![], index: 0 },
+        ];
+
+        let error = element.apply_ops(&ops).unwrap_err();
+        assert_eq!(error.op_index, 1);
+        assert_eq!(error.kind, DomOpErrorKind::InvalidPath { depth: 1 });
+        assert_eq!(element, original);
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let mut element = parse_element("<div><p>Text</p></div>");
+
+        let error = element
+            .apply_ops(&[DomOp::RemoveChild { path: vec![], index: 1 }])
+            .unwrap_err();
+        assert_eq!(error.kind, DomOpErrorKind::IndexOutOfRange { index: 1, len: 1 });
+
+        let error = element
+            .apply_ops(&[DomOp::ReplaceText { path: vec![], index: 0, text: String::new() }])
+            .unwrap_err();
+        assert_eq!(error.kind, DomOpErrorKind::NotText { index: 0 });
+    }
+}
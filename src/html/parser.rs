@@ -1,48 +1,257 @@
-use crate::html::tokenizer::{HtmlTokenizer, HtmlToken};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::html::entities::{decode_attribute_value, decode_text_into};
+use crate::html::foreign::Namespace;
+use crate::html::rope::Text;
+use crate::html::tokenizer::{HtmlTokenizer, HtmlToken, QuoteStyle, TemplateDelims};
+use crate::location::Span;
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq)]
+/// An element with its attributes and children. `Debug` shows it as an
+/// indented outline of tags and text.
+///
+/// `==` compares what the element is, not where it came from:
+/// `source_start` and `source_end` are ignored.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Element {
     pub tag_name: String,
     pub attributes: HashMap<String, String>,
     pub children: Vec<Node>,
+    /// How the start tag was written, recorded only by parsers created
+    /// with `recording_source_style`.
+    pub source_style: Option<Box<SourceStyle>>,
+    /// Byte offset of the `<` of the start tag in the parsed input; 0 for
+    /// elements built in code.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source_start: usize,
+    /// Byte offset just past the element: past its end tag, or past the
+    /// start tag of a void or self-closing element. An element closed
+    /// implicitly (`<li>a<li>b`) or by the end of the input ends after its
+    /// last child.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source_end: usize,
+}
+
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag_name == other.tag_name
+            && self.attributes == other.attributes
+            && self.children == other.children
+            && self.source_style == other.source_style
+    }
+}
+
+impl Element {
+    /// Returns the value of the attribute named `name`, including any
+    /// namespace prefix (`attr("xlink:href")`). The parser lower-cases
+    /// attribute names other than the camelCase ones of SVG and MathML, so
+    /// `name` is matched ignoring ASCII case if there is no attribute
+    /// spelled exactly like it.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        match self.attributes.get(name) {
+            Some(value) => Some(value),
+            None if name.bytes().any(|b| b.is_ascii_uppercase()) => {
+                self.attributes.get(&name.to_ascii_lowercase()).map(String::as_str)
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the value of the attribute whose name without its prefix is
+    /// `local_name`, so `attr_local("href")` finds both `href` and
+    /// `xlink:href`. An unprefixed attribute wins; among prefixed ones the
+    /// alphabetically first name is used.
+    pub fn attr_local(&self, local_name: &str) -> Option<&str> {
+        if let Some(value) = self.attr(local_name) {
+            return Some(value);
+        }
+        self.attributes
+            .iter()
+            .filter(|(name, _)| {
+                name.split_once(':').is_some_and(|(_, local)| local == local_name)
+            })
+            .min_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Per-element formatting details the serializer can reproduce.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceStyle {
+    /// Attribute names in source order with their quoting.
+    pub attributes: Vec<(String, QuoteStyle)>,
+    /// Whether the start tag ended in `/>`.
+    pub self_closing_slash: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Node {
     Element(Element),
-    Text(String),
+    /// Text, chunked when it runs past 64 KiB; see `Text`.
+    Text(Text),
     Comment(String),
+    /// A `<!DOCTYPE ...>` declaration, holding what follows the keyword
+    /// (`"html"` for `<!DOCTYPE html>`).
+    Doctype(String),
+}
+
+/// How whitespace-only text between tags is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhitespacePolicy {
+    /// Whitespace-only text is discarded everywhere, including at the root.
+    #[default]
+    Drop,
+    /// Whitespace-only text is kept as `Node::Text` in source order,
+    /// including at the root between the doctype, comments and elements.
+    Preserve,
+}
+
+/// A parsed HTML document.
+///
+/// `children` holds every root-level item in source order: comments before
+/// and after the doctype, the doctype itself, the root element(s), and —
+/// under `WhitespacePolicy::Preserve` — whitespace-only text between them.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    pub children: Vec<Node>,
+}
+
+impl Document {
+    /// Returns the content of the first doctype declaration, if any.
+    pub fn doctype(&self) -> Option<&str> {
+        self.children.iter().find_map(|node| match node {
+            Node::Doctype(doctype) => Some(doctype.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the first element at the root level, if any.
+    pub fn root_element(&self) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
 }
 
 pub struct HtmlParser<'a> {
     tokenizer: HtmlTokenizer<'a>,
+    /// Whether the first token has been read; not before parsing starts,
+    /// so the builder methods can still configure the tokenizer.
+    started: bool,
     current_token: Option<HtmlToken<'a>>,
+    current_span: Span,
+    /// Byte offset just past the last token moved past.
+    consumed_end: usize,
+    whitespace: WhitespacePolicy,
+    record_source_style: bool,
+    decode_entities: bool,
+    errors: Vec<ParseError>,
+    /// Names of the elements being parsed, outermost first.
+    open_elements: Vec<(&'a str, Namespace)>,
 }
 
 impl<'a> HtmlParser<'a> {
     pub fn new(input: &'a str) -> Self {
-        let mut tokenizer = HtmlTokenizer::new(input);
-        let current_token = tokenizer.next_token();
-        
+        Self::with_whitespace_policy(input, WhitespacePolicy::Drop)
+    }
+
+    pub fn with_whitespace_policy(input: &'a str, whitespace: WhitespacePolicy) -> Self {
+        let tokenizer = match whitespace {
+            WhitespacePolicy::Drop => HtmlTokenizer::new(input),
+            WhitespacePolicy::Preserve => HtmlTokenizer::preserving_whitespace(input),
+        };
+
         Self {
             tokenizer,
-            current_token,
+            started: false,
+            current_token: None,
+            current_span: Span::default(),
+            consumed_end: 0,
+            whitespace,
+            record_source_style: false,
+            decode_entities: true,
+            errors: Vec::new(),
+            open_elements: Vec::new(),
         }
     }
 
+    /// Accepts `.` in tag and attribute names; see
+    /// `HtmlTokenizer::allowing_dots_in_names`.
+    pub fn allowing_dots_in_names(mut self) -> Self {
+        self.tokenizer = self.tokenizer.allowing_dots_in_names();
+        self
+    }
+
+    /// Ends `<script>` content at the first `</script>`; see
+    /// `HtmlTokenizer::ignoring_script_escapes`.
+    pub fn ignoring_script_escapes(mut self) -> Self {
+        self.tokenizer = self.tokenizer.ignoring_script_escapes();
+        self
+    }
+
+    /// Reads template spans as opaque text; see
+    /// `HtmlTokenizer::with_template_syntax`.
+    pub fn with_template_syntax(mut self, delims: TemplateDelims) -> Self {
+        self.tokenizer = self.tokenizer.with_template_syntax(delims);
+        self
+    }
+
+    /// Makes the parser record each element's attribute order, quoting and
+    /// self-closing slash in `Element::source_style`.
+    pub fn recording_source_style(mut self) -> Self {
+        self.record_source_style = true;
+        self
+    }
+
+    /// Keeps character references such as `&amp;` as written in text and
+    /// attribute values, instead of decoding them.
+    pub fn keeping_raw_entities(mut self) -> Self {
+        self.decode_entities = false;
+        self
+    }
+
+    /// Parses the input into a list of root-level nodes in source order.
+    ///
+    /// Doctypes and comments are kept where they appear; stray end tags at
+    /// the root are ignored. Whitespace-only text follows the parser's
+    /// `WhitespacePolicy`.
+    ///
+    /// Tag and attribute names are lower-cased, and end tags match their
+    /// start tag ignoring ASCII case, so `<DIV></div>` is one `div`. Inside
+    /// `<svg>` and `<math>`, the camelCase names of SVG and MathML are
+    /// restored as in a browser: `<svg VIEWBOX="0 0 1 1">` has `viewBox`.
+    ///
+    /// Elements whose end tag HTML lets you omit are closed where it is
+    /// implied: `<li>a<li>b` gives two sibling `li` elements, and `<p>` ends
+    /// at the next block start tag such as `<div>` or `<p>`. The same goes
+    /// for `dt`, `dd`, `option`, `optgroup` and the table parts `tr`, `td`,
+    /// `th`, `thead`, `tbody` and `tfoot`, which also close when an end tag
+    /// of an enclosing element comes first, as `</ul>` does in
+    /// `<ul><li>a</ul>`.
+    ///
+    /// Character references in text and attribute values are decoded (see
+    /// `decode_html_entities`), except inside `<script>` and `<style>`,
+    /// unless the parser is `keeping_raw_entities`.
     pub fn parse(&mut self) -> Vec<Node> {
+        if !self.started {
+            self.started = true;
+            self.advance();
+        }
         let mut nodes = Vec::new();
         
         while let Some(token) = self.current_token.clone() {
             match token {
                 HtmlToken::StartTag { name, attributes, self_closing } => {
-                    let element = self.parse_element(&name, &attributes, self_closing);
+                    let element = self.parse_element(name, &attributes, self_closing);
                     nodes.push(Node::Element(element));
                 }
                 HtmlToken::Text(text) => {
-                    if !text.trim().is_empty() {
-                        nodes.push(Node::Text(text.to_string()));
+                    if self.keep_text(text) {
+                        nodes.push(Node::Text(self.text_content(text, None)));
                     }
                     self.advance();
                 }
@@ -50,13 +259,14 @@ impl<'a> HtmlParser<'a> {
                     nodes.push(Node::Comment(comment.to_string()));
                     self.advance();
                 }
-                HtmlToken::Doctype(_) => {
-                    // Skip doctype for now
+                HtmlToken::Doctype(doctype) => {
+                    nodes.push(Node::Doctype(doctype_content(doctype).to_string()));
                     self.advance();
                 }
-                HtmlToken::EndTag { .. } => {
+                HtmlToken::EndTag { name } => {
                     // Unexpected end tag at root level
-                    break;
+                    self.error(ParseErrorKind::UnexpectedToken, format!("unexpected end tag </{}>", name));
+                    self.advance();
                 }
             }
         }
@@ -64,42 +274,92 @@ impl<'a> HtmlParser<'a> {
         nodes
     }
 
-    fn parse_element(&mut self, name: &str, attributes: &[(&str, &str)], self_closing: bool) -> Element {
+    /// Like `parse`, but also returns every problem found in the input.
+    ///
+    /// Reported are end tags that close nothing, elements left unclosed at
+    /// the end of the input and doctypes inside elements. The returned
+    /// nodes are the same as `parse` would give.
+    pub fn parse_with_errors(&mut self) -> (Vec<Node>, Vec<ParseError>) {
+        let nodes = self.parse();
+        (nodes, std::mem::take(&mut self.errors))
+    }
+
+    /// Parses the input into a `Document`.
+    pub fn parse_document(&mut self) -> Document {
+        Document {
+            children: self.parse(),
+        }
+    }
+
+    fn parse_element(&mut self, name: &'a str, attributes: &[(&str, &str)], self_closing: bool) -> Element {
+        let lower_name = name.to_ascii_lowercase();
+        let namespace = Namespace::of_child(self.open_elements.last().copied(), &lower_name);
         let mut element = Element {
-            tag_name: name.to_string(),
+            tag_name: namespace.tag_name(lower_name),
             attributes: attributes.iter()
-                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .map(|(k, v)| (namespace.attribute_name(k.to_ascii_lowercase()), self.attribute_value(v)))
                 .collect(),
             children: Vec::new(),
+            source_style: None,
+            source_start: self.current_span.start.byte_offset,
+            source_end: self.current_span.end.byte_offset,
         };
 
+        if self.record_source_style {
+            let quotes = self.tokenizer.attribute_quotes();
+            element.source_style = Some(Box::new(SourceStyle {
+                attributes: attributes.iter()
+                    .zip(quotes)
+                    .map(|((name, _), quote)| (namespace.attribute_name(name.to_ascii_lowercase()), *quote))
+                    .collect(),
+                self_closing_slash: self_closing,
+            }));
+        }
+
+        let start_tag_span = self.current_span;
         self.advance(); // Move past start tag
 
-        if self_closing || self.is_void_element(name) {
+        if self_closing || is_void_element(name) {
             return element;
         }
 
+        let mut closed = false;
+        self.open_elements.push((name, namespace));
         // Parse children until we find the matching end tag
         while let Some(token) = self.current_token.clone() {
             match token {
                 HtmlToken::EndTag { name: end_name } => {
-                    if end_name == name {
+                    if end_name.eq_ignore_ascii_case(name) {
                         self.advance(); // Consume the end tag
+                        closed = true;
+                        break;
+                    } else if has_optional_end_tag(name) && self.closes_open_ancestor(end_name) {
+                        // Leave the end tag for the ancestor it belongs to
+                        closed = true;
                         break;
                     } else {
                         // Mismatched end tag, treat as text
+                        self.error(
+                            ParseErrorKind::UnexpectedToken,
+                            format!("end tag </{}> does not match <{}>", end_name, name),
+                        );
                         let text = format!("</{}>", end_name);
-                        element.children.push(Node::Text(text));
+                        element.children.push(Node::Text(text.into()));
                         self.advance();
                     }
                 }
+                HtmlToken::StartTag { name: child_name, .. }
+                    if closes_current(self.open_elements.iter().map(|(open, _)| *open), child_name) =>
+                {
+                    break;
+                }
                 HtmlToken::StartTag { name: child_name, attributes: child_attrs, self_closing } => {
-                    let child_element = self.parse_element(&child_name, &child_attrs, self_closing);
+                    let child_element = self.parse_element(child_name, &child_attrs, self_closing);
                     element.children.push(Node::Element(child_element));
                 }
                 HtmlToken::Text(text) => {
-                    if !text.trim().is_empty() {
-                        element.children.push(Node::Text(text.to_string()));
+                    if self.keep_text(text) {
+                        element.children.push(Node::Text(self.text_content(text, Some(name))));
                     }
                     self.advance();
                 }
@@ -109,24 +369,228 @@ impl<'a> HtmlParser<'a> {
                 }
                 HtmlToken::Doctype(_) => {
                     // Skip doctype
+                    self.error(ParseErrorKind::UnexpectedToken, "doctype inside an element");
                     self.advance();
                 }
             }
         }
 
+        self.open_elements.pop();
+        if !closed && !has_optional_end_tag(name) {
+            self.errors.push(ParseError::new(
+                start_tag_span,
+                ParseErrorKind::UnclosedTag,
+                format!("<{}> is never closed", name),
+            ));
+        }
+        element.source_end = self.consumed_end;
         element
     }
 
     fn advance(&mut self) {
+        self.consumed_end = self.current_span.end.byte_offset;
         self.current_token = self.tokenizer.next_token();
+        self.current_span = match self.current_token {
+            Some(_) => self.tokenizer.token_span(),
+            None => {
+                let end = self.tokenizer.token_span().end;
+                Span { start: end, end }
+            }
+        };
+    }
+
+    /// Records an error at the current token.
+    fn error(&mut self, kind: ParseErrorKind, message: impl Into<String>) {
+        self.errors.push(ParseError::new(self.current_span, kind, message));
+    }
+
+    /// The content of a text node inside `parent`, with character
+    /// references decoded unless `parent` holds raw text.
+    fn text_content(&self, text: &str, parent: Option<&str>) -> Text {
+        let raw_text = parent.is_some_and(|name| {
+            name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("style")
+        });
+        if self.decode_entities && !raw_text {
+            let mut content = Text::new();
+            decode_text_into(text, &mut content);
+            content
+        } else {
+            Text::from(text)
+        }
     }
 
-    fn is_void_element(&self, name: &str) -> bool {
-        matches!(name.to_lowercase().as_str(),
-            "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" |
-            "link" | "meta" | "param" | "source" | "track" | "wbr"
-        )
+    fn attribute_value(&self, value: &str) -> String {
+        if self.decode_entities {
+            decode_attribute_value(value).into_owned()
+        } else {
+            value.to_string()
+        }
+    }
+
+    fn keep_text(&self, text: &str) -> bool {
+        self.whitespace == WhitespacePolicy::Preserve || !text.trim().is_empty()
+    }
+
+    /// Returns whether `end_name` closes an element enclosing the one being
+    /// parsed.
+    fn closes_open_ancestor(&self, end_name: &str) -> bool {
+        let ancestors = &self.open_elements[..self.open_elements.len().saturating_sub(1)];
+        ancestors.iter().any(|(open, _)| open.eq_ignore_ascii_case(end_name))
+    }
+}
+
+/// The elements left open by a sequence of tokens, closed by the rules
+/// `HtmlParser` follows, for tools that work on the tokens themselves.
+/// Names are lower-cased.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OpenElements {
+    names: Vec<String>,
+}
+
+impl OpenElements {
+    /// The open elements, innermost last.
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Closes the elements that a start tag `name` closes implicitly, such
+    /// as an open `li` for another `li`.
+    pub(crate) fn close_before(&mut self, name: &str) {
+        while closes_current(self.names.iter().map(String::as_str), name) {
+            self.names.pop();
+        }
+    }
+
+    /// Opens the element of a start tag, unless it is void or self-closing.
+    /// Call `close_before` first.
+    pub(crate) fn open(&mut self, name: &str, self_closing: bool) {
+        if !self_closing && !is_void_element(name) {
+            self.names.push(name.to_ascii_lowercase());
+        }
+    }
+
+    /// Applies the end tag `name`: it closes the current element, or an
+    /// enclosing one along with the elements with optional end tags inside
+    /// it; other end tags close nothing. Returns whether it closed the
+    /// current element.
+    pub(crate) fn close(&mut self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        if self.names.last() == Some(&name) {
+            self.names.pop();
+            return true;
+        }
+        if self.names.contains(&name) {
+            while self.names.last().is_some_and(|top| *top != name && has_optional_end_tag(top)) {
+                self.names.pop();
+            }
+            if self.names.last() == Some(&name) {
+                self.names.pop();
+            }
+        }
+        false
+    }
+}
+
+/// Returns whether `name` is an element whose end tag may be left out,
+/// closing it when a start tag in `closes_implicitly` or an enclosing end
+/// tag comes first.
+pub(crate) fn has_optional_end_tag(name: &str) -> bool {
+    matches!(name.to_ascii_lowercase().as_str(),
+        "p" | "li" | "dt" | "dd" | "option" | "optgroup" |
+        "tr" | "td" | "th" | "thead" | "tbody" | "tfoot"
+    )
+}
+
+/// Returns whether a `next` start tag implicitly closes an open `name`
+/// element rather than nesting inside it.
+pub(crate) fn closes_implicitly(name: &str, next: &str) -> bool {
+    let next = next.to_ascii_lowercase();
+    match name.to_ascii_lowercase().as_str() {
+        "p" => matches!(next.as_str(),
+            "address" | "article" | "aside" | "blockquote" | "dd" | "details" | "div" |
+            "dl" | "dt" | "fieldset" | "figcaption" | "figure" | "footer" | "form" |
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "header" | "hgroup" | "hr" | "li" |
+            "main" | "menu" | "nav" | "ol" | "p" | "pre" | "section" | "table" | "ul"
+        ),
+        "li" => next == "li",
+        "dt" | "dd" => matches!(next.as_str(), "dt" | "dd"),
+        "option" => matches!(next.as_str(), "option" | "optgroup"),
+        "optgroup" => next == "optgroup",
+        "td" | "th" => matches!(next.as_str(), "td" | "th" | "tr" | "thead" | "tbody" | "tfoot"),
+        "tr" => matches!(next.as_str(), "tr" | "thead" | "tbody" | "tfoot"),
+        "thead" | "tbody" => matches!(next.as_str(), "tbody" | "tfoot"),
+        _ => false,
+    }
+}
+
+/// Returns whether a `next` start tag closes the innermost of the `open`
+/// elements, given outermost first.
+///
+/// That is the case when `closes_implicitly` says so, and for an `li`,
+/// `dt` or `dd` inside an open list item of the same kind: the new item
+/// closes that item and every element inside it, unless a special element
+/// other than `address`, `div` and `p` (such as `ul` or `table`) comes in
+/// between, as in the "in body" insertion mode. `<li><div>a<li>b` gives two
+/// sibling items.
+pub(crate) fn closes_current<'n>(open: impl DoubleEndedIterator<Item = &'n str>, next: &str) -> bool {
+    let mut open = open.rev().peekable();
+    if open.peek().is_some_and(|current| closes_implicitly(current, next)) {
+        return true;
     }
+    let items: &[&str] = match next.to_ascii_lowercase().as_str() {
+        "li" => &["li"],
+        "dt" | "dd" => &["dt", "dd"],
+        _ => return false,
+    };
+    for name in open {
+        let name = name.to_ascii_lowercase();
+        if items.contains(&name.as_str()) {
+            return true;
+        }
+        if is_special_element(&name) && !matches!(name.as_str(), "address" | "div" | "p") {
+            return false;
+        }
+    }
+    false
+}
+
+/// Returns whether `name`, lower-cased, is in the HTML "special" category:
+/// elements that stop the search for an open list item.
+fn is_special_element(name: &str) -> bool {
+    matches!(name,
+        "address" | "applet" | "area" | "article" | "aside" | "base" | "basefont" | "bgsound" |
+        "blockquote" | "body" | "br" | "button" | "caption" | "center" | "col" | "colgroup" |
+        "dd" | "details" | "dir" | "div" | "dl" | "dt" | "embed" | "fieldset" | "figcaption" |
+        "figure" | "footer" | "form" | "frame" | "frameset" | "h1" | "h2" | "h3" | "h4" | "h5" |
+        "h6" | "head" | "header" | "hgroup" | "hr" | "html" | "iframe" | "img" | "input" |
+        "keygen" | "li" | "link" | "listing" | "main" | "marquee" | "menu" | "meta" | "nav" |
+        "noembed" | "noframes" | "noscript" | "object" | "ol" | "p" | "param" | "plaintext" |
+        "pre" | "script" | "search" | "section" | "select" | "source" | "style" | "summary" |
+        "table" | "tbody" | "td" | "template" | "textarea" | "tfoot" | "th" | "thead" | "title" |
+        "tr" | "track" | "ul" | "wbr" | "xmp"
+    )
+}
+
+/// Returns whether `name` is a void element, which never has children or an
+/// end tag.
+pub(crate) fn is_void_element(name: &str) -> bool {
+    matches!(name.to_lowercase().as_str(),
+        "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" |
+        "link" | "meta" | "param" | "source" | "track" | "wbr"
+    )
+}
+
+/// Elements with `display: block` (or `list-item`, `table` and the like)
+/// in the default stylesheet.
+pub(crate) const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "caption", "dd", "details", "dialog", "div", "dl", "dt",
+    "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hgroup",
+    "hr", "html", "legend", "li", "main", "menu", "nav", "ol", "p", "pre", "section", "summary", "table", "tr", "ul",
+];
+
+fn doctype_content(raw: &str) -> &str {
+    // The tokenizer yields everything between '<' and '>', keyword included.
+    raw.get("!doctype".len()..).unwrap_or("").trim()
 }
 
 #[cfg(test)]
@@ -251,4 +715,409 @@ mod tests {
             panic!("Expected element node");
         }
     }
-}
\ No newline at end of file
+
+    const INTERLEAVED: &str = "<!-- a -->\n<!DOCTYPE html>\n<!-- b -->\n<html><body>Hi</body></html>\n";
+
+    #[test]
+    fn test_document_root_order_drop_whitespace() {
+        let document = HtmlParser::new(INTERLEAVED).parse_document();
+
+        assert_eq!(document.children.len(), 4);
+        assert_eq!(document.children[0], Node::Comment(" a ".to_string()));
+        assert_eq!(document.children[1], Node::Doctype("html".to_string()));
+        assert_eq!(document.children[2], Node::Comment(" b ".to_string()));
+        assert!(matches!(&document.children[3], Node::Element(e) if e.tag_name == "html"));
+        assert_eq!(document.doctype(), Some("html"));
+        assert_eq!(document.root_element().map(|e| e.tag_name.as_str()), Some("html"));
+    }
+
+    #[test]
+    fn test_document_root_order_preserve_whitespace() {
+        let document = HtmlParser::with_whitespace_policy(INTERLEAVED, WhitespacePolicy::Preserve)
+            .parse_document();
+
+        let newline = Node::Text("\n".into());
+        assert_eq!(document.children.len(), 8);
+        assert_eq!(document.children[0], Node::Comment(" a ".to_string()));
+        assert_eq!(document.children[1], newline);
+        assert_eq!(document.children[2], Node::Doctype("html".to_string()));
+        assert_eq!(document.children[3], newline);
+        assert_eq!(document.children[4], Node::Comment(" b ".to_string()));
+        assert_eq!(document.children[5], newline);
+        assert!(matches!(&document.children[6], Node::Element(e) if e.tag_name == "html"));
+        assert_eq!(document.children[7], newline);
+    }
+
+    #[test]
+    fn test_parse_with_errors() {
+        let (nodes, errors) = HtmlParser::new("<div>\n<p>Hi</span></p></div></div><ul>").parse_with_errors();
+
+        assert_eq!(nodes.len(), 2);
+        let kinds: Vec<_> = errors.iter().map(|error| error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParseErrorKind::UnexpectedToken,
+                ParseErrorKind::UnexpectedToken,
+                ParseErrorKind::UnclosedTag,
+            ]
+        );
+        assert_eq!(errors[0].message, "end tag </span> does not match <p>");
+        assert_eq!(errors[0].span.start.line, 2);
+        assert_eq!(errors[1].message, "unexpected end tag </div>");
+        assert_eq!(errors[2].message, "<ul> is never closed");
+        assert_eq!(errors[2].span.byte_range(), 34..38);
+    }
+
+    #[test]
+    fn test_well_formed_input_has_no_errors() {
+        let (_, errors) = HtmlParser::new("<!DOCTYPE html><p>Hi<br></p><img/>").parse_with_errors();
+
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_stray_root_end_tag_does_not_truncate() {
+        let mut parser = HtmlParser::new("</p><!-- kept --><div></div>");
+        let nodes = parser.parse();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0], Node::Comment(" kept ".to_string()));
+    }
+
+    #[test]
+    fn test_namespaced_tags() {
+        let html = r#"<fb:like href="https://example.com/page"></fb:like><custom:widget attr="x">hi</custom:widget><ng-container></ng-container>"#;
+        let nodes = HtmlParser::new(html).parse();
+
+        let names: Vec<_> = nodes
+            .iter()
+            .map(|node| match node {
+                Node::Element(element) => element.tag_name.as_str(),
+                _ => panic!("Expected element node"),
+            })
+            .collect();
+        assert_eq!(names, vec!["fb:like", "custom:widget", "ng-container"]);
+        let Node::Element(like) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(like.attr("href"), Some("https://example.com/page"));
+        assert!(like.children.is_empty());
+    }
+
+    #[test]
+    fn test_dots_in_names() {
+        let nodes = HtmlParser::new(r#"<my.widget v-on:click.prevent="go"><b>x</b></my.widget>"#)
+            .allowing_dots_in_names()
+            .parse();
+
+        let Node::Element(widget) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(widget.tag_name, "my.widget");
+        assert_eq!(widget.attr("v-on:click.prevent"), Some("go"));
+        assert_eq!(widget.children.len(), 1);
+    }
+
+    #[test]
+    fn test_template_syntax() {
+        let delims = TemplateDelims { open: "{{".to_string(), close: "}}".to_string() };
+        let nodes = HtmlParser::new(r#"<input value={{x y}} {{#if on}}checked{{/if}}><p>{{#if a < b}}yes{{/if}}</p>"#)
+            .with_template_syntax(delims)
+            .parse();
+
+        let [Node::Element(input), Node::Element(p)] = &nodes[..] else {
+            panic!("Expected two elements, got {:?}", nodes);
+        };
+        assert_eq!(input.attr("value"), Some("{{x y}}"));
+        assert_eq!(input.attr("{{#if on}}"), Some(""));
+        assert_eq!(p.children, vec![Node::Text("{{#if a < b}}yes{{/if}}".into())]);
+
+        // The first token is read with the option already set
+        let delims = TemplateDelims { open: "{{".to_string(), close: "}}".to_string() };
+        let nodes = HtmlParser::new("{{x <b>}}<i></i>").with_template_syntax(delims).parse();
+        assert_eq!(nodes[0], Node::Text("{{x <b>}}".into()));
+    }
+
+    #[test]
+    fn test_raw_text_elements() {
+        let nodes = HtmlParser::new("<head><script>for (i=0;i<n;i++) {}</script><style>p > a {}</style></head>").parse();
+
+        let Node::Element(head) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        let texts: Vec<_> = head
+            .children
+            .iter()
+            .map(|node| match node {
+                Node::Element(element) => element.children.clone(),
+                _ => panic!("Expected element node"),
+            })
+            .collect();
+        assert_eq!(texts[0], vec![Node::Text("for (i=0;i<n;i++) {}".into())]);
+        assert_eq!(texts[1], vec![Node::Text("p > a {}".into())]);
+    }
+
+    fn tag_names(nodes: &[Node]) -> Vec<&str> {
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Element(element) => Some(element.tag_name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn children(node: &Node) -> &[Node] {
+        match node {
+            Node::Element(element) => &element.children,
+            _ => panic!("Expected element node"),
+        }
+    }
+
+    #[test]
+    fn test_implied_end_tags() {
+        let (nodes, errors) = HtmlParser::new("<ul><li>a<li>b</ul>").parse_with_errors();
+
+        assert_eq!(tag_names(&nodes), vec!["ul"]);
+        let items = children(&nodes[0]);
+        assert_eq!(tag_names(items), vec!["li", "li"]);
+        assert_eq!(children(&items[0]), [Node::Text("a".into())]);
+        assert_eq!(children(&items[1]), [Node::Text("b".into())]);
+        assert!(errors.is_empty());
+
+        let nodes = HtmlParser::new("<p>one<p>two<div>three</div>").parse();
+        assert_eq!(tag_names(&nodes), vec!["p", "p", "div"]);
+
+        let nodes = HtmlParser::new("<p>text <b>bold</b><span>inline</span></p>").parse();
+        assert_eq!(tag_names(children(&nodes[0])), vec!["b", "span"]);
+
+        let nodes = HtmlParser::new("<dl><dt>term<dd>one<dd>two<dt>next</dl>").parse();
+        assert_eq!(tag_names(children(&nodes[0])), vec!["dt", "dd", "dd", "dt"]);
+
+        let nodes = HtmlParser::new("<select><option>a<option>b<optgroup><option>c</select>").parse();
+        let select = children(&nodes[0]);
+        assert_eq!(tag_names(select), vec!["option", "option", "optgroup"]);
+        assert_eq!(tag_names(children(&select[2])), vec!["option"]);
+    }
+
+    #[test]
+    fn test_implied_end_tags_in_tables() {
+        let (nodes, errors) =
+            HtmlParser::new("<table><thead><tr><th>h<tbody><tr><td>1<td>2<tr><td>3</table>").parse_with_errors();
+
+        let sections = children(&nodes[0]);
+        assert_eq!(tag_names(sections), vec!["thead", "tbody"]);
+        let rows = children(&sections[1]);
+        assert_eq!(tag_names(rows), vec!["tr", "tr"]);
+        assert_eq!(tag_names(children(&rows[0])), vec!["td", "td"]);
+        assert_eq!(tag_names(children(&rows[1])), vec!["td"]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_list_items_close_paragraphs_and_inline_elements() {
+        let (nodes, errors) = HtmlParser::new("<ul><li><p>x<li>y</ul>").parse_with_errors();
+
+        let items = children(&nodes[0]);
+        assert_eq!(tag_names(items), vec!["li", "li"]);
+        assert_eq!(tag_names(children(&items[0])), vec!["p"]);
+        assert_eq!(children(&items[1]), [Node::Text("y".into())]);
+        assert!(errors.is_empty());
+
+        let nodes = HtmlParser::new("<ul><li><div><span>a<li>b</ul>").parse();
+        assert_eq!(tag_names(children(&nodes[0])), vec!["li", "li"]);
+
+        let nodes = HtmlParser::new("<p>a<li>b<dl><dt><p>c<dd>d</dl>").parse();
+        assert_eq!(tag_names(&nodes), vec!["p", "li"]);
+        assert_eq!(tag_names(children(&children(&nodes[1])[1])), vec!["dt", "dd"]);
+
+        // A special element such as `table` keeps an outer item open
+        let nodes = HtmlParser::new("<ul><li><table><tr><td><li>x</table></ul>").parse();
+        assert_eq!(tag_names(children(&nodes[0])), vec!["li"]);
+    }
+
+    #[test]
+    fn test_nested_lists_are_not_closed() {
+        let nodes = HtmlParser::new("<ul><li>a<ul><li>b<li>c</ul><li>d</ul>").parse();
+
+        let items = children(&nodes[0]);
+        assert_eq!(tag_names(items), vec!["li", "li"]);
+        let inner = children(&children(&items[0])[1]);
+        assert_eq!(tag_names(inner), vec!["li", "li"]);
+    }
+
+    #[test]
+    fn test_required_end_tags_still_reported() {
+        let (nodes, errors) = HtmlParser::new("<div><p>a</span></div><li>open").parse_with_errors();
+
+        assert_eq!(tag_names(&nodes), vec!["div", "li"]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "end tag </span> does not match <p>");
+    }
+
+    #[test]
+    fn test_entities_are_decoded() {
+        let html = r#"<p title="Tom &amp; Jerry" data-url="?a=1&copy=2">&lt;b&gt; &copy; 2024&nbsp;&#169;&#x00A9;</p><script>a &amp;&amp; b</script>"#;
+        let nodes = HtmlParser::new(html).parse();
+
+        let Node::Element(p) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(p.attr("title"), Some("Tom & Jerry"));
+        assert_eq!(p.attr("data-url"), Some("?a=1&copy=2"));
+        assert_eq!(p.children, [Node::Text("<b> © 2024\u{a0}©©".into())]);
+        assert_eq!(children(&nodes[1]), [Node::Text("a &amp;&amp; b".into())]);
+
+        let nodes = HtmlParser::new("<textarea>&lt;</textarea>&amp;").parse();
+        assert_eq!(children(&nodes[0]), [Node::Text("<".into())]);
+        assert_eq!(nodes[1], Node::Text("&".into()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let document = HtmlParser::new("<!DOCTYPE html><!-- c --><div id=a class='b c'><p>x &amp; y<br></div>")
+            .recording_source_style()
+            .parse_document();
+
+        let json = serde_json::to_string(&document).unwrap();
+        assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+        let Some(Node::Element(div)) = document.children.last() else {
+            panic!("Expected element node");
+        };
+        let json = serde_json::to_string(div).unwrap();
+        assert_eq!(serde_json::from_str::<Element>(&json).unwrap(), *div);
+    }
+
+    #[test]
+    fn test_keeping_raw_entities() {
+        let nodes = HtmlParser::new(r#"<p title="&amp;">&lt;b&gt;</p>"#).keeping_raw_entities().parse();
+
+        let Node::Element(p) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(p.attr("title"), Some("&amp;"));
+        assert_eq!(p.children, [Node::Text("&lt;b&gt;".into())]);
+    }
+
+    #[test]
+    fn test_names_are_case_insensitive() {
+        let (nodes, errors) = HtmlParser::new(r#"<DIV></div><IMG SRC="x" Alt=y><Ul Class="a"><LI>one<li>two</Li></uL>"#)
+            .parse_with_errors();
+
+        assert!(errors.is_empty());
+        assert_eq!(tag_names(&nodes), vec!["div", "img", "ul"]);
+        let Node::Element(img) = &nodes[1] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(img.attributes.len(), 2);
+        assert_eq!(img.attr("src"), Some("x"));
+        assert_eq!(img.attr("SRC"), Some("x"));
+        assert_eq!(img.attr("alt"), Some("y"));
+
+        let Node::Element(list) = &nodes[2] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(list.attr("class"), Some("a"));
+        assert_eq!(tag_names(&list.children), vec!["li", "li"]);
+    }
+
+    #[test]
+    fn test_mixed_case_nesting() {
+        let nodes = HtmlParser::new("<Section><P>a<Span>b</SPAN></p><DIV>c</Div></SECTION>").parse();
+
+        assert_eq!(tag_names(&nodes), vec!["section"]);
+        let section = children(&nodes[0]);
+        assert_eq!(tag_names(section), vec!["p", "div"]);
+        assert_eq!(tag_names(children(&section[0])), vec!["span"]);
+    }
+
+    #[test]
+    fn test_foreign_content_names() {
+        let nodes = HtmlParser::new(concat!(
+            r#"<DIV CLASS="a"><svg VIEWBOX="0 0 1 1" preserveAspectRatio="none"><LinearGradient gradientUnits="x"/>"#,
+            r#"<foreignObject><Div Data-X="1"></Div></foreignObject></svg><math definitionURL="u"></math></DIV>"#,
+        ))
+        .parse();
+
+        let Node::Element(div) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(div.attr("class"), Some("a"));
+        assert_eq!(tag_names(&div.children), vec!["svg", "math"]);
+        let [Node::Element(svg), Node::Element(math)] = &div.children[..] else {
+            panic!("Expected two elements");
+        };
+        assert_eq!(svg.attr("viewBox"), Some("0 0 1 1"));
+        assert_eq!(svg.attr("preserveAspectRatio"), Some("none"));
+        assert_eq!(math.attr("definitionURL"), Some("u"));
+        assert_eq!(tag_names(&svg.children), vec!["linearGradient", "foreignObject"]);
+        let Node::Element(gradient) = &svg.children[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(gradient.attr("gradientUnits"), Some("x"));
+
+        // The children of <foreignObject> are HTML again
+        let html = children(&svg.children[1]);
+        assert_eq!(tag_names(html), vec!["div"]);
+        let Node::Element(inner) = &html[0] else {
+            panic!("Expected element node");
+        };
+        assert!(inner.attributes.contains_key("data-x"));
+    }
+
+    fn source_range(node: &Node) -> (usize, usize) {
+        match node {
+            Node::Element(element) => (element.source_start, element.source_end),
+            _ => panic!("Expected element node"),
+        }
+    }
+
+    #[test]
+    fn test_source_positions() {
+        let html = "<div><p>hi</p></div>";
+        let nodes = HtmlParser::new(html).parse();
+        let (div_start, div_end) = source_range(&nodes[0]);
+        let (p_start, p_end) = source_range(&children(&nodes[0])[0]);
+
+        assert_eq!((div_start, div_end), (0, 20));
+        assert_eq!((p_start, p_end), (5, 14));
+        assert!(div_start < p_start && p_end < div_end);
+        assert_eq!(&html[p_start..p_end], "<p>hi</p>");
+
+        let html = "<ul>\n  <li>a<br>\n  <li>b <img src=x/>\n</ul> <p>open";
+        let nodes = HtmlParser::new(html).parse();
+        let items = children(&nodes[0]);
+        let slice = |node: &Node| {
+            let (start, end) = source_range(node);
+            &html[start..end]
+        };
+        assert_eq!(slice(&nodes[0]), &html[..html.find(" <p>").unwrap()]);
+        assert_eq!(slice(&items[0]), "<li>a<br>");
+        assert_eq!(slice(&children(&items[0])[1]), "<br>");
+        assert_eq!(slice(&items[1]), "<li>b <img src=x/>");
+        assert_eq!(slice(&nodes[1]), "<p>open");
+    }
+
+    #[test]
+    fn test_source_positions_are_not_compared() {
+        let nodes = HtmlParser::new("<p>x</p>").parse();
+        let spaced = HtmlParser::new("   <p>x</p>").parse();
+
+        assert_ne!(source_range(&nodes[0]), source_range(&spaced[0]));
+        assert_eq!(nodes, spaced);
+    }
+
+    #[test]
+    fn test_large_text_is_chunked() {
+        let words = "caf&eacute; &amp; cr&egrave;me ".repeat(10_000);
+        let nodes = HtmlParser::new(&format!("<p>{}</p>", words)).parse();
+
+        let Node::Text(text) = &children(&nodes[0])[0] else { panic!("expected text") };
+        assert!(text.is_chunked());
+        assert!(text.chunks().all(|chunk| chunk.len() <= 64 * 1024));
+        assert_eq!(*text, "café & crème ".repeat(10_000));
+    }
+}
@@ -0,0 +1,295 @@
+use crate::css::parser::{CssParser, Selector};
+use crate::html::parser::{Document, Element, Node};
+
+impl Element {
+    /// Returns the first descendant, in document order, matching the
+    /// selector list `selector`. Invalid selectors match nothing.
+    ///
+    /// This element counts as an ancestor for combinators, so `div > p`
+    /// called on a `<div>` finds its `<p>` children; its own ancestors and
+    /// siblings are unknown and never match.
+    pub fn query_selector(&self, selector: &str) -> Option<&Element> {
+        self.query_selector_all(selector).into_iter().next()
+    }
+
+    /// Returns every descendant matching `selector` in document order; see
+    /// `query_selector`.
+    pub fn query_selector_all(&self, selector: &str) -> Vec<&Element> {
+        let root = Step { element: self, siblings: &[], index: 0 };
+        select(&self.children, selector, vec![root])
+    }
+}
+
+impl Document {
+    /// Returns the first element in the document matching `selector`.
+    pub fn query_selector(&self, selector: &str) -> Option<&Element> {
+        query_selector(&self.children, selector)
+    }
+
+    /// Returns every element in the document matching `selector`.
+    pub fn query_selector_all(&self, selector: &str) -> Vec<&Element> {
+        query_selector_all(&self.children, selector)
+    }
+}
+
+/// Returns the first element among `nodes` and their descendants matching
+/// `selector`, treating `nodes` as siblings at the root.
+pub fn query_selector<'n>(nodes: &'n [Node], selector: &str) -> Option<&'n Element> {
+    query_selector_all(nodes, selector).into_iter().next()
+}
+
+/// Returns every element among `nodes` and their descendants matching
+/// `selector`, in document order.
+pub fn query_selector_all<'n>(nodes: &'n [Node], selector: &str) -> Vec<&'n Element> {
+    select(nodes, selector, Vec::new())
+}
+
+/// Returns whether `selector` matches `element`, given its ancestors from
+/// the outermost down to its parent.
+///
+/// Sibling combinators look at the element's preceding siblings in its
+/// parent's children, so with no ancestors they never match. Each ancestor
+/// must be the parent of the next one, and `element` a child of the last.
+pub fn matches(selector: &Selector, element: &Element, ancestors: &[&Element]) -> bool {
+    let mut path = Vec::with_capacity(ancestors.len() + 1);
+    let mut parent: Option<&Element> = None;
+    for &element in ancestors.iter().chain(std::iter::once(&element)) {
+        let siblings = parent.map_or(&[][..], |parent| parent.children.as_slice());
+        let index = siblings
+            .iter()
+            .position(|node| matches!(node, Node::Element(child) if std::ptr::eq(child, element)))
+            .unwrap_or(0);
+        path.push(Step { element, siblings, index });
+        parent = Some(element);
+    }
+    matches_path(selector, &path)
+}
+
+/// One element on the path from the query root, with the list it sits in so
+/// sibling combinators can look at its preceding siblings.
+#[derive(Clone, Copy)]
+struct Step<'n> {
+    element: &'n Element,
+    siblings: &'n [Node],
+    index: usize,
+}
+
+fn select<'n>(nodes: &'n [Node], selector: &str, mut path: Vec<Step<'n>>) -> Vec<&'n Element> {
+    let mut found = Vec::new();
+    if let Some(selectors) = CssParser::new(selector).parse_selector_list() {
+        collect(nodes, &selectors, &mut path, &mut found);
+    }
+    found
+}
+
+fn collect<'n>(
+    nodes: &'n [Node],
+    selectors: &[Selector],
+    path: &mut Vec<Step<'n>>,
+    found: &mut Vec<&'n Element>,
+) {
+    for (index, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            path.push(Step { element, siblings: nodes, index });
+            if selectors.iter().any(|selector| matches_path(selector, path)) {
+                found.push(element);
+            }
+            collect(&element.children, selectors, path, found);
+            path.pop();
+        }
+    }
+}
+
+/// Returns whether `selector` matches the last element of `path`.
+fn matches_path(selector: &Selector, path: &[Step]) -> bool {
+    let Some((last, ancestors)) = path.split_last() else {
+        return false;
+    };
+    match selector {
+        Selector::Descendant(left, right) => {
+            matches_path(right, path)
+                && (1..=ancestors.len()).any(|len| matches_path(left, &ancestors[..len]))
+        }
+        Selector::Child(left, right) => {
+            matches_path(right, path) && !ancestors.is_empty() && matches_path(left, ancestors)
+        }
+        Selector::Adjacent(left, right) => {
+            matches_path(right, path)
+                && preceding_siblings(last).next().is_some_and(|sibling| {
+                    matches_path(left, &with_last(ancestors, sibling))
+                })
+        }
+        Selector::GeneralSibling(left, right) => {
+            matches_path(right, path)
+                && preceding_siblings(last).any(|sibling| matches_path(left, &with_last(ancestors, sibling)))
+        }
+        Selector::Compound(parts) => parts.iter().all(|part| matches_path(part, path)),
+        Selector::Not(argument) => !matches_path(argument, path),
+        Selector::NthChild(expr) => nth_position(last, false, false).is_some_and(|at| expr.matches(at)),
+        Selector::NthOfType(expr) => nth_position(last, true, false).is_some_and(|at| expr.matches(at)),
+        Selector::NthLastChild(expr) => nth_position(last, false, true).is_some_and(|at| expr.matches(at)),
+        Selector::NthLastOfType(expr) => nth_position(last, true, true).is_some_and(|at| expr.matches(at)),
+        simple => simple.matches_element(last.element),
+    }
+}
+
+/// The 1-based position of `step` among its element siblings, counting only
+/// those with its tag name if `of_type`, and from the end if `from_end`.
+/// `None` for the query root, whose siblings are unknown.
+fn nth_position(step: &Step, of_type: bool, from_end: bool) -> Option<usize> {
+    if step.siblings.is_empty() {
+        return None;
+    }
+    let (before, after) = step.siblings.split_at(step.index);
+    let counted = if from_end { &after[1..] } else { before };
+    let count = counted
+        .iter()
+        .filter(|node| match node {
+            Node::Element(sibling) => !of_type || sibling.tag_name.eq_ignore_ascii_case(&step.element.tag_name),
+            _ => false,
+        })
+        .count();
+    Some(count + 1)
+}
+
+/// The element siblings before `step`, nearest first.
+fn preceding_siblings<'n>(step: &Step<'n>) -> impl Iterator<Item = Step<'n>> {
+    let siblings = step.siblings;
+    siblings[..step.index]
+        .iter()
+        .enumerate()
+        .rev()
+        .filter_map(move |(index, node)| match node {
+            Node::Element(element) => Some(Step { element, siblings, index }),
+            _ => None,
+        })
+}
+
+fn with_last<'n>(ancestors: &[Step<'n>], last: Step<'n>) -> Vec<Step<'n>> {
+    let mut path = ancestors.to_vec();
+    path.push(last);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    const LARGE_HTML: &str = include_str!("../../tests/fixtures/large.html");
+
+    fn ids<'e>(elements: &[&'e Element]) -> Vec<&'e str> {
+        elements.iter().filter_map(|element| element.attr("id")).collect()
+    }
+
+    #[test]
+    fn test_child_combinator_skips_deeper_descendants() {
+        let document = HtmlParser::new(
+            r#"<div id="d"><p id="a"></p><section><p id="b"></p></section><p id="c"></p></div>"#,
+        )
+        .parse_document();
+
+        assert_eq!(ids(&document.query_selector_all("div > p")), vec!["a", "c"]);
+        assert_eq!(ids(&document.query_selector_all("div p")), vec!["a", "b", "c"]);
+        assert_eq!(ids(&document.query_selector_all("section > p, #d")), vec!["d", "b"]);
+    }
+
+    #[test]
+    fn test_sibling_combinators() {
+        let nodes = HtmlParser::new(
+            r#"<ul><li id="a" class="x"></li>text<li id="b"></li><li id="c"></li></ul>"#,
+        )
+        .parse();
+
+        assert_eq!(ids(&query_selector_all(&nodes, ".x + li")), vec!["b"]);
+        assert_eq!(ids(&query_selector_all(&nodes, ".x ~ li")), vec!["b", "c"]);
+        assert_eq!(query_selector(&nodes, "ul > *").and_then(|li| li.attr("id")), Some("a"));
+    }
+
+    #[test]
+    fn test_not_selector() {
+        let nodes = HtmlParser::new(
+            r#"<div><p id="a" class="x"></p><p id="b"></p></div><section><p id="c"></p></section>"#,
+        )
+        .parse();
+
+        assert_eq!(ids(&query_selector_all(&nodes, "p:not(.x)")), vec!["b", "c"]);
+        assert_eq!(ids(&query_selector_all(&nodes, "p:not(div > p)")), vec!["c"]);
+        assert_eq!(ids(&query_selector_all(&nodes, "p:not(.x + p):not(#c)")), vec!["a"]);
+    }
+
+    #[test]
+    fn test_matches_with_ancestors() {
+        let nodes = HtmlParser::new(
+            r#"<div class="container"><h1>a</h1><p>b</p><section><p>c</p></section></div>"#,
+        )
+        .parse();
+        let Node::Element(container) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        let children: Vec<&Element> = container
+            .children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Element(element) => Some(element),
+                _ => None,
+            })
+            .collect();
+        let (h1, p, section) = (children[0], children[1], children[2]);
+        let Node::Element(nested) = &section.children[0] else {
+            panic!("Expected element node");
+        };
+        let selector = |css: &str| CssParser::new(css).parse_selector_list().unwrap().remove(0);
+
+        assert!(matches(&selector("div > p"), p, &[container]));
+        assert!(!matches(&selector("div > p"), nested, &[container, section]));
+        assert!(matches(&selector(".container p"), nested, &[container, section]));
+        assert!(matches(&selector(".container h1"), h1, &[container]));
+        assert!(!matches(&selector(".container h1"), h1, &[]));
+        assert!(matches(&selector("h1 + p"), p, &[container]));
+        assert!(matches(&selector("h1 ~ section"), section, &[container]));
+        assert!(!matches(&selector("h1 + p"), p, &[]));
+    }
+
+    #[test]
+    fn test_element_scope() {
+        let nodes = HtmlParser::new(r#"<div><p id="a"></p><div><p id="b"></p></div></div>"#).parse();
+        let Node::Element(outer) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+
+        assert_eq!(ids(&outer.query_selector_all("div > p")), vec!["a", "b"]);
+        assert_eq!(ids(&outer.query_selector_all("div div > p")), vec!["b"]);
+        assert!(outer.query_selector("div >").is_none());
+    }
+
+    #[test]
+    fn test_feature_items_in_large_document() {
+        let document = HtmlParser::new(LARGE_HTML).parse_document();
+        let items = document.query_selector_all(".feature-item");
+
+        assert_eq!(items.len(), 3);
+        assert!(items.iter().all(|item| item.tag_name == "div"));
+
+        let main = document.query_selector("main.content").unwrap();
+        let headings = main.query_selector_all(".feature-item > h3");
+        assert_eq!(headings.len(), 3);
+        assert!(matches!(&headings[0].children[..], [Node::Text(text)] if text == "Feature 1"));
+        assert!(main.query_selector(".feature-item + footer").is_none());
+    }
+
+    #[test]
+    fn test_nth_selectors() {
+        let nodes = HtmlParser::new(
+            r#"<ul><li id="a"></li><p id="x"></p><li id="b"></li>text<li id="c"></li><li id="d"></li></ul>"#,
+        )
+        .parse();
+
+        assert_eq!(ids(&query_selector_all(&nodes, "li:nth-child(odd)")), vec!["a", "b", "d"]);
+        assert_eq!(ids(&query_selector_all(&nodes, "ul > :nth-child(even)")), vec!["x", "c"]);
+        assert_eq!(ids(&query_selector_all(&nodes, "li:nth-of-type(2n+1)")), vec!["a", "c"]);
+        assert_eq!(ids(&query_selector_all(&nodes, "li:nth-last-child(-n+2)")), vec!["c", "d"]);
+        assert_eq!(ids(&query_selector_all(&nodes, ":nth-last-of-type(1)")), vec!["x", "d"]);
+        assert_eq!(ids(&query_selector_all(&nodes, "li:not(:nth-child(3n-1))")), vec!["a", "b", "c"]);
+    }
+}
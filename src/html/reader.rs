@@ -0,0 +1,400 @@
+use crate::error::{ParseError, ParseErrorKind};
+use crate::html::parser::{HtmlParser, Node, OpenElements};
+use crate::html::tokenizer::{HtmlToken, HtmlTokenizer, RawTextScan, RAW_TEXT_ELEMENTS};
+use crate::location::{LineCounter, SourceLocation, Span};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+/// How many bytes `HtmlReaderParser` reads at a time by default.
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+impl HtmlParser<'_> {
+    /// Creates a parser that reads HTML from `reader` in chunks and yields
+    /// the top-level nodes one at a time, without holding the whole input
+    /// in memory; see `HtmlReaderParser`.
+    pub fn from_reader<R: Read>(reader: R) -> HtmlReaderParser<R> {
+        HtmlReaderParser {
+            reader,
+            buffer: Vec::with_capacity(DEFAULT_CAPACITY),
+            capacity: DEFAULT_CAPACITY,
+            text: String::new(),
+            offset: 0,
+            lines: LineCounter::new(),
+            scanned: 0,
+            open: OpenElements::default(),
+            raw_text: None,
+            nodes: VecDeque::new(),
+            errors: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+/// Parses HTML from a `Read` source, yielding each top-level node once it
+/// is complete. Created by `HtmlParser::from_reader`.
+///
+/// Input is read `capacity` bytes at a time. What has not been parsed yet
+/// is kept: the top-level node in progress, with the token and UTF-8
+/// sequence the last chunk ended in the middle of. Each node is parsed as
+/// `HtmlParser::new` would parse it in the whole input, so memory use
+/// follows the largest top-level node; a document with a single `<html>`
+/// root is only parsed once it has been read to the end.
+///
+/// `source_start` and `source_end` of elements are offsets in the whole
+/// input. A read error, or input that is not UTF-8, ends the iteration
+/// with an `Err` of kind `ParseErrorKind::Io` or `InvalidUtf8`. Parse
+/// errors do not end it; `take_errors` returns them.
+pub struct HtmlReaderParser<R> {
+    reader: R,
+    /// The last chunk read, and before it the start of a UTF-8 sequence
+    /// the chunk before ended in.
+    buffer: Vec<u8>,
+    capacity: usize,
+    /// Input decoded but not parsed yet.
+    text: String,
+    /// Offset in the input of the start of `text`.
+    offset: usize,
+    /// Lines and columns up to `offset`, for error locations.
+    lines: LineCounter,
+    /// How much of `text` has been scanned for the ends of top-level
+    /// nodes, and the elements open there.
+    scanned: usize,
+    open: OpenElements,
+    /// Set when `scanned` is in the content of a raw-text element.
+    raw_text: Option<RawTextScan>,
+    nodes: VecDeque<Node>,
+    errors: Vec<ParseError>,
+    done: bool,
+}
+
+impl<R: Read> HtmlReaderParser<R> {
+    /// Reads `capacity` bytes at a time instead of 64 KiB.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self.buffer = Vec::with_capacity(self.capacity);
+        self
+    }
+
+    /// Returns the parse errors found since the last call, as
+    /// `HtmlParser::parse_with_errors` reports them, with locations in
+    /// the whole input. A top-level node is parsed, and its errors found,
+    /// before it is yielded.
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Reads the next chunk and parses the top-level nodes it completes.
+    fn fill(&mut self) -> Result<(), ParseError> {
+        let end_of_input = self.read_chunk()? == 0;
+        self.decode(end_of_input)?;
+
+        let complete = if end_of_input {
+            self.done = true;
+            self.scanned = self.text.len();
+            self.raw_text = None;
+            self.text.len()
+        } else {
+            scan(&self.text, &mut self.scanned, &mut self.open, &mut self.raw_text)
+        };
+        if complete == 0 {
+            return Ok(());
+        }
+        let (mut nodes, errors) = HtmlParser::new(&self.text[..complete]).parse_with_errors();
+        for node in &mut nodes {
+            shift_source_offsets(node, self.offset);
+        }
+        self.nodes.extend(nodes);
+        let start = self.lines.location(self.offset);
+        self.errors.extend(errors.into_iter().map(|mut error| {
+            shift_location(&mut error.span.start, start);
+            shift_location(&mut error.span.end, start);
+            error
+        }));
+        self.text[..complete].chars().for_each(|ch| self.lines.advance(ch));
+        self.text.drain(..complete);
+        self.offset += complete;
+        self.scanned -= complete;
+        Ok(())
+    }
+
+    /// Reads up to `capacity` bytes after what `buffer` holds, returning
+    /// how many were read.
+    fn read_chunk(&mut self) -> Result<usize, ParseError> {
+        let kept = self.buffer.len();
+        self.buffer.resize(kept + self.capacity, 0);
+        loop {
+            match self.reader.read(&mut self.buffer[kept..]) {
+                Ok(read) => {
+                    self.buffer.truncate(kept + read);
+                    return Ok(read);
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.buffer.truncate(kept);
+                    return Err(self.error(0, ParseErrorKind::Io, error.to_string()));
+                }
+            }
+        }
+    }
+
+    /// Moves the complete UTF-8 sequences of `buffer` to `text`, keeping
+    /// one cut off at the end unless the input has ended.
+    fn decode(&mut self, end_of_input: bool) -> Result<(), ParseError> {
+        let valid = match std::str::from_utf8(&self.buffer) {
+            Ok(text) => {
+                self.text.push_str(text);
+                self.buffer.clear();
+                return Ok(());
+            }
+            Err(error) if error.error_len().is_none() && !end_of_input => error.valid_up_to(),
+            Err(error) => {
+                return Err(self.error(error.valid_up_to(), ParseErrorKind::InvalidUtf8, "invalid UTF-8 in the input"));
+            }
+        };
+        self.text.push_str(std::str::from_utf8(&self.buffer[..valid]).unwrap_or_default());
+        self.buffer.drain(..valid);
+        Ok(())
+    }
+
+    /// An error at `position` bytes into `buffer`.
+    fn error(&self, position: usize, kind: ParseErrorKind, message: impl Into<String>) -> ParseError {
+        let mut lines = self.lines;
+        self.text.chars().for_each(|ch| lines.advance(ch));
+        String::from_utf8_lossy(&self.buffer[..position]).chars().for_each(|ch| lines.advance(ch));
+        let location = lines.location(self.offset + self.text.len() + position);
+        ParseError::new(Span { start: location, end: location }, kind, message)
+    }
+}
+
+impl<R: Read> Iterator for HtmlReaderParser<R> {
+    type Item = Result<Node, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(node) = self.nodes.pop_front() {
+                return Some(Ok(node));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(error) = self.fill() {
+                self.done = true;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+/// Scans `text` from `scanned` for the ends of top-level nodes, with the
+/// elements in `open` open there, and returns where the last complete one
+/// ends, or 0.
+///
+/// A token counts only once the next one has started, as the chunk may
+/// have ended inside it; `scanned` and `open` are left just before it.
+/// The content of a raw-text element is searched for its end tag by
+/// `raw_text` instead, which goes on where it stopped on the next call,
+/// so content spread over many chunks is still read once.
+fn scan(text: &str, scanned: &mut usize, open: &mut OpenElements, raw_text: &mut Option<RawTextScan>) -> usize {
+    let mut complete = 0;
+    loop {
+        if let Some(search) = raw_text {
+            match search.find_end(&text[*scanned..], false) {
+                Some(end) => {
+                    *scanned += end;
+                    *raw_text = None;
+                }
+                None => return complete,
+            }
+        }
+
+        let start = *scanned;
+        let mut tokenizer = HtmlTokenizer::new(&text[start..]);
+        let mut current = tokenizer.next_token().map(|token| (token, tokenizer.token_span().byte_range()));
+        while let Some((token, range)) = current {
+            current = tokenizer.next_token().map(|token| (token, tokenizer.token_span().byte_range()));
+            if current.is_none() {
+                return complete;
+            }
+            match token {
+                HtmlToken::StartTag { name, self_closing, .. } => {
+                    open.close_before(name);
+                    if open.names().is_empty() {
+                        complete = start + range.start;
+                    }
+                    open.open(name, self_closing);
+                    if !self_closing && RAW_TEXT_ELEMENTS.iter().any(|raw| name.eq_ignore_ascii_case(raw)) {
+                        *scanned = start + range.end;
+                        *raw_text = Some(RawTextScan::new(name, true));
+                        break;
+                    }
+                }
+                HtmlToken::EndTag { name } => {
+                    open.close(name);
+                }
+                _ => {}
+            }
+            if open.names().is_empty() {
+                complete = start + range.end;
+            }
+            *scanned = start + range.end;
+        }
+        if raw_text.is_none() {
+            return complete;
+        }
+    }
+}
+
+/// Moves `location`, found in text that starts at `start` in the whole
+/// input, to where it is in the whole input.
+fn shift_location(location: &mut SourceLocation, start: SourceLocation) {
+    if location.line == 1 {
+        location.column += start.column - 1;
+    }
+    location.line += start.line - 1;
+    location.byte_offset += start.byte_offset;
+}
+
+/// Adds `offset` to the source offsets of `node` and its descendants.
+fn shift_source_offsets(node: &mut Node, offset: usize) {
+    if let Node::Element(element) = node {
+        element.source_start += offset;
+        element.source_end += offset;
+        for child in &mut element.children {
+            shift_source_offsets(child, offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Write};
+    use std::thread;
+
+    const LARGE_HTML: &str = include_str!("../../tests/fixtures/large.html");
+
+    /// The source offsets of every element under `nodes`, in document order.
+    fn source_offsets(nodes: &[Node]) -> Vec<(usize, usize)> {
+        let mut offsets = Vec::new();
+        for node in nodes {
+            if let Node::Element(element) = node {
+                offsets.push((element.source_start, element.source_end));
+                offsets.extend(source_offsets(&element.children));
+            }
+        }
+        offsets
+    }
+
+    fn parse_in_chunks(html: &str, capacity: usize) -> Vec<Node> {
+        HtmlParser::from_reader(html.as_bytes())
+            .with_capacity(capacity)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_chunks_parse_like_the_whole_input() {
+        let html = "<!DOCTYPE html>\n<!-- héllo -->text <b>bold</b>\n<p>one<div>two</div></p>\
+                    <ul><li>a<li>b</ul><script>if (a </scr + b) { x('</p>') }</script>\
+                    <style>p > a {}</style><title>日本 & co</title><br>\
+                    <script><!-- w('<script>x</script>') --></script>tail";
+
+        let expected = HtmlParser::new(html).parse();
+        for capacity in [1, 2, 3, 5, 16, 64, 4096] {
+            let nodes = parse_in_chunks(html, capacity);
+            assert_eq!(nodes, expected, "capacity {}", capacity);
+            assert_eq!(source_offsets(&nodes), source_offsets(&expected), "capacity {}", capacity);
+        }
+        assert_eq!(parse_in_chunks(LARGE_HTML, 100), HtmlParser::new(LARGE_HTML).parse());
+    }
+
+    #[test]
+    fn test_multi_megabyte_pipe() {
+        let section = |n: usize| {
+            format!(
+                "<section id=\"s{n}\" class=\"item\">\n  <h2>Section {n} – ünïcödé</h2>\n  <p>First<p>Second\n  \
+                 <ul><li>a<li>b</ul>\n  <script>var s = \"</scr\" + \"ipt>\";</script>\n</section>\n<!-- {n} -->\n"
+            )
+        };
+        let html: String = (0..30_000).map(section).collect();
+        assert!(html.len() > 5_000_000);
+
+        let (reader, mut writer) = io::pipe().unwrap();
+        let writer = thread::spawn(move || {
+            for n in 0..30_000 {
+                writer.write_all(section(n).as_bytes()).unwrap();
+            }
+        });
+        let nodes: Vec<Node> = HtmlParser::from_reader(reader).collect::<Result<_, _>>().unwrap();
+        writer.join().unwrap();
+
+        let expected = HtmlParser::new(&html).parse();
+        assert_eq!(nodes.len(), expected.len());
+        assert_eq!(nodes.len(), 60_000);
+        assert_eq!(nodes, expected);
+    }
+
+    #[test]
+    fn test_long_raw_text_is_read_once() {
+        let html = format!("<p>a</p><script>{}</script><style>{}</style><p>b</p>", "x<y; ".repeat(400_000), "a{}".repeat(500_000));
+
+        let nodes = parse_in_chunks(&html, 4096);
+
+        assert_eq!(nodes, HtmlParser::new(&html).parse());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let html = "<div>\n<p>Hi</span></p></div></div>\n<b>x</i></b>\n<ul>日本<li>a";
+        let (_, expected) = HtmlParser::new(html).parse_with_errors();
+        assert_eq!(expected.len(), 4);
+
+        for capacity in [1, 7, 4096] {
+            let mut parser = HtmlParser::from_reader(html.as_bytes()).with_capacity(capacity);
+            let mut errors = Vec::new();
+            while let Some(node) = parser.next() {
+                node.unwrap();
+                errors.extend(parser.take_errors());
+            }
+            assert_eq!(errors, expected, "capacity {}", capacity);
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8_ends_the_nodes() {
+        let mut input = b"<p>a</p>\n<p>b".to_vec();
+        input.extend([0xFF, b'<']);
+
+        let mut parser = HtmlParser::from_reader(input.as_slice()).with_capacity(4);
+
+        assert!(matches!(parser.next(), Some(Ok(Node::Element(p))) if p.source_start == 0));
+        let error = parser.next().unwrap().unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidUtf8);
+        assert_eq!((error.span.start.line, error.span.start.column, error.span.start.byte_offset), (2, 5, 13));
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_read_errors_end_the_nodes() {
+        struct Failing<'a>(&'a [u8]);
+
+        impl Read for Failing<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(io::Error::other("disk on fire"));
+                }
+                let read = self.0.read(buf)?;
+                Ok(read)
+            }
+        }
+
+        let results: Vec<_> = HtmlParser::from_reader(Failing(b"<i>x</i><b>y")).collect();
+
+        assert_eq!(results.len(), 2);
+        assert!(matches!(&results[0], Ok(Node::Element(i)) if i.tag_name == "i"));
+        let error = results[1].as_ref().unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::Io);
+        assert_eq!(error.message, "disk on fire");
+        assert_eq!(error.span.start.byte_offset, 12);
+    }
+}
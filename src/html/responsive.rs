@@ -0,0 +1,107 @@
+use crate::css::media::{parse_media_query_list_with_errors, parse_source_sizes, Length, MediaCondition, MediaQueryList};
+use crate::error::ParseError;
+use crate::html::parser::Element;
+
+impl Element {
+    /// Parses the source size list of an `<img>` or `<source>` (`sizes`) or
+    /// of a `<link>` (`imagesizes`), such as `(max-width: 600px) 100vw,
+    /// 50vw`, into sizes with the condition under which each applies. The
+    /// entry without a condition is the default. Other elements, and
+    /// entries that do not parse, give nothing.
+    pub fn parsed_sizes(&self) -> Vec<(Option<MediaCondition>, Length)> {
+        self.parsed_sizes_with_errors().0
+    }
+
+    /// Like `parsed_sizes`, but also returns a `ParseError` for each entry
+    /// left out. Spans are relative to the attribute value.
+    pub fn parsed_sizes_with_errors(&self) -> (Vec<(Option<MediaCondition>, Length)>, Vec<ParseError>) {
+        let attribute = match self.tag_name.to_ascii_lowercase().as_str() {
+            "img" | "source" => "sizes",
+            "link" => "imagesizes",
+            _ => return (Vec::new(), Vec::new()),
+        };
+        parse_source_sizes(self.attr(attribute).unwrap_or_default())
+    }
+
+    /// Parses the `media` attribute of a `<source>`, `<link>`, `<style>` or
+    /// `<meta>`, such as `(prefers-color-scheme: dark)`. Returns `None` for
+    /// other elements and when the attribute is missing.
+    pub fn parsed_media(&self) -> Option<MediaQueryList> {
+        self.parsed_media_with_errors().map(|(list, _)| list)
+    }
+
+    /// Like `parsed_media`, but also returns a `ParseError` for each query
+    /// left out. Spans are relative to the attribute value.
+    pub fn parsed_media_with_errors(&self) -> Option<(MediaQueryList, Vec<ParseError>)> {
+        if !matches!(self.tag_name.to_ascii_lowercase().as_str(), "source" | "link" | "style" | "meta") {
+            return None;
+        }
+        self.attr("media").map(parse_media_query_list_with_errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css::media::{MediaCondition, MediaFeature, MediaQuery, MediaValue};
+    use crate::html::parser::{Element, HtmlParser, Node};
+
+    fn elements(html: &str) -> Vec<Element> {
+        HtmlParser::new(html)
+            .parse()
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Element(element) => Some(element),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parsed_sizes() {
+        let elements = elements(concat!(
+            r#"<img sizes="(max-width: 600px) 100vw, 50vw">"#,
+            r#"<link rel=preload imagesizes="(min-width: 40em) 33vw, 100vw">"#,
+            r#"<link rel=icon sizes=16x16>"#,
+        ));
+
+        let sizes = elements[0].parsed_sizes();
+        assert_eq!(sizes.len(), 2);
+        assert!(sizes[0].0.is_some());
+        assert_eq!(sizes[1].0, None);
+        assert_eq!((sizes[1].1.value, sizes[1].1.unit.as_str()), (50.0, "vw"));
+        assert_eq!(elements[1].parsed_sizes().len(), 2);
+        assert!(elements[2].parsed_sizes().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_size_condition_is_reported() {
+        let elements = elements(r#"<source sizes="(max-width: 600px) 100vw, (min-width 40em) 50vw, 33vw">"#);
+
+        let (sizes, errors) = elements[0].parsed_sizes_with_errors();
+
+        let defaults: Vec<_> = sizes.iter().map(|(condition, _)| condition.is_none()).collect();
+        assert_eq!(defaults, [false, true]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span.byte_range(), 37..41);
+    }
+
+    #[test]
+    fn test_parsed_media() {
+        let elements =
+            elements(r#"<source media="(prefers-color-scheme: dark)"><div media="print"></div><link rel=stylesheet>"#);
+
+        assert_eq!(
+            elements[0].parsed_media().unwrap().queries,
+            vec![MediaQuery {
+                modifier: None,
+                media_type: None,
+                condition: Some(MediaCondition::Feature(MediaFeature::Plain {
+                    name: "prefers-color-scheme".to_string(),
+                    value: MediaValue::Ident("dark".to_string()),
+                })),
+            }]
+        );
+        assert_eq!(elements[1].parsed_media(), None);
+        assert_eq!(elements[2].parsed_media(), None);
+    }
+}
@@ -0,0 +1,422 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::{Bound, Deref, Range, RangeBounds};
+use std::sync::OnceLock;
+
+/// Size at which text is split into chunks rather than kept in one `String`.
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The content of a text node.
+///
+/// Text up to 64 KiB is one `String`. Longer text built through `push_str`
+/// (as the parser builds it) is a list of chunks of at most that size, so
+/// appending to it never moves what is already there and writing it out
+/// goes a chunk at a time: `text_content`, the serializer and `inner_text`
+/// all read `chunks()` rather than one joined copy.
+///
+/// `Text` dereferences to `str`, so every `&str` method works on it. For
+/// chunked text the first such call joins the chunks into a copy that is
+/// kept until the text next changes; use `chunks()` or `slice()` to stay
+/// clear of that copy.
+#[derive(Default)]
+pub struct Text {
+    repr: Repr,
+}
+
+enum Repr {
+    Flat(String),
+    Chunked {
+        chunks: Vec<String>,
+        len: usize,
+        joined: OnceLock<String>,
+    },
+}
+
+impl Default for Repr {
+    fn default() -> Self {
+        Repr::Flat(String::new())
+    }
+}
+
+impl Text {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Flat(text) => text.len(),
+            Repr::Chunked { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the text is held as more than one chunk.
+    pub fn is_chunked(&self) -> bool {
+        matches!(self.repr, Repr::Chunked { .. })
+    }
+
+    /// The pieces of the text in order, each ending on a character
+    /// boundary. Text that is not chunked is a single piece.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        let chunks = match &self.repr {
+            Repr::Flat(text) => std::slice::from_ref(text),
+            Repr::Chunked { chunks, .. } => chunks.as_slice(),
+        };
+        chunks.iter().map(String::as_str)
+    }
+
+    /// The text in `range`, a byte range as with `str` indexing. It is
+    /// borrowed when it lies within one chunk and copied when it spans more.
+    ///
+    /// Panics if the range is out of bounds or does not fall on character
+    /// boundaries.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Cow<'_, str> {
+        let Range { start, end } = self.byte_range(range);
+        if start == end {
+            return Cow::Borrowed("");
+        }
+        let mut pieces = Vec::new();
+        let mut offset = 0;
+        for chunk in self.chunks() {
+            let chunk_end = offset + chunk.len();
+            if chunk_end > start {
+                pieces.push(&chunk[start.max(offset) - offset..end.min(chunk_end) - offset]);
+            }
+            if chunk_end >= end {
+                break;
+            }
+            offset = chunk_end;
+        }
+        match pieces.as_slice() {
+            [] => Cow::Borrowed(""),
+            [piece] => Cow::Borrowed(piece),
+            pieces => Cow::Owned(pieces.concat()),
+        }
+    }
+
+    fn byte_range(&self, range: impl RangeBounds<usize>) -> Range<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end && end <= self.len(),
+            "byte range {}..{} out of bounds for text of length {}",
+            start,
+            end,
+            self.len()
+        );
+        start..end
+    }
+
+    /// Appends `text`. Past 64 KiB the text goes on in new chunks, so what
+    /// is already there is neither copied nor moved.
+    pub fn push_str(&mut self, mut text: &str) {
+        if let Repr::Flat(flat) = &mut self.repr {
+            if flat.len() + text.len() <= CHUNK_SIZE {
+                flat.push_str(text);
+                return;
+            }
+            let first = std::mem::take(flat);
+            let len = first.len();
+            let chunks = if first.is_empty() { Vec::new() } else { vec![first] };
+            self.repr = Repr::Chunked { chunks, len, joined: OnceLock::new() };
+        }
+        let Repr::Chunked { chunks, len, joined } = &mut self.repr else {
+            unreachable!("flat text was converted above");
+        };
+        joined.take();
+        *len += text.len();
+        while !text.is_empty() {
+            let room = chunks.last().map_or(0, |last| CHUNK_SIZE.saturating_sub(last.len()));
+            let mut split = room.min(text.len());
+            while !text.is_char_boundary(split) {
+                split -= 1;
+            }
+            if split == 0 {
+                chunks.push(String::with_capacity(CHUNK_SIZE));
+                continue;
+            }
+            chunks.last_mut().unwrap().push_str(&text[..split]);
+            text = &text[split..];
+        }
+    }
+
+    /// The text as one `str`. Chunked text is joined on the first call and
+    /// the joined copy kept until the text next changes.
+    pub fn as_str(&self) -> &str {
+        match &self.repr {
+            Repr::Flat(text) => text,
+            Repr::Chunked { chunks, joined, .. } => joined.get_or_init(|| chunks.concat()),
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        match self.repr {
+            Repr::Flat(text) => text,
+            Repr::Chunked { chunks, mut joined, .. } => joined.take().unwrap_or_else(|| chunks.concat()),
+        }
+    }
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Text {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Clones the chunks but not a joined copy of them.
+impl Clone for Text {
+    fn clone(&self) -> Self {
+        let repr = match &self.repr {
+            Repr::Flat(text) => Repr::Flat(text.clone()),
+            Repr::Chunked { chunks, len, .. } => {
+                Repr::Chunked { chunks: chunks.clone(), len: *len, joined: OnceLock::new() }
+            }
+        };
+        Self { repr }
+    }
+}
+
+/// Keeps the `String` as it is, however long: taking it costs no copy.
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Self { repr: Repr::Flat(text) }
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        let mut result = Text::new();
+        result.push_str(text);
+        result
+    }
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        text.into_string()
+    }
+}
+
+impl fmt::Write for Text {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// Writes the chunks one after another.
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+/// Shows the text quoted, as `{:?}` on a `str` does.
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Compares the characters, however either side is chunked.
+impl PartialEq for Text {
+    fn eq(&self, other: &Text) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let mut left = self.chunks();
+        let mut right = other.chunks();
+        let (mut a, mut b) = ("", "");
+        loop {
+            if a.is_empty() {
+                a = match left.next() {
+                    Some(chunk) => chunk,
+                    None => return b.is_empty() && right.all(str::is_empty),
+                };
+                continue;
+            }
+            if b.is_empty() {
+                b = match right.next() {
+                    Some(chunk) => chunk,
+                    None => return false,
+                };
+                continue;
+            }
+            let common = a.len().min(b.len());
+            if a.as_bytes()[..common] != b.as_bytes()[..common] {
+                return false;
+            }
+            a = &a[common..];
+            b = &b[common..];
+        }
+    }
+}
+
+impl Eq for Text {}
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let mut rest = other;
+        self.chunks().all(|chunk| match rest.strip_prefix(chunk) {
+            Some(after) => {
+                rest = after;
+                true
+            }
+            None => false,
+        })
+    }
+}
+
+impl PartialEq<&str> for Text {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<String> for Text {
+    fn eq(&self, other: &String) -> bool {
+        *self == *other.as_str()
+    }
+}
+
+impl PartialEq<Text> for str {
+    fn eq(&self, other: &Text) -> bool {
+        *other == *self
+    }
+}
+
+impl PartialEq<Text> for &str {
+    fn eq(&self, other: &Text) -> bool {
+        *other == **self
+    }
+}
+
+impl PartialEq<Text> for String {
+    fn eq(&self, other: &Text) -> bool {
+        *other == *self.as_str()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Text {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Text {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Text::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn large(pattern: &str) -> String {
+        pattern.repeat(3 * CHUNK_SIZE / pattern.len() + 1)
+    }
+
+    #[test]
+    fn test_short_text_is_one_chunk() {
+        let mut text = Text::from("hello");
+        text.push_str(", world");
+        assert!(!text.is_chunked());
+        assert_eq!(text.chunks().collect::<Vec<_>>(), ["hello, world"]);
+        assert_eq!(text, "hello, world");
+        assert_eq!(text.slice(7..), "world");
+    }
+
+    #[test]
+    fn test_long_text_is_split_on_character_boundaries() {
+        let source = large("é ☃ x ");
+        let text = Text::from(source.as_str());
+        assert!(text.is_chunked());
+        assert_eq!(text.len(), source.len());
+        assert!(text.chunks().count() > 3);
+        assert!(text.chunks().all(|chunk| !chunk.is_empty() && chunk.len() <= CHUNK_SIZE));
+        assert_eq!(text.chunks().collect::<String>(), source);
+        assert_eq!(text, source);
+        assert_eq!(text.as_str(), source);
+        assert_eq!(text.to_string(), source);
+        assert_eq!(text.clone().into_string(), source);
+    }
+
+    #[test]
+    fn test_push_str_keeps_earlier_chunks_in_place() {
+        let mut text = Text::from(large("abc").as_str());
+        let first = text.chunks().next().unwrap().as_ptr();
+        let joined = text.as_str().len();
+        text.push_str("tail");
+        assert_eq!(text.chunks().next().unwrap().as_ptr(), first);
+        assert_eq!(text.len(), joined + 4);
+        assert!(text.ends_with("abctail"));
+    }
+
+    #[test]
+    fn test_slice_within_and_across_chunks() {
+        let source = large("0123456789");
+        let text = Text::from(source.as_str());
+        assert!(matches!(text.slice(10..20), Cow::Borrowed("0123456789")));
+        let across = CHUNK_SIZE - 5..CHUNK_SIZE + 5;
+        assert!(matches!(text.slice(across.clone()), Cow::Owned(_)));
+        assert_eq!(text.slice(across.clone()), &source[across]);
+        assert_eq!(text.slice(..), source);
+        assert_eq!(text.slice(CHUNK_SIZE..CHUNK_SIZE), "");
+        assert_eq!(text.slice(source.len()..), "");
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_slice_out_of_bounds() {
+        Text::from("abc").slice(2..5);
+    }
+
+    #[test]
+    fn test_equality_ignores_chunking() {
+        let source = large("xyz");
+        let chunked = Text::from(source.as_str());
+        let flat = Text::from(source.clone());
+        assert!(chunked.is_chunked() && !flat.is_chunked());
+        assert_eq!(chunked, flat);
+        assert_ne!(chunked, Text::from(&source[1..]));
+        let mut changed = source.clone();
+        changed.replace_range(CHUNK_SIZE..CHUNK_SIZE + 1, "!");
+        assert_ne!(chunked, Text::from(changed));
+    }
+
+    #[test]
+    fn test_as_str_is_joined_again_after_a_change() {
+        let mut text = Text::from(large("ab").as_str());
+        assert!(text.as_str().ends_with("ab"));
+        text.push_str("c");
+        assert!(text.as_str().ends_with("abc"));
+        assert_eq!(text.as_str().len(), text.len());
+    }
+}
@@ -0,0 +1,533 @@
+use crate::html::parser::{is_void_element, Document, Element, Node};
+use crate::html::tokenizer::QuoteStyle;
+use crate::output::{write_limited, OutputError};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Options for `Serializer`.
+///
+/// `prefer_original_quotes` and `keep_self_closing_slash` only have an
+/// effect on elements parsed with `HtmlParser::recording_source_style`;
+/// other elements are written in the standard form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializeOpts {
+    /// Write attributes in their source order with their original quote
+    /// character (or none), instead of sorted and double-quoted.
+    pub prefer_original_quotes: bool,
+    /// Write `/>` for start tags that had it, instead of `>` (plus an end
+    /// tag for non-void elements).
+    pub keep_self_closing_slash: bool,
+    /// Pretty-print with this many spaces per level: the children of an
+    /// element that holds only elements and comments go on their own
+    /// lines, and whitespace-only text between them is dropped. Elements
+    /// with text in them are written on one line, so the text is unchanged.
+    pub indent: Option<usize>,
+    /// Write comments; when false they are left out. Defaults to true.
+    pub preserve_comments: bool,
+}
+
+impl Default for SerializeOpts {
+    fn default() -> Self {
+        Self {
+            prefer_original_quotes: false,
+            keep_self_closing_slash: false,
+            indent: None,
+            preserve_comments: true,
+        }
+    }
+}
+
+/// Converts a node tree back to HTML text.
+///
+/// In the standard form attributes are sorted by name and double-quoted,
+/// void elements have no slash and every other element gets an end tag.
+/// Text and attribute values are taken to be decoded, as `HtmlParser`
+/// leaves them by default: `&`, `<` and `>` in text and `&`, `<` and the
+/// quote character in attribute values are written as character references.
+/// The text of `<script>` and `<style>` is written as it is.
+///
+/// Formatting is idempotent: parsing the output (with the same whitespace
+/// policy, and `recording_source_style` if the options use it) and
+/// serializing it again with the same options gives the same text, byte
+/// for byte. Pretty-printing never re-indents or reflows text or
+/// comments, and only drops whitespace-only text, so nothing accumulates.
+#[derive(Debug, Clone, Default)]
+pub struct Serializer {
+    opts: SerializeOpts,
+}
+
+impl Serializer {
+    pub fn new(opts: SerializeOpts) -> Self {
+        Self { opts }
+    }
+
+    pub fn serialize(&self, nodes: &[Node]) -> String {
+        let mut out = String::new();
+        let _ = self.write_nodes(nodes, None, 0, &mut out);
+        out
+    }
+
+    pub fn serialize_element(&self, element: &Element) -> String {
+        let mut out = String::new();
+        let _ = self.write_element(element, 0, &mut out);
+        out
+    }
+
+    pub fn serialize_document(&self, document: &Document) -> String {
+        self.serialize(&document.children)
+    }
+
+    /// Writes the serialization of `nodes` into `out`, failing with
+    /// `OutputError::LimitExceeded` instead of writing more than `limit`
+    /// bytes. Output is cut only between whole tags and text nodes, or
+    /// between the chunks of a large text node.
+    pub fn write<W: fmt::Write + ?Sized>(
+        &self,
+        nodes: &[Node],
+        out: &mut W,
+        limit: Option<usize>,
+    ) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| self.write_nodes(nodes, None, 0, w))
+    }
+
+    /// Writes `nodes`, the children of `parent` (or the top level), which
+    /// sit `depth` levels deep.
+    fn write_nodes<W: fmt::Write + ?Sized>(
+        &self,
+        nodes: &[Node],
+        parent: Option<&Element>,
+        depth: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        let raw_text = parent.is_some_and(|element| holds_raw_text(&element.tag_name));
+        let Some(width) = self.opts.indent.filter(|_| !raw_text && is_breakable(nodes, parent)) else {
+            for node in nodes {
+                self.write_node(node, raw_text, depth, out)?;
+            }
+            return Ok(());
+        };
+
+        let mut first = true;
+        for node in nodes {
+            if matches!(node, Node::Text(_)) || !self.keeps(node) {
+                continue;
+            }
+            if parent.is_some() || !first {
+                write_line_break(out, width * depth)?;
+            }
+            first = false;
+            self.write_node(node, false, depth, out)?;
+        }
+        if parent.is_some() && !first {
+            write_line_break(out, width * depth.saturating_sub(1))?;
+        }
+        Ok(())
+    }
+
+    fn write_node<W: fmt::Write + ?Sized>(
+        &self,
+        node: &Node,
+        raw_text: bool,
+        depth: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        match node {
+            Node::Element(element) => self.write_element(element, depth, out),
+            Node::Text(text) if raw_text => text.chunks().try_for_each(|chunk| out.write_str(chunk)),
+            Node::Text(text) => text.chunks().try_for_each(|chunk| out.write_str(&escape_text(chunk))),
+            Node::Comment(comment) if self.opts.preserve_comments => write!(out, "<!--{}-->", comment),
+            Node::Comment(_) => Ok(()),
+            Node::Doctype(doctype) => write!(out, "<!DOCTYPE {}>", doctype),
+        }
+    }
+
+    fn keeps(&self, node: &Node) -> bool {
+        self.opts.preserve_comments || !matches!(node, Node::Comment(_))
+    }
+
+    fn write_element<W: fmt::Write + ?Sized>(&self, element: &Element, depth: usize, out: &mut W) -> fmt::Result {
+        // Build the start tag first so a capped writer never cuts inside it.
+        let mut start_tag = format!("<{}", element.tag_name);
+        for (name, value, quote) in self.ordered_attributes(element) {
+            write_attribute(&mut start_tag, name, value, quote)?;
+        }
+
+        let slash = self.opts.keep_self_closing_slash
+            && element.source_style.as_ref().is_some_and(|style| style.self_closing_slash);
+        if slash {
+            start_tag.push_str("/>");
+            return out.write_str(&start_tag);
+        }
+        start_tag.push('>');
+        out.write_str(&start_tag)?;
+
+        if is_void_element(&element.tag_name) {
+            return Ok(());
+        }
+        self.write_nodes(&element.children, Some(element), depth + 1, out)?;
+        write!(out, "</{}>", element.tag_name)
+    }
+
+    fn ordered_attributes<'e>(&self, element: &'e Element) -> Vec<(&'e str, &'e str, QuoteStyle)> {
+        let mut ordered = Vec::with_capacity(element.attributes.len());
+
+        if self.opts.prefer_original_quotes
+            && let Some(style) = &element.source_style
+        {
+            for (name, quote) in &style.attributes {
+                if let Some((name, value)) = element.attributes.get_key_value(name) {
+                    ordered.push((name.as_str(), value.as_str(), *quote));
+                }
+            }
+        }
+
+        let mut rest: Vec<_> = element
+            .attributes
+            .iter()
+            .filter(|(name, _)| !ordered.iter().any(|(seen, _, _)| seen == name))
+            .map(|(name, value)| (name.as_str(), value.as_str(), QuoteStyle::Double))
+            .collect();
+        rest.sort_by(|a, b| a.0.cmp(b.0));
+        ordered.extend(rest);
+        ordered
+    }
+}
+
+impl Node {
+    /// Returns this node as HTML in the standard form of `Serializer`.
+    pub fn to_html(&self) -> String {
+        Serializer::default().serialize(std::slice::from_ref(self))
+    }
+}
+
+impl Element {
+    /// Returns this element, with its descendants, as HTML in the standard
+    /// form of `Serializer`.
+    pub fn to_html(&self) -> String {
+        Serializer::default().serialize_element(self)
+    }
+}
+
+/// Writes the node as `to_html` does.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Serializer::default().write_nodes(std::slice::from_ref(self), None, 0, f)
+    }
+}
+
+/// Writes the element as `to_html` does.
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Serializer::default().write_element(self, 0, f)
+    }
+}
+
+fn write_attribute<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    name: &str,
+    value: &str,
+    quote: QuoteStyle,
+) -> fmt::Result {
+    let unquoted_ok = !value.is_empty()
+        && !value.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '=' | '<' | '>' | '`' | '/' | '&'));
+    match quote {
+        QuoteStyle::NoValue if value.is_empty() => write!(out, " {}", name),
+        QuoteStyle::Unquoted if unquoted_ok => write!(out, " {}={}", name, value),
+        QuoteStyle::Single => write!(out, " {}='{}'", name, escape_attribute(value, '\'')),
+        _ => write!(out, " {}=\"{}\"", name, escape_attribute(value, '"')),
+    }
+}
+
+fn write_line_break<W: fmt::Write + ?Sized>(out: &mut W, indent: usize) -> fmt::Result {
+    write!(out, "\n{:indent$}", "", indent = indent)
+}
+
+/// Returns whether the children of `parent` can go on their own lines
+/// without changing the tree a parser builds from the output.
+fn is_breakable(nodes: &[Node], parent: Option<&Element>) -> bool {
+    let keeps_whitespace = parent.is_some_and(|element| {
+        matches!(element.tag_name.to_ascii_lowercase().as_str(), "pre" | "textarea" | "title")
+    });
+    !keeps_whitespace
+        && nodes.iter().all(|node| match node {
+            Node::Text(text) => text.chunks().all(|chunk| chunk.trim().is_empty()),
+            _ => true,
+        })
+}
+
+fn holds_raw_text(tag_name: &str) -> bool {
+    tag_name.eq_ignore_ascii_case("script") || tag_name.eq_ignore_ascii_case("style")
+}
+
+/// Text with `&`, `<` and `>` escaped. Text with none of them, which is
+/// most text, is borrowed rather than copied, and a large text node is
+/// escaped a chunk at a time, so serializing it costs at most a chunk of
+/// extra memory.
+fn escape_text(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(ch),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+fn escape_attribute(value: &str, quote: char) -> Cow<'_, str> {
+    if !value.contains(['&', '<', quote]) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 16);
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' if quote == '"' => escaped.push_str("&quot;"),
+            '\'' if quote == '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::ops::DomOp;
+    use crate::html::parser::{HtmlParser, WhitespacePolicy};
+
+    #[test]
+    fn test_standard_form() {
+        let nodes = HtmlParser::new("<!DOCTYPE html><div id='a' class=b><br/><img src='x.png'/><p hidden>Hi</p></div>")
+            .parse();
+
+        assert_eq!(
+            Serializer::default().serialize(&nodes),
+            r#"<!DOCTYPE html><div class="b" id="a"><br><img src="x.png"><p hidden="">Hi</p></div>"#
+        );
+    }
+
+    #[test]
+    fn test_round_trip_standard_form() {
+        let source = "<!-- c --><ul class=\"list\"><li title='say \"hi\"'>One</li><li>Two</li></ul>";
+        let nodes = HtmlParser::new(source).parse();
+
+        let serialized = Serializer::default().serialize(&nodes);
+
+        assert_eq!(HtmlParser::new(&serialized).parse(), nodes);
+    }
+
+    #[test]
+    fn test_escaping() {
+        let nodes = HtmlParser::new(
+            r#"<p title='say "hi" &amp; go'>a &lt; b &amp;&amp; c &gt; d</p><script>if (a < b && c) {}</script><style>a > b {}</style>"#,
+        )
+        .parse();
+
+        assert_eq!(
+            Serializer::default().serialize(&nodes),
+            r#"<p title="say &quot;hi&quot; &amp; go">a &lt; b &amp;&amp; c &gt; d</p><script>if (a < b && c) {}</script><style>a > b {}</style>"#
+        );
+    }
+
+    #[test]
+    fn test_escaping_borrows_plain_text() {
+        assert!(matches!(escape_text("plain words"), Cow::Borrowed("plain words")));
+        assert!(matches!(escape_attribute("it's", '"'), Cow::Borrowed("it's")));
+        assert_eq!(escape_attribute("it's", '\''), "it&#39;s");
+
+        let text = "lorem ipsum ".repeat(100_000);
+        let nodes = vec![Node::Element(Element {
+            tag_name: "p".to_string(),
+            children: vec![Node::Text(text.as_str().into())],
+            ..Default::default()
+        })];
+        assert_eq!(Serializer::default().serialize(&nodes), format!("<p>{}</p>", text));
+    }
+
+    #[test]
+    fn test_large_text_is_escaped_a_chunk_at_a_time() {
+        let escaped = "a &lt; b &amp;&amp; c ".repeat(20_000);
+        let nodes = HtmlParser::new(&format!("<p>{}</p>", escaped)).parse();
+        let mut out = String::new();
+
+        // The first chunk escapes to about 137 KB; the second does not fit
+        let result = Serializer::default().write(&nodes, &mut out, Some(200_000));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 200_000 }));
+        assert!(out.starts_with("<p>a &lt; b &amp;&amp; c "));
+        assert!(out.len() > 100_000);
+        assert_eq!(Serializer::default().serialize(&nodes), format!("<p>{}</p>", escaped));
+    }
+
+    #[test]
+    fn test_original_quotes_are_escaped() {
+        let source = r#"<a title='it&#39;s' href=a&amp;b class="x">"#;
+        let nodes = HtmlParser::new(source).recording_source_style().parse();
+        let opts = SerializeOpts { prefer_original_quotes: true, ..Default::default() };
+
+        let serialized = Serializer::new(opts).serialize(&nodes);
+
+        assert_eq!(serialized, r#"<a title='it&#39;s' href="a&amp;b" class="x"></a>"#);
+        assert_eq!(HtmlParser::new(&serialized).parse(), HtmlParser::new(source).parse());
+    }
+
+    #[test]
+    fn test_comments() {
+        let nodes = HtmlParser::new("<!-- a --><p>x<!--b-->y</p>").parse();
+
+        assert_eq!(Serializer::default().serialize(&nodes), "<!-- a --><p>x<!--b-->y</p>");
+        let opts = SerializeOpts { preserve_comments: false, ..Default::default() };
+        assert_eq!(Serializer::new(opts).serialize(&nodes), "<p>xy</p>");
+    }
+
+    #[test]
+    fn test_indent() {
+        let nodes = HtmlParser::new(
+            "<!DOCTYPE html><html><body><!-- nav --><ul><li>One <b>1</b></li><li></li></ul><pre><b>x</b></pre><br></body></html>",
+        )
+        .parse();
+        let opts = SerializeOpts { indent: Some(2), ..Default::default() };
+
+        let serialized = Serializer::new(opts).serialize(&nodes);
+
+        assert_eq!(
+            serialized,
+            "<!DOCTYPE html>\n<html>\n  <body>\n    <!-- nav -->\n    <ul>\n      <li>One <b>1</b></li>\n      <li></li>\n    </ul>\n    <pre><b>x</b></pre>\n    <br>\n  </body>\n</html>"
+        );
+        assert_eq!(HtmlParser::new(&serialized).parse(), nodes);
+        assert_eq!(Serializer::new(opts).serialize(&HtmlParser::new(&serialized).parse()), serialized);
+    }
+
+    #[test]
+    fn test_round_trip_benchmark_documents() {
+        for source in [include_str!("../../tests/fixtures/small.html"), include_str!("../../tests/fixtures/large.html")] {
+            let nodes = HtmlParser::new(source).parse();
+
+            for opts in [SerializeOpts::default(), SerializeOpts { indent: Some(4), ..Default::default() }] {
+                let serialized = Serializer::new(opts).serialize(&nodes);
+                assert_eq!(HtmlParser::new(&serialized).parse(), nodes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_html() {
+        let nodes = HtmlParser::new(r#"<p class="a" title='1 < 2 & "3"'>x &lt; y<br></p>"#).parse();
+        let Node::Element(p) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+
+        let expected = r#"<p class="a" title="1 &lt; 2 &amp; &quot;3&quot;">x &lt; y<br></p>"#;
+        assert_eq!(p.to_html(), expected);
+        assert_eq!(nodes[0].to_html(), expected);
+        assert_eq!(p.children[0].to_html(), "x &lt; y");
+
+        let small = HtmlParser::new(include_str!("../../tests/fixtures/small.html")).parse();
+        let html: String = small.iter().map(Node::to_html).collect();
+        assert_eq!(HtmlParser::new(&html).parse(), small);
+    }
+
+    /// Every combination of the options.
+    fn all_opts() -> Vec<SerializeOpts> {
+        let mut all = Vec::new();
+        for bits in 0..16 {
+            for indent in [None, Some(0), Some(2)] {
+                all.push(SerializeOpts {
+                    prefer_original_quotes: bits & 1 != 0,
+                    keep_self_closing_slash: bits & 2 != 0,
+                    preserve_comments: bits & 4 != 0,
+                    indent: if bits & 8 != 0 { indent } else { None },
+                });
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn test_display() {
+        let nodes = HtmlParser::new("<div><p>hello</p></div>").parse();
+        let Node::Element(div) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+
+        assert_eq!(format!("{}", nodes[0]), "<div><p>hello</p></div>");
+        assert_eq!(div.to_string(), "<div><p>hello</p></div>");
+        assert_eq!(div.children[0].to_string(), div.children[0].to_html());
+        assert_eq!(Node::Text("a < b".into()).to_string(), "a &lt; b");
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let tricky = concat!(
+            "<!DOCTYPE html>\n<!-- top -->\n<div class='a'  id=x>\n  text <b> bold </b> tail\n",
+            "  <ul>\n    <li>one\n    <li>two<br/>\n  </ul>\n  <pre>\n  keep\n    this\n</pre>\n",
+            "  <p>multi\n     line\n  text</p><!--\n  multi-line\n  comment\n-->",
+            "<script>if (a < b) {}</script>\n</div>",
+        );
+        let sources = [
+            include_str!("../../tests/fixtures/small.html"),
+            include_str!("../../tests/fixtures/large.html"),
+            tricky,
+        ];
+
+        for (source, policy, opts) in sources
+            .into_iter()
+            .flat_map(|source| [WhitespacePolicy::Drop, WhitespacePolicy::Preserve].map(|policy| (source, policy)))
+            .flat_map(|(source, policy)| all_opts().into_iter().map(move |opts| (source, policy, opts)))
+        {
+            let format = |html: &str| {
+                let nodes = HtmlParser::with_whitespace_policy(html, policy).recording_source_style().parse();
+                Serializer::new(opts).serialize(&nodes)
+            };
+            let once = format(source);
+            assert_eq!(format(&once), once, "{:?} {:?}", policy, opts);
+        }
+    }
+
+    #[test]
+    fn test_original_style_gives_minimal_diff() {
+        let source = r#"<div id='main' class="a b" hidden data-n=3><br/><img src='x.png' alt="x"/><p title='old'>Hi</p><span/></div>"#;
+        let mut nodes = HtmlParser::new(source).recording_source_style().parse();
+        let opts = SerializeOpts { prefer_original_quotes: true, keep_self_closing_slash: true, ..Default::default() };
+
+        assert_eq!(Serializer::new(opts).serialize(&nodes), source);
+
+        if let Node::Element(div) = &mut nodes[0] {
+            div.apply_ops(&[DomOp::SetAttribute {
+                path: vec![2],
+                name: "title".to_string(),
+                value: "new".to_string(),
+            }])
+            .unwrap();
+        }
+        assert_eq!(
+            Serializer::new(opts).serialize(&nodes),
+            source.replace("title='old'", "title='new'")
+        );
+    }
+
+    #[test]
+    fn test_flags_off_ignore_recorded_style() {
+        let nodes = HtmlParser::new("<p a='1'/>").recording_source_style().parse();
+
+        assert_eq!(Serializer::default().serialize(&nodes), r#"<p a="1"></p>"#);
+    }
+
+    #[test]
+    fn test_write_limit_cuts_between_pieces() {
+        let nodes = HtmlParser::new("<div><p>Hello</p><p>World</p></div>").parse();
+        let mut out = String::new();
+
+        let result = Serializer::default().write(&nodes, &mut out, Some(19));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 19 }));
+        assert_eq!(out, "<div><p>Hello</p>");
+    }
+}
@@ -0,0 +1,345 @@
+use crate::css::declaration::{lookup_key, Declaration, DeclarationList};
+use crate::css::parser::{CssParser, Rule, Selector, Specificity};
+use crate::css::stylesheet::RuleSource;
+use crate::html::parser::{Document, Element, Node};
+use crate::html::query::matches;
+use std::collections::{BTreeSet, HashMap};
+
+/// A pseudo-element with styles of its own in `ComputedStyle::pseudo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoElement {
+    Before,
+    After,
+}
+
+impl PseudoElement {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "before" => Some(PseudoElement::Before),
+            "after" => Some(PseudoElement::After),
+            _ => None,
+        }
+    }
+}
+
+/// The cascaded declarations of an element and of its pseudo-elements;
+/// see `compute_style`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ComputedStyle {
+    /// The winning declaration of each property of the element itself,
+    /// sorted by property.
+    pub element: DeclarationList,
+    /// The same for each pseudo-element that some rule targets, from rules
+    /// whose selector ends in `::before` or `::after`. `content` is kept as
+    /// written, quotes included.
+    pub pseudo: HashMap<PseudoElement, DeclarationList>,
+}
+
+/// Cascades the rules of `stylesheet` onto `element`, given its ancestors
+/// from the outermost down to its parent, as `diff_computed_styles` does.
+///
+/// Rules for `::before` and `::after` go to `pseudo` instead of `element`;
+/// the `style` attribute applies to the element only. Other pseudo-elements
+/// are ignored.
+pub fn compute_style<R>(element: &Element, ancestors: &[&Element], stylesheet: &R) -> ComputedStyle
+where
+    R: RuleSource + ?Sized,
+{
+    let rules = stylesheet.rules();
+    let sorted = |declarations: HashMap<String, Declaration>| {
+        let mut declarations: Vec<_> = declarations.into_iter().collect();
+        declarations.sort_by(|a, b| a.0.cmp(&b.0));
+        declarations.into_iter().map(|(_, declaration)| declaration).collect::<DeclarationList>()
+    };
+
+    let mut style = ComputedStyle {
+        element: sorted(cascade_declarations(element, ancestors, rules, None, |_| true)),
+        pseudo: HashMap::new(),
+    };
+    for pseudo in [PseudoElement::Before, PseudoElement::After] {
+        let declarations = cascade_declarations(element, ancestors, rules, Some(pseudo), |_| true);
+        if !declarations.is_empty() {
+            style.pseudo.insert(pseudo, sorted(declarations));
+        }
+    }
+    style
+}
+
+/// An element whose cascaded declarations differ between two stylesheets;
+/// see `diff_computed_styles`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementStyleDiff {
+    /// Child indices from the document's top-level nodes down to the
+    /// element, as in `DomOp` paths: `[1, 0]` is the first child of the
+    /// second top-level node.
+    pub path: Vec<usize>,
+    /// Each property that differs, as `(property, value with the first
+    /// stylesheet, value with the second)`, sorted by property. `None`
+    /// means no declaration applies.
+    pub changed: Vec<(String, Option<String>, Option<String>)>,
+}
+
+/// Compares the declarations that apply to each element of `document` with
+/// `rules_a` and with `rules_b` (each a `[Rule]` list or an
+/// `OwnedStylesheet`), and returns the elements where any differ,
+/// in document order. With `properties`, only those properties (ignoring
+/// ASCII case) are compared.
+///
+/// Declarations come from the `style` attribute and the matching rules,
+/// ranked by `!important`, inline or not, specificity and then source
+/// order. This is the cascade only: values are compared as written, with
+/// no inheritance, defaults or `var()` substitution, so a change to an
+/// inherited property shows up on the elements the rule matches, not on
+/// their descendants.
+pub fn diff_computed_styles<A, B>(
+    document: &Document,
+    rules_a: &A,
+    rules_b: &B,
+    properties: Option<&[&str]>,
+) -> Vec<ElementStyleDiff>
+where
+    A: RuleSource + ?Sized,
+    B: RuleSource + ?Sized,
+{
+    let (rules_a, rules_b) = (rules_a.rules(), rules_b.rules());
+    let wanted = |property: &str| {
+        properties.is_none_or(|properties| properties.iter().any(|wanted| lookup_key(wanted) == property))
+    };
+    let mut diffs = Vec::new();
+    let mut path = Vec::new();
+    diff_children(&document.children, &mut Vec::new(), &mut path, &mut |element, ancestors, path| {
+        let a = cascade(element, ancestors, rules_a, wanted);
+        let b = cascade(element, ancestors, rules_b, wanted);
+        let changed: Vec<_> = a
+            .keys()
+            .chain(b.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|property| a.get(*property) != b.get(*property))
+            .map(|property| (property.clone(), a.get(property).cloned(), b.get(property).cloned()))
+            .collect();
+        if !changed.is_empty() {
+            diffs.push(ElementStyleDiff { path: path.to_vec(), changed });
+        }
+    });
+    diffs
+}
+
+fn diff_children<'n>(
+    nodes: &'n [Node],
+    ancestors: &mut Vec<&'n Element>,
+    path: &mut Vec<usize>,
+    visit: &mut impl FnMut(&Element, &[&Element], &[usize]),
+) {
+    for (index, node) in nodes.iter().enumerate() {
+        let Node::Element(element) = node else {
+            continue;
+        };
+        path.push(index);
+        visit(element, ancestors, path);
+        ancestors.push(element);
+        diff_children(&element.children, ancestors, path, visit);
+        ancestors.pop();
+        path.pop();
+    }
+}
+
+/// Where a declaration ranks in the cascade, lowest first: (important,
+/// from a `style` attribute, specificity, source order).
+type Precedence = (bool, bool, Specificity, usize);
+
+/// The winning value of each property of `element` that `wanted` accepts,
+/// from its `style` attribute and the rules of `stylesheet` that match it.
+/// Properties are keyed lower-cased, except custom properties; `wanted`
+/// sees the same keys.
+pub(crate) fn cascade(
+    element: &Element,
+    ancestors: &[&Element],
+    stylesheet: &[Rule],
+    wanted: impl Fn(&str) -> bool,
+) -> HashMap<String, String> {
+    cascade_declarations(element, ancestors, stylesheet, None, wanted)
+        .into_iter()
+        .map(|(property, declaration)| (property, declaration.value))
+        .collect()
+}
+
+/// Like `cascade`, but keeps the winning declarations, and with `pseudo`
+/// cascades the rules for that pseudo-element of `element` instead.
+fn cascade_declarations(
+    element: &Element,
+    ancestors: &[&Element],
+    stylesheet: &[Rule],
+    pseudo: Option<PseudoElement>,
+    wanted: impl Fn(&str) -> bool,
+) -> HashMap<String, Declaration> {
+    let inline = match (pseudo, element.attr("style")) {
+        (None, Some(style)) => CssParser::new(style).parse_inline_style(),
+        _ => Default::default(),
+    };
+    let matches_target = |selector: &Selector| match pseudo {
+        None => matches(selector, element, ancestors),
+        Some(pseudo) => split_pseudo_element(selector)
+            .is_some_and(|(subject, target)| target == pseudo && matches(&subject, element, ancestors)),
+    };
+
+    let mut candidates: Vec<(Precedence, &Declaration)> = Vec::new();
+    for (order, rule) in stylesheet.iter().enumerate() {
+        let specificity = rule
+            .selectors
+            .iter()
+            .filter(|selector| matches_target(selector))
+            .map(|selector| selector.specificity())
+            .max();
+        let Some(specificity) = specificity else {
+            continue;
+        };
+        for declaration in &rule.declarations {
+            candidates.push(((declaration.important, false, specificity, order), declaration));
+        }
+    }
+    for declaration in &inline {
+        candidates.push(((declaration.important, true, Specificity::default(), usize::MAX), declaration));
+    }
+
+    let mut winners: HashMap<String, (Precedence, &Declaration)> = HashMap::new();
+    for (rank, declaration) in candidates {
+        let key = lookup_key(&declaration.property);
+        if !wanted(&key) {
+            continue;
+        }
+        match winners.get_mut(key.as_ref()) {
+            Some(winner) if rank < winner.0 => {}
+            Some(winner) => *winner = (rank, declaration),
+            None => {
+                winners.insert(key.into_owned(), (rank, declaration));
+            }
+        }
+    }
+
+    winners.into_iter().map(|(property, (_, declaration))| (property, declaration.clone())).collect()
+}
+
+/// Splits a selector ending in `::before` or `::after` into the selector
+/// for the element the pseudo-element belongs to, and the pseudo-element.
+fn split_pseudo_element(selector: &Selector) -> Option<(Selector, PseudoElement)> {
+    let rebuild = |right: &Selector, combine: &dyn Fn(Box<Selector>) -> Selector| {
+        split_pseudo_element(right).map(|(subject, pseudo)| (combine(Box::new(subject)), pseudo))
+    };
+    match selector {
+        Selector::PseudoElement(name) => Some((Selector::Universal, PseudoElement::from_name(name)?)),
+        Selector::Compound(parts) => {
+            let (Selector::PseudoElement(name), rest) = parts.split_last()? else {
+                return None;
+            };
+            let subject = match rest {
+                [] => Selector::Universal,
+                [part] => part.clone(),
+                parts => Selector::Compound(parts.to_vec()),
+            };
+            Some((subject, PseudoElement::from_name(name)?))
+        }
+        Selector::Descendant(left, right) => rebuild(right, &|r| Selector::Descendant(left.clone(), r)),
+        Selector::Child(left, right) => rebuild(right, &|r| Selector::Child(left.clone(), r)),
+        Selector::Adjacent(left, right) => rebuild(right, &|r| Selector::Adjacent(left.clone(), r)),
+        Selector::GeneralSibling(left, right) => rebuild(right, &|r| Selector::GeneralSibling(left.clone(), r)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::stylesheet::OwnedStylesheet;
+    use crate::html::parser::HtmlParser;
+
+    const HTML: &str = r#"<main><p class="note">a</p><p>b</p><ul><li class="note">c</li></ul></main>"#;
+
+    #[test]
+    fn test_changed_class_color() {
+        let document = HtmlParser::new(HTML).parse_document();
+        let before = CssParser::new(".note { color: red; margin: 0 } p { color: black }").parse();
+        let after = CssParser::new(".note { color: blue; margin: 0 } p { color: black }").parse();
+
+        assert_eq!(
+            diff_computed_styles(&document, &before, &after, None),
+            vec![
+                ElementStyleDiff {
+                    path: vec![0, 0],
+                    changed: vec![("color".to_string(), Some("red".to_string()), Some("blue".to_string()))],
+                },
+                ElementStyleDiff {
+                    path: vec![0, 2, 0],
+                    changed: vec![("color".to_string(), Some("red".to_string()), Some("blue".to_string()))],
+                },
+            ]
+        );
+
+        let shared = OwnedStylesheet::parse(".note { color: blue; margin: 0 } p { color: black }");
+        assert_eq!(
+            diff_computed_styles(&document, &before, &*shared, None),
+            diff_computed_styles(&document, &before, &after, None)
+        );
+    }
+
+    #[test]
+    fn test_unrelated_change_reports_nothing() {
+        let document = HtmlParser::new(HTML).parse_document();
+        let before = CssParser::new(".note { color: red } table { border: 0 }").parse();
+        let after = CssParser::new(".note { color: red } table { border: 1px solid }").parse();
+
+        assert!(diff_computed_styles(&document, &before, &after, None).is_empty());
+    }
+
+    #[test]
+    fn test_cascade_order_and_allowlist() {
+        let document = HtmlParser::new(r#"<p class="a" style="margin: 1px">x</p>"#).parse_document();
+        let before = CssParser::new("p { color: red !important; margin: 0 } .a { color: green }").parse();
+        let after = CssParser::new("p { color: red; margin: 0 } .a { color: green; padding: 2px }").parse();
+
+        let diffs = diff_computed_styles(&document, &before, &after, None);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].changed,
+            vec![
+                ("color".to_string(), Some("red".to_string()), Some("green".to_string())),
+                ("padding".to_string(), None, Some("2px".to_string())),
+            ]
+        );
+
+        let diffs = diff_computed_styles(&document, &before, &after, Some(&["Padding", "margin"]));
+        assert_eq!(diffs[0].changed, vec![("padding".to_string(), None, Some("2px".to_string()))]);
+    }
+
+    #[test]
+    fn test_compute_style_pseudo_elements() {
+        let document = HtmlParser::new(r#"<nav><span class="badge" style="color: blue">3</span></nav>"#).parse_document();
+        let nav = document.root_element().unwrap();
+        let Node::Element(badge) = &nav.children[0] else {
+            panic!("expected the badge");
+        };
+        let stylesheet = CssParser::new(
+            ".badge::after { content: \"!\"; color: red } \
+             nav > .badge::before { content: \"#\" } \
+             span::after { content: \"?\" } \
+             .badge { color: green; font-weight: bold } \
+             .badge::first-line { color: gray }",
+        )
+        .parse();
+
+        let style = compute_style(badge, &[nav], &stylesheet);
+
+        assert_eq!(
+            style.element,
+            vec![Declaration::new("color", "blue"), Declaration::new("font-weight", "bold")]
+        );
+        assert!(style.element.get("content").is_none());
+        assert_eq!(style.pseudo.len(), 2);
+        assert_eq!(
+            style.pseudo[&PseudoElement::After],
+            vec![Declaration::new("color", "red"), Declaration::new("content", "\"!\"")]
+        );
+        assert_eq!(style.pseudo[&PseudoElement::Before], vec![Declaration::new("content", "\"#\"")]);
+        assert!(compute_style(nav, &[], &stylesheet).pseudo.is_empty());
+    }
+}
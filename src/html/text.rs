@@ -0,0 +1,320 @@
+use crate::html::parser::{Document, Element, Node};
+use crate::output::{write_limited, OutputError};
+use std::fmt;
+
+impl Node {
+    /// Returns the concatenated text of this node and its descendants.
+    /// Comments and doctypes contribute nothing.
+    pub fn text_content(&self) -> String {
+        let mut out = String::new();
+        let _ = write_text(self, &mut out);
+        out
+    }
+
+    /// Writes the text content into `out`, failing with
+    /// `OutputError::LimitExceeded` instead of writing more than `limit`
+    /// bytes. Text nodes are written whole or not at all, a chunked one
+    /// (see `Text`) a chunk at a time.
+    pub fn write_text_content<W: fmt::Write + ?Sized>(
+        &self,
+        out: &mut W,
+        limit: Option<usize>,
+    ) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_text(self, w))
+    }
+}
+
+impl Element {
+    /// Returns the concatenated text of all descendant text nodes.
+    pub fn text_content(&self) -> String {
+        let mut out = String::new();
+        let _ = write_children_text(&self.children, &mut out);
+        out
+    }
+
+    /// Writer-based form of `text_content`; see `Node::write_text_content`.
+    pub fn write_text_content<W: fmt::Write + ?Sized>(
+        &self,
+        out: &mut W,
+        limit: Option<usize>,
+    ) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_children_text(&self.children, w))
+    }
+}
+
+impl Document {
+    /// Returns the concatenated text of every text node in the document.
+    pub fn text_content(&self) -> String {
+        let mut out = String::new();
+        let _ = write_children_text(&self.children, &mut out);
+        out
+    }
+
+    /// Writer-based form of `text_content`; see `Node::write_text_content`.
+    pub fn write_text_content<W: fmt::Write + ?Sized>(
+        &self,
+        out: &mut W,
+        limit: Option<usize>,
+    ) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_children_text(&self.children, w))
+    }
+}
+
+/// Options for `find_text`. The default is a case-sensitive search within
+/// single text nodes, outside `<script>` and `<style>`, with no context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FindOptions {
+    /// Compare characters case-insensitively.
+    pub ignore_case: bool,
+    /// Let a match continue from one text node into the next when they are
+    /// adjacent siblings.
+    pub across_nodes: bool,
+    /// Treat every run of whitespace, in the text and in the needle, as a
+    /// single space.
+    pub collapse_whitespace: bool,
+    /// Also search text inside `<script>` and `<style>` elements.
+    pub include_script_style: bool,
+    /// How many characters of context to include on each side of a match
+    /// in `TextMatch::surrounding`.
+    pub context_chars: usize,
+}
+
+/// A match found by `find_text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextMatch {
+    /// Child indices from the searched nodes down to the element holding
+    /// the text; empty for text at the root.
+    pub element_path: Vec<usize>,
+    /// Byte offset of the match in the text node it starts in.
+    pub offset_in_text: usize,
+    /// The match with up to `context_chars` characters on each side, with
+    /// whitespace collapsed if `collapse_whitespace` is set.
+    pub surrounding: String,
+}
+
+/// Finds the non-overlapping occurrences of `needle` in the text nodes of
+/// `nodes` and their descendants, in document order. An empty needle
+/// matches nothing.
+pub fn find_text(nodes: &[Node], needle: &str, options: FindOptions) -> Vec<TextMatch> {
+    let needle: Vec<char> = searchable_chars(std::iter::once(needle), options)
+        .into_iter()
+        .map(|(ch, _)| ch)
+        .collect();
+    let mut matches = Vec::new();
+    if !needle.is_empty() {
+        find_in_children(nodes, &needle, options, &mut Vec::new(), &mut matches);
+    }
+    matches
+}
+
+fn find_in_children(
+    children: &[Node],
+    needle: &[char],
+    options: FindOptions,
+    path: &mut Vec<usize>,
+    matches: &mut Vec<TextMatch>,
+) {
+    let mut index = 0;
+    while index < children.len() {
+        match &children[index] {
+            Node::Element(element) => {
+                let raw_text = element.tag_name.eq_ignore_ascii_case("script")
+                    || element.tag_name.eq_ignore_ascii_case("style");
+                if options.include_script_style || !raw_text {
+                    path.push(index);
+                    find_in_children(&element.children, needle, options, path, matches);
+                    path.pop();
+                }
+                index += 1;
+            }
+            Node::Text(_) => {
+                // The run of text nodes searched as one
+                let mut run = Vec::new();
+                while let Some(Node::Text(text)) = children.get(index) {
+                    run.push(text.as_str());
+                    index += 1;
+                    if !options.across_nodes {
+                        break;
+                    }
+                }
+                find_in_run(&run, needle, options, path, matches);
+            }
+            Node::Comment(_) | Node::Doctype(_) => index += 1,
+        }
+    }
+}
+
+fn find_in_run(run: &[&str], needle: &[char], options: FindOptions, path: &[usize], matches: &mut Vec<TextMatch>) {
+    let haystack = searchable_chars(run.iter().copied(), options);
+    let same = |a: char, b: char| {
+        a == b || (options.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    };
+
+    let mut start = 0;
+    while start + needle.len() <= haystack.len() {
+        let window = &haystack[start..start + needle.len()];
+        if !window.iter().zip(needle).all(|(&(a, _), &b)| same(a, b)) {
+            start += 1;
+            continue;
+        }
+        let end = start + needle.len();
+        let (_, offset_in_text) = haystack[start];
+        let context_start = start.saturating_sub(options.context_chars);
+        let context_end = (end + options.context_chars).min(haystack.len());
+        matches.push(TextMatch {
+            element_path: path.to_vec(),
+            offset_in_text,
+            surrounding: haystack[context_start..context_end].iter().map(|&(ch, _)| ch).collect(),
+        });
+        start = end;
+    }
+}
+
+/// The characters of `texts` as searched, each with its byte offset in the
+/// text it came from.
+fn searchable_chars<'t>(texts: impl Iterator<Item = &'t str>, options: FindOptions) -> Vec<(char, usize)> {
+    let mut chars: Vec<(char, usize)> = Vec::new();
+    for text in texts {
+        for (offset, ch) in text.char_indices() {
+            if options.collapse_whitespace && ch.is_whitespace() {
+                if chars.last().is_some_and(|&(last, _)| last == ' ') {
+                    continue;
+                }
+                chars.push((' ', offset));
+            } else {
+                chars.push((ch, offset));
+            }
+        }
+    }
+    chars
+}
+
+fn write_text<W: fmt::Write + ?Sized>(node: &Node, out: &mut W) -> fmt::Result {
+    match node {
+        Node::Element(element) => write_children_text(&element.children, out),
+        Node::Text(text) => text.chunks().try_for_each(|chunk| out.write_str(chunk)),
+        Node::Comment(_) | Node::Doctype(_) => Ok(()),
+    }
+}
+
+fn write_children_text<W: fmt::Write + ?Sized>(children: &[Node], out: &mut W) -> fmt::Result {
+    for child in children {
+        write_text(child, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    #[test]
+    fn test_text_content() {
+        let document = HtmlParser::new("<div>Hello <!-- x --><b>big</b>, world</div>").parse_document();
+
+        assert_eq!(document.text_content(), "Hello big, world");
+        assert_eq!(document.children[0].text_content(), "Hello big, world");
+    }
+
+    fn element(tag_name: &str, children: Vec<Node>) -> Node {
+        Node::Element(Element {
+            tag_name: tag_name.to_string(),
+            attributes: Default::default(),
+            children,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_large_text_is_written_a_chunk_at_a_time() {
+        struct Pieces(usize, usize);
+        impl fmt::Write for Pieces {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0 += s.len();
+                self.1 = self.1.max(s.len());
+                Ok(())
+            }
+        }
+        let text = "lorem ipsum ".repeat(100_000);
+        let node = element("p", vec![Node::Text(text.as_str().into())]);
+
+        let mut pieces = Pieces(0, 0);
+        node.write_text_content(&mut pieces, None).unwrap();
+
+        assert_eq!(pieces.0, text.len());
+        assert!(pieces.1 <= 64 * 1024);
+        assert_eq!(node.text_content(), text);
+    }
+
+    #[test]
+    fn test_find_text_across_adjacent_nodes() {
+        // As if `caf&eacute; au lait` had been decoded into two text nodes
+        let nodes = vec![element("div", vec![
+            element("p", vec![Node::Text("café".into()), Node::Text(" au\n  lait".into())]),
+        ])];
+        let options = FindOptions { across_nodes: true, collapse_whitespace: true, context_chars: 2, ..Default::default() };
+
+        let matches = find_text(&nodes, "é au lait", options);
+
+        assert_eq!(
+            matches,
+            vec![TextMatch { element_path: vec![0, 0], offset_in_text: 3, surrounding: "afé au lait".to_string() }]
+        );
+        assert!(find_text(&nodes, "é au", FindOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_find_text_ignoring_case() {
+        let nodes = HtmlParser::new("<p>Rust and RUST and rust</p>text rust").parse();
+        let options = FindOptions { ignore_case: true, context_chars: 4, ..Default::default() };
+
+        let matches = find_text(&nodes, "rust", options);
+
+        let found: Vec<_> = matches.iter().map(|m| (m.element_path.clone(), m.offset_in_text, m.surrounding.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (vec![0], 0, "Rust and"),
+                (vec![0], 9, "and RUST and"),
+                (vec![0], 18, "and rust"),
+                (vec![], 5, "ext rust"),
+            ]
+        );
+        assert_eq!(find_text(&nodes, "rust", FindOptions::default()).len(), 2);
+    }
+
+    #[test]
+    fn test_find_text_skips_script_and_style() {
+        let nodes = HtmlParser::new("<div><script>var needle;</script><STYLE>.needle {}</STYLE><p>needle</p></div>").parse();
+
+        let paths: Vec<_> = find_text(&nodes, "needle", FindOptions::default())
+            .into_iter()
+            .map(|m| m.element_path)
+            .collect();
+        assert_eq!(paths, vec![vec![0, 2]]);
+
+        let options = FindOptions { include_script_style: true, ..Default::default() };
+        assert_eq!(find_text(&nodes, "needle", options).len(), 3);
+    }
+
+    #[test]
+    fn test_text_content_limit() {
+        let document = HtmlParser::new("<p>one </p><p>two </p><p>three</p>").parse_document();
+        let mut out = String::new();
+
+        let result = document.write_text_content(&mut out, Some(10));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 10 }));
+        assert_eq!(out, "one two ");
+    }
+
+    #[test]
+    fn test_text_content_within_limit() {
+        let root = HtmlParser::new("<p>short</p>").parse().remove(0);
+        let mut out = String::from("> ");
+
+        assert_eq!(root.write_text_content(&mut out, Some(5)), Ok(()));
+        assert_eq!(out, "> short");
+    }
+}
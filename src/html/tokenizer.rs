@@ -1,3 +1,27 @@
+#[cfg(any(feature = "html-dom", feature = "locations"))]
+use crate::location::Span;
+use crate::location::{LineCounter, SourceLocation};
+use std::collections::HashMap;
+
+/// How an attribute value was written in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuoteStyle {
+    Double,
+    Single,
+    Unquoted,
+    /// A bare attribute name with no `=value`, such as `hidden`.
+    NoValue,
+}
+
+/// The delimiters of a template language, such as `{{` and `}}` for
+/// Handlebars or `{%` and `%}` for Jinja statements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateDelims {
+    pub open: String,
+    pub close: String,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum HtmlToken<'a> {
     StartTag {
@@ -15,21 +39,118 @@ pub enum HtmlToken<'a> {
 
 pub struct HtmlTokenizer<'a> {
     input: &'a str,
+    /// Byte offset of the next unconsumed character; always on a char
+    /// boundary.
     position: usize,
+    lines: LineCounter,
+    token_start: SourceLocation,
+    attribute_locations: Vec<SourceLocation>,
+    attribute_quotes: Vec<QuoteStyle>,
+    preserve_whitespace: bool,
+    dots_in_names: bool,
+    /// Set after the start tag of a raw-text element: its content up to the
+    /// matching end tag is one `Text` token.
+    raw_text_element: Option<&'a str>,
+    /// Whether `<script>` content follows the script data escape states;
+    /// see `ignoring_script_escapes`.
+    script_escapes: bool,
+    /// Template spans read as opaque text; see `with_template_syntax`.
+    template_syntax: Option<TemplateDelims>,
+    /// Where the template spans met by failed scans end, or `None` for
+    /// those left unclosed, by where they start; see `template_len`.
+    template_ends: HashMap<usize, Option<usize>>,
 }
 
+/// Elements whose content is text up to their end tag, never markup.
+pub(crate) const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
 impl<'a> HtmlTokenizer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Self { input, position: 0 }
+        Self {
+            input,
+            position: 0,
+            lines: LineCounter::new(),
+            token_start: SourceLocation::START,
+            attribute_locations: Vec::new(),
+            attribute_quotes: Vec::new(),
+            preserve_whitespace: false,
+            dots_in_names: false,
+            raw_text_element: None,
+            script_escapes: true,
+            template_syntax: None,
+            template_ends: HashMap::new(),
+        }
+    }
+
+    /// Creates a tokenizer that keeps whitespace between tags as `Text`
+    /// tokens instead of skipping it.
+    pub fn preserving_whitespace(input: &'a str) -> Self {
+        Self { preserve_whitespace: true, ..Self::new(input) }
+    }
+
+    /// Makes the tokenizer accept `.` in tag and attribute names, as some
+    /// template syntaxes use (`<my.widget>`, `@click.prevent`). `:` is
+    /// always accepted, for prefixed names such as `fb:like`.
+    pub fn allowing_dots_in_names(mut self) -> Self {
+        self.dots_in_names = true;
+        self
+    }
+
+    /// Makes `<script>` content end at the first `</script>`, like the other
+    /// raw-text elements. By default it follows the script data escape
+    /// states of HTML, as browsers do: inside `<!-- ... -->` a `<script>`
+    /// starts a region in which `</script>` does not end the element, for
+    /// legacy scripts that write out `<script>` tags of their own.
+    pub fn ignoring_script_escapes(mut self) -> Self {
+        self.script_escapes = false;
+        self
+    }
+
+    /// Makes the tokenizer read spans from `delims.open` to the matching
+    /// `delims.close`, nested ones included, as opaque text in attribute
+    /// names, attribute values and text, so that `value={{x y}}` or
+    /// `{{#if a < b}}` stay whole. A span in place of an attribute name,
+    /// such as `{{#if x}}`, is an attribute of its own. Unclosed spans and
+    /// empty delimiters are read as ordinary characters.
+    pub fn with_template_syntax(mut self, delims: TemplateDelims) -> Self {
+        self.template_syntax = Some(delims);
+        self
+    }
+
+    /// Returns where the most recently emitted token starts.
+    pub fn location(&self) -> SourceLocation {
+        self.token_start
+    }
+
+    /// Returns where each attribute of the most recent start tag starts, in
+    /// the same order as the token's `attributes`.
+    pub fn attribute_locations(&self) -> &[SourceLocation] {
+        &self.attribute_locations
+    }
+
+    /// Returns how each attribute value of the most recent start tag was
+    /// quoted, in the same order as the token's `attributes`.
+    pub fn attribute_quotes(&self) -> &[QuoteStyle] {
+        &self.attribute_quotes
     }
 
     pub fn next_token(&mut self) -> Option<HtmlToken<'a>> {
-        self.skip_whitespace();
+        if !self.preserve_whitespace {
+            self.skip_whitespace();
+        }
         
         if self.position >= self.input.len() {
             return None;
         }
 
+        self.token_start = self.current_location();
+
+        if let Some(name) = self.raw_text_element.take()
+            && let Some(text) = self.parse_raw_text(name)
+        {
+            return Some(text);
+        }
+
         let current_char = self.current_char()?;
         
         if current_char == '<' {
@@ -40,13 +161,86 @@ impl<'a> HtmlTokenizer<'a> {
     }
 
     fn current_char(&self) -> Option<char> {
-        self.input.chars().nth(self.position)
+        self.input[self.position..].chars().next()
     }
 
     fn advance(&mut self) {
-        if self.position < self.input.len() {
-            self.position += 1;
+        if let Some(ch) = self.current_char() {
+            self.lines.advance(ch);
+            self.position += ch.len_utf8();
+        }
+    }
+
+    fn advance_to_end(&mut self) {
+        while self.position < self.input.len() {
+            self.advance();
+        }
+    }
+
+    fn current_location(&self) -> SourceLocation {
+        self.lines.location(self.position)
+    }
+
+    /// The span of the most recently emitted token.
+    #[cfg(any(feature = "html-dom", feature = "locations"))]
+    pub(crate) fn token_span(&self) -> Span {
+        Span {
+            start: self.token_start,
+            end: self.current_location(),
+        }
+    }
+
+    /// Returns the length of the template span at the current position,
+    /// up to and including its matching close delimiter.
+    ///
+    /// A scan that finds no matching close reads the rest of the input, so
+    /// it records where each span it passed ends. Scanning again from one
+    /// of them, as happens on every `{{` of `{{{{{{...`, is then a lookup
+    /// instead of another read to the end.
+    fn template_len(&mut self) -> Option<usize> {
+        let TemplateDelims { open, close } = self.template_syntax.as_ref()?;
+        let start = self.position;
+        if open.is_empty() || close.is_empty() || !self.input[start..].starts_with(open.as_str()) {
+            return None;
+        }
+        if let Some(end) = self.template_ends.get(&start) {
+            return end.map(|end| end - start);
+        }
+        // The starts of the spans not closed yet, innermost last
+        let mut unclosed = Vec::new();
+        let mut ends = Vec::new();
+        let mut index = start;
+        while let Some(ch) = self.input[index..].chars().next() {
+            // The close is tried first so that equal delimiters do not nest
+            if !unclosed.is_empty() && self.input[index..].starts_with(close.as_str()) {
+                index += close.len();
+                let span_start = unclosed.pop();
+                if unclosed.is_empty() {
+                    return Some(index - start);
+                }
+                ends.extend(span_start.map(|span_start| (span_start, Some(index))));
+            } else if self.input[index..].starts_with(open.as_str()) {
+                unclosed.push(index);
+                index += open.len();
+            } else {
+                index += ch.len_utf8();
+            }
         }
+        self.template_ends.extend(ends);
+        self.template_ends.extend(unclosed.into_iter().map(|span_start| (span_start, None)));
+        None
+    }
+
+    /// Consumes the template span at the current position, if any.
+    fn skip_template(&mut self) -> bool {
+        let Some(len) = self.template_len() else {
+            return false;
+        };
+        let end = self.position + len;
+        while self.position < end {
+            self.advance();
+        }
+        true
     }
 
     fn skip_whitespace(&mut self) {
@@ -60,7 +254,7 @@ impl<'a> HtmlTokenizer<'a> {
     }
 
     fn parse_tag_or_comment(&mut self) -> Option<HtmlToken<'a>> {
-        let start_pos = self.position;
+        let start = (self.position, self.lines);
         self.advance(); // Skip '<'
 
         // Check for comment
@@ -69,7 +263,10 @@ impl<'a> HtmlTokenizer<'a> {
         }
 
         // Check for doctype
-        if self.input[self.position..].to_lowercase().starts_with("!doctype") {
+        if self.input[self.position..]
+            .get(.."!doctype".len())
+            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("!doctype"))
+        {
             return self.parse_doctype();
         }
 
@@ -82,7 +279,7 @@ impl<'a> HtmlTokenizer<'a> {
         // Parse tag name
         let name_start = self.position;
         while let Some(ch) = self.current_char() {
-            if ch.is_alphanumeric() || ch == '-' || ch == '_' {
+            if self.is_name_char(ch) {
                 self.advance();
             } else {
                 break;
@@ -91,7 +288,7 @@ impl<'a> HtmlTokenizer<'a> {
 
         if name_start == self.position {
             // Invalid tag, treat as text
-            self.position = start_pos;
+            (self.position, self.lines) = start;
             return self.parse_text();
         }
 
@@ -112,6 +309,8 @@ impl<'a> HtmlTokenizer<'a> {
         // Parse attributes
         let mut attributes = Vec::new();
         let mut self_closing = false;
+        self.attribute_locations.clear();
+        self.attribute_quotes.clear();
 
         loop {
             self.skip_whitespace();
@@ -130,14 +329,23 @@ impl<'a> HtmlTokenizer<'a> {
                     }
                 }
                 Some(_) => {
-                    if let Some((attr_name, attr_value)) = self.parse_attribute() {
+                    let attr_start = self.current_location();
+                    if let Some((attr_name, attr_value, quote)) = self.parse_attribute() {
                         attributes.push((attr_name, attr_value));
+                        self.attribute_locations.push(attr_start);
+                        self.attribute_quotes.push(quote);
+                    } else {
+                        self.advance(); // Skip a character that cannot start a name
                     }
                 }
                 None => break,
             }
         }
 
+        if !self_closing && RAW_TEXT_ELEMENTS.iter().any(|raw| name.eq_ignore_ascii_case(raw)) {
+            self.raw_text_element = Some(name);
+        }
+
         Some(HtmlToken::StartTag {
             name,
             attributes,
@@ -145,14 +353,23 @@ impl<'a> HtmlTokenizer<'a> {
         })
     }
 
-    fn parse_attribute(&mut self) -> Option<(&'a str, &'a str)> {
-        // Parse attribute name
+    /// Characters of tag and attribute names: alphanumerics, `-`, `_`, `:`
+    /// for prefixed names such as `xlink:href`, and `.` if enabled.
+    fn is_name_char(&self, ch: char) -> bool {
+        ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == ':' || (ch == '.' && self.dots_in_names)
+    }
+
+    fn parse_attribute(&mut self) -> Option<(&'a str, &'a str, QuoteStyle)> {
+        // Parse attribute name. A template span of its own is a whole
+        // name; after a name character, spans are part of it (`data-{{x}}`)
         let name_start = self.position;
-        while let Some(ch) = self.current_char() {
-            if ch.is_alphanumeric() || ch == '-' || ch == '_' {
-                self.advance();
-            } else {
-                break;
+        if !self.skip_template() {
+            while let Some(ch) = self.current_char() {
+                if self.is_name_char(ch) {
+                    self.advance();
+                } else if !(self.position > name_start && self.skip_template()) {
+                    break;
+                }
             }
         }
 
@@ -166,7 +383,7 @@ impl<'a> HtmlTokenizer<'a> {
 
         // Check for '='
         if self.current_char() != Some('=') {
-            return Some((name, ""));
+            return Some((name, "", QuoteStyle::NoValue));
         }
         
         self.advance(); // Skip '='
@@ -174,15 +391,23 @@ impl<'a> HtmlTokenizer<'a> {
 
         // Parse attribute value
         let quote_char = self.current_char();
-        let value = if quote_char == Some('"') || quote_char == Some('\'') {
+        let quote = match quote_char {
+            Some('"') => QuoteStyle::Double,
+            Some('\'') => QuoteStyle::Single,
+            _ => QuoteStyle::Unquoted,
+        };
+        let value = if quote != QuoteStyle::Unquoted {
             self.advance(); // Skip opening quote
             let value_start = self.position;
             
             while let Some(ch) = self.current_char() {
+                if self.skip_template() {
+                    continue;
+                }
                 if ch == quote_char.unwrap() {
                     let value = &self.input[value_start..self.position];
                     self.advance(); // Skip closing quote
-                    return Some((name, value));
+                    return Some((name, value, quote));
                 }
                 self.advance();
             }
@@ -192,6 +417,9 @@ impl<'a> HtmlTokenizer<'a> {
             // Unquoted value
             let value_start = self.position;
             while let Some(ch) = self.current_char() {
+                if self.skip_template() {
+                    continue;
+                }
                 if ch.is_whitespace() || ch == '>' || ch == '/' {
                     break;
                 }
@@ -200,17 +428,21 @@ impl<'a> HtmlTokenizer<'a> {
             &self.input[value_start..self.position]
         };
 
-        Some((name, value))
+        Some((name, value, quote))
     }
 
     fn parse_comment(&mut self) -> Option<HtmlToken<'a>> {
-        self.position += 3; // Skip "!--"
+        for _ in 0..3 {
+            self.advance(); // Skip "!--"
+        }
         let content_start = self.position;
 
-        while self.position + 2 < self.input.len() {
-            if &self.input[self.position..self.position + 3] == "-->" {
+        while self.position < self.input.len() {
+            if self.input[self.position..].starts_with("-->") {
                 let content = &self.input[content_start..self.position];
-                self.position += 3; // Skip "-->"
+                for _ in 0..3 {
+                    self.advance(); // Skip "-->"
+                }
                 return Some(HtmlToken::Comment(content));
             }
             self.advance();
@@ -218,7 +450,7 @@ impl<'a> HtmlTokenizer<'a> {
 
         // Unclosed comment
         let content = &self.input[content_start..];
-        self.position = self.input.len();
+        self.advance_to_end();
         Some(HtmlToken::Comment(content))
     }
 
@@ -236,14 +468,35 @@ impl<'a> HtmlTokenizer<'a> {
 
         // Unclosed doctype
         let content = &self.input[start..];
-        self.position = self.input.len();
+        self.advance_to_end();
         Some(HtmlToken::Doctype(content))
     }
 
+    /// Consumes the content of a raw-text element up to its end tag,
+    /// `</name` in any case followed by whitespace, `/` or `>`, or up to the
+    /// end of the input. Returns `None`, consuming nothing, if the end tag
+    /// comes first.
+    fn parse_raw_text(&mut self, name: &str) -> Option<HtmlToken<'a>> {
+        let start = self.position;
+        let rest = &self.input[start..];
+        let end = RawTextScan::new(name, self.script_escapes).find_end(rest, true).unwrap_or(rest.len());
+
+        if end == 0 {
+            return None;
+        }
+        while self.position < start + end {
+            self.advance();
+        }
+        Some(HtmlToken::Text(&self.input[start..self.position]))
+    }
+
     fn parse_text(&mut self) -> Option<HtmlToken<'a>> {
         let start = self.position;
         
         while let Some(ch) = self.current_char() {
+            if self.skip_template() {
+                continue;
+            }
             if ch == '<' {
                 break;
             }
@@ -259,6 +512,127 @@ impl<'a> HtmlTokenizer<'a> {
     }
 }
 
+/// A search for the end tag of a raw-text element, which can go on where
+/// it stopped when more of the content arrives.
+///
+/// `<script>` content follows the script data states of HTML unless
+/// escapes are ignored: after `<!--` the script is escaped, and a
+/// `<script` there starts a double-escaped region that only `</script`
+/// (back to escaped) or `-->` (back to plain script data) leaves. `-->`
+/// also ends the escaped state.
+#[derive(Debug, Clone)]
+pub(crate) struct RawTextScan {
+    name: String,
+    script: bool,
+    state: ScriptState,
+    /// Dashes just before `index`, inside an escape.
+    dashes: usize,
+    /// How much of the content has been searched.
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptState {
+    Data,
+    Escaped,
+    DoubleEscaped,
+}
+
+impl RawTextScan {
+    pub(crate) fn new(name: &str, script_escapes: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            script: script_escapes && name.eq_ignore_ascii_case("script"),
+            state: ScriptState::Data,
+            dashes: 0,
+            index: 0,
+        }
+    }
+
+    /// Searches `content`, the content read so far, for the end tag and
+    /// returns where it starts. Unless `end_of_input`, the last few bytes,
+    /// where a tag may be cut off, are left to search once more content
+    /// has been added to the same `content`.
+    pub(crate) fn find_end(&mut self, content: &str, end_of_input: bool) -> Option<usize> {
+        let text = content.as_bytes();
+        // `</name`, then up to four bytes of whitespace
+        let stop = if end_of_input { text.len() } else { text.len().saturating_sub(self.name.len() + 6) };
+        while self.index < stop {
+            let rest = &text[self.index..];
+            if !self.script {
+                if rest[0] == b'<' && is_end_tag(&content[self.index..], &self.name) {
+                    return Some(self.index);
+                }
+                self.index += 1;
+                continue;
+            }
+            match self.state {
+                ScriptState::Data | ScriptState::Escaped if is_script_tag(rest, b"</") => return Some(self.index),
+                ScriptState::Data if rest.starts_with(b"<!--") => {
+                    // `<!--` ends with two dashes, so `<!-->` is escaped and
+                    // closed at once
+                    self.state = ScriptState::Escaped;
+                    self.dashes = 2;
+                    self.index += 4;
+                    continue;
+                }
+                ScriptState::Escaped if is_script_tag(rest, b"<") => {
+                    self.state = ScriptState::DoubleEscaped;
+                    self.dashes = 0;
+                    self.index += b"<script".len();
+                    continue;
+                }
+                ScriptState::DoubleEscaped if is_script_tag(rest, b"</") => {
+                    self.state = ScriptState::Escaped;
+                    self.dashes = 0;
+                    self.index += b"</script".len();
+                    continue;
+                }
+                _ => {}
+            }
+            match text[self.index] {
+                b'-' if self.state != ScriptState::Data => self.dashes += 1,
+                b'>' if self.dashes >= 2 => {
+                    self.state = ScriptState::Data;
+                    self.dashes = 0;
+                }
+                _ => self.dashes = 0,
+            }
+            self.index += 1;
+        }
+        None
+    }
+}
+
+/// Returns whether `text` starts with the end tag of `name`: `</name` in
+/// any case followed by whitespace, `/`, `>` or the end of the input.
+fn is_end_tag(text: &str, name: &str) -> bool {
+    let Some(after) = text.strip_prefix("</") else {
+        return false;
+    };
+    after.get(..name.len()).is_some_and(|tag| tag.eq_ignore_ascii_case(name))
+        && after[name.len()..].chars().next().is_none_or(|ch| ch.is_whitespace() || ch == '/' || ch == '>')
+}
+
+/// Returns whether `text` starts with `prefix` and `script` in any case,
+/// followed by whitespace, `/`, `>` or the end of the input.
+fn is_script_tag(text: &[u8], prefix: &[u8]) -> bool {
+    let Some(after) = text.strip_prefix(prefix) else {
+        return false;
+    };
+    after.get(..6).is_some_and(|name| name.eq_ignore_ascii_case(b"script"))
+        && after.get(6).is_none_or(|&byte| byte.is_ascii_whitespace() || byte == b'/' || byte == b'>')
+}
+
+#[cfg(feature = "locations")]
+impl<'a> HtmlTokenizer<'a> {
+    /// Like `next_token`, but also returns the source span of the token.
+    pub fn next_token_spanned(&mut self) -> Option<(HtmlToken<'a>, Span)> {
+        let token = self.next_token()?;
+        Some((token, self.token_span()))
+    }
+}
+
 impl<'a> Iterator for HtmlTokenizer<'a> {
     type Item = HtmlToken<'a>;
 
@@ -366,4 +740,288 @@ mod tests {
         assert!(matches!(tokens[5], HtmlToken::EndTag { name: "span" }));
         assert!(matches!(tokens[6], HtmlToken::EndTag { name: "div" }));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_preserving_whitespace() {
+        let tokenizer = HtmlTokenizer::preserving_whitespace("<!-- a -->\n  <p> x </p>");
+
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert_eq!(tokens.len(), 5);
+        assert!(matches!(tokens[1], HtmlToken::Text("\n  ")));
+        assert!(matches!(tokens[3], HtmlToken::Text(" x ")));
+    }
+
+    #[test]
+    fn test_locations() {
+        let mut tokenizer = HtmlTokenizer::new("<div\n  class=\"x\">\n<!-- c -->");
+
+        assert!(matches!(tokenizer.next_token(), Some(HtmlToken::StartTag { name: "div", .. })));
+        assert_eq!(tokenizer.location(), SourceLocation { line: 1, column: 1, byte_offset: 0 });
+        assert_eq!(
+            tokenizer.attribute_locations(),
+            &[SourceLocation { line: 2, column: 3, byte_offset: 7 }]
+        );
+
+        assert!(matches!(tokenizer.next_token(), Some(HtmlToken::Comment(" c "))));
+        assert_eq!(tokenizer.location(), SourceLocation { line: 3, column: 1, byte_offset: 18 });
+    }
+
+    #[cfg(feature = "locations")]
+    #[test]
+    fn test_spanned_tokens() {
+        let mut tokenizer = HtmlTokenizer::new("<p>\nHi</p>");
+
+        let (_, span) = tokenizer.next_token_spanned().unwrap();
+        assert_eq!(span.start, SourceLocation { line: 1, column: 1, byte_offset: 0 });
+        assert_eq!(span.end, SourceLocation { line: 1, column: 4, byte_offset: 3 });
+
+        let (token, span) = tokenizer.next_token_spanned().unwrap();
+        assert_eq!(token, HtmlToken::Text("Hi"));
+        assert_eq!(span.start, SourceLocation { line: 2, column: 1, byte_offset: 4 });
+    }
+
+    #[test]
+    fn test_multibyte_text_and_attributes() {
+        let tokens: Vec<_> = HtmlTokenizer::new("<p title='日本'>naïve <!-- ü --></p>").collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                HtmlToken::StartTag { name: "p", attributes: vec![("title", "日本")], self_closing: false },
+                HtmlToken::Text("naïve "),
+                HtmlToken::Comment(" ü "),
+                HtmlToken::EndTag { name: "p" },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_document_token_count() {
+        // Same shape as `generate_large_html()` in examples/performance_demo.rs.
+        let mut html = String::from("<!DOCTYPE html><html><head><title>Performance Test</title></head><body>");
+        for i in 0..1000 {
+            html.push_str(&format!(
+                r#"<div class="item-{}" id="item-{}">
+                    <h2>Item {}</h2>
+                    <p>This is item number {} with some content.</p>
+                    <ul>
+                        <li>Feature A</li>
+                        <li>Feature B</li>
+                        <li>Feature C</li>
+                    </ul>
+                    <img src="image-{}.jpg" alt="Image {}">
+                </div>"#,
+                i, i, i, i, i, i
+            ));
+        }
+        html.push_str("</body></html>");
+
+        let tokens: Vec<_> = HtmlTokenizer::new(&html).collect();
+
+        // 8 tokens of preamble, 20 per item, 2 closing tags.
+        assert_eq!(tokens.len(), 8 + 20 * 1000 + 2);
+        assert!(matches!(tokens.last(), Some(HtmlToken::EndTag { name: "html" })));
+    }
+
+    #[test]
+    fn test_attribute_quotes() {
+        let mut tokenizer = HtmlTokenizer::new(r#"<input a="1" b='2' c=3 d>"#);
+
+        tokenizer.next_token();
+        assert_eq!(
+            tokenizer.attribute_quotes(),
+            &[QuoteStyle::Double, QuoteStyle::Single, QuoteStyle::Unquoted, QuoteStyle::NoValue]
+        );
+    }
+
+    #[test]
+    fn test_invalid_attribute_characters_are_skipped() {
+        let tokens: Vec<_> = HtmlTokenizer::new(r#"<a .x="1" @click=go>t</a>"#).collect();
+
+        assert_eq!(
+            tokens[0],
+            HtmlToken::StartTag { name: "a", attributes: vec![("x", "1"), ("click", "go")], self_closing: false }
+        );
+        assert_eq!(tokens.len(), 3);
+    }
+
+    #[test]
+    fn test_dots_in_names() {
+        let tokens: Vec<_> = HtmlTokenizer::new("<my.widget a.b=1></my.widget>").allowing_dots_in_names().collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                HtmlToken::StartTag { name: "my.widget", attributes: vec![("a.b", "1")], self_closing: false },
+                HtmlToken::EndTag { name: "my.widget" },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_script_is_raw_text() {
+        let tokens: Vec<_> = HtmlTokenizer::new("<script>if (a<b && b>c) { x('</div>'); }</script><p>").collect();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[1], HtmlToken::Text("if (a<b && b>c) { x('</div>'); }"));
+        assert_eq!(tokens[2], HtmlToken::EndTag { name: "script" });
+        assert!(matches!(tokens[3], HtmlToken::StartTag { name: "p", .. }));
+    }
+
+    #[test]
+    fn test_style_and_other_raw_text_elements() {
+        let tokens: Vec<_> = HtmlTokenizer::new("<style>a > b { color: red }</STYLE >").collect();
+        assert_eq!(tokens[1], HtmlToken::Text("a > b { color: red }"));
+        assert_eq!(tokens[2], HtmlToken::EndTag { name: "STYLE" });
+
+        let tokens: Vec<_> = HtmlTokenizer::new("<TextArea><b>bold</b></textareas></textarea>").collect();
+        assert_eq!(tokens[1], HtmlToken::Text("<b>bold</b></textareas>"));
+        assert_eq!(tokens.len(), 3);
+
+        let tokens: Vec<_> = HtmlTokenizer::new("<title></title><script>never closed <b>").collect();
+        assert_eq!(tokens[1], HtmlToken::EndTag { name: "title" });
+        assert_eq!(tokens[3], HtmlToken::Text("never closed <b>"));
+        assert_eq!(tokens.len(), 4);
+    }
+
+    fn script_text(html: &str) -> Vec<HtmlToken<'_>> {
+        HtmlTokenizer::new(html).skip(1).take(2).collect()
+    }
+
+    #[test]
+    fn test_script_data_escapes() {
+        // The example from the HTML spec: after `<!-- <script>` the first
+        // `</script>` only leaves the double-escaped region
+        let html = concat!(
+            "<script>\n  var example = 'Consider this string: <!-- <script>';\n  console.log(example);\n</script>\n",
+            "<!-- despite appearances, this is actually part of the script still! -->\n",
+            "<script>\n ... // this is the same script block still...\n</script><p>",
+        );
+        assert_eq!(
+            script_text(html),
+            vec![
+                HtmlToken::Text(concat!(
+                    "var example = 'Consider this string: <!-- <script>';\n  console.log(example);\n</script>\n",
+                    "<!-- despite appearances, this is actually part of the script still! -->\n",
+                    "<script>\n ... // this is the same script block still...\n",
+                )),
+                HtmlToken::EndTag { name: "script" },
+            ]
+        );
+
+        // Outside a double-escaped region `</script>` ends it, escaped or not
+        let html = "<script><!-- if (a) { x('</SCRIPT>') } //--></script>";
+        assert_eq!(script_text(html)[0], HtmlToken::Text("<!-- if (a) { x('"));
+        let html = "<script><!-- a --> b('<script>'); c('</script>'); </script>";
+        assert_eq!(script_text(html)[0], HtmlToken::Text("<!-- a --> b('<script>'); c('"));
+        let html = "<script><!--> d('<script>') </script>";
+        assert_eq!(script_text(html)[0], HtmlToken::Text("<!--> d('<script>') "));
+    }
+
+    #[test]
+    fn test_split_script_end_tag_is_left_alone() {
+        let html = r#"<script>document.write("<script>" + "</scr" + "ipt>");</script><p>"#;
+        let tokens: Vec<_> = HtmlTokenizer::new(html).collect();
+
+        assert_eq!(tokens[1], HtmlToken::Text(r#"document.write("<script>" + "</scr" + "ipt>");"#));
+        assert_eq!(tokens[2], HtmlToken::EndTag { name: "script" });
+        assert!(matches!(tokens[3], HtmlToken::StartTag { name: "p", .. }));
+    }
+
+    #[test]
+    fn test_ignoring_script_escapes() {
+        let html = "<script><!--w('<script>x</script>')--></script>";
+        let tokens: Vec<_> = HtmlTokenizer::new(html).ignoring_script_escapes().collect();
+
+        assert_eq!(tokens[1], HtmlToken::Text("<!--w('<script>x"));
+        assert_eq!(script_text(html)[0], HtmlToken::Text("<!--w('<script>x</script>')-->"));
+    }
+
+    fn delims(open: &str, close: &str) -> TemplateDelims {
+        TemplateDelims { open: open.to_string(), close: close.to_string() }
+    }
+
+    #[test]
+    fn test_handlebars_template_syntax() {
+        let html = r#"<a href="{{ url "x" }}" value={{x y}} {{#if active}} data-{{key}}=1 {{/if}}>{{#if a < b}}hi{{/if}}</a>"#;
+        let tokens: Vec<_> = HtmlTokenizer::new(html).with_template_syntax(delims("{{", "}}")).collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                HtmlToken::StartTag {
+                    name: "a",
+                    attributes: vec![
+                        ("href", r#"{{ url "x" }}"#),
+                        ("value", "{{x y}}"),
+                        ("{{#if active}}", ""),
+                        ("data-{{key}}", "1"),
+                        ("{{/if}}", ""),
+                    ],
+                    self_closing: false,
+                },
+                HtmlToken::Text("{{#if a < b}}hi{{/if}}"),
+                HtmlToken::EndTag { name: "a" },
+            ]
+        );
+
+        // Without the option, the same markup splits
+        let tokens: Vec<_> = HtmlTokenizer::new(html).collect();
+        assert!(matches!(&tokens[0], HtmlToken::StartTag { attributes, .. } if attributes.contains(&("value", "{{x"))));
+    }
+
+    #[test]
+    fn test_jinja_template_syntax() {
+        let html = r#"<li class="{% if x %}a{% endif %}" id={% if y %}b{% else %}c{% endif %} {% if z %}hidden{% endif %}>{% for i in items %}<b>{{ i }}</b>{% endfor %}"#;
+        let mut tokenizer = HtmlTokenizer::new(html).with_template_syntax(delims("{%", "%}"));
+
+        assert_eq!(
+            tokenizer.next_token(),
+            Some(HtmlToken::StartTag {
+                name: "li",
+                attributes: vec![
+                    ("class", "{% if x %}a{% endif %}"),
+                    ("id", "{% if y %}b{% else %}c{% endif %}"),
+                    ("{% if z %}", ""),
+                    ("hidden{% endif %}", ""),
+                ],
+                self_closing: false,
+            })
+        );
+        assert_eq!(tokenizer.attribute_quotes()[1], QuoteStyle::Unquoted);
+        assert_eq!(tokenizer.next_token(), Some(HtmlToken::Text("{% for i in items %}")));
+        assert!(matches!(tokenizer.next_token(), Some(HtmlToken::StartTag { name: "b", .. })));
+    }
+
+    #[test]
+    fn test_unclosed_and_nested_template_spans() {
+        let tokens: Vec<_> = HtmlTokenizer::new("{{ {{a}} <b> }}<i>{{ open")
+            .with_template_syntax(delims("{{", "}}"))
+            .collect();
+
+        assert_eq!(tokens[0], HtmlToken::Text("{{ {{a}} <b> }}"));
+        assert!(matches!(tokens[1], HtmlToken::StartTag { name: "i", .. }));
+        assert_eq!(tokens[2], HtmlToken::Text("{{ open"));
+
+        let tokens: Vec<_> = HtmlTokenizer::new("<%= a < b %><p>").with_template_syntax(delims("<%", "%>")).collect();
+        assert_eq!(tokens[0], HtmlToken::Text("<%= a < b %>"));
+        assert!(matches!(tokens[1], HtmlToken::StartTag { name: "p", .. }));
+
+        // Spans inside an unclosed one still close on their own
+        let tokens: Vec<_> = HtmlTokenizer::new("{{ {{a}}<b>{{c <i x={{d}}>")
+            .with_template_syntax(delims("{{", "}}"))
+            .collect();
+        assert_eq!(tokens[0], HtmlToken::Text("{{ {{a}}"));
+        assert!(matches!(&tokens[3], HtmlToken::StartTag { name: "i", attributes, .. } if attributes == &[("x", "{{d}}")]));
+    }
+
+    #[test]
+    fn test_unclosed_template_spans_scan_once() {
+        let html = format!("<p title={}>{}", "{{".repeat(50_000), "{{{".repeat(50_000));
+        let tokens: Vec<_> = HtmlTokenizer::new(&html).with_template_syntax(delims("{{", "}}")).collect();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1], HtmlToken::Text(&html[html.find('>').unwrap() + 1..]));
+    }
+}
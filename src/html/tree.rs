@@ -0,0 +1,148 @@
+use crate::html::parser::{Element, Node};
+use crate::output::{write_limited, OutputError};
+use std::fmt::{self, Write};
+
+/// Shows the element as an indented outline rather than its fields: the
+/// start tag (attributes sorted by name), then each child on its own line,
+/// two spaces deeper. Text is shown quoted, as with `{:?}` on a `str`.
+impl fmt::Debug for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_outline(self, 0, f)
+    }
+}
+
+fn write_outline(element: &Element, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write_element_label(element, f)?;
+    for child in &element.children {
+        write!(f, "\n{:width$}", "", width = 2 * (depth + 1))?;
+        match child {
+            Node::Element(child) => write_outline(child, depth + 1, f)?,
+            node => write_label(node, f)?,
+        }
+    }
+    Ok(())
+}
+
+impl Node {
+    /// Returns the tree under this node drawn with box-drawing lines, one
+    /// node per line, for printing while debugging:
+    ///
+    /// ```text
+    /// <div>
+    /// ├── <p class="intro">
+    /// │   └── "hello"
+    /// └── <br>
+    /// ```
+    pub fn display_tree(&self) -> String {
+        let mut out = String::new();
+        let _ = write_tree(self, &mut out);
+        out
+    }
+
+    /// Writes `display_tree` into `out`, failing with
+    /// `OutputError::LimitExceeded` instead of writing more than `limit`
+    /// bytes. Each line is written whole or not at all.
+    pub fn write_tree<W: fmt::Write + ?Sized>(&self, out: &mut W, limit: Option<usize>) -> Result<(), OutputError> {
+        write_limited(out, limit, |w| write_tree(self, w))
+    }
+}
+
+fn write_tree<W: Write + ?Sized>(node: &Node, out: &mut W) -> fmt::Result {
+    let mut line = String::new();
+    write_label(node, &mut line)?;
+    line.push('\n');
+    out.write_str(&line)?;
+    if let Node::Element(element) = node {
+        write_tree_children(&element.children, "", out)?;
+    }
+    Ok(())
+}
+
+fn write_tree_children<W: Write + ?Sized>(nodes: &[Node], prefix: &str, out: &mut W) -> fmt::Result {
+    for (index, node) in nodes.iter().enumerate() {
+        let last = index + 1 == nodes.len();
+        let mut line = format!("{}{}", prefix, if last { "└── " } else { "├── " });
+        write_label(node, &mut line)?;
+        line.push('\n');
+        out.write_str(&line)?;
+        if let Node::Element(element) = node {
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            write_tree_children(&element.children, &prefix, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// One line describing `node`: a start tag, quoted text, a comment or a
+/// doctype.
+fn write_label<W: Write + ?Sized>(node: &Node, out: &mut W) -> fmt::Result {
+    match node {
+        Node::Element(element) => write_element_label(element, out),
+        Node::Text(text) => write!(out, "{:?}", text),
+        Node::Comment(comment) => write!(out, "<!--{}-->", comment),
+        Node::Doctype(doctype) => write!(out, "<!DOCTYPE {}>", doctype),
+    }
+}
+
+fn write_element_label<W: Write + ?Sized>(element: &Element, out: &mut W) -> fmt::Result {
+    let mut attributes: Vec<_> = element.attributes.iter().collect();
+    attributes.sort();
+    write!(out, "<{}", element.tag_name)?;
+    for (name, value) in attributes {
+        write!(out, " {}=\"{}\"", name, value)?;
+    }
+    out.write_str(">")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::parser::{HtmlParser, Node};
+    use crate::output::OutputError;
+
+    #[test]
+    fn test_display_tree() {
+        let nodes = HtmlParser::new(r#"<div><p id="a" class="intro">hello <b>you</b></p><!-- c --><br></div>"#).parse();
+
+        assert_eq!(
+            nodes[0].display_tree(),
+            concat!(
+                "<div>\n",
+                "├── <p class=\"intro\" id=\"a\">\n",
+                "│   ├── \"hello \"\n",
+                "│   └── <b>\n",
+                "│       └── \"you\"\n",
+                "├── <!-- c -->\n",
+                "└── <br>\n",
+            )
+        );
+        assert_eq!(Node::Text("x".into()).display_tree(), "\"x\"\n");
+    }
+
+    #[test]
+    fn test_write_tree_limit() {
+        let nodes = HtmlParser::new("<ul><li>one</li><li>two</li></ul>").parse();
+        let mut out = String::new();
+
+        let result = nodes[0].write_tree(&mut out, Some(45));
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 45 }));
+        assert_eq!(out, "<ul>\n├── <li>\n│   └── \"one\"\n");
+
+        let mut out = String::new();
+        assert_eq!(nodes[0].write_tree(&mut out, None), Ok(()));
+        assert_eq!(out, nodes[0].display_tree());
+    }
+
+    #[test]
+    fn test_element_debug_is_an_outline() {
+        let nodes = HtmlParser::new("<ul><li>one</li><li><i>two</i></li></ul>").parse();
+        let Node::Element(list) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+
+        assert_eq!(
+            format!("{:?}", list),
+            "<ul>\n  <li>\n    \"one\"\n  <li>\n    <i>\n      \"two\""
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::html::parser::{Document, Element, Node};
+use std::slice;
+
+/// Callbacks for a depth-first walk of a node tree; see `Node::walk`.
+///
+/// Every method does nothing by default, so a visitor only implements the
+/// ones it needs.
+pub trait NodeVisitor {
+    /// Called for an element before its children.
+    fn visit_element_enter(&mut self, _element: &Element) {}
+
+    /// Called for an element after its children.
+    fn visit_element_exit(&mut self, _element: &Element) {}
+
+    fn visit_text(&mut self, _text: &str) {}
+
+    fn visit_comment(&mut self, _comment: &str) {}
+
+    /// Called with what follows the `DOCTYPE` keyword (`html` for
+    /// `<!DOCTYPE html>`).
+    fn visit_doctype(&mut self, _doctype: &str) {}
+}
+
+impl Node {
+    /// Walks this node and its descendants depth-first, in document order,
+    /// calling `visitor` for each.
+    pub fn walk<V: NodeVisitor>(&self, visitor: &mut V) {
+        match self {
+            Node::Element(element) => element.walk(visitor),
+            Node::Text(text) => visitor.visit_text(text),
+            Node::Comment(comment) => visitor.visit_comment(comment),
+            Node::Doctype(doctype) => visitor.visit_doctype(doctype),
+        }
+    }
+}
+
+impl Element {
+    /// Walks this element and its descendants; see `Node::walk`.
+    pub fn walk<V: NodeVisitor>(&self, visitor: &mut V) {
+        visitor.visit_element_enter(self);
+        for child in &self.children {
+            child.walk(visitor);
+        }
+        visitor.visit_element_exit(self);
+    }
+
+    /// Every element nested in this one, not counting itself, depth-first
+    /// in document order. The iterator is lazy: it holds one position per
+    /// level of nesting rather than a list of the elements.
+    pub fn descendants(&self) -> impl Iterator<Item = &Element> {
+        self.descendant_nodes().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Every node nested in this element, not counting itself, in the same
+    /// order as `descendants`.
+    pub fn descendant_nodes(&self) -> impl Iterator<Item = &Node> {
+        DescendantNodes { stack: vec![self.children.iter()] }
+    }
+}
+
+/// Pre-order walk with an explicit stack of sibling iterators, one per
+/// level, so deep trees don't recurse.
+struct DescendantNodes<'a> {
+    stack: Vec<slice::Iter<'a, Node>>,
+}
+
+impl<'a> Iterator for DescendantNodes<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            let siblings = self.stack.last_mut()?;
+            let Some(node) = siblings.next() else {
+                self.stack.pop();
+                continue;
+            };
+            if let Node::Element(element) = node
+                && !element.children.is_empty()
+            {
+                self.stack.push(element.children.iter());
+            }
+            return Some(node);
+        }
+    }
+}
+
+impl Document {
+    /// Walks every node of the document; see `Node::walk`.
+    pub fn walk<V: NodeVisitor>(&self, visitor: &mut V) {
+        for node in &self.children {
+            node.walk(visitor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::parser::HtmlParser;
+
+    #[derive(Default)]
+    struct TagNames(Vec<String>);
+
+    impl NodeVisitor for TagNames {
+        fn visit_element_enter(&mut self, element: &Element) {
+            self.0.push(element.tag_name.clone());
+        }
+    }
+
+    /// Records every callback, to check their order.
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl NodeVisitor for Trace {
+        fn visit_element_enter(&mut self, element: &Element) {
+            self.0.push(format!("<{}>", element.tag_name));
+        }
+
+        fn visit_element_exit(&mut self, element: &Element) {
+            self.0.push(format!("</{}>", element.tag_name));
+        }
+
+        fn visit_text(&mut self, text: &str) {
+            self.0.push(text.to_string());
+        }
+
+        fn visit_comment(&mut self, comment: &str) {
+            self.0.push(format!("<!--{}-->", comment));
+        }
+
+        fn visit_doctype(&mut self, doctype: &str) {
+            self.0.push(format!("<!DOCTYPE {}>", doctype));
+        }
+    }
+
+    #[test]
+    fn test_tag_names_in_document_order() {
+        let document = HtmlParser::new(
+            "<html><head><title>T</title></head><body><ul><li>a<li><b>b</b></ul><p>c<br></p></body></html>",
+        )
+        .parse_document();
+        let mut names = TagNames::default();
+
+        document.walk(&mut names);
+
+        assert_eq!(names.0, ["html", "head", "title", "body", "ul", "li", "li", "b", "p", "br"]);
+    }
+
+    #[test]
+    fn test_callback_order() {
+        let nodes = HtmlParser::new("<!DOCTYPE html><div>x<!-- c --><p>y</p></div>").parse();
+        let mut trace = Trace::default();
+
+        for node in &nodes {
+            node.walk(&mut trace);
+        }
+
+        assert_eq!(trace.0, ["<!DOCTYPE html>", "<div>", "x", "<!-- c -->", "<p>", "y", "</p>", "</div>"]);
+    }
+
+    #[test]
+    fn test_descendants_in_document_order() {
+        let nodes = HtmlParser::new("<div>x<ul><li>a<li><b>b</b></ul><!-- c --><p>y<br></p></div>").parse();
+        let Node::Element(div) = &nodes[0] else {
+            panic!("Expected element node");
+        };
+
+        let names: Vec<_> = div.descendants().map(|element| element.tag_name.as_str()).collect();
+        assert_eq!(names, ["ul", "li", "li", "b", "p", "br"]);
+
+        let mut trace = Trace::default();
+        for node in div.descendant_nodes() {
+            if let Node::Element(element) = node {
+                trace.visit_element_enter(element);
+            } else {
+                node.walk(&mut trace);
+            }
+        }
+        assert_eq!(trace.0, ["x", "<ul>", "<li>", "a", "<li>", "<b>", "b", "<!-- c -->", "<p>", "y", "<br>"]);
+    }
+
+    #[test]
+    fn test_descendants_of_feature_grid() {
+        let document = HtmlParser::new(include_str!("../../tests/fixtures/large.html")).parse_document();
+        let root = document.root_element().expect("Expected a root element");
+        let grid = root
+            .descendants()
+            .find(|element| element.attr("class") == Some("feature-grid"))
+            .expect("Expected a .feature-grid element");
+
+        assert_eq!(grid.descendants().count(), 12);
+        assert_eq!(grid.descendants().filter(|element| element.tag_name == "img").count(), 3);
+        assert_eq!(grid.descendant_nodes().filter(|node| matches!(node, Node::Text(_))).count(), 6);
+    }
+}
@@ -1,5 +1,100 @@
+//! HTML and CSS tokenizers, with optional tree parsers and tools on top.
+//!
+//! The tokenizers are always available. Everything else is behind additive
+//! cargo features, all but `serde` enabled by default:
+//!
+//! - `html-dom`: `HtmlParser` and the `Node`/`Element` tree, DOM ops,
+//!   tree walks (`NodeVisitor`), minification (`minify_html`) and
+//!   streaming from a reader (`HtmlParser::from_reader`).
+//! - `css-ast`: `CssParser` and the `Rule`/`Selector` AST, CSS ops,
+//!   serialization (`Rule::to_css`, `stylesheet_to_css`, `serialize_css`)
+//!   and media queries (`parse_media_query_list`). With `html-dom` too,
+//!   `sizes` and `media` attributes (`Element::parsed_sizes`).
+//! - `matching`: selector matching (`query_selector`, `merge_into_body`,
+//!   `lint`); implies `html-dom` and `css-ast`.
+//! - `serialize`: the HTML `Serializer`; implies `html-dom`.
+//! - `extract`: text extraction and search (`text_content`, `find_text`),
+//!   `<link>` relations (`link_relations`) and heading ids
+//!   (`ensure_heading_ids`); implies `html-dom`. With `matching` too,
+//!   rendered text (`inner_text`).
+//! - `serde`: `Serialize` and `Deserialize` for `Node`, `Element`,
+//!   `Document`, `Rule`, `Selector`, `CssToken` and `OwnedCssToken`, with
+//!   the features that provide them.
+
 pub mod html;
 pub mod css;
+#[cfg(any(feature = "html-dom", feature = "css-ast"))]
+pub mod error;
+#[cfg(feature = "matching")]
+pub mod lint;
+pub mod location;
+#[cfg(any(feature = "html-dom", feature = "css-ast"))]
+pub mod output;
+
+#[cfg(all(test, feature = "matching", feature = "serialize"))]
+mod fixtures;
 
-pub use html::{HtmlTokenizer, HtmlParser, HtmlToken, Element, Node};
-pub use css::{CssTokenizer, CssParser, CssToken, Rule, Selector};
\ No newline at end of file
+pub use html::{HtmlTokenizer, HtmlToken, QuoteStyle, TemplateDelims, decode_html_entities};
+#[cfg(feature = "html-dom")]
+pub use html::{HtmlParser, Element, Node, Document, WhitespacePolicy, SourceStyle, Text};
+#[cfg(feature = "html-dom")]
+pub use html::{DomOp, DomOpError, DomOpErrorKind};
+#[cfg(feature = "html-dom")]
+pub use html::NodeVisitor;
+#[cfg(feature = "html-dom")]
+pub use html::{
+    minify_html, minify_html_with_options, write_minified_html, write_minified_html_with_options, HtmlMinifierOptions,
+};
+#[cfg(feature = "html-dom")]
+pub use html::HtmlReaderParser;
+#[cfg(feature = "serialize")]
+pub use html::{Serializer, SerializeOpts};
+#[cfg(feature = "matching")]
+pub use html::{matches, query_selector, query_selector_all};
+#[cfg(feature = "matching")]
+pub use html::{merge_into_body, MergePosition, MergeWarning, MergeError};
+#[cfg(feature = "matching")]
+pub use html::{compute_style, diff_computed_styles, ComputedStyle, ElementStyleDiff, PseudoElement};
+#[cfg(feature = "extract")]
+pub use html::{find_text, FindOptions, TextMatch};
+#[cfg(feature = "extract")]
+pub use html::{link_relations, LinkRel};
+#[cfg(feature = "extract")]
+pub use html::{ensure_heading_ids, SlugStyle};
+pub use css::{CssTokenizer, CssToken, TokenizeError, TokenizeErrorKind};
+pub use css::{CssStreamTokenizer, OwnedCssToken};
+pub use css::{SignificantTokens, TokensWithTrivia, Trivia};
+#[cfg(feature = "css-ast")]
+pub use css::{CssParser, Rule, Selector, AttrOp, Specificity, specificity};
+#[cfg(feature = "css-ast")]
+pub use css::{parse_nth, NthExpr};
+#[cfg(feature = "css-ast")]
+pub use css::{StylesheetItem, MediaRule, Declaration, DeclarationList};
+#[cfg(feature = "css-ast")]
+pub use css::{ImportRule, KeyframesRule, KeyframeStep, KeyframeSelector};
+#[cfg(feature = "css-ast")]
+pub use css::{CssOp, CssOpError, apply_css_ops};
+#[cfg(feature = "css-ast")]
+pub use css::{OwnedStylesheet, RuleSource};
+#[cfg(feature = "css-ast")]
+pub use css::{feature_report, FeatureReport, FeatureUsage};
+#[cfg(feature = "css-ast")]
+pub use css::{
+    serialize_css, stylesheet_to_css, write_serialized_css, write_stylesheet_css, CssSerializerOptions,
+};
+#[cfg(feature = "css-ast")]
+pub use css::{parse_media_query_list, parse_media_query_list_with_errors, parse_source_sizes, split_by_color_scheme};
+#[cfg(feature = "css-ast")]
+pub use css::{Length, MediaCondition, MediaFeature, MediaModifier, MediaQuery, MediaQueryList, MediaValue, RangeOp};
+#[cfg(feature = "css-ast")]
+pub use css::{ColorScheme, ColorSchemeSplit, Contrast, Environment};
+#[cfg(feature = "css-ast")]
+pub use css::{minify_css, minify_css_preserve_comments, write_minified_css};
+pub use css::{format_number, NumberFormat};
+#[cfg(feature = "matching")]
+pub use lint::{find_near_miss_selectors, NearMiss, NearMissReason};
+#[cfg(any(feature = "html-dom", feature = "css-ast"))]
+pub use output::{LimitedWriter, OutputError};
+pub use location::{SourceLocation, Span};
+#[cfg(any(feature = "html-dom", feature = "css-ast"))]
+pub use error::{ParseError, ParseErrorKind};
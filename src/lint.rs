@@ -0,0 +1,192 @@
+use crate::css::parser::Selector;
+use crate::css::stylesheet::RuleSource;
+use crate::html::parser::{Document, Element, Node};
+
+/// Why a selector was reported as almost matching an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NearMissReason {
+    /// The class or id only matches when compared case-insensitively.
+    CaseMismatch,
+    /// The class attribute contains the selector's class but also stray
+    /// whitespace (non-ASCII separators, leading/trailing or repeated
+    /// spaces) or duplicate class names.
+    WhitespaceInClassAttr,
+}
+
+/// A class or id selector that almost, but not cleanly, matches an element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    /// The full selector from the rule.
+    pub selector: Selector,
+    /// The raw value of the `class` or `id` attribute that was compared.
+    pub candidate_attribute_value: String,
+    /// Child indices from `Document::children` down to the element.
+    pub element_path: Vec<usize>,
+    pub reason: NearMissReason,
+}
+
+/// Reports class and id selectors in `rules` (a `[Rule]` slice or an
+/// `OwnedStylesheet`) that almost match elements of `document`.
+///
+/// Matching in standards mode is case-sensitive, so `.Nav` never matches
+/// `class="nav"`; such pairs are reported as `CaseMismatch`. Class
+/// attributes that contain a selector's class but are untidy are reported as
+/// `WhitespaceInClassAttr`. Results are in rule order, then document order.
+pub fn find_near_miss_selectors<R>(document: &Document, rules: &R) -> Vec<NearMiss>
+where
+    R: RuleSource + ?Sized,
+{
+    let mut elements = Vec::new();
+    let mut path = Vec::new();
+    collect_elements(&document.children, &mut path, &mut elements);
+
+    let mut misses = Vec::new();
+    for rule in rules.rules() {
+        for selector in &rule.selectors {
+            let mut simple = Vec::new();
+            simple_selectors(selector, &mut simple);
+
+            for (path, element) in &elements {
+                for target in &simple {
+                    let found = match target {
+                        Selector::Class(class) => check_class(element, class),
+                        Selector::Id(id) => check_id(element, id),
+                        _ => None,
+                    };
+                    if let Some((value, reason)) = found {
+                        misses.push(NearMiss {
+                            selector: selector.clone(),
+                            candidate_attribute_value: value.to_string(),
+                            element_path: path.clone(),
+                            reason,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    misses
+}
+
+fn collect_elements<'d>(
+    nodes: &'d [Node],
+    path: &mut Vec<usize>,
+    out: &mut Vec<(Vec<usize>, &'d Element)>,
+) {
+    for (index, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            path.push(index);
+            out.push((path.clone(), element));
+            collect_elements(&element.children, path, out);
+            path.pop();
+        }
+    }
+}
+
+fn simple_selectors<'s>(selector: &'s Selector, out: &mut Vec<&'s Selector>) {
+    match selector {
+        Selector::Descendant(left, right)
+        | Selector::Child(left, right)
+        | Selector::Adjacent(left, right)
+        | Selector::GeneralSibling(left, right) => {
+            simple_selectors(left, out);
+            simple_selectors(right, out);
+        }
+        Selector::Compound(parts) => parts.iter().for_each(|part| simple_selectors(part, out)),
+        _ => out.push(selector),
+    }
+}
+
+fn check_class<'e>(element: &'e Element, class: &str) -> Option<(&'e str, NearMissReason)> {
+    let value = element.attributes.get("class")?;
+
+    // HTML splits class lists on ASCII whitespace only.
+    let tokens: Vec<&str> = value.split_ascii_whitespace().collect();
+    let relevant = value
+        .split(char::is_whitespace)
+        .any(|token| token.eq_ignore_ascii_case(class));
+    if !relevant {
+        return None;
+    }
+
+    let untidy = tokens.join(" ") != *value
+        || tokens.iter().any(|token| token.contains(char::is_whitespace))
+        || tokens.iter().enumerate().any(|(i, token)| tokens[..i].contains(token));
+    if untidy {
+        return Some((value, NearMissReason::WhitespaceInClassAttr));
+    }
+
+    if tokens.contains(&class) {
+        None
+    } else {
+        Some((value, NearMissReason::CaseMismatch))
+    }
+}
+
+fn check_id<'e>(element: &'e Element, id: &str) -> Option<(&'e str, NearMissReason)> {
+    let value = element.attributes.get("id")?;
+    if value != id && value.eq_ignore_ascii_case(id) {
+        Some((value, NearMissReason::CaseMismatch))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::parser::CssParser;
+    use crate::html::parser::HtmlParser;
+
+    fn near_misses(html: &str, css: &str) -> Vec<NearMiss> {
+        let document = HtmlParser::new(html).parse_document();
+        let rules = CssParser::new(css).parse();
+        find_near_miss_selectors(&document, &rules)
+    }
+
+    #[test]
+    fn test_case_mismatch_flagged() {
+        let misses = near_misses(
+            r#"<div><button class="button">Go</button></div>"#,
+            ".Button { color: red; }",
+        );
+
+        assert_eq!(misses.len(), 1);
+        assert_eq!(misses[0].selector, Selector::Class("Button".to_string()));
+        assert_eq!(misses[0].candidate_attribute_value, "button");
+        assert_eq!(misses[0].element_path, vec![0, 0]);
+        assert_eq!(misses[0].reason, NearMissReason::CaseMismatch);
+    }
+
+    #[test]
+    fn test_exact_match_not_flagged() {
+        let misses = near_misses(
+            r#"<button class="primary button" id="go">Go</button>"#,
+            ".button, #go, div .primary { color: red; }",
+        );
+
+        assert!(misses.is_empty());
+    }
+
+    #[test]
+    fn test_id_case_mismatch() {
+        let misses = near_misses(r#"<p id="Intro"></p>"#, "#intro { margin: 0; }");
+
+        assert_eq!(misses.len(), 1);
+        assert_eq!(misses[0].reason, NearMissReason::CaseMismatch);
+    }
+
+    #[test]
+    fn test_untidy_class_attribute() {
+        let misses = near_misses(
+            "<a class=\" nav  nav\"></a><a class=\"nav\u{a0}item\"></a>",
+            ".nav { color: red; }",
+        );
+
+        assert_eq!(misses.len(), 2);
+        assert_eq!(misses[0].element_path, vec![0]);
+        assert_eq!(misses[0].reason, NearMissReason::WhitespaceInClassAttr);
+        assert_eq!(misses[1].element_path, vec![1]);
+        assert_eq!(misses[1].reason, NearMissReason::WhitespaceInClassAttr);
+    }
+}
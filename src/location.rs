@@ -0,0 +1,114 @@
+use std::ops::Range;
+
+/// A position in the source text.
+///
+/// `line` and `column` are 1-based; `column` counts characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+    pub byte_offset: usize,
+}
+
+impl SourceLocation {
+    /// The location of the first character of the input.
+    pub const START: SourceLocation = SourceLocation { line: 1, column: 1, byte_offset: 0 };
+}
+
+impl Default for SourceLocation {
+    fn default() -> Self {
+        Self::START
+    }
+}
+
+/// The source range a token covers, from its first character up to (not
+/// including) the character after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: SourceLocation,
+    pub end: SourceLocation,
+}
+
+impl Span {
+    /// The byte offsets covered by the span, for slicing the source.
+    pub fn byte_range(&self) -> Range<usize> {
+        self.start.byte_offset..self.end.byte_offset
+    }
+}
+
+/// Line and column bookkeeping shared by the tokenizers.
+///
+/// `\n`, `\r` and `\r\n` each end a line.
+///
+/// Columns advance once per grapheme cluster, approximated without Unicode
+/// tables: combining marks, variation selectors, emoji modifiers and
+/// zero-width-joiner sequences extend the preceding character instead of
+/// starting a new column.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LineCounter {
+    line: usize,
+    column: usize,
+    after_joiner: bool,
+    after_carriage_return: bool,
+}
+
+impl LineCounter {
+    pub(crate) fn new() -> Self {
+        Self { line: 1, column: 1, after_joiner: false, after_carriage_return: false }
+    }
+
+    pub(crate) fn advance(&mut self, ch: char) {
+        if ch == '\n' && self.after_carriage_return {
+            // The `\n` of a `\r\n` pair; the line already ended
+        } else if ch == '\n' || ch == '\r' {
+            self.line += 1;
+            self.column = 1;
+        } else if !self.after_joiner && !extends_grapheme(ch) {
+            self.column += 1;
+        }
+        self.after_joiner = ch == '\u{200D}';
+        self.after_carriage_return = ch == '\r';
+    }
+
+    pub(crate) fn location(&self, byte_offset: usize) -> SourceLocation {
+        SourceLocation { line: self.line, column: self.column, byte_offset }
+    }
+}
+
+fn extends_grapheme(ch: char) -> bool {
+    matches!(ch,
+        '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' |
+        '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}' | '\u{FE00}'..='\u{FE0F}' |
+        '\u{E0100}'..='\u{E01EF}' | '\u{1F3FB}'..='\u{1F3FF}' | '\u{200D}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_after(text: &str) -> usize {
+        let mut counter = LineCounter::new();
+        text.chars().for_each(|ch| counter.advance(ch));
+        counter.location(text.len()).column
+    }
+
+    #[test]
+    fn test_columns_count_graphemes() {
+        assert_eq!(column_after("abc"), 4);
+        assert_eq!(column_after("日本"), 3);
+        assert_eq!(column_after("e\u{301}x"), 3);
+        assert_eq!(column_after("👍🏽!"), 3);
+        assert_eq!(column_after("👩\u{200D}💻x"), 3);
+        assert_eq!(column_after("ab\ncd"), 3);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let mut counter = LineCounter::new();
+        "a\r\nb\rc\n\nd".chars().for_each(|ch| counter.advance(ch));
+        let location = counter.location(0);
+
+        assert_eq!((location.line, location.column), (5, 2));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+/// The error returned by the writer-based output functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputError {
+    /// Writing the next piece of output would have exceeded the byte cap.
+    LimitExceeded { limit: usize },
+    /// The caller-supplied writer reported an error.
+    Fmt,
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::LimitExceeded { limit } => {
+                write!(f, "output limit of {} bytes exceeded", limit)
+            }
+            OutputError::Fmt => write!(f, "writer error"),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+/// A `fmt::Write` adaptor that refuses to write past a byte cap.
+///
+/// Each `write_str` or `write!` call is all-or-nothing: a piece that does
+/// not fit is not written at all, so a capped output always ends at the
+/// boundary of the last complete piece (a whole tag, token or text node,
+/// or a chunk of a large text node).
+pub struct LimitedWriter<'w, W: fmt::Write + ?Sized> {
+    inner: &'w mut W,
+    limit: Option<usize>,
+    written: usize,
+    exceeded: bool,
+}
+
+impl<'w, W: fmt::Write + ?Sized> LimitedWriter<'w, W> {
+    pub fn new(inner: &'w mut W, limit: Option<usize>) -> Self {
+        Self { inner, limit, written: 0, exceeded: false }
+    }
+
+    /// Number of bytes written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Converts the result of a write sequence into an `OutputError`.
+    pub fn finish(&self, result: fmt::Result) -> Result<(), OutputError> {
+        match (result, self.limit) {
+            (Ok(()), _) => Ok(()),
+            (Err(_), Some(limit)) if self.exceeded => Err(OutputError::LimitExceeded { limit }),
+            (Err(_), _) => Err(OutputError::Fmt),
+        }
+    }
+}
+
+impl<W: fmt::Write + ?Sized> fmt::Write for LimitedWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(limit) = self.limit
+            && self.written + s.len() > limit
+        {
+            self.exceeded = true;
+            return Err(fmt::Error);
+        }
+        self.inner.write_str(s)?;
+        self.written += s.len();
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        match (args.as_str(), self.limit) {
+            (Some(s), _) => self.write_str(s),
+            (None, Some(_)) => self.write_str(&args.to_string()),
+            (None, None) => fmt::write(self, args),
+        }
+    }
+}
+
+/// Runs `write` against `out` capped at `limit` bytes.
+pub(crate) fn write_limited<W, F>(out: &mut W, limit: Option<usize>, write: F) -> Result<(), OutputError>
+where
+    W: fmt::Write + ?Sized,
+    F: FnOnce(&mut LimitedWriter<'_, W>) -> fmt::Result,
+{
+    let mut writer = LimitedWriter::new(out, limit);
+    let result = write(&mut writer);
+    writer.finish(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn test_pieces_are_all_or_nothing() {
+        let mut out = String::new();
+
+        let result = write_limited(&mut out, Some(8), |w| {
+            w.write_str("abc")?;
+            w.write_str("defg")?;
+            w.write_str("hij")
+        });
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 8 }));
+        assert_eq!(out, "abcdefg");
+    }
+
+    #[test]
+    fn test_no_limit() {
+        let mut out = String::new();
+
+        assert_eq!(write_limited(&mut out, None, |w| write!(w, "{}-{}", 1, 2)), Ok(()));
+        assert_eq!(out, "1-2");
+    }
+
+    #[test]
+    fn test_formatted_writes_are_all_or_nothing() {
+        let mut out = String::new();
+
+        let name = "a";
+        let result = write_limited(&mut out, Some(6), |w| {
+            write!(w, "<{}>", name)?;
+            write!(w, "</{}>", name)
+        });
+
+        assert_eq!(result, Err(OutputError::LimitExceeded { limit: 6 }));
+        assert_eq!(out, "<a>");
+    }
+}
@@ -0,0 +1,9 @@
+//! Inputs shared by the benchmarks and the regression tests, so both
+//! measure exactly the same documents.
+
+#![allow(dead_code)]
+
+pub const SMALL_HTML: &str = include_str!("small.html");
+pub const LARGE_HTML: &str = include_str!("large.html");
+pub const SMALL_CSS: &str = include_str!("small.css");
+pub const LARGE_CSS: &str = include_str!("large.css");
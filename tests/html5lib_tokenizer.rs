@@ -0,0 +1,395 @@
+//! Runs `HtmlTokenizer` against tokenizer tests in the html5lib-tests JSON
+//! format, found in `tests/fixtures/html5lib/*.test`.
+//!
+//! Cases listed in `tests/fixtures/html5lib/failing.txt` are known not to
+//! pass yet. Any other failing case fails the build, and so does a listed
+//! case that passes, so the list always matches what the tokenizer does:
+//! when a change fixes a case, remove its line in the same commit.
+//!
+//! Each case runs once per initial state. Only the data state is
+//! supported; a case in any other state fails.
+
+use html_css_parser::{HtmlToken, HtmlTokenizer};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/html5lib");
+
+#[test]
+fn test_html5lib_tokenizer_cases() {
+    let allowlist = fs::read_to_string(Path::new(FIXTURES).join("failing.txt")).expect("failing.txt");
+    let known_failures: BTreeSet<&str> = allowlist
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let mut files: Vec<_> = fs::read_dir(FIXTURES)
+        .expect("fixture directory")
+        .map(|entry| entry.expect("directory entry").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "test"))
+        .collect();
+    files.sort();
+
+    let mut total = 0;
+    let mut failures = BTreeSet::new();
+    for path in &files {
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let source = fs::read_to_string(path).expect("test file");
+        let json = Json::parse(&source).unwrap_or_else(|error| panic!("{}: {}", file_name, error));
+        for case in json.get("tests").and_then(Json::as_array).expect("`tests` array") {
+            for (id, passed) in run_case(&file_name, case) {
+                total += 1;
+                if !passed {
+                    failures.insert(id);
+                }
+            }
+        }
+    }
+
+    let unexpected: Vec<_> = failures.iter().filter(|id| !known_failures.contains(id.as_str())).collect();
+    let fixed: Vec<_> = known_failures.iter().filter(|id| !failures.contains(**id)).collect();
+    println!("html5lib tokenizer: {} of {} cases pass", total - failures.len(), total);
+    assert!(unexpected.is_empty(), "failing cases not in failing.txt:\n{:#?}", unexpected);
+    assert!(fixed.is_empty(), "cases in failing.txt that pass or do not exist:\n{:#?}", fixed);
+}
+
+/// Runs one test case in each of its initial states, returning the id of
+/// each run (`file: description`, plus `[state]` when not the data state)
+/// and whether it passed.
+fn run_case(file_name: &str, case: &Json) -> Vec<(String, bool)> {
+    let description = case.get("description").and_then(Json::as_str).expect("description");
+    let double_escaped = case.get("doubleEscaped") == Some(&Json::Bool(true));
+    let unescape = |text: &str| if double_escaped { unescape_double(text) } else { text.to_string() };
+
+    let input = unescape(case.get("input").and_then(Json::as_str).expect("input"));
+    let mut expected = Vec::new();
+    for token in case.get("output").and_then(Json::as_array).expect("output") {
+        push_token(&mut expected, normalize_expected(token, &unescape));
+    }
+
+    let states = match case.get("initialStates").and_then(Json::as_array) {
+        Some(states) => states.iter().map(|state| state.as_str().expect("state name")).collect(),
+        None => vec!["Data state"],
+    };
+    states
+        .into_iter()
+        .map(|state| {
+            let id = match state {
+                "Data state" => format!("{}: {}", file_name, description),
+                _ => format!("{}: {} [{}]", file_name, description, state),
+            };
+            let passed = state == "Data state" && tokenize(&input) == expected;
+            (id, passed)
+        })
+        .collect()
+}
+
+/// A token in the html5lib form, after normalization.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Doctype { name: Option<String>, public_id: Option<String>, system_id: Option<String> },
+    StartTag { name: String, attributes: Vec<(String, String)>, self_closing: bool },
+    EndTag(String),
+    Comment(String),
+    Character(String),
+}
+
+/// Appends `token`, merging adjacent character tokens as html5lib does.
+fn push_token(tokens: &mut Vec<Token>, token: Token) {
+    if let (Some(Token::Character(last)), Token::Character(text)) = (tokens.last_mut(), &token) {
+        last.push_str(text);
+    } else {
+        tokens.push(token);
+    }
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for token in HtmlTokenizer::preserving_whitespace(input) {
+        let token = match token {
+            HtmlToken::StartTag { name, attributes, self_closing } => {
+                // The JSON form keeps one value per name: the first, as the
+                // spec drops later duplicates.
+                let mut unique: Vec<(String, String)> = Vec::new();
+                for (name, value) in attributes {
+                    if !unique.iter().any(|(seen, _)| seen == name) {
+                        unique.push((name.to_string(), value.to_string()));
+                    }
+                }
+                unique.sort();
+                Token::StartTag { name: name.to_string(), attributes: unique, self_closing }
+            }
+            HtmlToken::EndTag { name } => Token::EndTag(name.to_string()),
+            HtmlToken::Text(text) => Token::Character(text.to_string()),
+            HtmlToken::Comment(text) => Token::Comment(text.to_string()),
+            HtmlToken::Doctype(content) => doctype(content),
+        };
+        push_token(&mut tokens, token);
+    }
+    tokens
+}
+
+/// Splits the raw doctype content (`!DOCTYPE html PUBLIC "..."`) into name
+/// and identifiers.
+fn doctype(content: &str) -> Token {
+    let rest = content.get("!doctype".len()..).unwrap_or("").trim();
+    let (name, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let mut quoted = rest.split(['"', '\'']).skip(1).step_by(2).map(str::to_string);
+    let keyword = rest.trim_start().get(..6).map(str::to_ascii_uppercase);
+    let (public_id, system_id) = match keyword.as_deref() {
+        Some("PUBLIC") => (quoted.next(), quoted.next()),
+        Some("SYSTEM") => (None, quoted.next()),
+        _ => (None, None),
+    };
+    Token::Doctype {
+        name: (!name.is_empty()).then(|| name.to_ascii_lowercase()),
+        public_id,
+        system_id,
+    }
+}
+
+fn normalize_expected(token: &Json, unescape: &impl Fn(&str) -> String) -> Token {
+    let parts = token.as_array().expect("token array");
+    let text = |index: usize| parts.get(index).and_then(Json::as_str).map(unescape);
+    match parts[0].as_str().expect("token type") {
+        "DOCTYPE" => Token::Doctype { name: text(1), public_id: text(2), system_id: text(3) },
+        "StartTag" => {
+            let mut attributes: Vec<(String, String)> = match &parts[2] {
+                Json::Object(members) => members
+                    .iter()
+                    .map(|(name, value)| (unescape(name), unescape(value.as_str().expect("attribute value"))))
+                    .collect(),
+                _ => panic!("attributes must be an object"),
+            };
+            attributes.sort();
+            Token::StartTag {
+                name: text(1).expect("tag name"),
+                attributes,
+                self_closing: parts.get(3) == Some(&Json::Bool(true)),
+            }
+        }
+        "EndTag" => Token::EndTag(text(1).expect("tag name")),
+        "Comment" => Token::Comment(text(1).expect("comment data")),
+        "Character" => Token::Character(text(1).expect("character data")),
+        other => panic!("unknown token type {}", other),
+    }
+}
+
+/// Decodes the `\uXXXX` escapes left in the strings of a `doubleEscaped`
+/// test. Unpaired surrogates become U+FFFD.
+fn unescape_double(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(hex) = rest.strip_prefix("\\u").and_then(|after| after.get(..4))
+            && let Ok(unit) = u16::from_str_radix(hex, 16)
+        {
+            units.push(unit);
+            rest = &rest[6..];
+            continue;
+        }
+        let ch = rest.chars().next().unwrap();
+        units.extend(ch.encode_utf16(&mut [0; 2]).iter());
+        rest = &rest[ch.len_utf8()..];
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Just enough JSON for the test files.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(source: &str) -> Result<Json, String> {
+        let mut reader = JsonReader { source, position: 0 };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        match reader.position == source.len() {
+            true => Ok(value),
+            false => Err(reader.error("trailing characters")),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct JsonReader<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl JsonReader<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.position)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.position += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|ch| matches!(ch, ' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.next_char() {
+            Some(ch) if ch == expected => Ok(()),
+            _ => Err(self.error(&format!("expected `{}`", expected))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.source[self.position..].starts_with(word) {
+            self.position += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unknown keyword"))
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit() || matches!(ch, '-' | '+' | '.' | 'e' | 'E')) {
+            self.position += 1;
+        }
+        self.source[start..self.position].parse().map(Json::Number).map_err(|_| self.error("bad number"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut units: Vec<u16> = Vec::new();
+        loop {
+            let ch = self.next_char().ok_or_else(|| self.error("unterminated string"))?;
+            let unit = match ch {
+                '"' => break,
+                '\\' => match self.next_char() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => {
+                        let hex = self.source.get(self.position..self.position + 4).ok_or_else(|| self.error("bad escape"))?;
+                        let unit = u16::from_str_radix(hex, 16).map_err(|_| self.error("bad escape"))?;
+                        self.position += 4;
+                        units.push(unit);
+                        continue;
+                    }
+                    _ => return Err(self.error("bad escape")),
+                },
+                ch => ch,
+            };
+            units.extend(unit.encode_utf16(&mut [0; 2]).iter());
+        }
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.next_char() {
+                Some(',') => {}
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.expect(':')?;
+            members.push((name, self.value()?));
+            self.skip_whitespace();
+            match self.next_char() {
+                Some(',') => {}
+                Some('}') => return Ok(Json::Object(members)),
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_json_reader() {
+    let json = Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": "x\"é😀", "c": {}} "#).unwrap();
+
+    assert_eq!(
+        json.get("a"),
+        Some(&Json::Array(vec![Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Null]))
+    );
+    assert_eq!(json.get("b").and_then(Json::as_str), Some("x\"é😀"));
+    assert_eq!(json.get("c"), Some(&Json::Object(Vec::new())));
+    assert!(Json::parse("[1,]").is_err());
+    assert_eq!(unescape_double(r"aA😀\uDC00"), "aA😀\u{FFFD}");
+}
@@ -0,0 +1,128 @@
+//! Guards against the parsers silently producing more or fewer tokens,
+//! nodes or rules for the benchmark inputs. The expected counts are not
+//! derived from anything: when a change alters them on purpose, check the
+//! new output and update the numbers in the same commit.
+
+mod fixtures;
+
+use fixtures::{LARGE_CSS, LARGE_HTML, SMALL_CSS, SMALL_HTML};
+use html_css_parser::{CssParser, CssTokenizer, HtmlParser, HtmlTokenizer, Node, StylesheetItem};
+use std::collections::BTreeMap;
+
+/// Counts every node in the trees, elements and their descendants alike.
+fn count_nodes(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Element(element) => 1 + count_nodes(&element.children),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Counts the elements in the trees by tag name.
+fn count_tags(nodes: &[Node]) -> BTreeMap<String, usize> {
+    fn walk(nodes: &[Node], counts: &mut BTreeMap<String, usize>) {
+        for node in nodes {
+            if let Node::Element(element) = node {
+                *counts.entry(element.tag_name.clone()).or_default() += 1;
+                walk(&element.children, counts);
+            }
+        }
+    }
+
+    let mut counts = BTreeMap::new();
+    walk(nodes, &mut counts);
+    counts
+}
+
+/// Returns the number of rules and declarations in a stylesheet, including
+/// those nested in `@media` blocks.
+fn count_rules(items: &[StylesheetItem]) -> (usize, usize) {
+    let mut rules = 0;
+    let mut declarations = 0;
+    for item in items {
+        let nested = match item {
+            StylesheetItem::Rule(rule) => std::slice::from_ref(rule),
+            StylesheetItem::Media(media) => media.rules.as_slice(),
+            _ => &[],
+        };
+        rules += nested.len();
+        declarations += nested.iter().map(|rule| rule.declarations.len()).sum::<usize>();
+    }
+    (rules, declarations)
+}
+
+fn tags(counts: &[(&str, usize)]) -> BTreeMap<String, usize> {
+    counts.iter().map(|(tag, count)| (tag.to_string(), *count)).collect()
+}
+
+#[test]
+fn test_small_html_counts() {
+    let nodes = HtmlParser::new(SMALL_HTML).parse();
+
+    assert_eq!(HtmlTokenizer::new(SMALL_HTML).count(), 19);
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(count_nodes(&nodes), 12);
+    assert_eq!(count_tags(&nodes), tags(&[("div", 1), ("h1", 1), ("li", 3), ("p", 1), ("ul", 1)]));
+}
+
+#[test]
+fn test_large_html_counts() {
+    let nodes = HtmlParser::new(LARGE_HTML).parse();
+
+    assert_eq!(HtmlTokenizer::new(LARGE_HTML).count(), 123);
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(count_nodes(&nodes), 76);
+    assert_eq!(
+        count_tags(&nodes),
+        tags(&[
+            ("a", 7),
+            ("blockquote", 2),
+            ("body", 1),
+            ("button", 1),
+            ("cite", 2),
+            ("div", 7),
+            ("footer", 1),
+            ("h1", 1),
+            ("h2", 1),
+            ("h3", 3),
+            ("head", 1),
+            ("header", 1),
+            ("html", 1),
+            ("img", 3),
+            ("li", 4),
+            ("main", 1),
+            ("meta", 2),
+            ("nav", 1),
+            ("p", 7),
+            ("section", 3),
+            ("title", 1),
+            ("ul", 1),
+        ])
+    );
+}
+
+#[test]
+fn test_small_css_counts() {
+    let rules = CssParser::new(SMALL_CSS).parse();
+    let items = CssParser::new(SMALL_CSS).parse_stylesheet();
+
+    assert_eq!(CssTokenizer::new(SMALL_CSS).count(), 64);
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules.iter().map(|rule| rule.declarations.len()).sum::<usize>(), 7);
+    assert_eq!(items.len(), 3);
+    assert_eq!(count_rules(&items), (3, 7));
+}
+
+#[test]
+fn test_large_css_counts() {
+    let rules = CssParser::new(LARGE_CSS).parse();
+    let items = CssParser::new(LARGE_CSS).parse_stylesheet();
+
+    assert_eq!(CssTokenizer::new(LARGE_CSS).count(), 1251);
+    assert_eq!(rules.len(), 30);
+    assert_eq!(rules.iter().map(|rule| rule.declarations.len()).sum::<usize>(), 122);
+    assert_eq!(items.len(), 31);
+    assert_eq!(count_rules(&items), (34, 127));
+}